@@ -0,0 +1,50 @@
+//! Compares the write latency of the standard and the mmap backed log writer
+//!
+//! Run with `cargo bench --features mmap --bench mmap_latency`.
+
+use std::{env, fs};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use glog::{ByteSize, Flags};
+use log::{Level, Log, Record};
+
+fn writers(c: &mut Criterion) {
+    let log_dir = env::temp_dir().join(format!("glog-mmap-latency-{}", std::process::id()));
+    // The files of both writers would have the same names
+    let flags = |writer: &str| {
+        let log_dir = log_dir.join(writer);
+        fs::create_dir_all(&log_dir).unwrap();
+        Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        }
+    };
+    let standard = glog::new().clone_with_flags(flags("standard")).unwrap();
+    let mapped = glog::new()
+        .with_mmap_writer(ByteSize::mib(16))
+        .clone_with_flags(flags("mmap"))
+        .unwrap();
+
+    let mut group = c.benchmark_group("log file writer");
+    for (name, logger) in [("standard", &standard), ("mmap", &mapped)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                logger.log(
+                    &Record::builder()
+                        .level(Level::Info)
+                        .file(Some("src/server.rs"))
+                        .line(Some(42))
+                        .args(format_args!("handled request {} in {}us", 1234, 567))
+                        .build(),
+                )
+            })
+        });
+    }
+    group.finish();
+    standard.flush();
+    mapped.flush();
+    fs::remove_dir_all(&log_dir).unwrap();
+}
+
+criterion_group!(benches, writers);
+criterion_main!(benches);
@@ -0,0 +1,44 @@
+//! Compares the fast path for plain stderr output with the general path
+//!
+//! Both write to stderr, run with `cargo bench --bench stderr_fast_path 2>/dev/null`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use glog::Flags;
+use log::{Level, Log, Record};
+
+fn stderr_paths(c: &mut Criterion) {
+    let fast = glog::new()
+        .clone_with_flags(Flags {
+            logtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+    // Keeping a tail of the output needs the general path, the output stays the same
+    let general = glog::new()
+        .clone_with_flags(Flags {
+            logtostderr: true,
+            tail_buffer_size: 1,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let mut group = c.benchmark_group("logtostderr");
+    for (name, logger) in [("fast path", &fast), ("general path", &general)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                logger.log(
+                    &Record::builder()
+                        .level(Level::Info)
+                        .file(Some("src/server.rs"))
+                        .line(Some(42))
+                        .args(format_args!("handled request {} in {}us", 1234, 567))
+                        .build(),
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, stderr_paths);
+criterion_main!(benches);
@@ -0,0 +1,50 @@
+//! Compares the latency of file records of 16KB written with and without concatenating the prefix
+//!
+//! Run with `cargo bench --bench vectored_write`.
+
+use std::{env, fs};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use glog::Flags;
+use log::{Level, Log, Record};
+
+fn file_paths(c: &mut Criterion) {
+    let log_dir = env::temp_dir().join(format!("glog-vectored-write-{}", std::process::id()));
+    // The files of both loggers would have the same names
+    let flags = |path: &str, tail_buffer_size| {
+        let log_dir = log_dir.join(path);
+        fs::create_dir_all(&log_dir).unwrap();
+        Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            tail_buffer_size,
+            ..Default::default()
+        }
+    };
+    let vectored = glog::new().clone_with_flags(flags("vectored", 0)).unwrap();
+    // A tail buffer needs the whole line, so it makes the logger concatenate every record
+    let concatenated = glog::new().clone_with_flags(flags("concatenated", 1)).unwrap();
+
+    let message = "x".repeat(16 * 1024);
+    let mut group = c.benchmark_group("file records of 16KB");
+    for (name, logger) in [("vectored", &vectored), ("concatenated", &concatenated)] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                logger.log(
+                    &Record::builder()
+                        .level(Level::Info)
+                        .file(Some("src/server.rs"))
+                        .line(Some(42))
+                        .args(format_args!("{}", message))
+                        .build(),
+                )
+            })
+        });
+    }
+    group.finish();
+    vectored.flush();
+    concatenated.flush();
+    fs::remove_dir_all(&log_dir).unwrap();
+}
+
+criterion_group!(benches, file_paths);
+criterion_main!(benches);
@@ -1,6 +1,7 @@
 fn main() {
     #[cfg(target_os = "windows")]
     windows::build! {
-        Windows::Win32::System::Threading::GetCurrentThreadId
+        Windows::Win32::System::Console::{GetStdHandle, STD_ERROR_HANDLE},
+        Windows::Win32::System::Threading::GetCurrentThreadId,
     };
 }
@@ -0,0 +1,18 @@
+//! Logs to files in the directory given as the first argument, the temporary directory by default
+//!
+//! ```sh
+//! cargo run --example init_files -- /tmp/init_files
+//! ```
+
+use std::{env, path::PathBuf};
+
+use log::*;
+
+fn main() {
+    let dir = env::args().nth(1).map_or_else(env::temp_dir, PathBuf::from);
+    let handle = glog::init_files(&dir).unwrap();
+
+    info!("Logging to files in {}", dir.display());
+    error!("Errors are copied to stderr");
+    handle.shutdown();
+}
@@ -0,0 +1,15 @@
+//! Takes the flags from the environment and the command line
+//!
+//! ```sh
+//! GLOG_logtostderr=1 cargo run --example init_from_env -- --minloglevel=WARNING
+//! ```
+
+use log::*;
+
+fn main() {
+    let handle = glog::init_from_env().unwrap();
+
+    info!("Hidden with --minloglevel=WARNING");
+    warn!("Logged with the flags from the environment and the command line");
+    handle.shutdown();
+}
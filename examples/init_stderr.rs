@@ -0,0 +1,15 @@
+//! Logs to stderr only
+//!
+//! ```sh
+//! cargo run --example init_stderr
+//! ```
+
+use log::*;
+
+fn main() {
+    let handle = glog::init_stderr().unwrap();
+
+    info!("Logging to stderr");
+    warn!("Colored if stderr is a terminal");
+    handle.shutdown();
+}
@@ -0,0 +1,49 @@
+#ifndef GLOG_RS_H
+#define GLOG_RS_H
+
+/* Generated with cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * `INFO` in the glog severity scheme
+ */
+#define GLOG_INFO 0
+
+/**
+ * `WARNING` in the glog severity scheme
+ */
+#define GLOG_WARNING 1
+
+/**
+ * `ERROR` in the glog severity scheme
+ */
+#define GLOG_ERROR 2
+
+/**
+ * `FATAL` in the glog severity scheme, aborts the process after logging
+ */
+#define GLOG_FATAL 3
+
+/**
+ * Log the nul terminated `msg` with the glog `severity` from `file` and `line`
+ *
+ * Invalid UTF-8 is replaced with `U+FFFD`. Nothing is logged if `msg` is null, a null `file`
+ * is logged as an empty file name and a negative `line` as 0. Unknown severities are logged as
+ * errors.
+ *
+ * # Safety
+ *
+ * `file` and `msg` have to be null or point to nul terminated strings.
+ */
+void glog_log(int severity, const char *file, int line, const char *msg);
+
+/**
+ * Flush all log output
+ */
+void glog_flush(void);
+
+#endif /* GLOG_RS_H */
@@ -0,0 +1,190 @@
+use std::{
+    sync::{
+        atomic::{AtomicI64, AtomicU64, AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use log::Level;
+
+const LEVELS: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+/// Number of buckets the error window is split into
+const BUCKETS: usize = 10;
+
+/// Settings of the adaptive verbosity, from the `adaptive_*` flags
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct AdaptiveConfig {
+    /// More `Warn` and `Error` records than this within `window` escalate
+    pub(crate) threshold: u32,
+    pub(crate) window: Duration,
+    /// The minimum level while escalated
+    pub(crate) level: Level,
+    /// The time an escalation lasts after the last burst
+    pub(crate) duration: Duration,
+}
+
+/// A change of the escalation caused by a record
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Transition {
+    /// Escalated after this many `Warn` and `Error` records within the window
+    Escalated(u32),
+    Restored,
+}
+
+/// Counts of the `Warn` and `Error` records in coarse buckets of the window
+#[derive(Default)]
+struct Buckets {
+    /// The number of the bucket since the epoch and its count, by the number modulo [`BUCKETS`]
+    counts: [(i64, u32); BUCKETS],
+}
+
+impl Buckets {
+    /// Count a record at `micros`, returns the count of the window ending with it
+    fn count(&mut self, micros: i64, window: Duration) -> u32 {
+        let width = (window.as_micros() as i64 / BUCKETS as i64).max(1);
+        let bucket = micros.div_euclid(width);
+        let slot = &mut self.counts[bucket.rem_euclid(BUCKETS as i64) as usize];
+        if slot.0 != bucket {
+            *slot = (bucket, 0);
+        }
+        slot.1 += 1;
+        let oldest = bucket - BUCKETS as i64 + 1;
+        self.counts
+            .iter()
+            .filter(|(number, _)| (oldest..=bucket).contains(number))
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+/// Lowers the minimum level for a while after a burst of errors, see
+/// [`Flags::adaptive_error_threshold`](crate::Flags::adaptive_error_threshold)
+pub(crate) struct Adaptive {
+    /// Index of the escalated level in [`LEVELS`], out of its bounds if not escalated
+    level: AtomicUsize,
+    /// Microseconds since the epoch at which the escalation ends, `i64::MAX` if not escalated
+    until: AtomicI64,
+    buckets: Mutex<Buckets>,
+    /// Sequence number of the last record of the adaptive buffer which is in the log files
+    dumped_seq: AtomicU64,
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Adaptive {
+            level: AtomicUsize::new(usize::MAX),
+            until: AtomicI64::new(i64::MAX),
+            buckets: Mutex::default(),
+            dumped_seq: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Adaptive {
+    /// `minloglevel` lowered to the escalated level
+    pub(crate) fn lower(&self, minloglevel: Level) -> Level {
+        LEVELS
+            .get(self.level.load(Ordering::Relaxed))
+            .map_or(minloglevel, |level| minloglevel.max(*level))
+    }
+
+    /// Count a record of `level` at the time returned by `now_micros`
+    ///
+    /// The closures are only called for `Warn` and `Error` records or while escalated.
+    pub(crate) fn observe(
+        &self,
+        level: Level,
+        config: impl FnOnce() -> Option<AdaptiveConfig>,
+        now_micros: impl FnOnce() -> i64,
+    ) -> Option<Transition> {
+        let until = self.until.load(Ordering::Acquire);
+        if level > Level::Warn && until == i64::MAX {
+            return None;
+        }
+        let Some(config) = config() else {
+            return (until != i64::MAX && self.cancel()).then_some(Transition::Restored);
+        };
+        let now = now_micros();
+        let duration = config.duration.as_micros() as i64;
+        if level <= Level::Warn {
+            let mut buckets = self.buckets.lock().unwrap();
+            let count = buckets.count(now, config.window);
+            if count > config.threshold {
+                // The burst goes on, so does the escalation
+                let escalated = self.until.swap(now.saturating_add(duration), Ordering::AcqRel) == i64::MAX;
+                self.level.store(config.level as usize - 1, Ordering::Relaxed);
+                return escalated.then_some(Transition::Escalated(count));
+            }
+        }
+        if now >= until && self.cancel() {
+            return Some(Transition::Restored);
+        }
+        None
+    }
+
+    /// End the escalation and forget the counted records, returns whether it was escalated
+    pub(crate) fn cancel(&self) -> bool {
+        let mut buckets = self.buckets.lock().unwrap();
+        *buckets = Buckets::default();
+        self.level.store(usize::MAX, Ordering::Relaxed);
+        self.until.swap(i64::MAX, Ordering::AcqRel) != i64::MAX
+    }
+
+    pub(crate) fn dumped_seq(&self) -> u64 {
+        self.dumped_seq.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_dumped_seq(&self, seq: u64) {
+        self.dumped_seq.store(seq, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000;
+
+    fn config() -> Option<AdaptiveConfig> {
+        Some(AdaptiveConfig {
+            threshold: 2,
+            window: Duration::from_secs(10),
+            level: Level::Debug,
+            duration: Duration::from_secs(30),
+        })
+    }
+
+    #[test]
+    fn escalates_on_bursts_until_they_stop() {
+        let adaptive = Adaptive::default();
+        let observe = |level, seconds: i64| adaptive.observe(level, config, || seconds * SECOND);
+        assert_eq!(observe(Level::Error, 0), None);
+        assert_eq!(observe(Level::Warn, 5), None);
+        // The first error left the window
+        assert_eq!(observe(Level::Error, 11), None);
+        assert_eq!(adaptive.lower(Level::Info), Level::Info);
+        assert_eq!(observe(Level::Error, 12), Some(Transition::Escalated(3)));
+        assert_eq!(adaptive.lower(Level::Info), Level::Debug);
+        assert_eq!(adaptive.lower(Level::Trace), Level::Trace);
+
+        assert_eq!(observe(Level::Error, 20), None);
+        assert_eq!(observe(Level::Info, 49), None);
+        assert_eq!(observe(Level::Info, 50), Some(Transition::Restored));
+        assert_eq!(adaptive.lower(Level::Info), Level::Info);
+        assert_eq!(observe(Level::Info, 51), None);
+    }
+
+    #[test]
+    fn disabling_restores() {
+        let adaptive = Adaptive::default();
+        for _ in 0..3 {
+            adaptive.observe(Level::Error, config, || 0);
+        }
+        assert_eq!(adaptive.lower(Level::Warn), Level::Debug);
+        assert_eq!(adaptive.observe(Level::Info, || None, || 0), Some(Transition::Restored));
+        assert_eq!(adaptive.lower(Level::Warn), Level::Warn);
+        assert!(!adaptive.cancel());
+    }
+}
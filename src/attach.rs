@@ -0,0 +1,166 @@
+//! One-shot attachments for the next record of a thread, see [`attach`]
+
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::redact::Redactor;
+
+/// A value attached to a record with [`attach`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Attachment {
+    pub key: String,
+    pub value: String,
+}
+
+/// An attachment waiting for the next record, counted as dropped unless it is taken
+struct Pending(Option<(Attachment, Instant)>);
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        if self.0.is_some() {
+            DROPPED.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+thread_local! {
+    static PENDING: RefCell<Pending> = const { RefCell::new(Pending(None)) };
+    static CURRENT: RefCell<Option<Arc<Attachment>>> = const { RefCell::new(None) };
+}
+
+/// Attachments which expired, were replaced or whose thread ended before a record was logged
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Attach `value` to the next record logged on this thread
+///
+/// Large values like a request dump can be attached to exactly one line without cutting them off
+/// with the message. The line gets a `[attached: key=<n bytes>]` note and the value follows as an
+/// indented block of at most `attachment_max_bytes`. An attachment which is still pending is
+/// replaced, it is dropped if no record follows within `attachment_ttl` or the thread ends.
+///
+/// # Example
+///
+/// ```
+/// use log::*;
+/// use glog::Flags;
+///
+/// glog::new().init(Flags::default()).unwrap();
+///
+/// glog::attach("request", "GET / HTTP/1.1\nHost: example.com");
+/// error!("Request failed");
+/// // E0401 12:34:56.987654   123 doc.rs:9] Request failed [attached: request=32 bytes]
+/// //     GET / HTTP/1.1
+/// //     Host: example.com
+/// ```
+pub fn attach(key: &str, value: impl Into<String>) {
+    let attachment = Attachment {
+        key: key.to_owned(),
+        value: value.into(),
+    };
+    // Replacing drops the previous `Pending`, which counts it
+    PENDING.with(|pending| *pending.borrow_mut() = Pending(Some((attachment, Instant::now()))));
+}
+
+/// The attachment of the record which is currently written, for use in a [`LogSink`](crate::LogSink)
+pub fn current_attachment() -> Option<Arc<Attachment>> {
+    CURRENT.with(|current| current.borrow().clone())
+}
+
+/// Number of dropped attachments of all threads
+pub(crate) fn dropped() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Take the pending attachment of this thread unless it is older than `ttl`
+fn take(ttl: Duration) -> Option<Attachment> {
+    let (attachment, attached_at) = PENDING.try_with(|pending| pending.borrow_mut().0.take()).ok()??;
+    if attached_at.elapsed() > ttl {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+        return None;
+    }
+    Some(attachment)
+}
+
+/// Makes the pending attachment the current one until it is dropped
+pub(crate) struct CurrentGuard(Option<Arc<Attachment>>);
+
+impl CurrentGuard {
+    /// Takes the pending attachment, see [`take`], with its value passed through `redactor`
+    pub(crate) fn take(ttl: Duration, redactor: Option<&Redactor>) -> CurrentGuard {
+        let attachment = take(ttl).map(|mut attachment| {
+            if let Some(redactor) = redactor {
+                if let Cow::Owned(redacted) = redactor(&attachment.value) {
+                    attachment.value = redacted;
+                }
+            }
+            Arc::new(attachment)
+        });
+        CurrentGuard(CURRENT.with(|current| current.replace(attachment)))
+    }
+}
+
+impl Drop for CurrentGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.0.take());
+    }
+}
+
+/// Formats the current attachment as note and indented block of at most `max_bytes` of its value
+pub(crate) struct Rendered {
+    pub(crate) max_bytes: usize,
+}
+
+impl fmt::Display for Rendered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some(attachment) = current_attachment() else {
+            return Ok(());
+        };
+        let value = &attachment.value;
+        write!(f, " [attached: {}={} bytes]", attachment.key, value.len())?;
+        let mut end = value.len().min(self.max_bytes);
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        for line in value[..end].lines() {
+            write!(f, "\n    {}", line)?;
+        }
+        if end < value.len() {
+            write!(f, "\n    [truncated {} bytes]", value.len() - end)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_indented_and_truncated() {
+        attach("dump", "first\nsecond ✓");
+        let _current = CurrentGuard::take(Duration::from_secs(60), None);
+        assert_eq!(
+            Rendered {
+                max_bytes: 64
+            }
+            .to_string(),
+            " [attached: dump=16 bytes]\n    first\n    second ✓"
+        );
+        assert_eq!(
+            Rendered {
+                max_bytes: 14
+            }
+            .to_string(),
+            " [attached: dump=16 bytes]\n    first\n    second \n    [truncated 3 bytes]"
+        );
+    }
+}
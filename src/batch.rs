@@ -0,0 +1,73 @@
+use std::fmt;
+
+use chrono::{DateTime, Local};
+use log::Level;
+
+use crate::{
+    context,
+    format::{self, FormatOptions, Sanitized},
+    redact::Redactor,
+};
+
+/// Collects the lines of a batch, see [`GlogHandle::log_batch`](crate::GlogHandle::log_batch)
+///
+/// All lines share the timestamp, thread id and source location of the batch. The messages and
+/// the context pairs of the lines are passed through the [redactor](crate::Glog::set_redactor).
+pub struct BatchWriter<'a> {
+    pub(crate) options: FormatOptions,
+    pub(crate) timestamp: DateTime<Local>,
+    pub(crate) level: Level,
+    pub(crate) tid: u64,
+    pub(crate) file: &'a str,
+    pub(crate) line: u32,
+    pub(crate) sanitize: bool,
+    /// Written before the messages with `log_target`
+    pub(crate) target: Option<&'a str>,
+    pub(crate) redactor: Option<&'a Redactor>,
+    pub(crate) lines: Vec<String>,
+    /// The unredacted lines for stderr without the `redact_stderr` flag
+    pub(crate) stderr_lines: Option<Vec<String>>,
+    /// The messages of the lines without the prefix, only kept for sinks
+    pub(crate) messages: Option<Vec<String>>,
+}
+
+impl BatchWriter<'_> {
+    /// Add a line with the message `args` to the batch
+    pub fn line(&mut self, args: fmt::Arguments<'_>) {
+        let redacted = self.redactor.map(|redactor| {
+            let (context, message) = (context::Current.to_string(), args.to_string());
+            (redactor(&context).into_owned(), redactor(&message).into_owned())
+        });
+        let (context, message): (&dyn fmt::Display, &dyn fmt::Display) = match &redacted {
+            Some((context, message)) => (context, message),
+            None => (&context::Current, &args),
+        };
+        if let Some(messages) = &mut self.messages {
+            messages.push(message.to_string());
+        }
+        let line = self.format_line(context, message);
+        self.lines.push(line);
+        if let Some(mut stderr_lines) = self.stderr_lines.take() {
+            stderr_lines.push(self.format_line(&context::Current, &args));
+            self.stderr_lines = Some(stderr_lines);
+        }
+    }
+
+    fn format_line(&self, context: &dyn fmt::Display, message: &dyn fmt::Display) -> String {
+        format::format_record(
+            &self.options,
+            self.timestamp,
+            self.level,
+            self.tid,
+            self.file,
+            self.line,
+            &format_args!(
+                "{}",
+                Sanitized::new(
+                    format_args!("{}{}{}", format::Target(self.target), context, message),
+                    self.sanitize
+                )
+            ),
+        )
+    }
+}
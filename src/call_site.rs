@@ -0,0 +1,153 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Mutex, RwLock},
+};
+
+/// The lines of a file which are part of a [`CallSites`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Lines {
+    /// Whether the whole file is part of the set, kept apart from the single lines
+    all: bool,
+    lines: HashSet<u32>,
+}
+
+/// A set of `file:line` call sites, where the line `None` stands for the whole file
+///
+/// Shared by `log_backtrace_at` and `suppress_at`, the file names are the ones logged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct CallSites(HashMap<String, Lines>);
+
+impl CallSites {
+    /// Add a call site, returns whether it wasn't part of the set yet
+    pub(crate) fn insert(&mut self, file: String, line: Option<u32>) -> bool {
+        let lines = self.0.entry(file).or_default();
+        match line {
+            Some(line) => lines.lines.insert(line),
+            None => !std::mem::replace(&mut lines.all, true),
+        }
+    }
+
+    /// Remove exactly the given call site, returns whether it was part of the set
+    pub(crate) fn remove(&mut self, file: &str, line: Option<u32>) -> bool {
+        let Some(lines) = self.0.get_mut(file) else {
+            return false;
+        };
+        let removed = match line {
+            Some(line) => lines.lines.remove(&line),
+            None => std::mem::take(&mut lines.all),
+        };
+        if *lines == Lines::default() {
+            self.0.remove(file);
+        }
+        removed
+    }
+
+    pub(crate) fn contains(&self, file: &str, line: u32) -> bool {
+        self.0.get(file).is_some_and(|lines| lines.all || lines.lines.contains(&line))
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Lists the call sites sorted and separated by commas, like the `suppress_at` flag
+impl fmt::Display for CallSites {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut call_sites: Vec<(&str, Option<u32>)> = self
+            .0
+            .iter()
+            .flat_map(|(file, lines)| {
+                let all = lines.all.then_some((file.as_str(), None));
+                all.into_iter()
+                    .chain(lines.lines.iter().map(move |line| (file.as_str(), Some(*line))))
+            })
+            .collect();
+        call_sites.sort();
+        for (i, (file, line)) in call_sites.into_iter().enumerate() {
+            let separator = if i == 0 { "" } else { ", " };
+            match line {
+                Some(line) => write!(f, "{}{}:{}", separator, file, line)?,
+                None => write!(f, "{}{}:*", separator, file)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The call sites of `suppress_at` and how many records were dropped at each line
+#[derive(Default)]
+pub(crate) struct Suppressions {
+    pub(crate) call_sites: RwLock<CallSites>,
+    dropped: Mutex<HashMap<(String, u32), u64>>,
+}
+
+impl Suppressions {
+    /// Whether the record at `file:line` is suppressed, `file` is only called if there are suppressions
+    pub(crate) fn suppress(&self, file: impl FnOnce() -> String, line: u32) -> bool {
+        let call_sites = self.call_sites.read().unwrap();
+        if call_sites.is_empty() {
+            return false;
+        }
+        let file = file();
+        if !call_sites.contains(&file, line) {
+            return false;
+        }
+        *self.dropped.lock().unwrap().entry((file, line)).or_insert(0) += 1;
+        true
+    }
+
+    /// The `file:line` of every call site with dropped records and their number, most first
+    pub(crate) fn summary(&self) -> Vec<(String, u64)> {
+        let mut summary: Vec<_> = self
+            .dropped
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|((file, line), dropped)| (format!("{}:{}", file, line), *dropped))
+            .collect();
+        summary.sort_by(|(a, a_dropped), (b, b_dropped)| b_dropped.cmp(a_dropped).then_with(|| a.cmp(b)));
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_and_whole_files() {
+        let mut call_sites = CallSites::default();
+        call_sites.insert("a.rs".to_owned(), Some(1));
+        call_sites.insert("a.rs".to_owned(), Some(2));
+        call_sites.insert("b.rs".to_owned(), None);
+        assert!(call_sites.contains("a.rs", 2));
+        assert!(!call_sites.contains("a.rs", 3));
+        assert!(call_sites.contains("b.rs", 3));
+
+        assert!(!call_sites.remove("b.rs", Some(3)));
+        assert!(call_sites.remove("b.rs", None));
+        assert!(!call_sites.contains("b.rs", 3));
+        assert!(call_sites.remove("a.rs", Some(1)));
+        assert!(call_sites.remove("a.rs", Some(2)));
+        assert!(call_sites.is_empty());
+    }
+
+    #[test]
+    fn whole_files_are_kept_apart_from_their_lines() {
+        let mut call_sites = CallSites::default();
+        call_sites.insert("a.rs".to_owned(), Some(1));
+        call_sites.insert("a.rs".to_owned(), None);
+        call_sites.insert("a.rs".to_owned(), Some(2));
+        assert_eq!(call_sites.to_string(), "a.rs:*, a.rs:1, a.rs:2");
+
+        assert!(call_sites.remove("a.rs", None));
+        assert!(!call_sites.contains("a.rs", 3));
+        assert!(call_sites.contains("a.rs", 2));
+        call_sites.insert("a.rs".to_owned(), None);
+        assert!(call_sites.remove("a.rs", Some(1)));
+        assert!(call_sites.contains("a.rs", 1));
+        assert_eq!(call_sites.to_string(), "a.rs:*, a.rs:2");
+    }
+}
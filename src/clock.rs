@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use chrono::{DateTime, Local, TimeZone};
+
+/// Keeps timestamps from going backwards when the wall clock is stepped back, e.g. by NTP
+#[derive(Debug)]
+pub(crate) struct MonotonicGuard {
+    last_micros: AtomicI64,
+}
+
+impl MonotonicGuard {
+    pub(crate) fn new() -> Self {
+        MonotonicGuard {
+            last_micros: AtomicI64::new(i64::MIN),
+        }
+    }
+
+    /// Microseconds since the epoch of the latest timestamp, `i64::MIN` before the first one
+    pub(crate) fn latest_micros(&self) -> i64 {
+        self.last_micros.load(Ordering::Relaxed)
+    }
+
+    /// Returns `now` if it isn't earlier than any previous timestamp.
+    /// Otherwise the latest previous timestamp and by how many microseconds the clock went backwards.
+    pub(crate) fn guard(&self, now: DateTime<Local>) -> Result<DateTime<Local>, (DateTime<Local>, i64)> {
+        let now_micros = now.timestamp() * 1_000_000 + i64::from(now.timestamp_subsec_micros());
+        let last_micros = self.last_micros.fetch_max(now_micros, Ordering::Relaxed);
+        if now_micros >= last_micros {
+            return Ok(now);
+        }
+        let last = Local
+            .timestamp_opt(
+                last_micros.div_euclid(1_000_000),
+                (last_micros.rem_euclid(1_000_000) * 1_000) as u32,
+            )
+            .unwrap();
+        Err((last, last_micros - now_micros))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+
+    #[test]
+    fn timestamps_never_decrease() {
+        let guard = MonotonicGuard::new();
+        let start = Local.timestamp_opt(1_617_280_496, 987_654_000).unwrap();
+
+        assert_eq!(guard.guard(start), Ok(start));
+        assert_eq!(guard.guard(start - Duration::seconds(2)), Err((start, 2_000_000)));
+        assert_eq!(guard.guard(start - Duration::microseconds(1)), Err((start, 1)));
+        assert_eq!(guard.guard(start), Ok(start));
+        assert_eq!(guard.guard(start + Duration::seconds(1)), Ok(start + Duration::seconds(1)));
+    }
+}
@@ -0,0 +1,209 @@
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::sync::{
+    atomic::{AtomicI32, Ordering},
+    Mutex,
+};
+
+use log::Level;
+
+use crate::log_fs::LogWriter;
+
+/// Destination for the log lines otherwise written to stderr
+///
+/// Set with [`Glog::with_console_writer`](crate::Glog::with_console_writer). On `wasm32` targets,
+/// which have no stderr, the lines are written to the browser console with the `wasm` feature
+/// and buffered for [`take_wasm_logs`](crate::take_wasm_logs) without it.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Mutex;
+/// use log::*;
+/// use glog::{ConsoleWriter, Flags};
+///
+/// struct Collect(Mutex<Vec<String>>);
+///
+/// impl ConsoleWriter for Collect {
+///     fn write_line(&self, _level: Level, line: &str) {
+///         self.0.lock().unwrap().push(line.to_owned());
+///     }
+/// }
+///
+/// glog::new()
+///     .with_console_writer(Collect(Mutex::new(Vec::new())))
+///     .init(Flags {
+///         logtostderr: true,
+///         ..Default::default()
+///     })
+///     .unwrap();
+///
+/// info!("Collected instead of written to stderr");
+/// ```
+pub trait ConsoleWriter: Send + Sync {
+    /// Write a single formatted log line without a trailing newline
+    fn write_line(&self, level: Level, line: &str);
+
+    /// Whether the lines can be shown, checked once by [`Glog::try_init`](crate::Glog::try_init)
+    ///
+    /// Without a console the [`console_fallback`](crate::Flags::console_fallback) flag applies.
+    fn is_available(&self) -> bool {
+        true
+    }
+}
+
+/// What happens to the output for stderr when there is no console, see [`console_available`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConsoleFallback {
+    /// Write to stderr anyway
+    #[default]
+    Keep,
+    /// Drop the lines, they are still counted in the [statistics](crate::GlogHandle::stats_snapshot)
+    Disable,
+    /// Append the lines to `<program>.console.log` in the `log_dir`
+    RedirectToFile,
+}
+
+/// Whether stderr leads somewhere
+///
+/// False for a closed or invalid stderr, like for Windows services, and on Unix for a stderr
+/// redirected to `/dev/null`, like for daemons. A terminal, pipe or file is a console.
+pub fn console_available() -> bool {
+    #[cfg(unix)]
+    {
+        use nix::sys::stat::{fstat, stat, SFlag};
+
+        let Ok(stderr) = fstat(2) else {
+            return false;
+        };
+        if nix::unistd::isatty(2).unwrap_or(false) {
+            return true;
+        }
+        let is_char_device = SFlag::from_bits_truncate(stderr.st_mode) & SFlag::S_IFMT == SFlag::S_IFCHR;
+        !(is_char_device && stat("/dev/null").is_ok_and(|null| null.st_rdev == stderr.st_rdev))
+    }
+    #[cfg(windows)]
+    {
+        use crate::bindings::Windows::Win32::System::Console::{GetStdHandle, STD_ERROR_HANDLE};
+
+        !unsafe { GetStdHandle(STD_ERROR_HANDLE) }.is_invalid()
+    }
+    #[cfg(not(any(unix, windows)))]
+    true
+}
+
+/// [`RAW_TARGET`] while raw writes go to stderr
+const RAW_TO_STDERR: i32 = -1;
+/// [`RAW_TARGET`] while raw writes are dropped
+const RAW_DROPPED: i32 = -2;
+
+/// Where [`Glog::write_stderr_raw`](crate::Glog::write_stderr_raw) writes to, the descriptor of
+/// the console file with [`ConsoleFallback::RedirectToFile`]
+///
+/// An atomic instead of a lock, raw writes are made from panic hooks and crash handlers.
+static RAW_TARGET: AtomicI32 = AtomicI32::new(RAW_TO_STDERR);
+
+/// The duplicate of the console file, -1 until the first redirect to a file
+///
+/// It's never closed, threads which loaded it from [`RAW_TARGET`] before another redirect may
+/// still write to it. The next redirect to a file points it at the new file.
+#[cfg(unix)]
+static RAW_FILE: AtomicI32 = AtomicI32::new(-1);
+
+/// Where raw writes go without a console, see [`redirect_raw`]
+pub(crate) enum RawTarget {
+    Stderr,
+    Dropped,
+    /// The descriptor of the console file, which is duplicated
+    #[cfg(unix)]
+    File(RawFd),
+}
+
+/// Let [`write_raw`] write to `target` instead of stderr
+///
+/// A file which can't be duplicated leaves the raw writes on stderr.
+pub(crate) fn redirect_raw(target: RawTarget) {
+    let target = match target {
+        RawTarget::Stderr => RAW_TO_STDERR,
+        RawTarget::Dropped => RAW_DROPPED,
+        #[cfg(unix)]
+        RawTarget::File(fd) => {
+            use nix::fcntl::{fcntl, FcntlArg, FdFlag};
+
+            let duplicated = match RAW_FILE.load(Ordering::Relaxed) {
+                -1 => fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(3)),
+                raw_file => nix::unistd::dup2(fd, raw_file)
+                    .and_then(|raw_file| fcntl(raw_file, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).map(|_| raw_file)),
+            };
+            match duplicated {
+                Ok(raw_file) => {
+                    RAW_FILE.store(raw_file, Ordering::Relaxed);
+                    raw_file
+                }
+                Err(_) => RAW_TO_STDERR,
+            }
+        }
+    };
+    RAW_TARGET.store(target, Ordering::Relaxed);
+}
+
+/// Write the raw `message` like [`redirect_raw`] selected, returns false if it goes to stderr
+///
+/// Takes no lock and makes at most a single `write(2)` call.
+pub(crate) fn write_raw(message: &str) -> bool {
+    match RAW_TARGET.load(Ordering::Relaxed) {
+        RAW_TO_STDERR => false,
+        RAW_DROPPED => true,
+        #[cfg(unix)]
+        fd => {
+            let _ = nix::unistd::write(fd, message.as_bytes());
+            true
+        }
+        #[cfg(not(unix))]
+        _ => true,
+    }
+}
+
+/// Drops the lines with [`ConsoleFallback::Disable`]
+pub(crate) struct NoConsole;
+
+impl ConsoleWriter for NoConsole {
+    fn write_line(&self, _level: Level, _line: &str) {}
+}
+
+/// Appends the lines to a file with [`ConsoleFallback::RedirectToFile`]
+pub(crate) struct FileConsole(pub(crate) Mutex<Box<dyn LogWriter>>);
+
+impl ConsoleWriter for FileConsole {
+    fn write_line(&self, _level: Level, line: &str) {
+        // There is nowhere left to report the error to
+        let _ = self.0.lock().unwrap().write_line(b"", line.as_bytes());
+    }
+}
+
+/// The default console of `wasm32` targets
+#[cfg(target_arch = "wasm32")]
+pub(crate) struct WasmConsole;
+
+#[cfg(all(target_arch = "wasm32", feature = "web-sys"))]
+impl ConsoleWriter for WasmConsole {
+    fn write_line(&self, _level: Level, line: &str) {
+        web_sys::console::log_1(&line.into());
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys")))]
+static WASM_LOGS: std::sync::Mutex<Vec<String>> = std::sync::Mutex::new(Vec::new());
+
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys")))]
+impl ConsoleWriter for WasmConsole {
+    fn write_line(&self, _level: Level, line: &str) {
+        WASM_LOGS.lock().unwrap().push(line.to_owned());
+    }
+}
+
+/// Take all log lines buffered by the default console of `wasm32` targets without the `web-sys` feature
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys")))]
+pub fn take_wasm_logs() -> Vec<String> {
+    std::mem::take(&mut *WASM_LOGS.lock().unwrap())
+}
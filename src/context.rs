@@ -0,0 +1,200 @@
+//! Key value pairs added to every record logged within a scope
+//!
+//! The context is kept per thread by [`with_context`]. Futures which may be resumed on other
+//! threads keep their context with [`with_async_context`], [`task_scope`] or
+//! [`FutureExt::with_log_context`], which require the `tokio-context` feature. The context of the
+//! task takes precedence over the context of the thread.
+//!
+//! # Example
+//!
+//! ```
+//! use log::*;
+//! use glog::{context, Flags};
+//!
+//! glog::new().init(Flags::default()).unwrap();
+//!
+//! context::with_context(&[("request_id", "42")], || {
+//!     info!("Handling request");
+//!     // I0401 12:34:56.987654   123 doc.rs:9] [request_id=42] Handling request
+//! });
+//! ```
+
+#[cfg(feature = "tokio-context")]
+use std::future::Future;
+use std::{cell::RefCell, fmt};
+
+#[cfg(feature = "tokio-context")]
+use tokio::task::futures::TaskLocalFuture;
+
+type Context = Vec<(String, String)>;
+
+/// A future running with additional context, created by [`task_scope`]
+#[cfg(feature = "tokio-context")]
+pub type WithLogContext<F> = TaskLocalFuture<Context, F>;
+
+thread_local! {
+    static THREAD_CONTEXT: RefCell<Context> = const { RefCell::new(Vec::new()) };
+}
+
+#[cfg(feature = "tokio-context")]
+tokio::task_local! {
+    static TASK_CONTEXT: Context;
+}
+
+fn to_owned<'a>(kvs: &'a [(&str, &str)]) -> impl Iterator<Item = (String, String)> + 'a {
+    kvs.iter().map(|(key, value)| (key.to_string(), value.to_string()))
+}
+
+/// Restores the previous length of the thread context, even if the scope panics
+struct ThreadContextGuard(usize);
+
+impl Drop for ThreadContextGuard {
+    fn drop(&mut self) {
+        THREAD_CONTEXT.with(|context| context.borrow_mut().truncate(self.0));
+    }
+}
+
+/// Add `kvs` to the context of the current thread while `f` runs
+pub fn with_context<R>(kvs: &[(&str, &str)], f: impl FnOnce() -> R) -> R {
+    let _guard = THREAD_CONTEXT.with(|context| {
+        let mut context = context.borrow_mut();
+        let guard = ThreadContextGuard(context.len());
+        context.extend(to_owned(kvs));
+        guard
+    });
+    f()
+}
+
+/// Add `kvs` to the context of the current task while `fut` runs
+///
+/// Unlike [`with_context`] the context follows the future across `.await` points, even if the
+/// runtime resumes it on another thread.
+///
+/// ```
+/// # async fn handle_request() {}
+/// use glog::context;
+///
+/// # let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// # runtime.block_on(async {
+/// context::with_async_context(&[("request_id", "42")], async {
+///     handle_request().await;
+///     log::info!("Request handled");
+/// })
+/// .await;
+/// # });
+/// ```
+#[cfg(feature = "tokio-context")]
+pub async fn with_async_context<F: Future>(kvs: &[(&str, &str)], fut: F) -> F::Output {
+    task_scope(kvs, fut).await
+}
+
+/// Run `fut` with `kvs` added to the context it is created in
+///
+/// The context of the current task, or of the current thread outside of a task scope, is captured
+/// when calling this, so the returned future can be spawned and keeps the context on any thread.
+///
+/// ```
+/// # async fn handle_request() {}
+/// use glog::context;
+///
+/// # let runtime = tokio::runtime::Builder::new_multi_thread().build().unwrap();
+/// # runtime.block_on(async {
+/// tokio::spawn(context::task_scope(&[("request_id", "42")], async {
+///     handle_request().await;
+///     log::info!("Request handled");
+/// }))
+/// .await
+/// .unwrap();
+/// # });
+/// ```
+#[cfg(feature = "tokio-context")]
+pub fn task_scope<F: Future>(kvs: &[(&str, &str)], fut: F) -> WithLogContext<F> {
+    let mut context = TASK_CONTEXT
+        .try_with(Clone::clone)
+        .unwrap_or_else(|_| THREAD_CONTEXT.with(|context| context.borrow().clone()));
+    context.extend(to_owned(kvs));
+    TASK_CONTEXT.scope(context, fut)
+}
+
+/// Extension trait to add context to any future, see [`task_scope`]
+///
+/// ```
+/// # async fn handle_request() {}
+/// use glog::context::FutureExt as _;
+///
+/// # let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// # runtime.block_on(async {
+/// handle_request().with_log_context(&[("request_id", "42")]).await;
+/// # });
+/// ```
+#[cfg(feature = "tokio-context")]
+pub trait FutureExt: Future + Sized {
+    /// Run this future with `kvs` added to the context it is created in
+    fn with_log_context(self, kvs: &[(&str, &str)]) -> WithLogContext<Self> {
+        task_scope(kvs, self)
+    }
+}
+
+#[cfg(feature = "tokio-context")]
+impl<F: Future> FutureExt for F {}
+
+/// Call `f` with the context of the current task, or of the current thread outside of a task scope
+pub(crate) fn with_current<R>(f: impl FnOnce(&[(String, String)]) -> R) -> R {
+    #[cfg(feature = "tokio-context")]
+    if let Ok(context) = TASK_CONTEXT.try_with(Clone::clone) {
+        return f(&context);
+    }
+    THREAD_CONTEXT.with(|context| f(&context.borrow()))
+}
+
+/// Formats the current context as `[key=value ...] ` or nothing if it's empty
+pub(crate) struct Current;
+
+impl fmt::Display for Current {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        with_current(|context| {
+            if context.is_empty() {
+                return Ok(());
+            }
+            write!(f, "[")?;
+            for (i, (key, value)) in context.iter().enumerate() {
+                if i > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}={}", key, value)?;
+            }
+            write!(f, "] ")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_thread_context() {
+        assert_eq!(Current.to_string(), "");
+        with_context(&[("request_id", "42")], || {
+            with_context(&[("user", "alice")], || {
+                assert_eq!(Current.to_string(), "[request_id=42 user=alice] ");
+            });
+            assert_eq!(Current.to_string(), "[request_id=42] ");
+        });
+        assert_eq!(Current.to_string(), "");
+    }
+
+    #[cfg(feature = "tokio-context")]
+    #[test]
+    fn task_context_takes_precedence_across_await_points() {
+        let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(2).build().unwrap();
+        let context = runtime.block_on(async {
+            let task = with_async_context(&[("request_id", "42")], async {
+                tokio::task::spawn_blocking(|| {}).await.unwrap();
+                with_context(&[("thread", "ignored")], || Current.to_string())
+            });
+            tokio::spawn(task).await.unwrap()
+        });
+        assert_eq!(context, "[request_id=42] ");
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    RwLock,
+};
+#[cfg(unix)]
+use std::{io, iter, os::unix::io::RawFd};
+
+use backtrace::Backtrace;
+use log::{Level, Record};
+#[cfg(unix)]
+use nix::{
+    sys::signal::{self, SigHandler, Signal},
+    unistd,
+};
+
+use crate::{output, redact::INTERNAL_TARGET, trace, trace::BacktraceFilter, BacktraceStyle};
+
+/// Set by the first crash path writing a full report, e.g. a fatal error followed by the `SIGABRT`
+/// of the abort, so the others only note that they were suppressed
+static CRASH_REPORTED: AtomicBool = AtomicBool::new(false);
+
+/// Style and filter of the stack trace of fatal errors, set by the registered logger
+static FATAL_BACKTRACE: RwLock<(BacktraceStyle, Option<BacktraceFilter>)> = RwLock::new((BacktraceStyle::Auto, None));
+
+pub(crate) fn set_fatal_backtrace(style: BacktraceStyle, filter: Option<BacktraceFilter>) {
+    *FATAL_BACKTRACE.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = (style, filter);
+}
+
+/// Whether the caller is the first to report a crash and should write the full report
+///
+/// Async-signal-safe, atomic booleans are lock-free on all supported platforms.
+pub(crate) fn first_crash_report() -> bool {
+    !CRASH_REPORTED.swap(true, Ordering::SeqCst)
+}
+
+/// Whether a crash was reported, e.g. by a fatal error
+pub(crate) fn crash_reported() -> bool {
+    CRASH_REPORTED.load(Ordering::SeqCst)
+}
+
+/// Log the stack trace of a fatal error, or only a note if another crash path already reported
+pub(crate) fn log_fatal_report() {
+    let report = if first_crash_report() {
+        let (style, filter) = FATAL_BACKTRACE
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        let backtrace = trace::render_backtrace(&Backtrace::new(), style, filter.as_ref());
+        format!("*** Fatal error stack trace: ***\n{}", backtrace)
+    } else {
+        "*** suppressed duplicate crash report from the fatal error path ***".to_owned()
+    };
+    log::logger().log(
+        &Record::builder()
+            .level(Level::Error)
+            .target(INTERNAL_TARGET)
+            .args(format_args!("{}", report))
+            .build(),
+    );
+}
+
+/// Log file descriptors from the ERROR to the TRACE file, -1 for levels without a file
+#[cfg(unix)]
+pub(crate) type CrashFds = [RawFd; 5];
+
+/// Register handlers for `SIGSEGV`, `SIGBUS` and `SIGABRT` writing the signal and a stack trace to `fds`
+///
+/// The descriptors have to stay open for the lifetime of the process.
+#[cfg(unix)]
+pub(crate) fn install(fds: CrashFds) -> io::Result<()> {
+    for &signal in [Signal::SIGSEGV, Signal::SIGBUS, Signal::SIGABRT].iter() {
+        // Formatted up front, the handler must not allocate
+        let header = format!(
+            "*** {} received by PID {}; stack trace: ***\n",
+            signal.as_str(),
+            std::process::id()
+        )
+        .into_bytes();
+        let suppressed = format!(
+            "*** suppressed duplicate crash report from the {} handler ***\n",
+            signal.as_str()
+        )
+        .into_bytes();
+        // SAFETY: the action only calls async-signal-safe functions and doesn't allocate
+        unsafe {
+            signal_hook_registry::register_unchecked(signal as libc::c_int, move |info| {
+                on_crash(signal, &fds, &header, &suppressed, info)
+            })?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn on_crash(signal: Signal, fds: &CrashFds, header: &[u8], suppressed: &[u8], info: &libc::siginfo_t) {
+    if first_crash_report() {
+        write_report(fds, header, info);
+    } else {
+        write_all(fds, suppressed);
+    }
+    // Let the default action terminate the process, a fault raises the signal again anyway once
+    // the handler returns
+    // SAFETY: resetting to the default handler is always sound
+    let _ = unsafe { signal::signal(signal, SigHandler::SigDfl) };
+    let _ = signal::raise(signal);
+}
+
+#[cfg(unix)]
+fn write_report(fds: &CrashFds, header: &[u8], info: &libc::siginfo_t) {
+    let mut hex_buffer = [0; 18];
+    write_all(fds, header);
+    write_all(fds, b"*** fault address ");
+    // SAFETY: si_addr is set for SIGSEGV and SIGBUS
+    write_all(fds, hex(unsafe { info.si_addr() } as usize, &mut hex_buffer));
+    write_all(fds, b" ***\n");
+    // SAFETY: only instruction pointers are written, symbols aren't resolved as that allocates
+    unsafe {
+        backtrace::trace_unsynchronized(|frame| {
+            write_all(fds, b"    @ ");
+            write_all(fds, hex(frame.ip() as usize, &mut hex_buffer));
+            write_all(fds, b"\n");
+            true
+        });
+    }
+}
+
+/// Write `bytes` to stderr and all log files with `write(2)`
+#[cfg(unix)]
+fn write_all(fds: &CrashFds, bytes: &[u8]) {
+    for &fd in iter::once(&output::stderr_fd()).chain(fds.iter().filter(|fd| **fd >= 0)) {
+        let mut remaining = bytes;
+        while !remaining.is_empty() {
+            match unistd::write(fd, remaining) {
+                Ok(0) | Err(_) => break,
+                Ok(written) => remaining = &remaining[written..],
+            }
+        }
+    }
+}
+
+/// Format `value` as `0x...` into `buffer` without allocating
+#[cfg(unix)]
+fn hex(mut value: usize, buffer: &mut [u8; 18]) -> &[u8] {
+    let mut start = buffer.len();
+    loop {
+        start -= 1;
+        buffer[start] = b"0123456789abcdef"[value & 0xf];
+        value >>= 4;
+        if value == 0 {
+            break;
+        }
+    }
+    buffer[start - 2..start].copy_from_slice(b"0x");
+    &buffer[start - 2..]
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_without_allocating() {
+        let mut buffer = [0; 18];
+        assert_eq!(hex(0, &mut buffer), b"0x0");
+        assert_eq!(hex(0x7f3a_0042, &mut buffer), b"0x7f3a0042");
+        assert_eq!(hex(usize::MAX, &mut buffer), format!("{:#x}", usize::MAX).as_bytes());
+    }
+}
@@ -0,0 +1,181 @@
+//! Machine readable first and last lines of log files written with [`FileHeader::JsonEnvelope`]
+//!
+//! The first line of every level log file is an [`Envelope`] describing the process and the file, the
+//! last line is a [`Footer`] appended by [`GlogHandle::shutdown`](crate::GlogHandle::shutdown) or
+//! [`glog::exit`](crate::exit).
+//! A file recreated after external removal starts a new segment with its own envelope, linked to
+//! the previous segment by the name it was moved to, and the previous segment gets a footer. A file with an
+//! envelope but no footer belongs to a process which crashed or is still running, see
+//! [`FileStats::crashed`](crate::merge::FileStats::crashed).
+//!
+//! Both lines are single JSON objects, told apart from each other and from records by their first
+//! key. With the `serde` feature they can be deserialized into the structs, e.g. with `serde_json`.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+use chrono::{DateTime, Local, SecondsFormat};
+
+use crate::format::JsonString;
+
+/// The version of the envelope and footer layout, incremented on incompatible changes
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The start of the envelope line
+pub(crate) const ENVELOPE_PREFIX: &str = "{\"glog_envelope\":";
+/// The start of the footer line
+pub(crate) const FOOTER_PREFIX: &str = "{\"glog_footer\":";
+
+/// The header written at the start of every log file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileHeader {
+    /// The `Log file created at:` lines of glog
+    #[default]
+    Text,
+    /// A single line [`Envelope`] instead, with a [`Footer`] on shutdown
+    ///
+    /// Only for the level log files, the files of [`Flags::file_per_thread`](crate::Flags::file_per_thread)
+    /// and [`Glog::with_module_log_file`](crate::Glog::with_module_log_file) keep the text header.
+    JsonEnvelope,
+}
+
+/// The first line of a log file with [`FileHeader::JsonEnvelope`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Envelope {
+    /// The [`SCHEMA_VERSION`]
+    pub glog_envelope: u32,
+    pub program: String,
+    pub host: String,
+    pub user: String,
+    pub pid: u32,
+    /// Identifies the run of the process, shared by all of its log files
+    pub session: String,
+    /// RFC 3339 with microseconds
+    pub created: String,
+    /// The severity of the file, e.g. `INFO`
+    pub level: String,
+    /// The number of files created at this path by the process before
+    pub segment: u64,
+    /// The current file name of the previous segment
+    ///
+    /// `None` for the first segment and for segments whose predecessor was deleted, or where the
+    /// name of an open file can't be looked up, which is everywhere but Linux.
+    pub previous: Option<String>,
+    /// The values of all builtin flags by name, except for the `redaction_salt`
+    ///
+    /// Also has `console_available`, whether the process had a console when the logger was
+    /// initialized, see [`console_available`](crate::console_available).
+    pub flags: BTreeMap<String, String>,
+    /// The layout of the records, like the `Log line format:` line of the text header
+    pub format: String,
+}
+
+/// The last line of a log file with [`FileHeader::JsonEnvelope`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Footer {
+    /// The [`SCHEMA_VERSION`]
+    pub glog_footer: u32,
+    /// RFC 3339 with microseconds
+    pub end: String,
+    /// The number of records written to the file
+    pub records: u64,
+    /// Whether the file was closed without a preceding fatal error
+    pub clean: bool,
+}
+
+/// RFC 3339 with microseconds, as used for the times in the envelope and footer
+pub(crate) fn timestamp(time: DateTime<Local>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, false)
+}
+
+impl Envelope {
+    /// The envelope as a single line of JSON, starting with [`ENVELOPE_PREFIX`]
+    pub(crate) fn to_json(&self) -> String {
+        let mut json = format!(
+            "{}{},\"program\":{},\"host\":{},\"user\":{},\"pid\":{},\"session\":{},\"created\":{},\"level\":{},\"segment\":{},\"previous\":",
+            ENVELOPE_PREFIX,
+            self.glog_envelope,
+            JsonString(&self.program),
+            JsonString(&self.host),
+            JsonString(&self.user),
+            self.pid,
+            JsonString(&self.session),
+            JsonString(&self.created),
+            JsonString(&self.level),
+            self.segment,
+        );
+        match &self.previous {
+            Some(previous) => {
+                let _ = write!(json, "{}", JsonString(previous));
+            }
+            None => json.push_str("null"),
+        }
+        json.push_str(",\"flags\":{");
+        for (i, (name, value)) in self.flags.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            let _ = write!(json, "{}:{}", JsonString(name), JsonString(value));
+        }
+        let _ = write!(json, "}},\"format\":{}}}", JsonString(&self.format));
+        json
+    }
+}
+
+impl Footer {
+    /// The footer as a single line of JSON, starting with [`FOOTER_PREFIX`]
+    pub(crate) fn to_json(&self) -> String {
+        format!(
+            "{}{},\"end\":{},\"records\":{},\"clean\":{}}}",
+            FOOTER_PREFIX,
+            self.glog_footer,
+            JsonString(&self.end),
+            self.records,
+            self.clean
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_lines() {
+        let envelope = Envelope {
+            glog_envelope: SCHEMA_VERSION,
+            program: "server".to_owned(),
+            host: "host".to_owned(),
+            user: "alice".to_owned(),
+            pid: 42,
+            session: "42-1617280496000000".to_owned(),
+            created: "2021-04-01T12:34:56.000000+00:00".to_owned(),
+            level: "INFO".to_owned(),
+            segment: 1,
+            previous: Some("server.log.\"INFO\"".to_owned()),
+            flags: [("logtostderr", "false"), ("v", "0")]
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            format: "[IWE]mmdd hh:mm:ss.uuuuuu threadid file:line] msg".to_owned(),
+        };
+        assert_eq!(
+            envelope.to_json(),
+            "{\"glog_envelope\":1,\"program\":\"server\",\"host\":\"host\",\"user\":\"alice\",\"pid\":42,\
+             \"session\":\"42-1617280496000000\",\"created\":\"2021-04-01T12:34:56.000000+00:00\",\"level\":\"INFO\",\
+             \"segment\":1,\"previous\":\"server.log.\\\"INFO\\\"\",\"flags\":{\"logtostderr\":\"false\",\"v\":\"0\"},\
+             \"format\":\"[IWE]mmdd hh:mm:ss.uuuuuu threadid file:line] msg\"}"
+        );
+        let footer = Footer {
+            glog_footer: SCHEMA_VERSION,
+            end: "2021-04-01T12:35:00.000000+00:00".to_owned(),
+            records: 3,
+            clean: true,
+        };
+        assert_eq!(
+            footer.to_json(),
+            "{\"glog_footer\":1,\"end\":\"2021-04-01T12:35:00.000000+00:00\",\"records\":3,\"clean\":true}"
+        );
+    }
+}
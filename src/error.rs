@@ -0,0 +1,73 @@
+use std::{error::Error, fmt, io, path::PathBuf};
+
+use crate::{FlagConflict, FlagParseError};
+
+/// Errors returned when initializing the logger
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum GlogError {
+    /// Another logger was already registered with the [`log`] crate
+    SetLogger(log::SetLoggerError),
+    /// glog was already initialized, it can only be initialized once per process
+    AlreadyInitialized,
+    /// Logging to files isn't supported on this target, use `logtostderr` instead
+    FilesUnsupported,
+    /// One of the [`Flags`](crate::Flags) has an invalid value
+    InvalidFlag(FlagParseError),
+    /// A line passed to [`Glog::with_extra_headers`](crate::Glog::with_extra_headers) contains a newline
+    InvalidHeader(String),
+    /// The format passed to [`Glog::with_stderr_timestamp_format`](crate::Glog::with_stderr_timestamp_format) is invalid
+    InvalidTimestampFormat(String),
+    /// Two flags conflict and `strict_flags` is set
+    FlagConflict(FlagConflict),
+    /// A log file or its symlink couldn't be created
+    CreateLogFile(PathBuf, io::Error),
+}
+
+impl fmt::Display for GlogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlogError::SetLogger(why) => write!(f, "couldn't register the logger: {}", why),
+            GlogError::AlreadyInitialized => write!(f, "glog was already initialized"),
+            GlogError::FilesUnsupported => write!(f, "logging to files isn't supported on this target"),
+            GlogError::InvalidFlag(why) => write!(f, "{}", why),
+            GlogError::FlagConflict(conflict) => write!(f, "{}", conflict),
+            GlogError::InvalidHeader(line) => write!(f, "header line {:?} contains a newline", line),
+            GlogError::InvalidTimestampFormat(format) => write!(f, "invalid timestamp format {:?}", format),
+            GlogError::CreateLogFile(path, why) => write!(f, "couldn't create {}: {}", path.display(), why),
+        }
+    }
+}
+
+impl Error for GlogError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GlogError::SetLogger(why) => Some(why),
+            GlogError::AlreadyInitialized
+            | GlogError::FilesUnsupported
+            | GlogError::InvalidHeader(_)
+            | GlogError::InvalidTimestampFormat(_) => None,
+            GlogError::InvalidFlag(why) => Some(why),
+            GlogError::FlagConflict(conflict) => Some(conflict),
+            GlogError::CreateLogFile(_, why) => Some(why),
+        }
+    }
+}
+
+impl From<log::SetLoggerError> for GlogError {
+    fn from(why: log::SetLoggerError) -> Self {
+        GlogError::SetLogger(why)
+    }
+}
+
+impl From<FlagParseError> for GlogError {
+    fn from(why: FlagParseError) -> Self {
+        GlogError::InvalidFlag(why)
+    }
+}
+
+impl From<FlagConflict> for GlogError {
+    fn from(conflict: FlagConflict) -> Self {
+        GlogError::FlagConflict(conflict)
+    }
+}
@@ -0,0 +1,216 @@
+use std::{
+    error::Error,
+    fmt,
+    panic::Location,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use log::{Level, Record};
+
+static MAX_DEPTH: AtomicUsize = AtomicUsize::new(16);
+
+pub(crate) fn set_max_depth(max_depth: usize) {
+    MAX_DEPTH.store(max_depth, Ordering::Relaxed);
+}
+
+/// Displays `message` followed by a `caused by` line for each source of `error`
+pub(crate) struct ErrorChain<'a> {
+    pub(crate) error: &'a dyn Error,
+    pub(crate) message: fmt::Arguments<'a>,
+    pub(crate) max_depth: usize,
+}
+
+impl fmt::Display for ErrorChain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.message, self.error)?;
+        let mut source = self.error.source();
+        for _ in 0..self.max_depth {
+            match source {
+                Some(error) => {
+                    write!(f, "\n  caused by: {}", error)?;
+                    source = error.source();
+                }
+                None => return Ok(()),
+            }
+        }
+        if source.is_some() {
+            write!(f, "\n  ...")?;
+        }
+        Ok(())
+    }
+}
+
+/// Lets [`error_chain!`](crate::error_chain) accept boxed trait objects next to errors
+///
+/// Boxed trait objects like `Box<dyn Error + Send + Sync>` don't implement [`Error`]. The macro
+/// calls `as_dyn_error` on `&ErrorRef(&error)`, so method resolution prefers [`ViaBox`] for boxes
+/// and falls back to [`ViaError`] for all other errors.
+#[doc(hidden)]
+pub mod select {
+    use std::error::Error;
+
+    pub struct ErrorRef<'a, T: ?Sized>(pub &'a T);
+
+    pub trait ViaError<'a> {
+        fn as_dyn_error(&self) -> &'a (dyn Error + 'a);
+    }
+
+    impl<'a, T: Error + 'a> ViaError<'a> for ErrorRef<'a, T> {
+        fn as_dyn_error(&self) -> &'a (dyn Error + 'a) {
+            self.0
+        }
+    }
+
+    pub trait ViaBox<'a> {
+        fn as_dyn_error(&self) -> &'a (dyn Error + 'a);
+    }
+
+    macro_rules! via_box {
+        ($($bounds:tt)*) => {
+            impl<'a> ViaBox<'a> for &ErrorRef<'a, Box<dyn Error $($bounds)*>> {
+                fn as_dyn_error(&self) -> &'a (dyn Error + 'a) {
+                    &**self.0
+                }
+            }
+        };
+    }
+
+    via_box!();
+    via_box!(+ Send);
+    via_box!(+ Send + Sync);
+}
+
+/// Log `args` and `error` followed by every source of `error` on its own line
+///
+/// At most [`error_chain_max_depth`](crate::Flags::error_chain_max_depth) sources are logged.
+/// Usually called through [`error_chain!`](crate::error_chain).
+#[track_caller]
+pub fn log_error_chain(level: Level, error: &dyn Error, args: fmt::Arguments<'_>) {
+    if level > log::max_level() {
+        return;
+    }
+    let location = Location::caller();
+    log::logger().log(
+        &Record::builder()
+            .level(level)
+            .file(Some(location.file()))
+            .line(Some(location.line()))
+            .args(format_args!(
+                "{}",
+                ErrorChain {
+                    error,
+                    message: args,
+                    max_depth: MAX_DEPTH.load(Ordering::Relaxed),
+                }
+            ))
+            .build(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Local, NaiveDate, TimeZone};
+
+    use super::*;
+    use crate::format::{format_record, FormatOptions, MultilineStyle};
+
+    #[derive(Debug)]
+    struct Layer(&'static str, Option<Box<Layer>>);
+
+    impl fmt::Display for Layer {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    impl Error for Layer {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            self.1.as_deref().map(|source| source as &dyn Error)
+        }
+    }
+
+    fn chain(max_depth: usize) -> String {
+        let error = Layer(
+            "couldn't load config",
+            Some(Box::new(Layer(
+                "couldn't read file",
+                Some(Box::new(Layer("permission denied", None))),
+            ))),
+        );
+        ErrorChain {
+            error: &error,
+            message: format_args!("startup failed"),
+            max_depth,
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn multiline_styles() {
+        let format = |style| {
+            let naive = NaiveDate::from_ymd_opt(2021, 4, 1)
+                .unwrap()
+                .and_hms_micro_opt(12, 34, 56, 987)
+                .unwrap();
+            let opts = FormatOptions::default().multiline_style(style);
+            let timestamp = Local.from_local_datetime(&naive).unwrap();
+            format_record(
+                &opts,
+                timestamp,
+                Level::Error,
+                123,
+                "a.rs",
+                42,
+                &format_args!("{}", chain(16)),
+            )
+        };
+        assert_eq!(
+            format(MultilineStyle::Raw),
+            "E0401 12:34:56.000987   123 a.rs:42] startup failed: couldn't load config\n\
+             \x20 caused by: couldn't read file\n\
+             \x20 caused by: permission denied"
+        );
+        assert_eq!(
+            format(MultilineStyle::Prefixed),
+            "E0401 12:34:56.000987   123 a.rs:42] startup failed: couldn't load config\n\
+             E0401 12:34:56.000987   123 a.rs:42]   caused by: couldn't read file\n\
+             E0401 12:34:56.000987   123 a.rs:42]   caused by: permission denied"
+        );
+    }
+
+    #[test]
+    fn capped_at_max_depth() {
+        assert_eq!(
+            chain(1),
+            "startup failed: couldn't load config\n  caused by: couldn't read file\n  ..."
+        );
+        assert_eq!(chain(0), "startup failed: couldn't load config\n  ...");
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)] // The borrow selects the implementation like in the macro
+    fn boxed_and_borrowed_errors() {
+        use select::{ErrorRef, ViaBox, ViaError};
+
+        let source = Some(Box::new(Layer("permission denied", None)));
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(Layer("couldn't read file", source));
+        let borrowed: &dyn Error = &*boxed;
+        let layer = Layer("couldn't read file", None);
+        for error in [
+            (&ErrorRef(&boxed)).as_dyn_error(),
+            (&ErrorRef(&borrowed)).as_dyn_error(),
+            (&ErrorRef(&layer)).as_dyn_error(),
+        ] {
+            assert_eq!(error.to_string(), "couldn't read file");
+        }
+        assert_eq!(
+            (&ErrorRef(&boxed)).as_dyn_error().source().unwrap().to_string(),
+            "permission denied"
+        );
+
+        // Without a registered logger the records are dropped, the macro only has to accept them
+        crate::error_chain!(Level::Error, boxed, "boxed");
+        crate::error_chain!(Level::Error, borrowed, "borrowed");
+        crate::error_chain!(Level::Error, Box::<dyn Error>::from("plain box"), "owned");
+    }
+}
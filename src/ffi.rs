@@ -0,0 +1,71 @@
+//! C interface for logging from other languages
+//!
+//! The functions are declared in `include/glog.h`, which is generated with
+//! `cbindgen --config cbindgen.toml --output include/glog.h src/ffi.rs`. The logger has to be initialized
+//! from Rust before, records logged earlier are dropped.
+
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    ffi::CStr,
+    os::raw::{c_char, c_int},
+};
+
+use log::Level;
+
+/// `INFO` in the glog severity scheme
+pub const GLOG_INFO: c_int = 0;
+/// `WARNING` in the glog severity scheme
+pub const GLOG_WARNING: c_int = 1;
+/// `ERROR` in the glog severity scheme
+pub const GLOG_ERROR: c_int = 2;
+/// `FATAL` in the glog severity scheme, aborts the process after logging
+pub const GLOG_FATAL: c_int = 3;
+
+/// Log the nul terminated `msg` with the glog `severity` from `file` and `line`
+///
+/// Invalid UTF-8 is replaced with `U+FFFD`. Nothing is logged if `msg` is null, a null `file`
+/// is logged as an empty file name and a negative `line` as 0. Unknown severities are logged as
+/// errors.
+///
+/// # Safety
+///
+/// `file` and `msg` have to be null or point to nul terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn glog_log(severity: c_int, file: *const c_char, line: c_int, msg: *const c_char) {
+    let Some(message) = c_str(msg) else {
+        return;
+    };
+    let level = match severity {
+        GLOG_INFO => Level::Info,
+        GLOG_WARNING => Level::Warn,
+        _ => Level::Error,
+    };
+    crate::log_str(
+        level,
+        &c_str(file).unwrap_or_default(),
+        u32::try_from(line).unwrap_or(0),
+        &message,
+    );
+    if severity == GLOG_FATAL {
+        crate::__abort();
+    }
+}
+
+/// Flush all log output
+#[no_mangle]
+pub extern "C" fn glog_flush() {
+    log::logger().flush();
+}
+
+/// The lossily converted string `ptr` points to, `None` for null pointers
+///
+/// # Safety
+///
+/// `ptr` has to be null or point to a nul terminated string.
+unsafe fn c_str<'a>(ptr: *const c_char) -> Option<Cow<'a, str>> {
+    if ptr.is_null() {
+        return None;
+    }
+    Some(CStr::from_ptr(ptr).to_string_lossy())
+}
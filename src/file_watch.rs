@@ -0,0 +1,129 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+use log::Record;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::LogSink;
+
+/// A [`LogSink`] streaming lines appended to the files of a directory, e.g. for test frameworks
+///
+/// Lines appended to files in the watched directory, including files created later like rotated
+/// log files, are sent to the [`receiver`](FileWatchSink::receiver). Records logged through the
+/// sink are sent directly. Lines already in the files when the sink is created are skipped.
+///
+/// Watching the `log_dir` of the same logger delivers its lines twice, once through the sink and
+/// once for every log file they are written to.
+///
+/// # Example
+///
+/// ```
+/// use log::*;
+/// use glog::{FileWatchSink, Flags};
+///
+/// let sink = FileWatchSink::new(std::env::temp_dir().join("other-service")).unwrap();
+/// let lines = sink.receiver();
+/// glog::new().with_sink(sink).init(Flags::default()).unwrap();
+///
+/// info!("Started");
+/// assert!(lines.recv().unwrap().ends_with("] Started"));
+/// ```
+pub struct FileWatchSink {
+    sender: mpsc::Sender<String>,
+    receiver: Mutex<Option<mpsc::Receiver<String>>>,
+    // Stops the tailing thread when dropped
+    _watcher: Mutex<RecommendedWatcher>,
+}
+
+impl FileWatchSink {
+    /// Start watching `dir`, which is created if it doesn't exist
+    pub fn new(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let (sender, receiver) = mpsc::channel();
+        let (events_sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(events_sender).map_err(io::Error::other)?;
+        let mut offsets = HashMap::new();
+        for entry in fs::read_dir(&dir)?.flatten() {
+            if let Ok(metadata) = fs::symlink_metadata(entry.path()) {
+                offsets.insert(entry.path(), metadata.len());
+            }
+        }
+        watcher.watch(&dir, RecursiveMode::NonRecursive).map_err(io::Error::other)?;
+
+        let lines = sender.clone();
+        thread::Builder::new().name("glog-file-watch".to_owned()).spawn(move || {
+            for event in events.into_iter().flatten() {
+                if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                    continue;
+                }
+                for path in event.paths {
+                    let offset = offsets.entry(path.clone()).or_insert(0);
+                    // Files removed in the meantime have nothing left to send
+                    for line in tail(&path, offset).unwrap_or_default() {
+                        if lines.send(line).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        })?;
+
+        Ok(FileWatchSink {
+            sender,
+            receiver: Mutex::new(Some(receiver)),
+            _watcher: Mutex::new(watcher),
+        })
+    }
+
+    /// The lines of the watched files and the records written to the sink
+    ///
+    /// # Panics
+    ///
+    /// If called more than once.
+    pub fn receiver(&self) -> mpsc::Receiver<String> {
+        self.receiver.lock().unwrap().take().expect("the receiver was already taken")
+    }
+}
+
+/// The complete lines of `path` after `offset`, advancing it past them
+///
+/// Symlinks, like the ones to the latest log files, are skipped so no line is sent twice.
+fn tail(path: &Path, offset: &mut u64) -> io::Result<Vec<String>> {
+    if !fs::symlink_metadata(path)?.is_file() {
+        return Ok(Vec::new());
+    }
+    let mut file = File::open(path)?;
+    if file.metadata()?.len() < *offset {
+        // Truncated, start over
+        *offset = 0;
+    }
+    file.seek(SeekFrom::Start(*offset))?;
+    let mut appended = Vec::new();
+    file.read_to_end(&mut appended)?;
+    // A partially written line is read again once it is complete
+    let complete = appended
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |newline| newline + 1);
+    *offset += complete as u64;
+    Ok(appended[..complete]
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| String::from_utf8_lossy(line).into_owned())
+        .collect())
+}
+
+impl LogSink for FileWatchSink {
+    fn write(&self, _record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+        // Nobody is listening anymore if the receiver was dropped
+        let _ = self.sender.send(message.to_owned());
+        Ok(())
+    }
+}
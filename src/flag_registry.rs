@@ -0,0 +1,803 @@
+//! Parsing of glog-style command lines and environments into [`Flags`] and application flags
+
+use std::{
+    borrow::Cow,
+    env,
+    error::Error,
+    ffi::OsString,
+    fmt,
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use log::Level;
+
+use crate::{call_site::CallSites, format::MultilineStyle, BacktraceStyle, ByteSize, ConsoleFallback, Flags};
+
+/// A flag with a value glog can't use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagParseError {
+    pub(crate) flag: Cow<'static, str>,
+    pub(crate) value: String,
+    pub(crate) reason: &'static str,
+}
+
+impl FlagParseError {
+    /// Name of the invalid flag
+    pub fn flag(&self) -> &str {
+        &self.flag
+    }
+
+    /// The rejected value
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl fmt::Display for FlagParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid value {:?} for {}: {}", self.value, self.flag, self.reason)
+    }
+}
+
+impl Error for FlagParseError {}
+
+/// Split a `file:line` call site of `flag` into the file name and line number, `None` for `file:*` if `wildcard` is allowed
+pub(crate) fn parse_call_site(flag: &'static str, s: &str, wildcard: bool) -> Result<(String, Option<u32>), FlagParseError> {
+    let error = |reason| FlagParseError {
+        flag: flag.into(),
+        value: s.to_owned(),
+        reason,
+    };
+    let (file, line) = s.rsplit_once(':').ok_or_else(|| error("expected file:line"))?;
+    if file.is_empty() {
+        return Err(error("the file name is empty"));
+    }
+    if wildcard && line == "*" {
+        return Ok((file.to_owned(), None));
+    }
+    let line = line.parse().map_err(|_| error("the line isn't a number"))?;
+    Ok((file.to_owned(), Some(line)))
+}
+
+/// Split a `log_backtrace_at` value into the file name and line number
+pub(crate) fn parse_log_backtrace_at(s: &str) -> Result<(String, u32), FlagParseError> {
+    let (file, line) = parse_call_site("log_backtrace_at", s, false)?;
+    Ok((file, line.unwrap_or_default()))
+}
+
+/// Parse the call sites of `suppress_at`, each value may contain several separated by commas
+pub(crate) fn parse_suppress_at(values: &[String]) -> Result<CallSites, FlagParseError> {
+    let mut call_sites = CallSites::default();
+    for call_site in values.iter().flat_map(|value| value.split(',')).map(str::trim) {
+        if !call_site.is_empty() {
+            let (file, line) = parse_call_site("suppress_at", call_site, true)?;
+            call_sites.insert(file, line);
+        }
+    }
+    Ok(call_sites)
+}
+
+/// Parse a `minloglevel` value, a level name or a glog severity number
+///
+/// Level names are case-insensitive and include glog's `WARNING`. The numbers are the glog
+/// severities `0` for `INFO`, `1` for `WARNING` and `2` or `3` (`FATAL`) for `ERROR`, extended
+/// by the verbose levels `-1` for `DEBUG` and `-2` for `TRACE`.
+///
+/// # Example
+///
+/// ```
+/// use log::Level;
+///
+/// assert_eq!(glog::parse_minloglevel("debug"), Ok(Level::Debug));
+/// assert_eq!(glog::parse_minloglevel("1"), Ok(Level::Warn));
+/// assert_eq!(glog::parse_minloglevel("-2"), Ok(Level::Trace));
+/// assert!(glog::parse_minloglevel("verbose").is_err());
+/// ```
+pub fn parse_minloglevel(s: &str) -> Result<Level, FlagParseError> {
+    let value = s.trim();
+    let level = match value.to_ascii_uppercase().as_str() {
+        "TRACE" | "-2" => Level::Trace,
+        "DEBUG" | "-1" => Level::Debug,
+        "INFO" | "0" => Level::Info,
+        "WARN" | "WARNING" | "1" => Level::Warn,
+        "ERROR" | "2" | "3" => Level::Error,
+        _ => {
+            return Err(FlagParseError {
+                flag: "minloglevel".into(),
+                value: s.to_owned(),
+                reason: "expected one of TRACE, DEBUG, INFO, WARN or ERROR, or a glog severity from -2 to 3",
+            })
+        }
+    };
+    Ok(level)
+}
+
+/// A type of a flag in a [`FlagRegistry`]
+pub trait FlagValue: Clone + Send + Sync + 'static {
+    /// Parse a value from the command line or environment, the error is the reason it's rejected
+    fn parse_flag(s: &str) -> Result<Self, &'static str>;
+
+    /// The value as it's given on the command line
+    fn display_flag(&self) -> String;
+
+    /// The value of the flag given without one, like `--logtostderr`
+    fn implicit() -> Option<Self> {
+        None
+    }
+}
+
+impl FlagValue for bool {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "true" | "yes" | "1" => Ok(true),
+            "false" | "no" | "0" => Ok(false),
+            _ => Err("expected true or false"),
+        }
+    }
+
+    fn display_flag(&self) -> String {
+        self.to_string()
+    }
+
+    fn implicit() -> Option<Self> {
+        Some(true)
+    }
+}
+
+impl FlagValue for String {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        Ok(s.to_owned())
+    }
+
+    fn display_flag(&self) -> String {
+        self.clone()
+    }
+}
+
+impl FlagValue for OsString {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        Ok(s.into())
+    }
+
+    fn display_flag(&self) -> String {
+        self.to_string_lossy().into_owned()
+    }
+}
+
+macro_rules! integer_flag_values {
+    ($($integer:ty),*) => {
+        $(
+            impl FlagValue for $integer {
+                fn parse_flag(s: &str) -> Result<Self, &'static str> {
+                    s.trim().parse().map_err(|_| "expected a whole number")
+                }
+
+                fn display_flag(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+integer_flag_values!(u32, u64, usize, i64);
+
+/// Units of durations and their length in milliseconds
+const DURATION_UNITS: &[(&str, u64)] = &[("ms", 1), ("s", 1000), ("m", 60 * 1000), ("h", 60 * 60 * 1000)];
+
+/// Parsed from a whole number with one of the units `ms`, `s`, `m` or `h`, seconds without a unit
+impl FlagValue for Duration {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        let value = s.trim();
+        let (number, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len()));
+        let number: u64 = number.parse().map_err(|_| "expected a whole number with an optional unit")?;
+        let multiplier = match unit {
+            "" => 1000,
+            unit => match DURATION_UNITS.iter().find(|(name, _)| *name == unit) {
+                Some((_, multiplier)) => *multiplier,
+                None => return Err("expected one of the units ms, s, m or h"),
+            },
+        };
+        number
+            .checked_mul(multiplier)
+            .map(Duration::from_millis)
+            .ok_or("the duration is too long")
+    }
+
+    #[allow(clippy::manual_is_multiple_of)] // is_multiple_of needs Rust 1.87
+    fn display_flag(&self) -> String {
+        let millis = self.as_millis();
+        match DURATION_UNITS
+            .iter()
+            .rev()
+            .find(|(_, multiplier)| millis != 0 && millis % u128::from(*multiplier) == 0)
+        {
+            Some((unit, multiplier)) => format!("{}{}", millis / u128::from(*multiplier), unit),
+            None => format!("{}ms", millis),
+        }
+    }
+}
+
+impl FlagValue for ByteSize {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        s.parse().map_err(|why: FlagParseError| why.reason)
+    }
+
+    fn display_flag(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Parsed with [`parse_minloglevel`]
+impl FlagValue for Level {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        parse_minloglevel(s).map_err(|why| why.reason)
+    }
+
+    fn display_flag(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl FlagValue for MultilineStyle {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "raw" => Ok(MultilineStyle::Raw),
+            "prefixed" => Ok(MultilineStyle::Prefixed),
+            _ => Err("expected raw or prefixed"),
+        }
+    }
+
+    fn display_flag(&self) -> String {
+        format!("{:?}", self).to_ascii_lowercase()
+    }
+}
+
+impl FlagValue for BacktraceStyle {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "short" => Ok(BacktraceStyle::Short),
+            "full" => Ok(BacktraceStyle::Full),
+            "auto" => Ok(BacktraceStyle::Auto),
+            _ => Err("expected short, full or auto"),
+        }
+    }
+
+    fn display_flag(&self) -> String {
+        format!("{:?}", self).to_ascii_lowercase()
+    }
+}
+
+impl FlagValue for ConsoleFallback {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "keep" => Ok(ConsoleFallback::Keep),
+            "disable" => Ok(ConsoleFallback::Disable),
+            "redirect_to_file" => Ok(ConsoleFallback::RedirectToFile),
+            _ => Err("expected keep, disable or redirect_to_file"),
+        }
+    }
+
+    fn display_flag(&self) -> String {
+        match self {
+            ConsoleFallback::Keep => "keep",
+            ConsoleFallback::Disable => "disable",
+            ConsoleFallback::RedirectToFile => "redirect_to_file",
+        }
+        .to_owned()
+    }
+}
+
+/// An empty value is `None`
+impl<T: FlagValue> FlagValue for Option<T> {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        if s.is_empty() {
+            return Ok(None);
+        }
+        T::parse_flag(s).map(Some)
+    }
+
+    fn display_flag(&self) -> String {
+        self.as_ref().map(T::display_flag).unwrap_or_default()
+    }
+}
+
+/// A comma-separated list
+impl FlagValue for Vec<String> {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        Ok(s.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_owned)
+            .collect())
+    }
+
+    fn display_flag(&self) -> String {
+        self.join(",")
+    }
+}
+
+/// A comma-separated list of `LEVEL=directory`
+impl FlagValue for Vec<(Level, PathBuf)> {
+    fn parse_flag(s: &str) -> Result<Self, &'static str> {
+        s.split(',')
+            .filter(|item| !item.trim().is_empty())
+            .map(|item| {
+                let (level, dir) = item
+                    .split_once('=')
+                    .ok_or("expected a comma-separated list of LEVEL=directory")?;
+                Ok((Level::parse_flag(level)?, PathBuf::from(dir.trim())))
+            })
+            .collect()
+    }
+
+    fn display_flag(&self) -> String {
+        let items: Vec<_> = self
+            .iter()
+            .map(|(level, dir)| format!("{}={}", level, dir.display()))
+            .collect();
+        items.join(",")
+    }
+}
+
+/// Parse a flag value, `None` if the flag was given without one
+fn parse<T: FlagValue>(value: Option<&str>) -> Result<T, &'static str> {
+    match value {
+        Some(value) => T::parse_flag(value),
+        None => T::implicit().ok_or("expected a value"),
+    }
+}
+
+fn is_switch<T: FlagValue>(_: &T) -> bool {
+    T::implicit().is_some()
+}
+
+/// A field of [`Flags`] which can be set by name
+struct Builtin {
+    name: &'static str,
+    description: &'static str,
+    set: fn(&mut Flags, Option<&str>) -> Result<(), &'static str>,
+    display: fn(&Flags) -> String,
+    is_switch: fn(&Flags) -> bool,
+}
+
+macro_rules! builtins {
+    ($($name:ident: $description:literal,)*) => {
+        &[$(
+            Builtin {
+                name: stringify!($name),
+                description: $description,
+                set: |flags, value| {
+                    flags.$name = parse(value)?;
+                    Ok(())
+                },
+                display: |flags| flags.$name.display_flag(),
+                is_switch: |flags| is_switch(&flags.$name),
+            },
+        )*]
+    };
+}
+
+const BUILTINS: &[Builtin] = builtins! {
+    colorlogtostderr: "Colorize levels more severe than INFO when logging to stderr",
+    minloglevel: "Minimum level that is logged",
+    log_backtrace_at: "Log a backtrace at this file:line",
+    logtostderr: "Log to stderr instead of log files",
+    alsologtostderr: "Log to stderr and log files",
+    stderrthreshold: "Also log this level and more severe ones to stderr",
+    log_dir: "Directory of the log files",
+    trim_source_prefix: "Strip this prefix from logged source paths",
+    preallocate_log_bytes: "Reserve this much disk space for every new log file",
+    log_dir_overrides: "Directories of the log files of single levels, as LEVEL=directory",
+    detect_deleted_files: "Recreate log files which were removed",
+    exit_on_dfatal: "Abort on dfatal! in release builds as well",
+    monotonic_timestamps: "Never log a timestamp earlier than a previous one",
+    log_hostname_as_fqdn: "Use the fully qualified domain name of the host",
+    sanitize_messages: "Escape control characters in messages",
+    stderr_verbose_level: "Prefix lines on stderr with the full level name",
+    multiline_style: "How messages spanning several lines are written, raw or prefixed",
+    error_chain_max_depth: "Maximum number of sources logged by error_chain!",
+    backtrace_style: "Detail of logged backtraces, short, full or auto",
+    redact_stderr: "Apply the redactor to stderr as well",
+    redaction_salt: "Salt of redacted hashes",
+    tail_buffer_size: "Lines per level kept in memory for tail, 0 disables it",
+    logtostderr_if_no_log_dir: "Log to stderr if a log file can't be created",
+    module_logs_to_level_files: "Write records of module log files to the level log files as well",
+    strict_flags: "Fail initialization on conflicting flags",
+    lock_shared_files: "Lock log files around every write",
+    file_per_thread: "Write the records of every thread to its own log files",
+    log_crate_version: "Write the version of glog-rs into the log file headers",
+    color_reset_on_newline: "Reset the color before the newline instead of after it",
+    attachment_max_bytes: "Bytes of an attachment written below the line",
+    attachment_ttl: "Drop an attachment if no record is logged within this time",
+    header_include_stats: "Write the number of messages so far into the log file headers",
+    suppress_at: "Drop the records logged at these file:line call sites",
+    log_index_bytes: "Index the log files whenever this much was written",
+    log_index_interval: "Index the log files whenever this much time passed",
+    adaptive_error_threshold: "Lower the minimum level after more WARNING and ERROR records than this",
+    adaptive_error_window: "The time adaptive_error_threshold counts the records of",
+    adaptive_min_level: "The minimum level after adaptive_error_threshold was exceeded",
+    adaptive_duration: "The time the minimum level stays lowered after the last burst",
+    adaptive_dump_ring_buffer: "Write the buffered records below the minimum level on escalation",
+    log_target: "Write the target of the records into the log lines",
+    target_policy: "Levels, sampling and rate limits of targets like hyper=warn,sqlx=info@sample:0.1",
+    console_fallback: "Without a console keep, disable or redirect_to_file the output for stderr",
+    fsync_on: "Sync the log files to disk after records of this level or more severe ones",
+};
+
+/// The values of all builtin flags in `flags` by name
+pub(crate) fn snapshot(flags: &Flags) -> impl Iterator<Item = (&'static str, String)> + '_ {
+    BUILTINS.iter().map(move |builtin| (builtin.name, (builtin.display)(flags)))
+}
+
+/// The value of a flag registered with [`FlagRegistry::register`]
+pub struct FlagHandle<T>(Arc<RwLock<T>>);
+
+impl<T> Clone for FlagHandle<T> {
+    fn clone(&self) -> Self {
+        FlagHandle(self.0.clone())
+    }
+}
+
+impl<T: FlagValue> FlagHandle<T> {
+    /// The parsed value, or the default if the flag wasn't given
+    pub fn get(&self) -> T {
+        self.0.read().unwrap().clone()
+    }
+}
+
+/// A registered application flag, erasing its type
+trait CustomFlag: Send + Sync {
+    fn set(&self, value: Option<&str>) -> Result<(), &'static str>;
+
+    fn is_switch(&self) -> bool;
+}
+
+impl<T: FlagValue> CustomFlag for FlagHandle<T> {
+    fn set(&self, value: Option<&str>) -> Result<(), &'static str> {
+        *self.0.write().unwrap() = parse(value)?;
+        Ok(())
+    }
+
+    fn is_switch(&self) -> bool {
+        T::implicit().is_some()
+    }
+}
+
+enum Slot {
+    Builtin(&'static Builtin),
+    Custom(Box<dyn CustomFlag>),
+}
+
+struct Entry {
+    name: &'static str,
+    description: &'static str,
+    default: String,
+    slot: Slot,
+}
+
+/// What [`FlagRegistry::parse_args`] does with flags which aren't registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownFlags {
+    /// Fail with a [`FlagParseError`]
+    Error,
+    /// Return them with the other arguments
+    PassThrough,
+}
+
+/// The logging [`Flags`] and the flags of an application, set from the command line and environment
+///
+/// Flags are given as `--name=value`, `--name value` or with a single dash. Flags of type `bool`
+/// may be given without a value to set them. Everything after `--` is left to the application.
+/// The environment variable of a flag is its name prefixed with `GLOG_`, e.g. `GLOG_logtostderr`.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use glog::FlagRegistry;
+///
+/// let mut registry = FlagRegistry::new();
+/// let timeout = registry.register("request_timeout", Duration::from_secs(5), "Timeout of a request");
+///
+/// registry.parse_env().unwrap();
+/// let args = registry.parse_args(["--logtostderr", "--request_timeout=500ms", "input.txt"]).unwrap();
+///
+/// assert_eq!(args, ["input.txt"]);
+/// assert_eq!(timeout.get(), Duration::from_millis(500));
+/// let flags = registry.into_flags();
+/// assert!(flags.logtostderr);
+/// // glog::new().init(flags).unwrap();
+/// ```
+pub struct FlagRegistry {
+    flags: Flags,
+    entries: Vec<Entry>,
+    unknown_flags: UnknownFlags,
+}
+
+impl Default for FlagRegistry {
+    fn default() -> Self {
+        let flags = Flags::default();
+        let entries = BUILTINS
+            .iter()
+            .map(|builtin| Entry {
+                name: builtin.name,
+                description: builtin.description,
+                default: (builtin.display)(&flags),
+                slot: Slot::Builtin(builtin),
+            })
+            .collect();
+        FlagRegistry {
+            flags,
+            entries,
+            unknown_flags: UnknownFlags::Error,
+        }
+    }
+}
+
+impl FlagRegistry {
+    /// A registry of the logging flags with their defaults
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Change what happens to flags which aren't registered, they are errors by default
+    pub fn with_unknown_flags(mut self, unknown_flags: UnknownFlags) -> Self {
+        self.unknown_flags = unknown_flags;
+        self
+    }
+
+    /// Add a flag of the application, its value is read through the returned handle
+    ///
+    /// # Panics
+    ///
+    /// If a flag with this name is already registered, including the logging flags.
+    pub fn register<T: FlagValue>(&mut self, name: &'static str, default: T, description: &'static str) -> FlagHandle<T> {
+        assert!(self.entry(name).is_none(), "the flag {} is already registered", name);
+        let handle = FlagHandle(Arc::new(RwLock::new(default.clone())));
+        self.entries.push(Entry {
+            name,
+            description,
+            default: default.display_flag(),
+            slot: Slot::Custom(Box::new(handle.clone())),
+        });
+        handle
+    }
+
+    /// The logging flags as parsed so far
+    pub fn flags(&self) -> &Flags {
+        &self.flags
+    }
+
+    /// The logging flags to initialize glog with
+    pub fn into_flags(self) -> Flags {
+        self.flags
+    }
+
+    fn entry(&self, name: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.name == name)
+    }
+
+    fn set(&mut self, name: &str, value: Option<&str>) -> Result<(), FlagParseError> {
+        let Some(entry) = self.entries.iter().find(|entry| entry.name == name) else {
+            return Err(FlagParseError {
+                flag: name.to_owned().into(),
+                value: value.unwrap_or_default().to_owned(),
+                reason: "unknown flag",
+            });
+        };
+        let result = match &entry.slot {
+            Slot::Builtin(builtin) => (builtin.set)(&mut self.flags, value),
+            Slot::Custom(custom) => custom.set(value),
+        };
+        result.map_err(|reason| FlagParseError {
+            flag: entry.name.into(),
+            value: value.unwrap_or_default().to_owned(),
+            reason,
+        })
+    }
+
+    /// Set the flags given in `args`, which don't include the program name, and return the other arguments
+    pub fn parse_args<I, S>(&mut self, args: I) -> Result<Vec<String>, FlagParseError>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let mut args = args.into_iter().map(Into::into);
+        let mut rest = Vec::new();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                rest.extend(args);
+                break;
+            }
+            let flag = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-'));
+            // Arguments like `-` or `-1` aren't flags
+            let Some(flag) = flag.filter(|flag| flag.starts_with(|c: char| c.is_ascii_alphabetic())) else {
+                rest.push(arg);
+                continue;
+            };
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_owned())),
+                None => (flag, None),
+            };
+            let is_switch = match self.entry(name).map(|entry| &entry.slot) {
+                Some(Slot::Builtin(builtin)) => (builtin.is_switch)(&self.flags),
+                Some(Slot::Custom(custom)) => custom.is_switch(),
+                None if self.unknown_flags == UnknownFlags::PassThrough => {
+                    rest.push(arg);
+                    continue;
+                }
+                None => true,
+            };
+            let value = match value {
+                None if !is_switch => args.next(),
+                value => value,
+            };
+            self.set(name, value.as_deref())?;
+        }
+        Ok(rest)
+    }
+
+    /// Set the flags with a `GLOG_<name>` environment variable
+    pub fn parse_env(&mut self) -> Result<(), FlagParseError> {
+        let names: Vec<_> = self.entries.iter().map(|entry| entry.name).collect();
+        for name in names {
+            match env::var(format!("GLOG_{}", name)) {
+                Ok(value) => self.set(name, Some(&value))?,
+                Err(env::VarError::NotPresent) => {}
+                Err(env::VarError::NotUnicode(value)) => {
+                    return Err(FlagParseError {
+                        flag: name.into(),
+                        value: value.to_string_lossy().into_owned(),
+                        reason: "the value isn't valid unicode",
+                    })
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A listing of all flags with their defaults and descriptions, the logging flags first
+    pub fn describe(&self) -> String {
+        let mut description = String::new();
+        for entry in &self.entries {
+            description.push_str(&format!("  --{}", entry.name));
+            if !entry.default.is_empty() {
+                description.push_str(&format!(" (default: {})", entry.default));
+            }
+            description.push_str(&format!("\n      {}\n", entry.description));
+        }
+        description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minloglevels() {
+        let cases: &[(&str, Option<Level>)] = &[
+            ("TRACE", Some(Level::Trace)),
+            ("debug", Some(Level::Debug)),
+            ("Info", Some(Level::Info)),
+            (" WARN ", Some(Level::Warn)),
+            ("warning", Some(Level::Warn)),
+            ("ERROR", Some(Level::Error)),
+            ("0", Some(Level::Info)),
+            ("1", Some(Level::Warn)),
+            ("2", Some(Level::Error)),
+            ("3", Some(Level::Error)),
+            ("4", None),
+            ("-1", Some(Level::Debug)),
+            ("-2", Some(Level::Trace)),
+            ("-3", None),
+            ("fatal", None),
+            ("", None),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_minloglevel(value).ok(), *expected, "{:?}", value);
+        }
+        assert_eq!(
+            parse_minloglevel("verbose").unwrap_err().to_string(),
+            "invalid value \"verbose\" for minloglevel: expected one of TRACE, DEBUG, INFO, WARN or ERROR, or a glog severity from -2 to 3"
+        );
+        let mut registry = FlagRegistry::new();
+        registry.parse_args(["--minloglevel", "-1"]).unwrap();
+        assert_eq!(registry.flags().minloglevel, Level::Debug);
+    }
+
+    #[test]
+    fn valid_log_backtrace_at() {
+        assert_eq!(parse_log_backtrace_at("main.rs:42"), Ok(("main.rs".to_owned(), 42)));
+        assert_eq!(parse_log_backtrace_at("src/a:b.rs:7"), Ok(("src/a:b.rs".to_owned(), 7)));
+    }
+
+    #[test]
+    fn invalid_log_backtrace_at() {
+        for value in [
+            "src/foo.rs:abc",
+            "main.rs",
+            "main.rs:",
+            ":42",
+            "main.rs:-1",
+            "main.rs:4294967296",
+        ]
+        .iter()
+        {
+            let error = parse_log_backtrace_at(value).unwrap_err();
+            assert_eq!(error.flag(), "log_backtrace_at");
+            assert_eq!(error.value(), *value);
+        }
+        assert_eq!(
+            parse_log_backtrace_at("src/foo.rs:abc").unwrap_err().to_string(),
+            "invalid value \"src/foo.rs:abc\" for log_backtrace_at: the line isn't a number"
+        );
+        assert!(parse_log_backtrace_at("main.rs:*").is_err());
+    }
+
+    #[test]
+    fn suppress_at_lists() {
+        let call_sites = parse_suppress_at(&["a.rs:1, a.rs:2".to_owned(), "b.rs:*".to_owned(), String::new()]).unwrap();
+        assert!(call_sites.contains("a.rs", 1) && call_sites.contains("a.rs", 2) && call_sites.contains("b.rs", 7));
+        assert!(!call_sites.contains("a.rs", 3));
+        assert_eq!(
+            parse_suppress_at(&["a.rs:1,a.rs".to_owned()]).unwrap_err().to_string(),
+            "invalid value \"a.rs\" for suppress_at: expected file:line"
+        );
+    }
+
+    #[test]
+    fn durations() {
+        let cases: &[(&str, Result<Duration, &str>)] = &[
+            ("90", Ok(Duration::from_secs(90))),
+            ("250ms", Ok(Duration::from_millis(250))),
+            ("2m", Ok(Duration::from_secs(120))),
+            ("1h", Ok(Duration::from_secs(3600))),
+            ("1.5s", Err("expected one of the units ms, s, m or h")),
+            ("s", Err("expected a whole number with an optional unit")),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(Duration::parse_flag(value), *expected, "{:?}", value);
+        }
+        assert_eq!(Duration::from_secs(120).display_flag(), "2m");
+        assert_eq!(Duration::from_millis(1500).display_flag(), "1500ms");
+        assert_eq!(Duration::ZERO.display_flag(), "0ms");
+    }
+
+    #[test]
+    fn builtin_values() {
+        let mut registry = FlagRegistry::new();
+        let rest = registry
+            .parse_args([
+                "-minloglevel=warning",
+                "--log_dir_overrides=ERROR=/var/log/errors,WARN=/tmp",
+                "--stderrthreshold",
+                "ERROR",
+                "--preallocate_log_bytes=4MiB",
+                "--multiline_style=prefixed",
+                "-",
+            ])
+            .unwrap();
+        assert_eq!(rest, ["-"]);
+        let flags = registry.flags();
+        assert_eq!(flags.minloglevel, Level::Warn);
+        assert_eq!(
+            flags.log_dir_overrides,
+            [
+                (Level::Error, PathBuf::from("/var/log/errors")),
+                (Level::Warn, PathBuf::from("/tmp"))
+            ]
+        );
+        assert_eq!(flags.stderrthreshold, Some(Level::Error));
+        assert_eq!(flags.preallocate_log_bytes, Some(ByteSize::mib(4)));
+        assert_eq!(flags.multiline_style, MultilineStyle::Prefixed);
+        assert_eq!(
+            registry.parse_args(["--logtostderr=maybe"]).unwrap_err().to_string(),
+            "invalid value \"maybe\" for logtostderr: expected true or false"
+        );
+        assert_eq!(registry.parse_args(["--minloglevel"]).unwrap_err().reason, "expected a value");
+    }
+}
@@ -1,6 +1,8 @@
-use std::{env::temp_dir, ffi::OsString, path::PathBuf};
+use std::{env::temp_dir, error::Error, ffi::OsString, fmt, path::PathBuf, str::FromStr, time::Duration};
 
-use log::Level;
+use log::{Level, LevelFilter};
+
+use crate::{format::MultilineStyle, parse_minloglevel, BacktraceStyle, ConsoleFallback, FlagParseError};
 
 /// The flag structure used to initialize glog.
 ///
@@ -12,9 +14,9 @@ use log::Level;
 ///
 /// This are the defaults for each flag:
 /// ```
-/// use std::{env::temp_dir, ffi::OsString, path::PathBuf};
+/// use std::{env::temp_dir, ffi::OsString, path::PathBuf, time::Duration};
 /// use log::*;
-/// use glog::Flags;
+/// use glog::{format::MultilineStyle, BacktraceStyle, ConsoleFallback, Flags};
 ///
 /// let flags = Flags::default();
 ///
@@ -23,25 +25,192 @@ use log::Level;
 /// assert!(flags.log_backtrace_at.is_none());
 /// assert_eq!(flags.logtostderr, false);
 /// assert_eq!(flags.alsologtostderr, false);
+/// assert!(flags.stderrthreshold.is_none());
 /// assert_eq!(flags.log_dir, [temp_dir(), PathBuf::from("")].iter().collect::<PathBuf>().into_os_string());
+/// assert!(flags.trim_source_prefix.is_none());
+/// assert!(flags.preallocate_log_bytes.is_none());
+/// assert!(flags.log_dir_overrides.is_empty());
+/// assert_eq!(flags.detect_deleted_files, false);
+/// assert_eq!(flags.exit_on_dfatal, false);
+/// assert_eq!(flags.monotonic_timestamps, false);
+/// assert_eq!(flags.log_hostname_as_fqdn, false);
+/// assert_eq!(flags.sanitize_messages, false);
+/// assert_eq!(flags.stderr_verbose_level, false);
+/// assert_eq!(flags.multiline_style, MultilineStyle::Raw);
+/// assert_eq!(flags.error_chain_max_depth, 16);
+/// assert_eq!(flags.backtrace_style, BacktraceStyle::Auto);
+/// assert_eq!(flags.redact_stderr, false);
+/// assert!(flags.redaction_salt.is_empty());
+/// assert_eq!(flags.tail_buffer_size, 0);
+/// assert_eq!(flags.logtostderr_if_no_log_dir, false);
+/// assert_eq!(flags.module_logs_to_level_files, false);
+/// assert_eq!(flags.strict_flags, false);
+/// assert_eq!(flags.lock_shared_files, false);
+/// assert_eq!(flags.file_per_thread, false);
+/// assert_eq!(flags.log_crate_version, false);
+/// assert_eq!(flags.color_reset_on_newline, true);
+/// assert_eq!(flags.attachment_max_bytes, 64 * 1024);
+/// assert_eq!(flags.attachment_ttl, Duration::from_secs(60));
+/// assert_eq!(flags.header_include_stats, false);
+/// assert!(flags.suppress_at.is_empty());
+/// assert!(flags.log_index_bytes.is_none());
+/// assert!(flags.log_index_interval.is_none());
+/// assert!(flags.adaptive_error_threshold.is_none());
+/// assert_eq!(flags.adaptive_error_window, Duration::from_secs(10));
+/// assert_eq!(flags.adaptive_min_level, Level::Debug);
+/// assert_eq!(flags.adaptive_duration, Duration::from_secs(60));
+/// assert_eq!(flags.adaptive_dump_ring_buffer, false);
+/// assert_eq!(flags.log_target, false);
+/// assert!(flags.target_policy.is_empty());
+/// assert_eq!(flags.console_fallback, ConsoleFallback::Keep);
+/// assert!(flags.fsync_on.is_none());
 /// ```
 #[derive(Debug, Clone)]
 pub struct Flags {
     /// [`Info`]: ../log/enum.Level.html#variant.Info
     /// If logging to stderr try to colorize levels more severe than [`Info`]
+    ///
+    /// With the `color-backtrace` feature backtraces printed to a terminal are colorized as well.
+    /// Ignored if the `NO_COLOR` environment variable is set, to any value, see <https://no-color.org>.
     pub colorlogtostderr: bool,
     /// Minimum level (inclusive) that should be logged
     pub minloglevel: Level,
     /// Optionally log a backtrace at `filename:line` log invocation.
+    /// The file name is matched without directories, also with `trim_source_prefix`.
     /// The log level has to be enabled for it to work.
     /// Will be written in the log file with the lowest severity.
     pub log_backtrace_at: Option<String>,
-    /// Log to stderr instead of logfiles
+    /// Log to stderr instead of logfiles, takes precedence over `alsologtostderr` and `stderrthreshold`
     pub logtostderr: bool,
     /// Log to stderr and logfiles
     pub alsologtostderr: bool,
+    /// Also log to stderr at this level and more severe ones when logging to files.
+    /// Unlike C++ glog which defaults to `ERROR`, nothing is copied to stderr by default.
+    pub stderrthreshold: Option<Level>,
     /// Directory in which to store the log files
     pub log_dir: OsString,
+    /// Strip this prefix from the source file path instead of only logging the file name.
+    /// The directory structure relative to the prefix is preserved.
+    /// Files not starting with the prefix are logged with their file name only.
+    /// `log_backtrace_at` and `suppress_at` keep matching the file name only.
+    pub trim_source_prefix: Option<String>,
+    /// Reserve this much disk space for every new log file to reduce fragmentation.
+    /// Only supported on Linux and Windows, it's silently skipped on other platforms and
+    /// filesystems without support.
+    pub preallocate_log_bytes: Option<ByteSize>,
+    /// Store the log file of a level in a different directory than `log_dir`.
+    /// If a level is listed multiple times the last entry is used.
+    /// Records are only copied to the files of less severe levels in the same directory, e.g. ERROR
+    /// records written to a persistent directory don't end up in the INFO file of an ephemeral one.
+    pub log_dir_overrides: Vec<(Level, PathBuf)>,
+    /// Periodically check whether a log file was removed, e.g. by an operator or logrotate,
+    /// and recreate it instead of writing to the removed file.
+    /// A file put in its place by someone else is appended to instead.
+    pub detect_deleted_files: bool,
+    /// Abort on [`dfatal!`](crate::dfatal) in release builds as well
+    pub exit_on_dfatal: bool,
+    /// Never log a timestamp earlier than a previous one, even if the wall clock goes backwards
+    pub monotonic_timestamps: bool,
+    /// Use the fully qualified domain name of the host in log file names and headers.
+    /// Falls back to the short hostname if it can't be resolved within 100ms.
+    pub log_hostname_as_fqdn: bool,
+    /// Escape control characters other than newlines in messages as `\xNN`.
+    /// Prevents messages from injecting terminal escape codes into the log output.
+    pub sanitize_messages: bool,
+    /// Prefix lines logged to stderr with the full level name, e.g. `[ERROR]`.
+    /// The log files keep the glog format.
+    pub stderr_verbose_level: bool,
+    /// How messages spanning several lines are written
+    pub multiline_style: MultilineStyle,
+    /// Maximum number of sources logged by [`error_chain!`](crate::error_chain)
+    pub error_chain_max_depth: usize,
+    /// How much detail logged backtraces contain
+    pub backtrace_style: BacktraceStyle,
+    /// Apply the [redactor](crate::Glog::set_redactor) to stderr as well, not only to the log files
+    pub redact_stderr: bool,
+    /// Salt of the hashes displayed by [`HashedDisplay`](crate::HashedDisplay)
+    pub redaction_salt: String,
+    /// Number of lines per level kept in memory for [`Glog::tail`](crate::Glog::tail), 0 disables it
+    pub tail_buffer_size: usize,
+    /// Log to stderr as well instead of failing if a log file can't be created, e.g. because `log_dir`
+    /// doesn't exist. The missing files are created on a later write once that succeeds.
+    pub logtostderr_if_no_log_dir: bool,
+    /// Write the records of modules with a [module log file](crate::Glog::with_module_log_file)
+    /// to the level log files as well
+    pub module_logs_to_level_files: bool,
+    /// Fail initialization on [conflicting flags](FlagConflict) instead of printing a warning
+    pub strict_flags: bool,
+    /// Take an exclusive advisory lock around every write to a log file, for log files shared with
+    /// other processes. Writes without the lock if it can't be taken within 100ms.
+    pub lock_shared_files: bool,
+    /// Write the records of every thread to its own log files named
+    /// `<program>.<host>.<user>.log.<LEVEL>.<yyyymmdd-hhmmss>.<pid>.<tid>`, avoiding contention on
+    /// the shared log files.
+    ///
+    /// The thread files are created on the first record of a thread and closed when it exits, so
+    /// thread pools keep one file per level and worker. The symlinks keep pointing to the process
+    /// wide files, which only receive backtraces and crash reports. Deleted thread files aren't
+    /// detected, and the order of records across threads is only given by their timestamps.
+    pub file_per_thread: bool,
+    /// Write the version of glog-rs into the log file headers
+    pub log_crate_version: bool,
+    /// Reset the color of `colorlogtostderr` before the newline of a line instead of after it
+    ///
+    /// The terminal isn't left colored if the process dies between writing the line and the reset.
+    pub color_reset_on_newline: bool,
+    /// Bytes of an [attachment](crate::attach) written below the line, the rest is cut off
+    pub attachment_max_bytes: usize,
+    /// Drop an [attachment](crate::attach) if no record is logged on its thread within this time
+    pub attachment_ttl: Duration,
+    /// Write the number of messages logged so far into the headers of the log files
+    ///
+    /// Files recreated after `detect_deleted_files` found them removed also count the files before
+    /// them, so changes of the error rate can be spotted from the headers alone. Files shared with
+    /// other processes through [`Glog::with_module_log_file`](crate::Glog::with_module_log_file) don't get the counts.
+    pub header_include_stats: bool,
+    /// Drop the records logged at these `file:line` call sites, `file:*` drops every record of a file
+    ///
+    /// An entry may list several comma-separated call sites. The file names are matched like in
+    /// `log_backtrace_at`. The dropped records are counted per call site, see
+    /// [`GlogHandle::suppressed_call_sites`](crate::GlogHandle::suppressed_call_sites).
+    pub suppress_at: Vec<String>,
+    /// Add an entry to the [index](crate::index) of a log file whenever this much was written since the last one
+    pub log_index_bytes: Option<ByteSize>,
+    /// Add an entry to the [index](crate::index) of a log file whenever this much time passed since the last one
+    pub log_index_interval: Option<Duration>,
+    /// Log `adaptive_min_level` and more severe records for a while after more than this many
+    /// `Warn` and `Error` records within `adaptive_error_window`
+    ///
+    /// The escalation ends once no burst happened for `adaptive_duration`. Internal `Warn` records
+    /// announce its start and end. Changing the minimum level through
+    /// [`GlogHandle::set_min_level`](crate::GlogHandle::set_min_level) ends it.
+    pub adaptive_error_threshold: Option<u32>,
+    /// The time `adaptive_error_threshold` counts the records of, in coarse buckets of a tenth of it
+    pub adaptive_error_window: Duration,
+    /// The minimum level while `adaptive_error_threshold` is exceeded
+    pub adaptive_min_level: Level,
+    /// The time an escalation lasts after the last burst
+    pub adaptive_duration: Duration,
+    /// On escalation, write the records below the minimum level kept by the
+    /// [adaptive buffer](crate::Glog::with_adaptive_buffer) to the log files
+    pub adaptive_dump_ring_buffer: bool,
+    /// Write `(target: <target>)` of the record after the `file:line]` of glog lines
+    ///
+    /// JSON sink messages always contain the `target`.
+    pub log_target: bool,
+    /// The levels, sampling and rate limits of targets, see [`TargetPolicy`]
+    ///
+    /// Replaced at runtime with [`GlogHandle::set_target_policy`](crate::GlogHandle::set_target_policy).
+    pub target_policy: String,
+    /// What happens to the output for stderr if there is no console, see [`console_available`](crate::console_available)
+    ///
+    /// Without a console `colorlogtostderr` is ignored.
+    pub console_fallback: ConsoleFallback,
+    /// Sync the log files to disk after writing a record of this level or a more severe one
+    ///
+    /// The records are then kept even if the process or the machine goes down right after, e.g.
+    /// with [`std::process::exit`] instead of [`glog::exit`](crate::exit).
+    pub fsync_on: Option<Level>,
 }
 
 impl Default for Flags {
@@ -52,6 +221,7 @@ impl Default for Flags {
             log_backtrace_at: None,
             logtostderr: false,
             alsologtostderr: false,
+            stderrthreshold: None,
             log_dir: [
                 temp_dir().into_os_string(),
                 OsString::from(""), // Users may not append a / or \ to their env vars
@@ -59,6 +229,697 @@ impl Default for Flags {
             .iter()
             .collect::<PathBuf>()
             .into_os_string(),
+            trim_source_prefix: None,
+            preallocate_log_bytes: None,
+            log_dir_overrides: Vec::new(),
+            detect_deleted_files: false,
+            exit_on_dfatal: false,
+            monotonic_timestamps: false,
+            log_hostname_as_fqdn: false,
+            sanitize_messages: false,
+            stderr_verbose_level: false,
+            multiline_style: MultilineStyle::Raw,
+            error_chain_max_depth: 16,
+            backtrace_style: BacktraceStyle::Auto,
+            redact_stderr: false,
+            redaction_salt: String::new(),
+            tail_buffer_size: 0,
+            logtostderr_if_no_log_dir: false,
+            module_logs_to_level_files: false,
+            strict_flags: false,
+            lock_shared_files: false,
+            file_per_thread: false,
+            log_crate_version: false,
+            color_reset_on_newline: true,
+            attachment_max_bytes: 64 * 1024,
+            attachment_ttl: Duration::from_secs(60),
+            header_include_stats: false,
+            suppress_at: Vec::new(),
+            log_index_bytes: None,
+            log_index_interval: None,
+            adaptive_error_threshold: None,
+            adaptive_error_window: Duration::from_secs(10),
+            adaptive_min_level: Level::Debug,
+            adaptive_duration: Duration::from_secs(60),
+            adaptive_dump_ring_buffer: false,
+            log_target: false,
+            target_policy: String::new(),
+            console_fallback: ConsoleFallback::Keep,
+            fsync_on: None,
+        }
+    }
+}
+
+/// A size in bytes of a flag like `preallocate_log_bytes`
+///
+/// Parsed from a whole number with an optional unit: `B`, the decimal `KB`, `MB`, `GB` and `TB`
+/// or the binary `KiB`, `MiB`, `GiB` and `TiB`. Numbers without a unit are megabytes of
+/// 2<sup>20</sup> bytes like the size flags of C++ glog. Displayed with the largest binary unit
+/// which represents it exactly.
+///
+/// # Example
+///
+/// ```
+/// use glog::ByteSize;
+///
+/// assert_eq!("2GiB".parse(), Ok(ByteSize::gib(2)));
+/// assert_eq!("1800".parse(), Ok(ByteSize::mib(1800)));
+/// assert_eq!("1800MB".parse::<ByteSize>().unwrap().as_bytes(), 1_800_000_000);
+/// assert_eq!(ByteSize::kib(2048).to_string(), "2MiB");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSize(u64);
+
+const UNITS: &[(&str, u64)] = &[
+    ("B", 1),
+    ("KB", 1_000),
+    ("MB", 1_000_000),
+    ("GB", 1_000_000_000),
+    ("TB", 1_000_000_000_000),
+    ("KiB", 1 << 10),
+    ("MiB", 1 << 20),
+    ("GiB", 1 << 30),
+    ("TiB", 1 << 40),
+];
+
+impl ByteSize {
+    /// `bytes` bytes
+    pub const fn b(bytes: u64) -> Self {
+        ByteSize(bytes)
+    }
+
+    /// `kib` kibibytes, saturating at `u64::MAX` bytes
+    pub const fn kib(kib: u64) -> Self {
+        ByteSize(kib.saturating_mul(1 << 10))
+    }
+
+    /// `mib` mebibytes, saturating at `u64::MAX` bytes
+    pub const fn mib(mib: u64) -> Self {
+        ByteSize(mib.saturating_mul(1 << 20))
+    }
+
+    /// `gib` gibibytes, saturating at `u64::MAX` bytes
+    pub const fn gib(gib: u64) -> Self {
+        ByteSize(gib.saturating_mul(1 << 30))
+    }
+
+    /// The number of bytes
+    pub const fn as_bytes(self) -> u64 {
+        self.0
+    }
+}
+
+impl FromStr for ByteSize {
+    type Err = FlagParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = |reason| FlagParseError {
+            flag: "byte size".into(),
+            value: s.to_owned(),
+            reason,
+        };
+        let value = s.trim();
+        let (number, unit) = value.split_at(value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len()));
+        let number: u64 = match number.parse() {
+            Ok(number) => number,
+            Err(_) if number.is_empty() => return Err(error("expected a whole number with an optional unit")),
+            Err(_) => return Err(error("the number is too large")),
+        };
+        let multiplier = match unit.trim_start() {
+            "" => 1 << 20,
+            unit => match UNITS.iter().find(|(name, _)| *name == unit) {
+                Some((_, multiplier)) => *multiplier,
+                None => return Err(error("expected one of the units B, KB, MB, GB, TB, KiB, MiB, GiB or TiB")),
+            },
+        };
+        number
+            .checked_mul(multiplier)
+            .map(ByteSize)
+            .ok_or_else(|| error("the size is too large"))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    #[allow(clippy::manual_is_multiple_of)] // is_multiple_of needs Rust 1.87
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, multiplier) = UNITS
+            .iter()
+            .rev()
+            .take(4)
+            .find(|(_, multiplier)| self.0 != 0 && self.0 % multiplier == 0)
+            .unwrap_or(&UNITS[0]);
+        write!(f, "{}{}", self.0 / multiplier, unit)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ByteSize {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialized from a string like [`FromStr`] or a number of megabytes
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ByteSize {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = ByteSize;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a size like \"1800MB\" or a number of megabytes")
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<ByteSize, E> {
+                value.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<ByteSize, E> {
+                value
+                    .checked_mul(1 << 20)
+                    .map(ByteSize)
+                    .ok_or_else(|| E::custom("the size is too large"))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// The levels, sampling and rate limits of targets parsed from `target_policy`
+///
+/// A policy is a comma-separated list of clauses `target=LEVEL[@sample:P][@rate:N/UNIT]`:
+///
+/// - `LEVEL` is `OFF` or a level like in `minloglevel`.
+/// - `sample:P` keeps the fraction `P` between 0 and 1 of the records, evenly spread.
+/// - `rate:N/UNIT` keeps at most `N` records per second (`s`), minute (`m`) or hour (`h`).
+///
+/// A target matches the records of its module and submodules, the longest matching target
+/// applies. A clause without `target=` is the default for the other targets. The level of a
+/// matching clause replaces `minloglevel` for all outputs, targets without one use `minloglevel`.
+///
+/// # Example
+///
+/// ```
+/// use glog::TargetPolicy;
+/// use log::LevelFilter;
+///
+/// let policy: TargetPolicy = "hyper=warn, sqlx=info@sample:0.1, my_app::metrics=info@rate:10/s".parse().unwrap();
+/// assert_eq!(policy.rule("sqlx::query").unwrap().sample, Some(0.1));
+/// assert_eq!(policy.rule("hyper").unwrap().level, LevelFilter::Warn);
+/// assert!(policy.rule("my_app").is_none());
+///
+/// let error = "hyper=warn, sqlx=loud".parse::<TargetPolicy>().unwrap_err();
+/// assert_eq!(error.value(), "sqlx=loud");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TargetPolicy {
+    /// The clauses with a target, the longest target first
+    rules: Vec<(String, TargetRule)>,
+    default: Option<TargetRule>,
+}
+
+/// The policy of the records of a target, see [`TargetPolicy`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct TargetRule {
+    pub level: LevelFilter,
+    /// The fraction of the records kept
+    pub sample: Option<f64>,
+    pub rate: Option<RateLimit>,
+}
+
+/// At most `count` records per `per`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    pub count: u32,
+    pub per: Duration,
+}
+
+impl TargetPolicy {
+    /// The rule of the longest target matching `target`, or the default rule
+    pub fn rule(&self, target: &str) -> Option<&TargetRule> {
+        self.position(target).map(|position| self.rules().nth(position).unwrap())
+    }
+
+    /// The position of [`TargetPolicy::rule`] in [`TargetPolicy::rules`]
+    pub(crate) fn position(&self, target: &str) -> Option<usize> {
+        self.rules
+            .iter()
+            .position(|(prefix, _)| crate::matches_module_prefix(target, prefix))
+            .or_else(|| self.default.as_ref().map(|_| self.rules.len()))
+    }
+
+    /// All rules, the ones of targets from the longest target followed by the default rule
+    pub(crate) fn rules(&self) -> impl Iterator<Item = &TargetRule> {
+        self.rules.iter().map(|(_, rule)| rule).chain(&self.default)
+    }
+}
+
+/// Parse the `LEVEL[@sample:P][@rate:N/UNIT]` of a clause
+fn parse_target_rule(rule: &str) -> Result<TargetRule, &'static str> {
+    let mut parts = rule.split('@').map(str::trim);
+    let level = match parts.next().unwrap_or_default() {
+        level if level.eq_ignore_ascii_case("off") => LevelFilter::Off,
+        "" => return Err("expected [target=]LEVEL[@sample:P][@rate:N/UNIT]"),
+        level => parse_minloglevel(level)
+            .map_err(|_| "expected one of the levels OFF, TRACE, DEBUG, INFO, WARN or ERROR")?
+            .to_level_filter(),
+    };
+    let mut target_rule = TargetRule {
+        level,
+        sample: None,
+        rate: None,
+    };
+    for option in parts {
+        match option.split_once(':').map(|(name, value)| (name.trim(), value.trim())) {
+            Some(("sample", value)) => {
+                let sample = value
+                    .parse()
+                    .ok()
+                    .filter(|sample| (0.0..=1.0).contains(sample))
+                    .ok_or("expected a sample probability between 0 and 1")?;
+                target_rule.sample = Some(sample);
+            }
+            Some(("rate", value)) => {
+                let error = "expected a rate like 10/s, 100/m or 1000/h";
+                let (count, unit) = value.split_once('/').ok_or(error)?;
+                let per = match unit.trim() {
+                    "s" => Duration::from_secs(1),
+                    "m" => Duration::from_secs(60),
+                    "h" => Duration::from_secs(60 * 60),
+                    _ => return Err(error),
+                };
+                let count = count.trim().parse().map_err(|_| error)?;
+                target_rule.rate = Some(RateLimit {
+                    count,
+                    per,
+                });
+            }
+            _ => return Err("expected the options sample:P or rate:N/UNIT after @"),
+        }
+    }
+    Ok(target_rule)
+}
+
+impl FromStr for TargetPolicy {
+    type Err = FlagParseError;
+
+    /// Parse a policy, the error has the offending clause as its value
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut policy = TargetPolicy::default();
+        for clause in s.split(',').map(str::trim).filter(|clause| !clause.is_empty()) {
+            let error = |reason| FlagParseError {
+                flag: "target_policy".into(),
+                value: clause.to_owned(),
+                reason,
+            };
+            let (target, rule) = match clause.split_once('=') {
+                Some((target, rule)) => (Some(target.trim()), rule),
+                None => (None, clause),
+            };
+            let rule = parse_target_rule(rule).map_err(error)?;
+            match target {
+                Some("") => return Err(error("expected a target before =")),
+                Some(target) if policy.rules.iter().any(|(other, _)| other == target) => {
+                    return Err(error("the target has more than one clause"))
+                }
+                Some(target) => {
+                    let position = policy
+                        .rules
+                        .iter()
+                        .position(|(other, _)| other.len() < target.len())
+                        .unwrap_or(policy.rules.len());
+                    policy.rules.insert(position, (target.to_owned(), rule));
+                }
+                None if policy.default.is_some() => return Err(error("there is more than one clause without a target")),
+                None => policy.default = Some(rule),
+            }
+        }
+        Ok(policy)
+    }
+}
+
+/// Two flags which don't make sense together
+///
+/// Initialization prints a warning for every conflict, or fails on the first one with `strict_flags`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FlagConflict {
+    flags: [&'static str; 2],
+    problem: &'static str,
+    suggestion: &'static str,
+}
+
+impl FlagConflict {
+    /// Names of the conflicting flags
+    pub fn flags(&self) -> [&str; 2] {
+        self.flags
+    }
+}
+
+impl fmt::Display for FlagConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} conflicts with {}: {}, {}",
+            self.flags[0], self.flags[1], self.problem, self.suggestion
+        )
+    }
+}
+
+impl Error for FlagConflict {}
+
+/// Whether stderr receives any records when logging to files
+fn stderr_output(flags: &Flags) -> bool {
+    flags.logtostderr || flags.alsologtostderr || flags.stderrthreshold.is_some()
+}
+
+/// Whether a conflict occurs with the given flags
+type Condition = fn(&Flags) -> bool;
+
+/// All known conflicts with the condition under which they occur
+#[rustfmt::skip]
+const CONFLICTS: &[(Condition, FlagConflict)] = &[
+    (|flags| flags.logtostderr && flags.log_dir != Flags::default().log_dir, FlagConflict {
+        flags: ["logtostderr", "log_dir"],
+        problem: "no log files are written to log_dir",
+        suggestion: "remove log_dir or disable logtostderr to write log files",
+    }),
+    (|flags| flags.logtostderr && !flags.log_dir_overrides.is_empty(), FlagConflict {
+        flags: ["logtostderr", "log_dir_overrides"],
+        problem: "no log files are written to the overridden directories",
+        suggestion: "remove log_dir_overrides or disable logtostderr to write log files",
+    }),
+    (|flags| flags.logtostderr && flags.alsologtostderr, FlagConflict {
+        flags: ["logtostderr", "alsologtostderr"],
+        problem: "alsologtostderr has no effect without log files",
+        suggestion: "use only logtostderr for stderr output, or only alsologtostderr for stderr and log files",
+    }),
+    (|flags| flags.logtostderr && flags.stderrthreshold.is_some(), FlagConflict {
+        flags: ["logtostderr", "stderrthreshold"],
+        problem: "all levels are logged to stderr regardless of the threshold",
+        suggestion: "use only logtostderr for stderr output, or only stderrthreshold to copy severe records from the log files",
+    }),
+    (|flags| flags.logtostderr && flags.logtostderr_if_no_log_dir, FlagConflict {
+        flags: ["logtostderr", "logtostderr_if_no_log_dir"],
+        problem: "there is no log file to fall back from",
+        suggestion: "remove logtostderr_if_no_log_dir or disable logtostderr to write log files",
+    }),
+    (|flags| flags.logtostderr && flags.preallocate_log_bytes.is_some(), FlagConflict {
+        flags: ["logtostderr", "preallocate_log_bytes"],
+        problem: "there are no log files to preallocate",
+        suggestion: "remove preallocate_log_bytes or disable logtostderr to write log files",
+    }),
+    (|flags| flags.logtostderr && flags.detect_deleted_files, FlagConflict {
+        flags: ["logtostderr", "detect_deleted_files"],
+        problem: "there are no log files to watch",
+        suggestion: "remove detect_deleted_files or disable logtostderr to write log files",
+    }),
+    (|flags| flags.logtostderr && flags.file_per_thread, FlagConflict {
+        flags: ["logtostderr", "file_per_thread"],
+        problem: "there are no log files to split",
+        suggestion: "remove file_per_thread or disable logtostderr to write log files",
+    }),
+    (|flags| flags.logtostderr && flags.module_logs_to_level_files, FlagConflict {
+        flags: ["logtostderr", "module_logs_to_level_files"],
+        problem: "there are no level log files",
+        suggestion: "remove module_logs_to_level_files or disable logtostderr to write log files",
+    }),
+    (|flags| flags.colorlogtostderr && !stderr_output(flags), FlagConflict {
+        flags: ["colorlogtostderr", "logtostderr"],
+        problem: "nothing is logged to stderr",
+        suggestion: "enable logtostderr, alsologtostderr or stderrthreshold to get colored stderr output",
+    }),
+    (|flags| flags.stderr_verbose_level && !stderr_output(flags), FlagConflict {
+        flags: ["stderr_verbose_level", "logtostderr"],
+        problem: "nothing is logged to stderr",
+        suggestion: "enable logtostderr, alsologtostderr or stderrthreshold to get stderr output",
+    }),
+];
+
+/// The conflicts between `flags`, an error for the first one with `strict_flags`
+pub(crate) fn check_conflicts(flags: &Flags) -> Result<Vec<FlagConflict>, FlagConflict> {
+    let conflicts: Vec<FlagConflict> = CONFLICTS
+        .iter()
+        .filter(|(applies, _)| applies(flags))
+        .map(|(_, conflict)| conflict.clone())
+        .collect();
+    match conflicts.first() {
+        Some(conflict) if flags.strict_flags => Err(conflict.clone()),
+        _ => Ok(conflicts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_sizes() {
+        let cases: &[(&str, Result<u64, &str>)] = &[
+            ("0", Ok(0)),
+            ("1800", Ok(1800 << 20)),
+            ("4096B", Ok(4096)),
+            ("1800MB", Ok(1_800_000_000)),
+            ("2GiB", Ok(2 << 30)),
+            (" 16 KiB ", Ok(16 << 10)),
+            ("1TB", Ok(1_000_000_000_000)),
+            (
+                "1.5q",
+                Err("expected one of the units B, KB, MB, GB, TB, KiB, MiB, GiB or TiB"),
+            ),
+            (
+                "2gib",
+                Err("expected one of the units B, KB, MB, GB, TB, KiB, MiB, GiB or TiB"),
+            ),
+            ("MB", Err("expected a whole number with an optional unit")),
+            ("-1", Err("expected a whole number with an optional unit")),
+            ("", Err("expected a whole number with an optional unit")),
+            ("18446744073709551616B", Err("the number is too large")),
+            ("17179869184GiB", Err("the size is too large")),
+            ("17592186044416", Err("the size is too large")),
+        ];
+        for (value, expected) in cases {
+            let parsed = value.parse::<ByteSize>().map(ByteSize::as_bytes).map_err(|why| why.reason);
+            assert_eq!(parsed, *expected, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn byte_sizes_display_round_trips() {
+        for (size, displayed) in [
+            (ByteSize::b(0), "0B"),
+            (ByteSize::b(1000), "1000B"),
+            (ByteSize::kib(3), "3KiB"),
+            (ByteSize::mib(1800), "1800MiB"),
+            (ByteSize::gib(2), "2GiB"),
+            (ByteSize::gib(1024), "1TiB"),
+        ]
+        .iter()
+        {
+            assert_eq!(size.to_string(), *displayed);
+            assert_eq!(displayed.parse(), Ok(*size));
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn byte_sizes_deserialize_numbers_as_megabytes() {
+        assert_eq!(serde_json::from_str::<ByteSize>("10").unwrap(), ByteSize::mib(10));
+        assert_eq!(serde_json::from_str::<ByteSize>("\"10B\"").unwrap(), ByteSize::b(10));
+        let too_large = serde_json::from_str::<ByteSize>("17592186044416").unwrap_err();
+        assert!(too_large.to_string().contains("the size is too large"), "{}", too_large);
+    }
+
+    #[test]
+    fn target_policies() {
+        let policy: TargetPolicy = " hyper=warn, sqlx=info@sample:0.1 ,,my_app::metrics=INFO@rate:10/s, my_app=off, error"
+            .parse()
+            .unwrap();
+        let rule = |target| policy.rule(target).cloned();
+        assert_eq!(rule("hyper::client").map(|rule| rule.level), Some(LevelFilter::Warn));
+        assert_eq!(rule("sqlx").and_then(|rule| rule.sample), Some(0.1));
+        assert_eq!(
+            rule("my_app::metrics::http").and_then(|rule| rule.rate),
+            Some(RateLimit {
+                count: 10,
+                per: Duration::from_secs(1),
+            })
+        );
+        assert_eq!(rule("my_app::db").map(|rule| rule.level), Some(LevelFilter::Off));
+        // Not a submodule, so the default
+        assert_eq!(rule("hyperx").map(|rule| rule.level), Some(LevelFilter::Error));
+        assert_eq!("".parse(), Ok(TargetPolicy::default()));
+
+        let cases: &[(&str, &str, &str)] = &[
+            (
+                "a=info, b=loud",
+                "b=loud",
+                "expected one of the levels OFF, TRACE, DEBUG, INFO, WARN or ERROR",
+            ),
+            (
+                "a=info@sample:1.5",
+                "a=info@sample:1.5",
+                "expected a sample probability between 0 and 1",
+            ),
+            (
+                "a=info@rate:10/d",
+                "a=info@rate:10/d",
+                "expected a rate like 10/s, 100/m or 1000/h",
+            ),
+            (
+                "a=info@burst:3",
+                "a=info@burst:3",
+                "expected the options sample:P or rate:N/UNIT after @",
+            ),
+            ("=info", "=info", "expected a target before ="),
+            ("a=", "a=", "expected [target=]LEVEL[@sample:P][@rate:N/UNIT]"),
+            ("a=info, a=warn", "a=warn", "the target has more than one clause"),
+            ("info, a=warn, warn", "warn", "there is more than one clause without a target"),
+        ];
+        for (policy, clause, reason) in cases {
+            let error = policy.parse::<TargetPolicy>().unwrap_err();
+            assert_eq!(
+                (error.flag(), error.value(), error.reason),
+                ("target_policy", *clause, *reason),
+                "{:?}",
+                policy
+            );
+        }
+    }
+
+    #[test]
+    fn conflicts() {
+        let cases: Vec<(Flags, [&str; 2])> = vec![
+            (
+                Flags {
+                    logtostderr: true,
+                    log_dir: "/var/log/".into(),
+                    ..Default::default()
+                },
+                ["logtostderr", "log_dir"],
+            ),
+            (
+                Flags {
+                    logtostderr: true,
+                    log_dir_overrides: vec![(Level::Error, "/var/log/".into())],
+                    ..Default::default()
+                },
+                ["logtostderr", "log_dir_overrides"],
+            ),
+            (
+                Flags {
+                    logtostderr: true,
+                    alsologtostderr: true,
+                    ..Default::default()
+                },
+                ["logtostderr", "alsologtostderr"],
+            ),
+            (
+                Flags {
+                    logtostderr: true,
+                    stderrthreshold: Some(Level::Warn),
+                    ..Default::default()
+                },
+                ["logtostderr", "stderrthreshold"],
+            ),
+            (
+                Flags {
+                    logtostderr: true,
+                    logtostderr_if_no_log_dir: true,
+                    ..Default::default()
+                },
+                ["logtostderr", "logtostderr_if_no_log_dir"],
+            ),
+            (
+                Flags {
+                    logtostderr: true,
+                    preallocate_log_bytes: Some(ByteSize::kib(1)),
+                    ..Default::default()
+                },
+                ["logtostderr", "preallocate_log_bytes"],
+            ),
+            (
+                Flags {
+                    logtostderr: true,
+                    detect_deleted_files: true,
+                    ..Default::default()
+                },
+                ["logtostderr", "detect_deleted_files"],
+            ),
+            (
+                Flags {
+                    logtostderr: true,
+                    file_per_thread: true,
+                    ..Default::default()
+                },
+                ["logtostderr", "file_per_thread"],
+            ),
+            (
+                Flags {
+                    logtostderr: true,
+                    module_logs_to_level_files: true,
+                    ..Default::default()
+                },
+                ["logtostderr", "module_logs_to_level_files"],
+            ),
+            (
+                Flags {
+                    colorlogtostderr: true,
+                    ..Default::default()
+                },
+                ["colorlogtostderr", "logtostderr"],
+            ),
+            (
+                Flags {
+                    stderr_verbose_level: true,
+                    ..Default::default()
+                },
+                ["stderr_verbose_level", "logtostderr"],
+            ),
+        ];
+        assert_eq!(cases.len(), CONFLICTS.len());
+
+        for (flags, names) in cases {
+            let conflicts = check_conflicts(&flags).unwrap();
+            assert_eq!(conflicts.iter().map(FlagConflict::flags).collect::<Vec<_>>(), [names]);
+            let message = conflicts[0].to_string();
+            assert!(
+                message.starts_with(&format!("{} conflicts with {}: ", names[0], names[1])),
+                "{}",
+                message
+            );
+            assert!(message.contains(names[0]) && message.contains(names[1]));
+
+            let strict = Flags {
+                strict_flags: true,
+                ..flags
+            };
+            assert_eq!(check_conflicts(&strict).unwrap_err(), conflicts[0]);
+        }
+    }
+
+    #[test]
+    fn no_conflicts() {
+        for flags in [
+            Flags::default(),
+            Flags {
+                logtostderr: true,
+                colorlogtostderr: true,
+                strict_flags: true,
+                ..Default::default()
+            },
+            Flags {
+                alsologtostderr: true,
+                stderr_verbose_level: true,
+                strict_flags: true,
+                ..Default::default()
+            },
+        ]
+        .iter()
+        {
+            assert_eq!(check_conflicts(flags), Ok(Vec::new()));
         }
     }
 }
@@ -0,0 +1,504 @@
+//! Formatting of log lines in the [`glog`] format
+//!
+//! These functions are the exact implementation used by the logger itself. They can be used to
+//! produce identical log lines in places that can't go through the [`standard logging`] frontend.
+//!
+//! The output for a given set of [`FormatOptions`] is considered part of the stable API and
+//! will only change in a semver incompatible release.
+//!
+//! [`glog`]: https://github.com/google/glog
+//! [`standard logging`]: https://crates.io/crates/log
+//!
+//! # Example
+//!
+//! ```
+//! use chrono::Local;
+//! use log::Level;
+//! use glog::format::{format_record, FormatOptions};
+//!
+//! let line = format_record(&FormatOptions::default(), Local::now(), Level::Info, 123, "main.rs", 42, &format_args!("It works!"));
+//! assert!(line.starts_with('I'));
+//! assert!(line.ends_with("   123 main.rs:42] It works!"));
+//! ```
+
+use std::{
+    fmt::{self, Write as _},
+    path::Path,
+};
+
+use chrono::{DateTime, Local, SecondsFormat};
+use log::{Level, Record};
+
+use crate::{attach, context, Flags};
+
+/// Options changing the format of a log line
+///
+/// The defaults are compatible with [`glog`](https://github.com/google/glog).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormatOptions {
+    /// Add the year to the timestamp, see [`Glog::with_year`](crate::Glog::with_year)
+    pub with_year: bool,
+    /// Log [`Trace`](Level::Trace) and [`Debug`](Level::Debug) as [`Info`](Level::Info),
+    /// see [`Glog::reduced_log_levels`](crate::Glog::reduced_log_levels)
+    pub reduced_log_levels: bool,
+    /// How messages spanning several lines are written
+    pub multiline_style: MultilineStyle,
+    /// Letters replacing the first letter of the level names, see
+    /// [`Glog::with_custom_level_name`](crate::Glog::with_custom_level_name)
+    level_letters: [Option<char>; 5],
+}
+
+/// How messages spanning several lines are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultilineStyle {
+    /// Write the continuation lines as they are, like [`glog`](https://github.com/google/glog)
+    Raw,
+    /// Repeat the prefix of the log line on every continuation line
+    Prefixed,
+}
+
+impl FormatOptions {
+    /// Enable the year in the timestamp
+    pub fn with_year(mut self, with_year: bool) -> Self {
+        self.with_year = with_year;
+        self
+    }
+
+    /// Change whether [`Trace`](Level::Trace) and [`Debug`](Level::Debug) are logged as [`Info`](Level::Info)
+    pub fn reduced_log_levels(mut self, reduced_log_levels: bool) -> Self {
+        self.reduced_log_levels = reduced_log_levels;
+        self
+    }
+
+    /// Change how messages spanning several lines are written
+    pub fn multiline_style(mut self, multiline_style: MultilineStyle) -> Self {
+        self.multiline_style = multiline_style;
+        self
+    }
+
+    /// Write `letter` for records logged as `level`
+    pub(crate) fn level_letter(mut self, level: Level, letter: char) -> Self {
+        self.level_letters[level as usize - 1] = Some(letter);
+        self
+    }
+
+    /// The letter of `level` as written in the lines, after reducing it with `reduced_log_levels`
+    pub(crate) fn letter(&self, level: Level) -> char {
+        let level = match_level(level, self.reduced_log_levels);
+        self.level_letters[level as usize - 1].unwrap_or_else(|| level.as_str().chars().next().unwrap())
+    }
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            with_year: false,
+            reduced_log_levels: true,
+            multiline_style: MultilineStyle::Raw,
+            level_letters: [None; 5],
+        }
+    }
+}
+
+/// Formats [`Record`]s as glog lines like the logger, without writing them anywhere
+///
+/// Useful to produce glog lines in a custom [`log::Log`] implementation.
+///
+/// # Example
+///
+/// ```
+/// use log::{Level, Record};
+/// use glog::{format::GlogFormatter, Flags};
+///
+/// let formatter = GlogFormatter::new(&Flags {
+///     trim_source_prefix: Some("/src/my_crate/".to_owned()),
+///     ..Default::default()
+/// });
+/// let line = formatter.format(
+///     &Record::builder()
+///         .level(Level::Warn)
+///         .file(Some("/src/my_crate/src/main.rs"))
+///         .line(Some(42))
+///         .args(format_args!("Disk almost full"))
+///         .build(),
+/// );
+/// assert!(line.starts_with('W'));
+/// assert!(line.ends_with(" src/main.rs:42] Disk almost full"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct GlogFormatter {
+    options: FormatOptions,
+    trim_source_prefix: Option<String>,
+    sanitize_messages: bool,
+    include_context: bool,
+    attachment_max_bytes: usize,
+    log_target: bool,
+}
+
+impl GlogFormatter {
+    /// A formatter using the format related `flags` and the default [`FormatOptions`]
+    pub fn new(flags: &Flags) -> Self {
+        GlogFormatter {
+            options: FormatOptions::default().multiline_style(flags.multiline_style),
+            trim_source_prefix: flags.trim_source_prefix.clone(),
+            sanitize_messages: flags.sanitize_messages,
+            include_context: true,
+            attachment_max_bytes: flags.attachment_max_bytes,
+            log_target: flags.log_target,
+        }
+    }
+
+    /// Leave out the [context](crate::context) of the records
+    pub(crate) fn without_context(mut self) -> Self {
+        self.include_context = false;
+        self
+    }
+
+    /// Replace the [`FormatOptions`], including the multiline style taken from the flags
+    pub fn with_options(mut self, options: FormatOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// The glog line of `record` at the current time, without a trailing newline
+    pub fn format(&self, record: &Record) -> String {
+        self.format_message(record, record.args(), Local::now())
+    }
+
+    /// The glog line of `record` at `timestamp` with `args` as message
+    pub(crate) fn format_message(&self, record: &Record, args: &fmt::Arguments, timestamp: DateTime<Local>) -> String {
+        let context: &dyn fmt::Display = if self.include_context { &context::Current } else { &"" };
+        format_record(
+            &self.options,
+            timestamp,
+            record.level(),
+            crate::get_tid(),
+            &source_file_name(record.file().unwrap_or(""), self.trim_source_prefix.as_deref()),
+            record.line().unwrap_or(0),
+            &format_args!(
+                "{}",
+                Sanitized::new(
+                    format_args!(
+                        "{}{}{}{}",
+                        Target(self.log_target.then(|| record.target())),
+                        context,
+                        args,
+                        attach::Rendered {
+                            max_bytes: self.attachment_max_bytes
+                        }
+                    ),
+                    self.sanitize_messages
+                )
+            ),
+        )
+    }
+}
+
+/// Writes `(target: <target>) ` before the message if there is a target
+pub(crate) struct Target<'a>(pub(crate) Option<&'a str>);
+
+impl fmt::Display for Target<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(target) => write!(f, "(target: {}) ", target),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The JSON object of `record` for sinks with [`LogFormat::Json`](crate::LogFormat::Json)
+pub(crate) fn format_json(
+    record: &Record,
+    level: &str,
+    timestamp: DateTime<Local>,
+    file: &str,
+    message: &fmt::Arguments,
+    include_context: bool,
+) -> String {
+    let mut json = format!(
+        "{{\"timestamp\":{},\"level\":{},\"thread\":{},\"file\":{},\"line\":{},\"target\":{}",
+        JsonString(&timestamp.to_rfc3339_opts(SecondsFormat::Micros, false)),
+        JsonString(level),
+        crate::get_tid(),
+        JsonString(file),
+        record.line().unwrap_or(0),
+        JsonString(record.target()),
+    );
+    if include_context {
+        json.push_str(",\"context\":{");
+        context::with_current(|context| {
+            for (i, (key, value)) in context.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                let _ = write!(json, "{}:{}", JsonString(key), JsonString(value));
+            }
+        });
+        json.push('}');
+    }
+    let _ = write!(json, ",\"message\":{}}}", JsonString(&message.to_string()));
+    json
+}
+
+/// Displays a string as a quoted JSON string
+pub(crate) struct JsonString<'a>(pub(crate) &'a str);
+
+impl fmt::Display for JsonString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_char('"')?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\t' => f.write_str("\\t")?,
+                c if c.is_control() => write!(f, "\\u{:04x}", c as u32)?,
+                c => f.write_char(c)?,
+            }
+        }
+        f.write_char('"')
+    }
+}
+
+/// The source file as logged, relative to `trim_source_prefix` if it starts with it or the file name otherwise
+pub(crate) fn source_file_name(file: &str, trim_source_prefix: Option<&str>) -> String {
+    if let Some(relative_path) = trim_source_prefix.and_then(|prefix| file.strip_prefix(prefix)) {
+        return relative_path.trim_start_matches(['/', '\\']).to_owned();
+    }
+    Path::new(file)
+        .file_name()
+        .unwrap_or_default()
+        .to_os_string()
+        .into_string()
+        .unwrap_or_default()
+}
+
+pub(crate) fn match_level(level: Level, reduced_log_levels: bool) -> Level {
+    match level {
+        Level::Debug | Level::Trace if reduced_log_levels => Level::Info,
+        _ => level,
+    }
+}
+
+/// Displays the inner value with control characters other than `\n` escaped as `\xNN` if enabled
+pub(crate) struct Sanitized<T> {
+    value: T,
+    enabled: bool,
+}
+
+impl<T: fmt::Display> Sanitized<T> {
+    pub(crate) fn new(value: T, enabled: bool) -> Self {
+        Sanitized {
+            value,
+            enabled,
+        }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Sanitized<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.enabled {
+            write!(EscapingWriter(f), "{}", self.value)
+        } else {
+            write!(f, "{}", self.value)
+        }
+    }
+}
+
+struct EscapingWriter<'a, W>(&'a mut W);
+
+impl<W: fmt::Write> fmt::Write for EscapingWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut unescaped = 0;
+        for (i, c) in s.char_indices() {
+            if c.is_ascii_control() && c != '\n' {
+                self.0.write_str(&s[unescaped..i])?;
+                write!(self.0, "\\x{:02x}", c as u32)?;
+                unescaped = i + 1;
+            }
+        }
+        self.0.write_str(&s[unescaped..])
+    }
+}
+
+/// Writes `prefix` after every newline
+struct PrefixingWriter<'a, W> {
+    out: &'a mut W,
+    prefix: &'a str,
+}
+
+impl<W: fmt::Write> fmt::Write for PrefixingWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, line) in s.split('\n').enumerate() {
+            if i > 0 {
+                self.out.write_char('\n')?;
+                self.out.write_str(self.prefix)?;
+            }
+            self.out.write_str(line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Format a single log line without a trailing newline
+pub fn format_record(
+    opts: &FormatOptions,
+    timestamp: DateTime<Local>,
+    level: Level,
+    tid: u64,
+    file: &str,
+    line: u32,
+    message: &fmt::Arguments,
+) -> String {
+    let mut log_line = String::new();
+    write_record(&mut log_line, opts, timestamp, level, tid, file, line, message).expect("formatting into a String can't fail");
+    log_line
+}
+
+/// Write a single log line without a trailing newline into `out`
+#[allow(clippy::too_many_arguments)]
+pub fn write_record<W: fmt::Write>(
+    out: &mut W,
+    opts: &FormatOptions,
+    timestamp: DateTime<Local>,
+    level: Level,
+    tid: u64,
+    file: &str,
+    line: u32,
+    message: &fmt::Arguments,
+) -> fmt::Result {
+    match opts.multiline_style {
+        MultilineStyle::Raw => {
+            write_prefix(out, opts, timestamp, level, tid, file, line)?;
+            write!(out, "{}", message)
+        }
+        MultilineStyle::Prefixed => {
+            let mut prefix = String::new();
+            write_prefix(&mut prefix, opts, timestamp, level, tid, file, line)?;
+            out.write_str(&prefix)?;
+            write!(
+                PrefixingWriter {
+                    out,
+                    prefix: &prefix,
+                },
+                "{}",
+                message
+            )
+        }
+    }
+}
+
+/// Write the part of a log line before the message, see [`write_record`]
+pub(crate) fn write_prefix<W: fmt::Write>(
+    out: &mut W,
+    opts: &FormatOptions,
+    timestamp: DateTime<Local>,
+    level: Level,
+    tid: u64,
+    file: &str,
+    line: u32,
+) -> fmt::Result {
+    let timestamp_format = if opts.with_year {
+        "%Y%m%d %H:%M:%S%.6f"
+    } else {
+        "%m%d %H:%M:%S%.6f"
+    };
+    write!(
+        out,
+        "{}{} {:5} {}:{}] ",
+        opts.letter(level),
+        timestamp.format(timestamp_format),
+        tid,
+        file,
+        line,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone};
+
+    use super::*;
+
+    fn timestamp() -> DateTime<Local> {
+        let naive = NaiveDate::from_ymd_opt(2021, 4, 1)
+            .unwrap()
+            .and_hms_micro_opt(12, 34, 56, 987)
+            .unwrap();
+        Local.from_local_datetime(&naive).unwrap()
+    }
+
+    fn format(opts: FormatOptions, level: Level) -> String {
+        format_record(&opts, timestamp(), level, 123, "a.rs", 42, &format_args!("{}", "msg"))
+    }
+
+    #[test]
+    fn golden_lines() {
+        let glog = FormatOptions::default();
+        let year = glog.with_year(true);
+        let levels = glog.reduced_log_levels(false);
+        let year_levels = year.reduced_log_levels(false);
+        let cases = [
+            (glog, Level::Trace, "I0401 12:34:56.000987   123 a.rs:42] msg"),
+            (glog, Level::Debug, "I0401 12:34:56.000987   123 a.rs:42] msg"),
+            (glog, Level::Info, "I0401 12:34:56.000987   123 a.rs:42] msg"),
+            (glog, Level::Warn, "W0401 12:34:56.000987   123 a.rs:42] msg"),
+            (glog, Level::Error, "E0401 12:34:56.000987   123 a.rs:42] msg"),
+            (levels, Level::Trace, "T0401 12:34:56.000987   123 a.rs:42] msg"),
+            (levels, Level::Debug, "D0401 12:34:56.000987   123 a.rs:42] msg"),
+            (levels, Level::Info, "I0401 12:34:56.000987   123 a.rs:42] msg"),
+            (year, Level::Debug, "I20210401 12:34:56.000987   123 a.rs:42] msg"),
+            (year, Level::Error, "E20210401 12:34:56.000987   123 a.rs:42] msg"),
+            (year_levels, Level::Trace, "T20210401 12:34:56.000987   123 a.rs:42] msg"),
+            (year_levels, Level::Warn, "W20210401 12:34:56.000987   123 a.rs:42] msg"),
+        ];
+        for (opts, level, expected) in cases.iter() {
+            assert_eq!(format(*opts, *level), *expected, "{:?} {}", opts, level);
+        }
+    }
+
+    #[test]
+    fn write_record_matches_format_record() {
+        let opts = FormatOptions::default();
+        let mut buffer = String::from("prefix ");
+        write_record(
+            &mut buffer,
+            &opts,
+            timestamp(),
+            Level::Warn,
+            123,
+            "a.rs",
+            42,
+            &format_args!("{}", "msg"),
+        )
+        .unwrap();
+        assert_eq!(buffer, format!("prefix {}", format(opts, Level::Warn)));
+    }
+
+    #[test]
+    fn multiline_styles() {
+        let message = format_args!("first\nsecond");
+        let raw = FormatOptions::default();
+        let prefixed = raw.multiline_style(MultilineStyle::Prefixed);
+        assert_eq!(
+            format_record(&raw, timestamp(), Level::Info, 123, "a.rs", 42, &message),
+            "I0401 12:34:56.000987   123 a.rs:42] first\nsecond"
+        );
+        assert_eq!(
+            format_record(&prefixed, timestamp(), Level::Info, 123, "a.rs", 42, &message),
+            "I0401 12:34:56.000987   123 a.rs:42] first\nI0401 12:34:56.000987   123 a.rs:42] second"
+        );
+    }
+
+    #[test]
+    fn sanitized() {
+        let message = "\x1b[31mred\x1b[0m\x00 \x7f\tmulti\nline ✓";
+        assert_eq!(Sanitized::new(message, false).to_string(), message);
+        assert_eq!(
+            Sanitized::new(message, true).to_string(),
+            "\\x1b[31mred\\x1b[0m\\x00 \\x7f\\x09multi\nline ✓"
+        );
+    }
+}
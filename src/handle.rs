@@ -0,0 +1,426 @@
+use std::{panic::Location, sync::Arc, time::Duration};
+
+use log::{Level, Log, Record};
+
+use crate::{
+    call_site::CallSites,
+    flag_registry,
+    redact::INTERNAL_TARGET,
+    schedule,
+    target_policy::ActivePolicy,
+    BatchWriter,
+    FlagParseError,
+    Glog,
+    LogFileInfo,
+    LogSink,
+    ScheduleWindow,
+    SelfTestReport,
+    SinkConfig,
+    SinkId,
+    StatsSnapshot,
+    StreamWriter,
+    TargetPolicy,
+};
+
+/// Handle to the registered logger to change its behavior at runtime
+///
+/// Returned by [`Glog::try_init`].
+///
+/// Dropping the handle doesn't flush the log output, and [`std::process::exit`] skips it anyway.
+/// End the process with [`glog::exit`](crate::exit), which shuts the registered logger down within
+/// a timeout, or call [`GlogHandle::shutdown`] before exiting another way.
+#[must_use = "end the process with glog::exit, or call GlogHandle::shutdown before exiting another way"]
+#[derive(Clone)]
+pub struct GlogHandle {
+    pub(crate) logger: Glog,
+}
+
+impl GlogHandle {
+    pub(crate) fn new(logger: Glog) -> Self {
+        GlogHandle {
+            logger,
+        }
+    }
+
+    /// Change the minimum level (inclusive) that should be logged
+    ///
+    /// Ends an [escalation](crate::Flags::adaptive_error_threshold) of the minimum level.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// let handle = glog::new()
+    ///     .try_init(Flags {
+    ///         logtostderr: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    ///
+    /// debug!("Not logged with the default minloglevel");
+    /// assert_eq!(handle.stats_snapshot().messages.debug, 0);
+    ///
+    /// handle.set_min_level(Level::Debug);
+    /// assert_eq!(handle.current_min_level(), Level::Debug);
+    ///
+    /// debug!("Logged now");
+    /// assert_eq!(handle.stats_snapshot().messages.debug, 1);
+    /// ```
+    pub fn set_min_level(&self, level: Level) {
+        self.change_min_level(level, None);
+    }
+
+    /// Change the minimum level like [`GlogHandle::set_min_level`], noting `reason` in the audit record
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// let handle = glog::new().try_init(Flags::default()).unwrap();
+    ///
+    /// // Logs "minloglevel changed from INFO to DEBUG: incident-1234"
+    /// handle.set_min_level_with_reason(Level::Debug, "incident-1234");
+    /// ```
+    pub fn set_min_level_with_reason(&self, level: Level, reason: &str) {
+        self.change_min_level(level, Some(reason));
+    }
+
+    /// Every change is audited with an INFO record which is logged at any minimum level
+    ///
+    /// It is written while the more verbose of both levels is in effect, so it is surrounded by
+    /// the records of that level.
+    fn change_min_level(&self, level: Level, reason: Option<&str>) {
+        let old = self.logger.flags().minloglevel;
+        let audit = || self.audit("minloglevel", &old.to_string(), &level.to_string(), reason);
+        if level < old {
+            audit();
+        }
+        self.logger.flags.write().unwrap().minloglevel = level;
+        self.logger.adaptive.cancel();
+        log::set_max_level(self.logger.max_level().to_level_filter());
+        if level > old {
+            audit();
+        }
+    }
+
+    /// Log the change of the runtime configuration `field` from `old` to `new`
+    fn audit(&self, field: &str, old: &str, new: &str, reason: Option<&str>) {
+        let reason = reason.map_or_else(String::new, |reason| format!(": {}", reason));
+        self.logger.log(
+            &Record::builder()
+                .level(Level::Info)
+                .target(INTERNAL_TARGET)
+                .args(format_args!("{} changed from {} to {}{}", field, old, new, reason))
+                .build(),
+        );
+    }
+
+    /// Additionally write the records selected by `config` to `sink`, see [`Glog::add_sink_with`]
+    ///
+    /// Enables the levels the sink requests, if they are more verbose than before.
+    pub fn add_sink_with<S: LogSink + 'static>(&self, config: SinkConfig, sink: S) -> SinkId {
+        let id = self.logger.sinks.add(config, Arc::new(sink));
+        log::set_max_level(self.logger.max_level().to_level_filter());
+        id
+    }
+
+    /// Stop writing to the sink `id`, returns whether it was still registered
+    ///
+    /// Disables the levels only the sink requested.
+    pub fn remove_sink(&self, id: SinkId) -> bool {
+        let removed = self.logger.sinks.remove(id);
+        log::set_max_level(self.logger.max_level().to_level_filter());
+        removed
+    }
+
+    /// Replace the [target policy](crate::Flags::target_policy) as a whole
+    ///
+    /// The samplers and rate limiters start over. On a parse error the current policy is kept and
+    /// the error has the offending clause as its value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::Flags;
+    ///
+    /// let handle = glog::new()
+    ///     .try_init(Flags {
+    ///         logtostderr: true,
+    ///         target_policy: "hyper=warn".to_owned(),
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    ///
+    /// handle.set_target_policy("hyper=warn, sqlx=debug@sample:0.1").unwrap();
+    /// let error = handle.set_target_policy("hyper=warn@sample:2").unwrap_err();
+    /// assert_eq!(error.value(), "hyper=warn@sample:2");
+    /// ```
+    pub fn set_target_policy(&self, policy: &str) -> Result<(), FlagParseError> {
+        self.change_target_policy(policy, None)
+    }
+
+    /// Replace the target policy like [`GlogHandle::set_target_policy`], noting `reason` in the audit record
+    pub fn set_target_policy_with_reason(&self, policy: &str, reason: &str) -> Result<(), FlagParseError> {
+        self.change_target_policy(policy, Some(reason))
+    }
+
+    /// The audit record is written after the change, it isn't subject to the target policy
+    fn change_target_policy(&self, policy: &str, reason: Option<&str>) -> Result<(), FlagParseError> {
+        let parsed: TargetPolicy = policy.parse()?;
+        *self.logger.target_policy.write().unwrap() = Arc::new(ActivePolicy::new(parsed));
+        let old = std::mem::replace(&mut self.logger.flags.write().unwrap().target_policy, policy.to_owned());
+        log::set_max_level(self.logger.max_level().to_level_filter());
+        self.audit("target_policy", &format!("{:?}", old), &format!("{:?}", policy), reason);
+        Ok(())
+    }
+
+    /// Replace the time windows raising the minimum level, see [`Glog::with_schedule`]
+    ///
+    /// The change is audited like [`GlogHandle::set_min_level`].
+    pub fn set_schedule(&self, windows: Vec<ScheduleWindow>) {
+        let new = schedule::describe(&windows);
+        let old = schedule::describe(&self.logger.schedule.set(windows));
+        self.audit("schedule", &format!("{:?}", old), &format!("{:?}", new), None);
+    }
+
+    /// Drop the records of a `file:line` or `file:*` call site, like the `suppress_at` flag
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::Flags;
+    ///
+    /// let handle = glog::new()
+    ///     .try_init(Flags {
+    ///         logtostderr: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    ///
+    /// handle.add_suppression("vendored.rs:120").unwrap();
+    /// handle.add_suppression("noisy.rs:*").unwrap();
+    /// assert!(handle.add_suppression("noisy.rs").is_err());
+    ///
+    /// assert!(handle.remove_suppression("noisy.rs:*"));
+    /// assert!(handle.suppressed_call_sites().is_empty());
+    /// ```
+    pub fn add_suppression(&self, call_site: &str) -> Result<(), FlagParseError> {
+        let (file, line) = flag_registry::parse_call_site("suppress_at", call_site, true)?;
+        self.change_suppressions(|call_sites| call_sites.insert(file, line));
+        Ok(())
+    }
+
+    /// Stop dropping the records of exactly this call site, returns whether it was suppressed
+    ///
+    /// Removing a line doesn't affect a `file:*` of the same file, and removing `file:*` keeps
+    /// the lines of the file which were suppressed on their own.
+    pub fn remove_suppression(&self, call_site: &str) -> bool {
+        flag_registry::parse_call_site("suppress_at", call_site, true)
+            .is_ok_and(|(file, line)| self.change_suppressions(|call_sites| call_sites.remove(&file, line)))
+    }
+
+    /// Apply `change` to the suppressed call sites and audit it if it returns `true`
+    fn change_suppressions(&self, change: impl FnOnce(&mut CallSites) -> bool) -> bool {
+        let (old, new) = {
+            let mut call_sites = self.logger.suppressions.call_sites.write().unwrap();
+            let old = call_sites.to_string();
+            if !change(&mut call_sites) {
+                return false;
+            }
+            (old, call_sites.to_string())
+        };
+        self.audit("suppress_at", &format!("{:?}", old), &format!("{:?}", new), None);
+        true
+    }
+
+    /// The `file:line` of every call site with suppressed records and their number, the most dropped first
+    pub fn suppressed_call_sites(&self) -> Vec<(String, u64)> {
+        self.logger.suppressions.summary()
+    }
+
+    /// Check that logging works end to end, e.g. at deploy time
+    ///
+    /// Logs a uniquely tagged record with the `glog::internal` target at every enabled level,
+    /// flushes and reads the log files back to find the records in every file they belong in.
+    /// Every sink additionally gets a test record on another thread, which it has to accept within
+    /// a second. glog-rs doesn't rotate files itself, so there is no rotation to check.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::Flags;
+    ///
+    /// let handle = glog::new().try_init(Flags::default()).unwrap();
+    /// let report = handle.self_test();
+    /// for failure in report.failures() {
+    ///     eprintln!("{} is broken: {}", failure.component, failure.error.as_ref().unwrap());
+    /// }
+    /// assert!(report.passed());
+    /// ```
+    pub fn self_test(&self) -> SelfTestReport {
+        self.logger.self_test()
+    }
+
+    /// The minimum level (inclusive) that is currently logged
+    pub fn current_min_level(&self) -> Level {
+        self.logger.flags().minloglevel
+    }
+
+    /// Wait until the marker with the sequence number `seq` has been written and flushed
+    ///
+    /// The logger is flushed once the marker was written, so markers nobody waits for don't cost
+    /// a flush. Returns `false` if the marker wasn't written within `timeout`.
+    /// See [`marker`](crate::marker) for an example.
+    pub fn wait_for_marker(&self, seq: u64, timeout: Duration) -> bool {
+        if !self.logger.markers.wait(seq, timeout) {
+            return false;
+        }
+        self.logger.flush();
+        true
+    }
+
+    /// Get the current logging statistics, see [`Glog::stats_snapshot`]
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
+        self.logger.stats_snapshot()
+    }
+
+    /// Percentiles of the write latency since the last call, see [`Glog::measure_write_latency`]
+    #[cfg(feature = "metrics")]
+    pub fn measure_write_latency(&self, percentiles: &[f64]) -> std::collections::HashMap<String, Duration> {
+        self.logger.measure_write_latency(percentiles)
+    }
+
+    /// The longest write to the log files, see [`Glog::max_write_latency`]
+    #[cfg(feature = "metrics")]
+    pub fn max_write_latency(&self) -> Duration {
+        self.logger.max_write_latency()
+    }
+
+    /// Write `record` and `extra_context` to stderr in emergencies, see [`Glog::write_stderr_with_context`]
+    pub fn write_stderr_with_context(&self, record: &Record, extra_context: &str) {
+        self.logger.write_stderr_with_context(record, extra_context);
+    }
+
+    /// The latest lines of `level` and more severe levels, see [`Glog::tail`]
+    pub fn tail(&self, level: Level, n: usize) -> Vec<String> {
+        self.logger.tail(level, n)
+    }
+
+    /// List the log files this process created in the log directories
+    ///
+    /// Includes the files currently written to as well as older files of this process matching
+    /// the log file naming scheme, ordered from the least severe level and oldest file.
+    /// Files of other processes are never included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// let handle = glog::new().try_init(Flags::default()).unwrap();
+    ///
+    /// info!("A log message");
+    /// for log_file in handle.log_files() {
+    ///     println!("{} {} bytes", log_file.path.display(), log_file.size);
+    /// }
+    /// ```
+    pub fn log_files(&self) -> Vec<LogFileInfo> {
+        self.logger.log_files()
+    }
+
+    /// Flush the output and append the [`Footer`](crate::envelope::Footer) to the level log files
+    ///
+    /// Only does the flush unless the files were created with [`FileHeader::JsonEnvelope`](crate::FileHeader::JsonEnvelope).
+    /// A capture of fd 2 is released first, see `release_raw_stderr`.
+    /// Call it once right before the process exits, records logged afterwards follow the footer.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::{FileHeader, Flags};
+    ///
+    /// let handle = glog::new()
+    ///     .with_file_header(FileHeader::JsonEnvelope)
+    ///     .try_init(Flags::default())
+    ///     .unwrap();
+    ///
+    /// info!("The last message");
+    /// handle.shutdown();
+    /// ```
+    pub fn shutdown(&self) {
+        // The last lines written to the captured fd 2 are logged before the footer
+        #[cfg(all(unix, feature = "capture-stderr"))]
+        if let Err(why) = crate::release_raw_stderr() {
+            Glog::write_stderr_raw(&format!("glog: couldn't restore stderr: {}\n", why));
+        }
+        self.logger.shutdown();
+    }
+
+    /// Log all lines added to the [`BatchWriter`] by `f` as one contiguous block
+    ///
+    /// The lines share a single timestamp and the source location of the call. They are written
+    /// to each output at once, so lines logged by other threads can't end up inside the block.
+    /// `target` is also the module path of the block, usually `module_path!()` like the default
+    /// target of the `log` macros. `f` isn't called if `level` isn't logged for `target`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// let handle = glog::new().try_init(Flags::default()).unwrap();
+    ///
+    /// let config = [("threads", 4), ("port", 8080)];
+    /// handle.log_batch(Level::Info, module_path!(), |batch| {
+    ///     for (key, value) in config.iter() {
+    ///         batch.line(format_args!("{} = {}", key, value));
+    ///     }
+    /// });
+    /// ```
+    #[track_caller]
+    pub fn log_batch(&self, level: Level, target: &str, f: impl FnOnce(&mut BatchWriter<'_>)) {
+        let location = Location::caller();
+        self.logger.write_batch(
+            &Record::builder()
+                .level(level)
+                .target(target)
+                .module_path(Some(target))
+                .file(Some(location.file()))
+                .line(Some(location.line()))
+                .args(format_args!(""))
+                .build(),
+            f,
+        );
+    }
+
+    /// Log what is written to the returned writer line by line, e.g. a large report
+    ///
+    /// Every line becomes a record at `level` tagged with `name`, see [`StreamWriter`]. Nothing is
+    /// logged if `level` isn't logged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io::{self, Write};
+    ///
+    /// use glog::{ByteSize, Flags};
+    /// use log::*;
+    ///
+    /// let handle = glog::new().try_init(Flags::default()).unwrap();
+    ///
+    /// let mut report = handle.writer(Level::Info, "report").with_max_bytes(ByteSize::mib(1));
+    /// writeln!(report, "{} requests", 1200).unwrap();
+    /// io::copy(&mut "slowest: /search\nfailed: /login\n".as_bytes(), &mut report).unwrap();
+    /// ```
+    #[track_caller]
+    pub fn writer(&self, level: Level, name: &str) -> StreamWriter<'_> {
+        StreamWriter::new(&self.logger, level, name, Location::caller())
+    }
+}
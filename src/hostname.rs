@@ -0,0 +1,85 @@
+use std::{ffi::OsString, sync::mpsc, thread, time::Duration};
+
+use crate::Glog;
+
+/// Upper bound for resolving the fully qualified domain name of the host
+const FQDN_RESOLUTION_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The name of this host, optionally resolved to its fully qualified domain name
+///
+/// Falls back to the short hostname with a warning on stderr if the resolution fails or takes too long.
+pub(crate) fn hostname(fqdn: bool) -> OsString {
+    #[cfg(target_arch = "wasm32")]
+    let hostname = OsString::new();
+    #[cfg(not(target_arch = "wasm32"))]
+    let hostname = gethostname::gethostname();
+    // Resolving needs a thread for the timeout, which wasm32 doesn't have
+    if !fqdn || cfg!(target_arch = "wasm32") {
+        return hostname;
+    }
+
+    let (resolved_sender, resolved_receiver) = mpsc::channel();
+    let short_hostname = hostname.clone();
+    thread::spawn(move || {
+        let _ = resolved_sender.send(canonical_hostname(&short_hostname));
+    });
+    match resolved_receiver.recv_timeout(FQDN_RESOLUTION_TIMEOUT) {
+        Ok(Some(fqdn)) => OsString::from(fqdn),
+        Ok(None) => {
+            Glog::write_stderr_raw("glog: couldn't resolve the fully qualified hostname, using the short hostname\n");
+            hostname
+        }
+        Err(_) => {
+            Glog::write_stderr_raw("glog: timed out resolving the fully qualified hostname, using the short hostname\n");
+            hostname
+        }
+    }
+}
+
+/// The id of this machine from `/etc/machine-id`, if it can be read
+pub(crate) fn machine_id() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    if let Ok(machine_id) = std::fs::read_to_string("/etc/machine-id") {
+        let machine_id = machine_id.trim();
+        if !machine_id.is_empty() {
+            return Some(machine_id.to_owned());
+        }
+    }
+    None
+}
+
+#[cfg(target_family = "unix")]
+fn canonical_hostname(hostname: &OsString) -> Option<String> {
+    use std::{
+        ffi::{CStr, CString},
+        os::unix::ffi::OsStrExt,
+        ptr,
+    };
+
+    let node = CString::new(hostname.as_bytes()).ok()?;
+    // SAFETY: addrinfo is a plain C struct for which all zeroes is a valid value
+    let mut hints: libc::addrinfo = unsafe { std::mem::zeroed() };
+    hints.ai_flags = libc::AI_CANONNAME;
+    let mut addresses = ptr::null_mut();
+    // SAFETY: node and hints are valid for the duration of the call, addresses is freed below
+    if unsafe { libc::getaddrinfo(node.as_ptr(), ptr::null(), &hints, &mut addresses) } != 0 {
+        return None;
+    }
+    // SAFETY: getaddrinfo succeeded so addresses points to at least one entry
+    let canonical_name = unsafe {
+        let canonical_name = (*addresses).ai_canonname;
+        let canonical_name = if canonical_name.is_null() {
+            None
+        } else {
+            Some(CStr::from_ptr(canonical_name).to_string_lossy().into_owned())
+        };
+        libc::freeaddrinfo(addresses);
+        canonical_name
+    };
+    canonical_name.filter(|name| !name.is_empty())
+}
+
+#[cfg(not(target_family = "unix"))]
+fn canonical_hostname(_hostname: &OsString) -> Option<String> {
+    None
+}
@@ -0,0 +1,96 @@
+use std::ffi::{OsStr, OsString};
+
+use chrono::{DateTime, Local};
+use if_empty::*;
+
+use crate::{hostname, log_file};
+
+/// Everything identifying this process in log file names and headers
+///
+/// Replaced by fixed values in tests to get predictable file names and headers.
+#[derive(Debug, Clone)]
+pub(crate) struct Identity {
+    /// File name of the executable, empty if unknown
+    pub(crate) program: OsString,
+    /// Empty if unknown
+    pub(crate) hostname: OsString,
+    /// Empty if unknown
+    pub(crate) username: String,
+    pub(crate) pid: u32,
+    pub(crate) start_time: DateTime<Local>,
+    /// Used instead of the current time if set
+    pub(crate) fixed_time: Option<DateTime<Local>>,
+}
+
+impl Identity {
+    /// The identity of the running process with the short hostname
+    pub(crate) fn current() -> Self {
+        let exe = std::env::current_exe().ok();
+        Identity {
+            program: exe
+                .as_deref()
+                .and_then(|exe| exe.file_name())
+                .map(OsStr::to_owned)
+                .unwrap_or_default(),
+            hostname: hostname::hostname(false),
+            #[cfg(not(target_arch = "wasm32"))]
+            username: whoami::username(),
+            #[cfg(target_arch = "wasm32")]
+            username: String::new(),
+            pid: std::process::id(),
+            start_time: Local::now(),
+            fixed_time: None,
+        }
+    }
+
+    pub(crate) fn now(&self) -> DateTime<Local> {
+        self.fixed_time.unwrap_or_else(Local::now)
+    }
+
+    pub(crate) fn program(&self) -> OsString {
+        self.program.clone().if_empty(OsString::from("UNKNOWN"))
+    }
+
+    /// The hostname for the log file header
+    pub(crate) fn header_hostname(&self) -> &str {
+        self.hostname.to_str().unwrap_or("UNKNOWN")
+    }
+
+    /// The start of all log file names, `<program>.<hostname>.<username>.log.`
+    pub(crate) fn log_file_name_prefix(&self) -> OsString {
+        let mut prefix = self.program();
+        prefix.push(".");
+        prefix.push(self.hostname.clone().if_empty(OsString::from("(unknown)")));
+        prefix.push(".");
+        prefix.push(self.username.clone().if_empty("invalid-user".to_string()));
+        prefix.push(".log.");
+        prefix
+    }
+
+    /// The end of the names of log files created now, `.<yyyymmdd-hhmmss>.<pid>`
+    pub(crate) fn log_file_name_suffix(&self) -> String {
+        format!(".{}.{}", self.now().format(log_file::FILE_NAME_TIMESTAMP_FORMAT), self.pid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn fallbacks() {
+        let identity = Identity {
+            program: OsString::new(),
+            hostname: OsString::new(),
+            username: String::new(),
+            pid: 42,
+            start_time: Local.with_ymd_and_hms(2021, 4, 1, 12, 0, 0).unwrap(),
+            fixed_time: Some(Local.with_ymd_and_hms(2021, 4, 1, 12, 34, 56).unwrap()),
+        };
+        assert_eq!(identity.log_file_name_prefix(), "UNKNOWN.(unknown).invalid-user.log.");
+        assert_eq!(identity.log_file_name_suffix(), ".20210401-123456.42");
+        assert_eq!(identity.header_hostname(), "");
+    }
+}
@@ -0,0 +1,284 @@
+//! Sparse indexes of the log files to seek to a time without reading the whole file
+//!
+//! With [`Flags::log_index_bytes`](crate::Flags::log_index_bytes) or
+//! [`Flags::log_index_interval`](crate::Flags::log_index_interval) the logger keeps a sidecar
+//! `<log file>.idx` next to every level log file. A log file recreated after it was removed gets
+//! a new sidecar. The sidecar is a text file starting with the line `glog index 1`, followed by
+//! one line per entry:
+//!
+//! ```text
+//! <offset> <micros> <check>
+//! ```
+//!
+//! `offset` is the byte offset of the start of a line in the log file and `micros` the time in
+//! microseconds since the Unix epoch by which every line before `offset` was written, so none of
+//! them has a later timestamp. `check` is the FNV-1a hash of `<offset> <micros>` in hexadecimal.
+//! Entries are written in increasing order of both.
+//!
+//! A missing, damaged or foreign sidecar makes [`lookup`] return offset 0, the readers fall back
+//! to reading the whole file instead of skipping lines they are looking for.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::io::BufRead;
+//! use chrono::{Local, TimeZone};
+//! use glog::index::Seekable;
+//!
+//! let path = "/tmp/server.host.alice.log.INFO.20210401-123456.42";
+//! let around = Local.with_ymd_and_hms(2021, 4, 1, 14, 30, 0).unwrap();
+//! for line in Seekable::open_at(path, around).unwrap().lines() {
+//!     println!("{}", line.unwrap());
+//! }
+//! ```
+
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+
+use crate::log_fs::{FileStat, LogWriter};
+
+/// The first line of a sidecar
+const HEADER: &str = "glog index 1";
+
+/// Suffix appended to the path of a log file for its sidecar
+pub const INDEX_SUFFIX: &str = ".idx";
+
+/// The path of the sidecar of the log file at `log_path`
+pub fn index_path(log_path: impl AsRef<Path>) -> PathBuf {
+    let mut path = log_path.as_ref().as_os_str().to_owned();
+    path.push(INDEX_SUFFIX);
+    path.into()
+}
+
+/// FNV-1a, detects damaged entries
+fn check(offset: u64, micros: i64) -> u64 {
+    format!("{} {}", offset, micros)
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// The entries of a sidecar if all of them are intact and fit a log file of `log_len` bytes
+///
+/// A last line without a newline is ignored, the logger may have been writing it.
+fn parse(sidecar: &str, log_len: u64) -> Option<Vec<(u64, i64)>> {
+    let mut lines = sidecar.split_inclusive('\n').filter(|line| line.ends_with('\n'));
+    if lines.next()? != format!("{}\n", HEADER) {
+        return None;
+    }
+    let mut entries: Vec<(u64, i64)> = Vec::new();
+    for line in lines {
+        let mut fields = line.trim_end_matches('\n').split(' ');
+        let (offset, micros, checked) = (fields.next()?, fields.next()?, fields.next()?);
+        let (offset, micros): (u64, i64) = (offset.parse().ok()?, micros.parse().ok()?);
+        let in_order = entries
+            .last()
+            .map_or(true, |&(last_offset, last_micros)| offset > last_offset && micros >= last_micros);
+        if fields.next().is_some() || checked != format!("{:x}", check(offset, micros)) || !in_order || offset > log_len {
+            return None;
+        }
+        entries.push((offset, micros));
+    }
+    Some(entries)
+}
+
+/// A byte offset into the log file at `log_path` before every line logged at or after `timestamp`
+///
+/// Reading from the offset skips at most one index granule of lines before `timestamp`. Without an
+/// intact sidecar the offset is 0. Fails if the log file can't be accessed.
+pub fn lookup(log_path: impl AsRef<Path>, timestamp: DateTime<Local>) -> io::Result<u64> {
+    let log_path = log_path.as_ref();
+    let log_len = fs::metadata(log_path)?.len();
+    let sidecar = match fs::read(index_path(log_path)) {
+        Ok(sidecar) => sidecar,
+        Err(why) if why.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(why) => return Err(why),
+    };
+    let Some(entries) = std::str::from_utf8(&sidecar).ok().and_then(|sidecar| parse(sidecar, log_len)) else {
+        return Ok(0);
+    };
+    let target = timestamp.timestamp_micros();
+    Ok(entries
+        .iter()
+        .take_while(|(_, micros)| *micros < target)
+        .last()
+        .map_or(0, |(offset, _)| *offset))
+}
+
+/// Opens log files at a time, see [`lookup`]
+pub struct Seekable;
+
+impl Seekable {
+    /// Open the log file at `path` positioned at the offset [`lookup`] returns for `timestamp`
+    pub fn open_at(path: impl AsRef<Path>, timestamp: DateTime<Local>) -> io::Result<BufReader<File>> {
+        let offset = lookup(path.as_ref(), timestamp)?;
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        Ok(BufReader::new(file))
+    }
+}
+
+/// Counts the bytes written to a log file, for the offsets of its index
+pub(crate) struct Counted {
+    file: Box<dyn LogWriter>,
+    written: Arc<AtomicU64>,
+}
+
+impl Write for Counted {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.written.fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl LogWriter for Counted {
+    fn stat(&self) -> io::Result<FileStat> {
+        self.file.stat()
+    }
+
+    fn set_group(&self, gid: u32) -> io::Result<()> {
+        self.file.set_group(gid)
+    }
+
+    fn preallocate(&self, bytes: u64) -> io::Result<()> {
+        self.file.preallocate(bytes)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.file.sync()
+    }
+
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<RawFd> {
+        self.file.raw_fd()
+    }
+
+    fn current_path(&self) -> Option<PathBuf> {
+        self.file.current_path()
+    }
+
+    fn try_lock(&self) -> io::Result<bool> {
+        self.file.try_lock()
+    }
+
+    fn unlock(&self) -> io::Result<()> {
+        self.file.unlock()
+    }
+
+    fn write_line(&mut self, prefix: &[u8], message: &[u8]) -> io::Result<()> {
+        self.file.write_line(prefix, message)?;
+        let len = prefix.len() + message.len() + 1;
+        self.written.fetch_add(len as u64, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+/// Appends the entries of a log file to its sidecar
+pub(crate) struct IndexWriter {
+    sidecar: Option<Box<dyn LogWriter>>,
+    written: Arc<AtomicU64>,
+    every_bytes: Option<u64>,
+    every: Option<Duration>,
+    last_offset: u64,
+    last_micros: i64,
+    /// The latest time a line was written at
+    max_micros: i64,
+}
+
+impl IndexWriter {
+    /// Index `file`, which already contains `len` bytes, returns the file to write the lines to
+    pub(crate) fn new(
+        file: Box<dyn LogWriter>,
+        len: u64,
+        mut sidecar: Box<dyn LogWriter>,
+        every_bytes: Option<u64>,
+        every: Option<Duration>,
+        now_micros: i64,
+    ) -> (Box<dyn LogWriter>, IndexWriter) {
+        let written = Arc::new(AtomicU64::new(len));
+        let sidecar = writeln!(sidecar, "{}", HEADER).ok().map(|()| sidecar);
+        let index = IndexWriter {
+            sidecar,
+            written: written.clone(),
+            every_bytes,
+            every,
+            last_offset: len,
+            last_micros: now_micros,
+            max_micros: i64::MIN,
+        };
+        (
+            Box::new(Counted {
+                file,
+                written,
+            }),
+            index,
+        )
+    }
+
+    /// Called before a line is written at `now_micros`, adds an entry if a granule is full
+    pub(crate) fn before_line(&mut self, now_micros: i64) {
+        let offset = self.written.load(Ordering::Relaxed);
+        let bytes_due = self.every_bytes.is_some_and(|bytes| offset - self.last_offset >= bytes);
+        let time_due = self
+            .every
+            .is_some_and(|every| now_micros.saturating_sub(self.last_micros) >= every.as_micros() as i64);
+        if (bytes_due || time_due) && offset > self.last_offset && self.max_micros != i64::MIN {
+            if let Some(sidecar) = &mut self.sidecar {
+                let entry = format!("{} {} {:x}\n", offset, self.max_micros, check(offset, self.max_micros));
+                // A partially written entry damages the sidecar, which is then ignored by readers
+                if sidecar.write_all(entry.as_bytes()).is_err() {
+                    self.sidecar = None;
+                }
+            }
+            self.last_offset = offset;
+            self.last_micros = now_micros;
+        }
+        self.max_micros = self.max_micros.max(now_micros);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(offset: u64, micros: i64) -> String {
+        format!("{} {} {:x}\n", offset, micros, check(offset, micros))
+    }
+
+    #[test]
+    fn damaged_sidecars_have_no_entries() {
+        let intact = format!("{}\n{}{}", HEADER, entry(100, 5), entry(200, 7));
+        assert_eq!(parse(&intact, 300), Some(vec![(100, 5), (200, 7)]));
+        assert_eq!(parse(&format!("{}200 9", intact), 300), Some(vec![(100, 5), (200, 7)]));
+
+        let cases = [
+            intact.replacen("200 7", "200 8", 1),
+            intact.replacen("glog index 1", "glog index 2", 1),
+            format!("{}\n{}{}", HEADER, entry(200, 7), entry(100, 5)),
+            format!("{}\n{}{}", HEADER, entry(100, 7), entry(200, 5)),
+            format!("{}\n{}x\n", HEADER, entry(100, 5)),
+        ];
+        for sidecar in cases.iter() {
+            assert_eq!(parse(sidecar, 300), None, "{:?}", sidecar);
+        }
+        assert_eq!(parse(&intact, 150), None);
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    error::Error,
+    ffi::CStr,
+    io,
+    os::raw::{c_int, c_void},
+};
+
+use log::{Level, Record};
+
+use crate::LogSink;
+
+type SdJournalSendv = unsafe extern "C" fn(*const libc::iovec, c_int) -> c_int;
+
+/// A [`LogSink`] sending records to the systemd journal
+///
+/// Every record is sent with the `PRIORITY`, `MESSAGE`, `CODE_FILE`, `CODE_LINE` and `CODE_FUNC`
+/// fields. As Rust has no function names at runtime `CODE_FUNC` is the module path of the record.
+/// `libsystemd` is loaded at runtime, so binaries still work on systems without systemd.
+pub struct JournaldSink {
+    sendv: SdJournalSendv,
+}
+
+impl JournaldSink {
+    /// Load `sd_journal_sendv` from `libsystemd`
+    pub fn new() -> io::Result<Self> {
+        // SAFETY: both names are nul terminated, the library is never unloaded so the symbol stays valid
+        unsafe {
+            let library = libc::dlopen(b"libsystemd.so.0\0".as_ptr().cast(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+            if library.is_null() {
+                return Err(dl_error());
+            }
+            let sendv = libc::dlsym(library, b"sd_journal_sendv\0".as_ptr().cast());
+            if sendv.is_null() {
+                return Err(dl_error());
+            }
+            Ok(JournaldSink {
+                sendv: std::mem::transmute::<*mut c_void, SdJournalSendv>(sendv),
+            })
+        }
+    }
+}
+
+fn dl_error() -> io::Error {
+    // SAFETY: dlerror returns null or a nul terminated string valid until the next dl call
+    let why = unsafe { libc::dlerror() };
+    if why.is_null() {
+        return io::Error::other("couldn't load libsystemd");
+    }
+    io::Error::other(unsafe { CStr::from_ptr(why) }.to_string_lossy().into_owned())
+}
+
+/// The syslog priority of `level`
+fn priority(level: Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+fn journal_fields(record: &Record) -> Vec<String> {
+    let mut fields = vec![
+        format!("PRIORITY={}", priority(record.level())),
+        format!("MESSAGE={}", record.args()),
+    ];
+    if let Some(file) = record.file() {
+        fields.push(format!("CODE_FILE={}", file));
+    }
+    if let Some(line) = record.line() {
+        fields.push(format!("CODE_LINE={}", line));
+    }
+    if let Some(module_path) = record.module_path() {
+        fields.push(format!("CODE_FUNC={}", module_path));
+    }
+    fields
+}
+
+impl LogSink for JournaldSink {
+    fn write(&self, record: &Record, _message: &str) -> Result<(), Box<dyn Error>> {
+        let fields = journal_fields(record);
+        let iovecs: Vec<libc::iovec> = fields
+            .iter()
+            .map(|field| libc::iovec {
+                iov_base: field.as_ptr() as *mut c_void,
+                iov_len: field.len(),
+            })
+            .collect();
+        // SAFETY: the iovecs point into `fields` which outlives the call
+        let sent = unsafe { (self.sendv)(iovecs.as_ptr(), iovecs.len() as c_int) };
+        if sent < 0 {
+            return Err(io::Error::from_raw_os_error(-sent).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields() {
+        let record = Record::builder()
+            .level(Level::Warn)
+            .file(Some("src/main.rs"))
+            .line(Some(42))
+            .module_path(Some("app::server"))
+            .args(format_args!("two\nlines"))
+            .build();
+        assert_eq!(
+            journal_fields(&record),
+            [
+                "PRIORITY=4",
+                "MESSAGE=two\nlines",
+                "CODE_FILE=src/main.rs",
+                "CODE_LINE=42",
+                "CODE_FUNC=app::server"
+            ]
+        );
+    }
+}
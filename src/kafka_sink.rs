@@ -0,0 +1,107 @@
+use std::{
+    error::Error,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use log::Record;
+use rdkafka::{
+    config::ClientConfig,
+    error::{KafkaError, RDKafkaErrorCode},
+    producer::{BaseRecord, DefaultProducerContext, Producer, ThreadedProducer},
+    util::Timeout,
+};
+
+use crate::{LogFormat, LogSink};
+
+/// Records waiting for delivery before new ones are dropped
+const QUEUE_CAPACITY: usize = 100_000;
+/// Upper bound for delivering the queued records when the sink is flushed
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A [`LogSink`] producing records to a Kafka topic, keyed by their level
+///
+/// The payload is the record formatted as [`LogFormat::Json`], whatever the format the sink was
+/// added with. Records are delivered by a background thread from a bounded queue. When the queue
+/// is full, e.g. while the brokers can't be reached, records are dropped and counted in
+/// [`dropped`](KafkaSink::dropped).
+///
+/// # Example
+///
+/// ```no_run
+/// use glog::{Flags, KafkaSink};
+///
+/// let kafka = KafkaSink::new("kafka-1:9092,kafka-2:9092", "logs").unwrap();
+/// glog::new().with_sink(kafka).init(Flags::default()).unwrap();
+/// ```
+pub struct KafkaSink {
+    producer: ThreadedProducer<DefaultProducerContext>,
+    topic: String,
+    dropped: AtomicU64,
+}
+
+impl KafkaSink {
+    /// Produce to `topic` on the comma-separated `brokers`, which are connected to in the background
+    pub fn new(brokers: &str, topic: &str) -> Result<Self, KafkaError> {
+        KafkaSink::with_queue_capacity(brokers, topic, QUEUE_CAPACITY)
+    }
+
+    fn with_queue_capacity(brokers: &str, topic: &str, capacity: usize) -> Result<Self, KafkaError> {
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("queue.buffering.max.messages", capacity.to_string())
+            .create()?;
+        Ok(KafkaSink {
+            producer,
+            topic: topic.to_owned(),
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// The number of records dropped because the queue was full
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl LogSink for KafkaSink {
+    fn write(&self, record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+        let produced = self
+            .producer
+            .send(BaseRecord::to(&self.topic).key(record.level().as_str()).payload(message));
+        match produced {
+            Ok(()) => Ok(()),
+            Err((KafkaError::MessageProduction(RDKafkaErrorCode::QueueFull), _)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(())
+            }
+            Err((why, _)) => Err(why.into()),
+        }
+    }
+
+    fn flush(&self) {
+        let _ = self.producer.flush(Timeout::After(FLUSH_TIMEOUT));
+    }
+
+    fn required_format(&self) -> Option<LogFormat> {
+        Some(LogFormat::Json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    #[test]
+    fn records_are_dropped_when_the_queue_is_full() {
+        // Nothing listens on the port, so the records stay queued
+        let sink = KafkaSink::with_queue_capacity("127.0.0.1:1", "logs", 2).unwrap();
+        for i in 0..5 {
+            let record = Record::builder().level(Level::Info).build();
+            sink.write(&record, &format!("{{\"message\":\"record {}\"}}", i)).unwrap();
+        }
+        assert_eq!(sink.dropped(), 3);
+    }
+}
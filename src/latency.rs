@@ -0,0 +1,103 @@
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use hdrhistogram::Histogram;
+use log::Level;
+
+const LEVELS: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+/// Histograms of the time records take to be written to the log files, per level of the record
+pub(crate) struct WriteLatency {
+    /// Nanoseconds from 1ns to a minute with 3 significant digits
+    histograms: [Mutex<Histogram<u64>>; 5],
+    max_nanos: AtomicU64,
+}
+
+/// Records the time since it was created when dropped
+pub(crate) struct WriteTimer<'a> {
+    latency: &'a WriteLatency,
+    level: Level,
+    started: Instant,
+}
+
+impl Drop for WriteTimer<'_> {
+    fn drop(&mut self) {
+        let nanos = u64::try_from(self.started.elapsed().as_nanos()).unwrap_or(u64::MAX);
+        self.latency.histograms[self.level as usize - 1]
+            .lock()
+            .unwrap()
+            .saturating_record(nanos);
+        self.latency.max_nanos.fetch_max(nanos, Ordering::Relaxed);
+    }
+}
+
+impl Default for WriteLatency {
+    fn default() -> Self {
+        let histogram = || Mutex::new(Histogram::new_with_bounds(1, 60_000_000_000, 3).unwrap());
+        WriteLatency {
+            histograms: [histogram(), histogram(), histogram(), histogram(), histogram()],
+            max_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+impl WriteLatency {
+    /// Time the write of a record of `level` until the timer is dropped
+    pub(crate) fn time(&self, level: Level) -> WriteTimer<'_> {
+        WriteTimer {
+            latency: self,
+            level,
+            started: Instant::now(),
+        }
+    }
+
+    /// The `percentiles` of the levels with writes as `"<LEVEL>/p<percentile>"`, resets the histograms
+    pub(crate) fn measure(&self, percentiles: &[f64]) -> HashMap<String, Duration> {
+        let mut measured = HashMap::new();
+        for level in LEVELS.iter() {
+            let mut histogram = self.histograms[*level as usize - 1].lock().unwrap();
+            if histogram.is_empty() {
+                continue;
+            }
+            for percentile in percentiles {
+                let nanos = histogram.value_at_percentile(*percentile);
+                measured.insert(format!("{}/p{}", level, percentile), Duration::from_nanos(nanos));
+            }
+            histogram.reset();
+        }
+        measured
+    }
+
+    pub(crate) fn max(&self) -> Duration {
+        Duration::from_nanos(self.max_nanos.load(Ordering::Relaxed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_per_level() {
+        let latency = WriteLatency::default();
+        for _ in 0..10 {
+            drop(latency.time(Level::Info));
+        }
+        drop(latency.time(Level::Error));
+
+        let measured = latency.measure(&[50.0, 99.9]);
+        let mut keys: Vec<_> = measured.keys().map(String::as_str).collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["ERROR/p50", "ERROR/p99.9", "INFO/p50", "INFO/p99.9"]);
+        assert!(measured["INFO/p50"] <= measured["INFO/p99.9"]);
+        assert!(latency.measure(&[50.0]).is_empty());
+        assert!(latency.max() > Duration::ZERO);
+    }
+}
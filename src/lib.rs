@@ -72,54 +72,331 @@
 //! // I20210401 12:34:56.000100  1234 doc.rs:16] An informational message
 //! ```
 
+#![allow(clippy::unnecessary_map_or)] // Option::is_none_or needs Rust 1.82
+
 use std::{
+    any::Any,
+    borrow::Cow,
     cell::RefCell,
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     convert::TryInto,
+    env,
+    error::Error,
     ffi::{OsStr, OsString},
-    fs::{File, OpenOptions},
-    io::{LineWriter, Write},
+    fmt,
+    fs,
+    io::{self, IsTerminal, LineWriter, Write},
+    panic::{self, AssertUnwindSafe},
     path::{Path, PathBuf},
-    str::FromStr,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+        Arc,
+        Mutex,
+        MutexGuard,
+        PoisonError,
+        RwLock,
+        RwLockReadGuard,
+    },
+    thread,
+    time::{Duration, Instant},
 };
 
 use backtrace::Backtrace;
-use bimap::BiMap;
-use chrono::{DateTime, Local};
-use if_empty::*;
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime,
+    Local,
+};
 use log::{Level, Log, Metadata, Record};
+#[cfg(unix)]
+use termcolor::{Ansi, NoColor};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
-use thread_local::ThreadLocal;
 
+#[macro_use]
+mod macros;
+
+mod adaptive;
+mod attach;
+mod batch;
+mod call_site;
+mod clock;
+mod console;
+pub mod context;
+mod crash;
+pub mod envelope;
+mod error;
+mod error_chain;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "file-watch")]
+mod file_watch;
+mod flag_registry;
 mod flags;
+pub mod format;
+mod handle;
+mod hostname;
+mod identity;
+pub mod index;
+#[cfg(all(feature = "journald", target_os = "linux"))]
+mod journald;
+#[cfg(feature = "kafka-sink")]
+mod kafka_sink;
+#[cfg(feature = "metrics")]
+mod latency;
+mod log_file;
+mod log_fs;
+mod marker;
+pub mod merge;
+#[cfg(all(unix, feature = "mmap"))]
+mod mmap;
+mod output;
+#[cfg(all(unix, feature = "capture-stderr"))]
+mod raw_stderr;
+mod record;
+mod redact;
+mod ring_buffer;
+mod schedule;
+mod self_test;
+mod sink;
+#[cfg(feature = "slog")]
+mod slog_drain;
+mod stats;
+mod stream;
+mod tail;
+mod target_policy;
+mod tee;
+mod template;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+mod thread_files;
+mod trace;
+mod watchdog;
+
+use adaptive::{Adaptive, AdaptiveConfig, Transition};
+pub use attach::{attach, current_attachment, Attachment};
+pub use batch::BatchWriter;
+use call_site::{CallSites, Suppressions};
+use clock::MonotonicGuard;
+#[cfg(all(target_arch = "wasm32", not(feature = "web-sys")))]
+pub use console::take_wasm_logs;
+pub use console::{console_available, ConsoleFallback, ConsoleWriter};
+use console::RawTarget;
+pub use envelope::FileHeader;
+use envelope::{Envelope, Footer};
+pub use error::GlogError;
+pub use error_chain::log_error_chain;
+#[doc(hidden)]
+pub use error_chain::select as __error_chain;
+#[cfg(feature = "file-watch")]
+pub use file_watch::FileWatchSink;
+pub use flag_registry::{parse_minloglevel, FlagHandle, FlagParseError, FlagRegistry, FlagValue, UnknownFlags};
+pub use flags::{ByteSize, FlagConflict, Flags, RateLimit, TargetPolicy, TargetRule};
+use format::{FormatOptions, GlogFormatter, MultilineStyle, Sanitized};
+pub use handle::GlogHandle;
+use identity::Identity;
+use index::IndexWriter;
+#[cfg(all(feature = "journald", target_os = "linux"))]
+pub use journald::JournaldSink;
+#[cfg(feature = "kafka-sink")]
+pub use kafka_sink::KafkaSink;
+#[doc(hidden)]
+pub use log as __log;
+use log_file::LogFile;
+pub use log_file::{FileEncoding, LogFileInfo};
+use log_fs::{LogFs, LogWriter, RealFs};
+#[doc(hidden)]
+pub use macros::{__abort, __exit_on_dfatal};
+use marker::Markers;
+pub use marker::{marker, MARKER_TARGET};
+pub use output::redirect_fatal_to_stderr;
+use output::OutputPlan;
+#[cfg(all(unix, feature = "capture-stderr"))]
+pub use raw_stderr::{capture_raw_stderr, release_raw_stderr, RAW_STDERR_TARGET};
+pub use record::{GlogRecord, GlogRecordBuilder};
+use redact::Redactor;
+pub use redact::{HashedDisplay, Redacted};
+pub use ring_buffer::{LogEvent, QueryParams, QueryResponse, RingBufferSink};
+use schedule::Schedule;
+pub use schedule::ScheduleWindow;
+pub use self_test::{SelfTestCheck, SelfTestReport};
+pub use sink::{LogFormat, LogSink, SinkConfig, SinkId};
+use sink::{SinkErrorHandler, Sinks};
+#[cfg(feature = "slog")]
+pub use slog_drain::{slog_level_to_log, GlogFmtDrain};
+use stats::Stats;
+pub use stats::{LevelCounters, StatsInterval, StatsSnapshot};
+pub use stream::StreamWriter;
+use tail::TailBuffers;
+use target_policy::{ActivePolicy, Matched};
+use tee::{TeeWriter, Tees};
+use template::TemplateRenderer;
+pub use template::{log_template, parse_template, TEMPLATE_TARGET};
+use thread_files::ThreadFiles;
+use trace::BacktraceFilter;
+pub use trace::BacktraceStyle;
+use watchdog::WriteWatchdog;
 
-pub use flags::Flags;
+/// Upper bound for flushing the log output in [`exit`]
+const EXIT_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+/// Upper bound for waiting on the lock of a log file with `lock_shared_files`
+const SHARED_FILE_LOCK_TIMEOUT: Duration = Duration::from_millis(100);
+/// Appended to the name of a log file until its header is complete
+const IN_PROGRESS_SUFFIX: &str = ".inprogress";
+/// Upper bound for waiting on another process to publish a shared log file
+const IN_PROGRESS_PUBLISH_TIMEOUT: Duration = Duration::from_secs(1);
+/// Age after which an in progress log file is considered left behind by a crash
+const STALE_IN_PROGRESS_AGE: Duration = Duration::from_secs(5 * 60);
+/// Duration of the rotation hook after which a warning is printed
+const ROTATION_HOOK_WARNING: Duration = Duration::from_secs(1);
+/// Whether a log file was recovered with [`Glog::with_crash_recovery`], which is only reported once
+static POISON_RECOVERED: AtomicBool = AtomicBool::new(false);
+/// Whether `lock_shared_files` was reported as unsupported, which is only reported once
+static LOCKING_UNSUPPORTED: AtomicBool = AtomicBool::new(false);
+/// Whether a logger was registered by [`Glog::try_init`]
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+/// The logger registered by [`Glog::try_init`], shut down by [`exit`]
+static REGISTERED: Mutex<Option<GlogHandle>> = Mutex::new(None);
+
+type WriteErrorHandler = Arc<dyn Fn(Level, io::Error) + Send + Sync>;
+type RotationHook = Arc<dyn Fn(Level, &Path) + Send + Sync>;
+type StderrFilter = Arc<dyn Fn(&Record) -> bool + Send + Sync>;
+type TimestampFn = Arc<dyn Fn() -> DateTime<Local> + Send + Sync>;
 
 /// The logging structure doing all the heavy lifting
 pub struct Glog {
-    stderr_writer: ThreadLocal<RefCell<StandardStream>>,
+    /// Shared by all threads, per thread writers would be kept until the logger is dropped
+    stderr_writer: Arc<StandardStream>,
     compatible_verbosity: bool,
     compatible_date: bool,
-    flags: Flags,
+    flags: Arc<RwLock<Flags>>,
     application_fingerprint: Option<String>,
-    start_time: DateTime<Local>,
-    file_writer: HashMap<Level, Arc<Mutex<RefCell<File>>>>,
-    level_integers: BiMap<Level, i8>,
+    file_writer: HashMap<Level, Arc<Mutex<RefCell<LogFile>>>>,
+    /// Module prefixes with their log file, the longest prefix first
+    module_log_files: Vec<(String, Arc<Mutex<LogFile>>)>,
+    stats: Arc<Stats>,
+    #[cfg(feature = "metrics")]
+    write_latency: Arc<latency::WriteLatency>,
+    tail: Arc<TailBuffers>,
+    backtrace_filter: Option<BacktraceFilter>,
+    /// The parsed `log_backtrace_at`
+    backtrace_at: CallSites,
+    suppressions: Arc<Suppressions>,
+    log_file_group: Option<u32>,
+    markers: Arc<Markers>,
+    monotonic_guard: Arc<MonotonicGuard>,
+    identity: Identity,
+    log_file_name_prefix: OsString,
+    console_writer: Option<Arc<dyn ConsoleWriter>>,
+    write_error_handler: Option<WriteErrorHandler>,
+    /// Names replacing the standard level names in the output
+    custom_level_names: HashMap<Level, String>,
+    rotation_hook: Option<RotationHook>,
+    sinks: Arc<Sinks>,
+    sink_error_handler: SinkErrorHandler,
+    template_renderer: Option<Arc<TemplateRenderer>>,
+    file_encoding: FileEncoding,
+    file_header: FileHeader,
+    /// The [`chrono` format](chrono::format::strftime) of the stderr timestamps, see [`Glog::with_stderr_timestamp_format`]
+    stderr_timestamp_format: Option<String>,
+    extra_headers: Vec<String>,
+    redactor: Option<Redactor>,
+    stderr_filter: Option<StderrFilter>,
+    timestamp_fn: Option<TimestampFn>,
+    machine_id: Option<String>,
+    fs: Arc<dyn LogFs>,
+    write_watchdog: Option<Arc<WriteWatchdog>>,
+    tees: Arc<Tees>,
+    thread_files: ThreadFiles,
+    schedule: Arc<Schedule>,
+    adaptive: Arc<Adaptive>,
+    /// Swapped as a whole by [`GlogHandle::set_target_policy`]
+    target_policy: Arc<RwLock<Arc<ActivePolicy>>>,
+    /// Written to the log files on escalation with `adaptive_dump_ring_buffer`
+    adaptive_buffer: Option<RingBufferSink>,
+    /// Records only go to the sinks, see [`Glog::drain_to_sink`]
+    drain: bool,
+    /// See [`Glog::with_crash_recovery`]
+    crash_recovery: bool,
+    /// Whether the console was available at initialization, see [`ConsoleWriter::is_available`]
+    console_available: bool,
+    /// The write paths skipping the general formatting, chosen by [`Glog::configure`]
+    fast_paths: FastPaths,
+    #[cfg(all(unix, feature = "mmap"))]
+    mmap_chunk: Option<ByteSize>,
+    /// Descriptors written by the crash handler, `None` if it isn't installed
+    #[cfg(unix)]
+    crash_fds: Option<crash::CrashFds>,
+}
+
+/// The write paths skipping the general formatting the configuration of a [`Glog`] allows
+///
+/// Chosen once by [`Glog::configure`] instead of checking all the features for every record.
+#[derive(Debug, Clone, Copy, Default)]
+struct FastPaths {
+    /// Plain stderr output, see [`Glog::write_stderr_fast`]
+    stderr: bool,
+    /// Log files without redaction, see [`Glog::write_file_fast`]
+    files: bool,
 }
 
 impl Glog {
     /// Create a new Glog object for logging
     pub fn new() -> Glog {
         Glog {
-            stderr_writer: ThreadLocal::new(),
+            stderr_writer: Arc::new(StandardStream::stderr(ColorChoice::Auto)),
             compatible_verbosity: true,
             compatible_date: true,
-            flags: Flags::default(),
+            flags: Arc::new(RwLock::new(Flags::default())),
             application_fingerprint: None,
-            start_time: Local::now(),
             file_writer: HashMap::new(),
-            level_integers: BiMap::new(),
+            module_log_files: Vec::new(),
+            stats: Arc::new(Stats::default()),
+            #[cfg(feature = "metrics")]
+            write_latency: Arc::default(),
+            tail: Arc::new(TailBuffers::default()),
+            backtrace_filter: None,
+            backtrace_at: CallSites::default(),
+            suppressions: Arc::new(Suppressions::default()),
+            log_file_group: None,
+            markers: Arc::new(Markers::default()),
+            monotonic_guard: Arc::new(MonotonicGuard::new()),
+            identity: Identity::current(),
+            log_file_name_prefix: OsString::new(),
+            #[cfg(target_arch = "wasm32")]
+            console_writer: Some(Arc::new(console::WasmConsole)),
+            #[cfg(not(target_arch = "wasm32"))]
+            console_writer: None,
+            write_error_handler: None,
+            custom_level_names: HashMap::new(),
+            rotation_hook: None,
+            sinks: Arc::new(Sinks::default()),
+            sink_error_handler: report_sink_errors_once(),
+            template_renderer: None,
+            file_encoding: FileEncoding::Utf8,
+            file_header: FileHeader::Text,
+            stderr_timestamp_format: None,
+            extra_headers: Vec::new(),
+            redactor: None,
+            stderr_filter: None,
+            timestamp_fn: None,
+            machine_id: hostname::machine_id(),
+            fs: Arc::new(RealFs),
+            write_watchdog: None,
+            tees: Arc::new(Tees::default()),
+            thread_files: ThreadFiles::new(),
+            schedule: Arc::new(Schedule::default()),
+            adaptive: Arc::new(Adaptive::default()),
+            target_policy: Arc::new(RwLock::new(Arc::new(ActivePolicy::default()))),
+            adaptive_buffer: None,
+            drain: false,
+            crash_recovery: false,
+            console_available: true,
+            fast_paths: FastPaths::default(),
+            #[cfg(all(unix, feature = "mmap"))]
+            mmap_chunk: None,
+            #[cfg(unix)]
+            crash_fds: None,
         }
     }
 
@@ -136,20 +413,167 @@ impl Glog {
     ///
     /// info!("A log message");
     /// ```
-    pub fn init(&mut self, flags: Flags) -> Result<(), log::SetLoggerError> {
-        self.level_integers.insert(Level::Trace, -2);
-        self.level_integers.insert(Level::Debug, -1);
-        self.level_integers.insert(Level::Info, 0);
-        self.level_integers.insert(Level::Warn, 1);
-        self.level_integers.insert(Level::Error, 2);
-        self.flags = flags;
-        if !self.flags.logtostderr {
-            self.create_log_files();
+    pub fn init(&mut self, flags: Flags) -> Result<(), GlogError> {
+        self.try_init(flags).map(|_| ())
+    }
+
+    /// [`standard logging`]: https://crates.io/crates/log
+    /// Initialize the logging object and register it with the [`standard logging`] frontend
+    ///
+    /// Returns a [`GlogHandle`] to change the configuration of the registered logger at runtime.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// let handle = glog::new().try_init(Flags::default()).unwrap();
+    ///
+    /// info!("A log message");
+    /// handle.set_min_level(Level::Warn);
+    /// info!("This message is not logged anymore");
+    /// ```
+    ///
+    /// Fails if a flag is malformed, e.g. `log_backtrace_at` without a line number, if glog or another
+    /// logger was already registered or if logging to files was requested on a target without file
+    /// support like `wasm32`.
+    pub fn try_init(&mut self, flags: Flags) -> Result<GlogHandle, GlogError> {
+        if INITIALIZED.load(Ordering::Acquire) {
+            return Err(GlogError::AlreadyInitialized);
+        }
+        let redirect_fatal = !flags.logtostderr && !self.drain && flags.log_dir.is_empty();
+        let (exit_on_dfatal, error_chain_max_depth) = (flags.exit_on_dfatal, flags.error_chain_max_depth);
+        let redaction_salt = flags.redaction_salt.clone();
+        let backtrace_style = flags.backtrace_style;
+        self.configure(flags)?;
+        if redirect_fatal {
+            redirect_fatal_to_stderr();
+        }
+        macros::set_exit_on_dfatal(exit_on_dfatal);
+        error_chain::set_max_depth(error_chain_max_depth);
+        redact::set_salt(&redaction_salt);
+        crash::set_fatal_backtrace(backtrace_style, self.backtrace_filter.clone());
+        log::set_max_level(self.max_level().to_level_filter());
+        log::set_boxed_logger(Box::new(self.clone()))?;
+        // Neither is started for a logger that lost the registration
+        #[cfg(unix)]
+        if self.crash_fds.is_some() {
+            self.register_crash_handler();
+        }
+        if let Some(watchdog) = &self.write_watchdog {
+            let handler = self.write_error_handler.clone();
+            watchdog.spawn(move |level, why| match &handler {
+                Some(handler) => handler(level, why),
+                None => {
+                    Glog::write_stderr_raw(&format!("glog: {}, aborting\n", why));
+                    std::process::abort();
+                }
+            });
+        }
+        INITIALIZED.store(true, Ordering::Release);
+        *REGISTERED.lock().unwrap_or_else(PoisonError::into_inner) = Some(GlogHandle::new(self.clone()));
+        Ok(GlogHandle::new(self.clone()))
+    }
+
+    /// A logger with the settings and sinks of this one but with `flags` and its own log files
+    ///
+    /// For subsystems which need e.g. another `log_dir` or `minloglevel` next to the registered
+    /// logger. The returned logger isn't registered, records are passed to it with [`Log::log`].
+    /// Process wide flags like `exit_on_dfatal` keep the values of [`try_init`](Glog::try_init).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::{Flags, RingBufferSink};
+    ///
+    /// let recent = RingBufferSink::new(100);
+    /// let mut glog = glog::new().with_sink(recent.clone());
+    /// glog.try_init(Flags::default()).unwrap();
+    ///
+    /// let audit = glog
+    ///     .clone_with_flags(Flags {
+    ///         logtostderr: true,
+    ///         minloglevel: Level::Warn,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    /// audit.log(&Record::builder().level(Level::Warn).args(format_args!("Permission denied")).build());
+    /// assert_eq!(recent.latest_seq(), 1);
+    /// ```
+    pub fn clone_with_flags(&self, flags: Flags) -> Result<Glog, GlogError> {
+        let mut glog = self.clone();
+        glog.flags = Arc::new(RwLock::new(Flags::default()));
+        glog.file_writer = HashMap::new();
+        glog.suppressions = Arc::new(Suppressions::default());
+        glog.target_policy = Arc::new(RwLock::new(Arc::new(ActivePolicy::default())));
+        glog.thread_files = ThreadFiles::new();
+        glog.configure(flags)?;
+        Ok(glog)
+    }
+
+    /// Check `flags` and apply them to this logger, creating its log files
+    fn configure(&mut self, mut flags: Flags) -> Result<(), GlogError> {
+        self.backtrace_at = CallSites::default();
+        if let Some(log_backtrace_at) = &flags.log_backtrace_at {
+            let (file, line) = flag_registry::parse_log_backtrace_at(log_backtrace_at)?;
+            self.backtrace_at.insert(file, Some(line));
+        }
+        *self.suppressions.call_sites.write().unwrap() = flag_registry::parse_suppress_at(&flags.suppress_at)?;
+        *self.target_policy.write().unwrap() = Arc::new(ActivePolicy::new(flags.target_policy.parse()?));
+        if env::var_os("NO_COLOR").is_some() {
+            flags.colorlogtostderr = false;
+        }
+        // Before any warnings, which take the fallback as well
+        self.console_available = self
+            .console_writer
+            .as_ref()
+            .map_or_else(console_available, |writer| writer.is_available());
+        if !self.console_available {
+            flags.colorlogtostderr = false;
+            self.apply_console_fallback(&flags)?;
+        }
+        for conflict in flags::check_conflicts(&flags)? {
+            Glog::write_stderr_raw(&format!("glog: {}\n", conflict));
+        }
+        if let Some(line) = self.extra_headers.iter().find(|line| line.contains(['\n', '\r'])) {
+            return Err(GlogError::InvalidHeader(line.clone()));
+        }
+        if let Some(format) = &self.stderr_timestamp_format {
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                return Err(GlogError::InvalidTimestampFormat(format.clone()));
+            }
+        }
+        if flags.log_hostname_as_fqdn {
+            self.identity.hostname = hostname::hostname(true);
+        }
+        flags.backtrace_style = flags.backtrace_style.resolve();
+        let files = !flags.logtostderr && !self.drain;
+        *self.flags.write().unwrap() = flags;
+        if files {
+            #[cfg(target_arch = "wasm32")]
+            return Err(GlogError::FilesUnsupported);
+            #[cfg(not(target_arch = "wasm32"))]
+            self.create_log_files()
+                .map_err(|(path, why)| GlogError::CreateLogFile(PathBuf::from(path), why))?;
+        }
+        // After creating the files, which may enable alsologtostderr
+        let fast_paths = self.select_fast_paths(&self.flags());
+        self.fast_paths = fast_paths;
+        Ok(())
+    }
+
+    /// The fast paths the configuration allows, sinks added at runtime are checked per record
+    fn select_fast_paths(&self, flags: &Flags) -> FastPaths {
+        let no_redirects = flags.tail_buffer_size == 0
+            && self.console_writer.is_none()
+            && (self.redactor.is_none() || !flags.redact_stderr)
+            && self.stderr_timestamp_format.is_none();
+        FastPaths {
+            stderr: no_redirects && !flags.colorlogtostderr && !flags.stderr_verbose_level && flags.log_backtrace_at.is_none(),
+            files: no_redirects && self.redactor.is_none(),
         }
-        // todo(#4): restore this once this can be changed during runtime for glog
-        // log::set_max_level(LevelFilter::Trace);
-        log::set_max_level(self.flags.minloglevel.to_level_filter());
-        log::set_boxed_logger(Box::new(self.clone()))
     }
 
     /// Enable the year in the log timestamp
@@ -258,250 +682,2550 @@ impl Glog {
         self
     }
 
-    fn match_level(&self, level: &Level) -> Level {
-        match level {
-            Level::Debug if self.compatible_verbosity => Level::Info,
-            Level::Trace if self.compatible_verbosity => Level::Info,
-            _ => *level,
-        }
-    }
-
-    fn create_log_files(&mut self) {
-        let log_file_dir = self.flags.log_dir.clone();
-        let mut log_file_name = OsString::new();
-        let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from_str("UNKNOWN").unwrap_or_default());
-        let exe = exe.file_name().unwrap_or_else(|| OsStr::new("UNKNOWN"));
-        log_file_name.push(exe);
-        log_file_name.push(".");
-        log_file_name.push(gethostname::gethostname().if_empty(OsString::from("(unknown)")));
-        log_file_name.push(".");
-        log_file_name.push(whoami::username().if_empty("invalid-user".to_string()));
-        log_file_name.push(".log.");
-
-        let log_file_suffix = format!(".{}.{}", Local::now().format("%Y%m%d-%H%M%S"), std::process::id());
-
-        let mut log_file_base = OsString::new();
-        log_file_base.push(&log_file_dir);
-        log_file_base.push(log_file_name);
-
-        let mut symlink_file_base = OsString::new();
-        symlink_file_base.push(log_file_dir);
-        symlink_file_base.push(exe);
-        symlink_file_base.push(".");
-        if !self.compatible_verbosity {
-            for level in &[Level::Trace, Level::Debug] {
-                let mut log_file_path = log_file_base.clone();
-                log_file_path.push(level.to_string().to_uppercase());
-                log_file_path.push(&log_file_suffix);
-                self.write_file_header(&log_file_path, level);
-                let mut symlink_file_name = symlink_file_base.clone();
-                symlink_file_name.push(level.to_string().to_uppercase());
-                self.create_symlink(&log_file_path, &symlink_file_name);
-            }
-        }
-        for level in &[Level::Info, Level::Warn, Level::Error] {
-            let mut log_file_path = log_file_base.clone();
-            log_file_path.push(level.to_string().to_uppercase());
-            log_file_path.push(&log_file_suffix);
-            self.write_file_header(&log_file_path, level);
-            let mut symlink_file_name = symlink_file_base.clone();
-            symlink_file_name.push(level.to_string().to_uppercase());
-            self.create_symlink(&log_file_path, &symlink_file_name);
-        }
+    /// Append `headers` to the header of every log file, one line each
+    ///
+    /// Useful to record build metadata like the git revision in every log file.
+    /// [`try_init`](Glog::try_init) fails if a line contains a newline.
+    pub fn with_extra_headers(mut self, headers: Vec<String>) -> Self {
+        self.extra_headers = headers;
+        self
     }
 
-    fn write_file_header(&mut self, file_path: &OsString, level: &Level) {
-        {
-            let mut file = match File::create(file_path) {
-                Err(why) => panic!(
-                    "couldn't create {}: {}",
-                    file_path.to_str().unwrap_or("<INVALID FILE PATH>"),
-                    why
-                ),
-                Ok(file) => file,
-            };
-
-            let running_duration = Local::now() - self.start_time;
-
-            // todo(#3): integrate UTC
-            file.write_fmt(
-                format_args!("Log file created at:\n{}\nRunning on machine: {}\n{}Running duration (h:mm:ss): {}:{:02}:{:02}\nLog line format: [{}IWE]{}mmdd hh:mm:ss.uuuuuu threadid file:line] msg\n",
-                    Local::now().format("%Y/%m/%d %H:%M:%S"),
-                    gethostname::gethostname().to_str().unwrap_or("UNKNOWN"),
-                    if self.application_fingerprint.is_some() { format!("Application fingerprint: {}\n", self.application_fingerprint.clone().unwrap()) } else { String::new() },
-                    running_duration.num_hours(),
-                    running_duration.num_minutes(),
-                    running_duration.num_seconds(),
-                    if self.compatible_verbosity { "" } else { "TD" },
-                    if self.compatible_date { "" } else { "yyyy" },
-                )
-            ).expect("couldn't write log file header");
-
-            if let Err(why) = file.flush() {
-                panic!(
-                    "couldn't flush {} after writing file header: {}",
-                    file_path.to_str().unwrap(),
-                    why
-                )
-            }
-        }
-        self.file_writer.insert(
-            *level,
-            Arc::new(Mutex::new(RefCell::new(
-                OpenOptions::new()
-                    .append(true)
-                    .open(file_path)
-                    .expect("Couldn't open file after header is written"),
-            ))),
-        );
+    /// Write the log files in `encoding` instead of UTF-8
+    ///
+    /// UTF-16 files start with a byte order mark, as expected by some Windows log analysis tools.
+    /// The output to stderr is not affected.
+    pub fn with_log_file_encoding(mut self, encoding: FileEncoding) -> Self {
+        self.file_encoding = encoding;
+        self
     }
 
-    /// On supported platforms creates short stable named symlinks pointing to latest log file.
-    /// Example /tmp/main.INFO -> /tmp/main.hostname.username.log.INFO.<timestamp>
-    fn create_symlink(&self, long_name: &OsString, symlink_name: &OsString) {
-        #[cfg(target_family = "unix")]
-        {
-            // Unconditionally remove any existing symlink
-            let _ = std::fs::remove_file(symlink_name);
-            // Create new symlink
-            std::os::unix::fs::symlink(long_name, symlink_name)
-                .unwrap_or_else(|_| panic!("failed to create symlink {}", symlink_name.to_str().unwrap()));
-        }
+    /// Write the timestamps of the lines on stderr in the [`chrono` format](chrono::format::strftime) `format`
+    ///
+    /// The log files keep the glog timestamps. [`try_init`](Glog::try_init) fails if `format` is invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// glog::new()
+    ///     .with_stderr_timestamp_format("%Y-%m-%dT%H:%M:%S%.6f%:z")
+    ///     .init(Flags {
+    ///         logtostderr: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    ///
+    /// info!("A log message");
+    /// // I2021-04-01T12:34:56.000100+02:00  1234 doc.rs:14] A log message
+    /// ```
+    pub fn with_stderr_timestamp_format(mut self, format: &str) -> Self {
+        self.stderr_timestamp_format = Some(format.to_owned());
+        self
     }
 
-    fn should_log_backtrace(&self, file_name: &str, line: u32) -> bool {
-        if self.flags.log_backtrace_at.is_some() {
-            format!("{file_name}:{line}") == *self.flags.log_backtrace_at.as_ref().unwrap()
-        } else {
-            false
-        }
+    /// Start the log files with `header` instead of the glog text header
+    ///
+    /// With [`FileHeader::JsonEnvelope`] the level log files also end with a footer written by
+    /// [`GlogHandle::shutdown`] or [`exit`], see the [`envelope`] module.
+    pub fn with_file_header(mut self, header: FileHeader) -> Self {
+        self.file_header = header;
+        self
     }
 
-    fn record_to_file_name(record: &Record) -> String {
-        Path::new(record.file().unwrap_or(""))
-            .file_name()
-            .unwrap_or_default()
-            .to_os_string()
-            .into_string()
-            .unwrap_or_default()
+    /// Take the timestamps of records and log file headers from `timestamp` instead of the wall clock
+    ///
+    /// Meant for tests with a fake clock. `monotonic_timestamps` still applies to the returned times.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{Local, TimeZone};
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// glog::new()
+    ///     .with_timestamp_fn(|| Local.with_ymd_and_hms(2021, 4, 1, 12, 34, 56).unwrap())
+    ///     .init(Flags {
+    ///         logtostderr: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    ///
+    /// info!("A log message");
+    /// // I0401 12:34:56.000000   123 doc.rs:15] A log message
+    /// ```
+    pub fn with_timestamp_fn<F>(mut self, timestamp: F) -> Self
+    where
+        F: Fn() -> DateTime<Local> + Send + Sync + 'static,
+    {
+        self.timestamp_fn = Some(Arc::new(timestamp));
+        self
     }
 
-    fn build_log_message(&self, record: &Record) -> String {
-        format!(
-            "{}{} {:5} {}:{}] {}",
-            self.match_level(&record.metadata().level()).as_str().chars().next().unwrap(),
-            Local::now().format(&format!("{}%m%d %H:%M:%S%.6f", if self.compatible_date { "" } else { "%Y" })),
-            get_tid(),
-            Glog::record_to_file_name(record),
-            record.line().unwrap_or(0),
-            record.args(),
-        )
+    /// Write `id` as the machine or pod id into the log file headers
+    ///
+    /// Defaults to the contents of `/etc/machine-id` on Linux, if it can be read.
+    pub fn with_machine_id(mut self, id: &str) -> Self {
+        self.machine_id = Some(id.to_owned());
+        self
     }
 
-    fn write_stderr(&self, record: &Record) {
-        let stderr_writer = self
-            .stderr_writer
-            .get_or(|| RefCell::new(StandardStream::stderr(ColorChoice::Auto)));
-        let stderr_writer = stderr_writer.borrow_mut();
-        let mut stderr_writer = LineWriter::new(stderr_writer.lock());
-
-        if self.flags.colorlogtostderr {
-            stderr_writer
-                .get_mut()
-                .set_color(ColorSpec::new().set_fg(match record.metadata().level() {
-                    Level::Error => Some(Color::Red),
-                    Level::Warn => Some(Color::Yellow),
-                    _ => None,
-                }))
-                .expect("failed to set color");
-        }
-
-        let file_name = Glog::record_to_file_name(record);
-
-        writeln!(stderr_writer, "{}", self.build_log_message(record)).expect("couldn't write log message");
-
-        if self.flags.colorlogtostderr {
-            stderr_writer.get_mut().reset().expect("failed to reset color");
-        }
+    /// Use `identity` in log file names and headers instead of the one of this process
+    #[cfg(test)]
+    fn with_identity(mut self, identity: Identity) -> Self {
+        self.identity = identity;
+        self
+    }
 
-        if self.should_log_backtrace(&file_name, record.line().unwrap_or(0)) {
-            writeln!(stderr_writer, "{:?}", Backtrace::new()).expect("Couldn't write backtrace");
-        }
+    /// Create the log files in `fs` instead of the real file system
+    #[cfg(test)]
+    fn with_fs(mut self, fs: impl LogFs + 'static) -> Self {
+        self.fs = Arc::new(fs);
+        self
     }
 
-    fn level_as_int(&self, level: &Level) -> i8 {
-        *self.level_integers.get_by_left(&self.match_level(level)).unwrap()
+    /// Change the group of created log files to `gid`, keeping the owner
+    ///
+    /// This allows e.g. a log collecting daemon running as a dedicated group to read the log files.
+    /// Only supported on Unix, ignored on other platforms.
+    pub fn with_log_file_group(mut self, gid: u32) -> Self {
+        self.log_file_group = Some(gid);
+        self
     }
 
-    fn write_file(&self, record: &Record) {
-        // prevent writing to non existing writer if minloglevel is <INFO
-        for level_int in self.level_as_int(&self.flags.minloglevel)..=self.level_as_int(&record.level()) {
-            let level = self.level_integers.get_by_right(&level_int).unwrap();
-            let file_write_guard = self.file_writer.get(level).unwrap().lock().unwrap();
-            let mut file_writer = (*file_write_guard).borrow_mut();
-            if let Err(why) = file_writer.write_fmt(format_args!("{}\n", self.build_log_message(record))) {
-                panic!("couldn't write log message to file for level {}: {}", record.level(), why)
-            }
-        }
+    /// Abort if writing to a log file takes longer than `limit`, e.g. on a hanging network file system
+    ///
+    /// A background thread checks the running writes every `limit / 2`, making the hang visible
+    /// instead of silently blocking every logging thread. A stuck write is passed to the
+    /// [write error handler](Glog::with_write_error_handler) with [`io::ErrorKind::TimedOut`] if
+    /// there is one, otherwise the process is aborted after printing the level of the stuck file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use glog::Flags;
+    ///
+    /// glog::new().with_write_guard(Duration::from_secs(30)).init(Flags::default()).unwrap();
+    /// ```
+    pub fn with_write_guard(mut self, limit: Duration) -> Self {
+        self.write_watchdog = Some(Arc::new(WriteWatchdog::new(limit)));
+        self
+    }
 
-        if self.should_log_backtrace(&Glog::record_to_file_name(record), record.line().unwrap_or(0)) {
-            let level = self.match_level(&self.flags.minloglevel);
-            let file_write_guard = self.file_writer.get(&level).unwrap().lock().unwrap();
-            let mut file_writer = (*file_write_guard).borrow_mut();
-            if let Err(why) = file_writer.write_fmt(format_args!("{:?}\n", Backtrace::new())) {
-                panic!("couldn't write backtrace to {} file: {}", level, why)
-            }
-        }
+    /// Write `name` instead of the standard name of `level`
+    ///
+    /// The glog lines start with the first letter of `name`, JSON sinks get the whole name. Names
+    /// of [`Trace`](Level::Trace) and [`Debug`](Level::Debug) are only used with
+    /// [`reduced_log_levels(false)`](Glog::reduced_log_levels), otherwise they are logged as `Info`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// glog::new()
+    ///     .with_custom_level_name(Level::Error, "CRITICAL")
+    ///     .init(Flags::default())
+    ///     .unwrap();
+    ///
+    /// error!("Disk full");
+    /// // C0401 12:34:56.987654   123 doc.rs:9] Disk full
+    /// ```
+    pub fn with_custom_level_name(mut self, level: Level, name: &str) -> Self {
+        self.custom_level_names.insert(level, name.to_owned());
+        self
     }
 
-    fn write_sinks(&self) {}
-}
+    /// Call `handler` with the level of the log file and the error if writing to a log file fails
+    ///
+    /// The record is dropped for that file. By default the error is printed to stderr.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// glog::new()
+    ///     .with_write_error_handler(|level, why| eprintln!("lost a {} record: {}", level, why))
+    ///     .init(Flags::default())
+    ///     .unwrap();
+    /// ```
+    pub fn with_write_error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(Level, io::Error) + Send + Sync + 'static,
+    {
+        self.write_error_handler = Some(Arc::new(handler));
+        self
+    }
 
-impl Log for Glog {
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        self.flags.minloglevel >= metadata.level()
+    /// Call `hook` with the level and path of a log file after it was rotated
+    ///
+    /// glog-rs doesn't rotate the files itself, a rotation is noticed with `detect_deleted_files`
+    /// once the file was removed from its path, e.g. by logrotate, and recreated there. The path
+    /// is the one of the recreated file, glog-rs doesn't know where the archived file was moved
+    /// to. The hook runs on the logging thread while the file is locked, so it should only kick
+    /// off work like shipping or compressing the archive. A warning is printed if it takes over a second.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::Flags;
+    ///
+    /// glog::new()
+    ///     .with_log_rotation_hook(|level, path| eprintln!("rotated the {} log {}", level, path.display()))
+    ///     .init(Flags {
+    ///         detect_deleted_files: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn with_log_rotation_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(Level, &Path) + Send + Sync + 'static,
+    {
+        self.rotation_hook = Some(Arc::new(hook));
+        self
     }
 
-    fn log(&self, record: &Record) {
-        if !self.enabled(record.metadata()) {
-            return;
-        }
+    /// Call `handler` with the index of the sink, the message and the error if a sink fails
+    ///
+    /// Sinks are numbered in the order they were added, a panicking sink is reported as an error.
+    /// The other sinks still receive the record. By default every distinct error of a sink is
+    /// printed to stderr once.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::Flags;
+    ///
+    /// glog::new()
+    ///     .with_sink_error_handler(|index, _message, why| eprintln!("sink {} failed: {}", index, why))
+    ///     .init(Flags::default())
+    ///     .unwrap();
+    /// ```
+    pub fn with_sink_error_handler<F>(mut self, handler: F) -> Self
+    where
+        F: Fn(usize, &str, Box<dyn Error>) + Send + Sync + 'static,
+    {
+        self.sink_error_handler = Arc::new(handler);
+        self
+    }
 
-        if self.flags.logtostderr || self.flags.alsologtostderr {
-            self.write_stderr(record);
-        }
-        if !self.flags.logtostderr {
-            self.write_file(record);
-        }
-        self.write_sinks();
+    /// Raise the minimum level during the time `windows`, like logging only `Info` at night
+    ///
+    /// The strictest floor of the active windows and `minloglevel` applies, records below it only
+    /// go to sinks with a more verbose `min_level`. Windows use the local time of the clock set with
+    /// [`Glog::with_timestamp_fn`], see [`ScheduleWindow`] for the exact semantics. The floor is
+    /// cached until the next window starts or ends, so records usually only compare timestamps.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use chrono::{NaiveTime, Weekday};
+    /// use log::*;
+    /// use glog::{Flags, ScheduleWindow};
+    ///
+    /// glog::new()
+    ///     .with_schedule(vec![
+    ///         // Debug logging only during business hours
+    ///         ScheduleWindow {
+    ///             start: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+    ///             end: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+    ///             weekdays: Vec::new(),
+    ///             floor: Level::Info,
+    ///         },
+    ///         ScheduleWindow {
+    ///             start: NaiveTime::MIN,
+    ///             end: NaiveTime::MIN,
+    ///             weekdays: vec![Weekday::Sat, Weekday::Sun],
+    ///             floor: Level::Info,
+    ///         },
+    ///     ])
+    ///     .init(Flags {
+    ///         minloglevel: Level::Debug,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn with_schedule(self, windows: Vec<ScheduleWindow>) -> Self {
+        self.schedule.set(windows);
+        self
     }
 
-    fn flush(&self) {
-        let stderr_writer = self
-            .stderr_writer
-            .get_or(|| RefCell::new(StandardStream::stderr(ColorChoice::Auto)));
-        let mut stderr_writer = stderr_writer.borrow_mut();
-        stderr_writer.flush().ok();
+    /// Keep the records preceding an error burst in `buffer`, see [`Flags::adaptive_dump_ring_buffer`]
+    ///
+    /// `buffer` has to be added as a sink with a `min_level` of at least `adaptive_min_level`, its
+    /// lines are written to the log files as they are.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use log::*;
+    /// use glog::{Flags, RingBufferSink, SinkConfig};
+    ///
+    /// let recent = RingBufferSink::new(1000);
+    /// glog::new()
+    ///     .add_sink_with(
+    ///         SinkConfig {
+    ///             min_level: Some(Level::Debug),
+    ///             ..Default::default()
+    ///         },
+    ///         recent.clone(),
+    ///     )
+    ///     .with_adaptive_buffer(recent)
+    ///     .init(Flags {
+    ///         adaptive_error_threshold: Some(20),
+    ///         adaptive_error_window: Duration::from_secs(10),
+    ///         adaptive_dump_ring_buffer: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn with_adaptive_buffer(mut self, buffer: RingBufferSink) -> Self {
+        self.adaptive_buffer = Some(buffer);
+        self
+    }
 
-        for file in self.file_writer.values() {
-            let file_guard = file.lock().unwrap();
-            let mut file_writer = (*file_guard).borrow_mut();
-            file_writer.flush().expect("couldn't sync log to disk");
-        }
+    /// Write the log files through memory mappings, extending them in chunks of `chunk` bytes
+    ///
+    /// Records are copied into the mapping instead of being written with a system call, a
+    /// background thread maps the next chunk ahead and unmaps the full ones. The files still take
+    /// the lock of their level. Flushing cuts off the unused end of the chunks, so call [`exit`]
+    /// or [`GlogHandle::shutdown`], which also cuts off the files of other threads, before the
+    /// process ends. After a crash the files end with NUL bytes up to the end of the next chunk.
+    /// Module log files can be shared with other processes and are never mapped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::{ByteSize, Flags};
+    ///
+    /// glog::new().with_mmap_writer(ByteSize::mib(4)).init(Flags::default()).unwrap();
+    /// ```
+    #[cfg(all(unix, feature = "mmap"))]
+    pub fn with_mmap_writer(mut self, chunk: ByteSize) -> Self {
+        self.mmap_chunk = Some(chunk);
+        self
     }
-}
 
-#[cfg(target_os = "macos")]
-fn get_tid() -> u64 {
-    nix::sys::pthread::pthread_self().try_into().unwrap()
-}
+    /// Panic if writing to a log file fails
+    pub fn abort_on_write_error(self) -> Self {
+        self.with_write_error_handler(|level, why| panic!("couldn't write log message to file for level {}: {}", level, why))
+    }
 
-#[cfg(any(target_os = "linux", target_os = "android"))]
-fn get_tid() -> u64 {
-    nix::unistd::gettid().as_raw().try_into().unwrap()
-}
+    /// Silently drop records which can't be written to a log file
+    pub fn ignore_write_errors(self) -> Self {
+        self.with_write_error_handler(|_, _| {})
+    }
 
-#[cfg(target_os = "windows")]
+    /// Render the records of [`tmpl!`] on stderr with `renderer`
+    ///
+    /// `renderer` is called with the template id and its parameters and returns the human readable
+    /// message or `None` for unknown templates. The log files and sinks always receive the machine
+    /// readable form `template:<id> key=value ...`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::{tmpl, Flags};
+    ///
+    /// glog::new()
+    ///     .set_template_renderer(|id, params| match id {
+    ///         "disk_low" => Some(format!("Disk {} is {}% full", params[1].1, params[0].1)),
+    ///         _ => None,
+    ///     })
+    ///     .init(Flags {
+    ///         alsologtostderr: true,
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    ///
+    /// tmpl!(Level::Warn, "disk_low", used = 95, mount = "/data");
+    /// // File:   W0401 12:34:56.987654   123 doc.rs:13] template:disk_low used=95 mount=/data
+    /// // Stderr: W0401 12:34:56.987654   123 doc.rs:13] Disk /data is 95% full
+    /// ```
+    pub fn set_template_renderer<F>(mut self, renderer: F) -> Self
+    where
+        F: Fn(&str, &[(&str, String)]) -> Option<String> + Send + Sync + 'static,
+    {
+        self.template_renderer = Some(Arc::new(renderer));
+        self
+    }
+
+    /// Replace sensitive values in messages with the result of `redactor` before writing them
+    ///
+    /// The redactor receives the message of the record without the glog prefix and is applied to the
+    /// log files and sinks, and to stderr with the `redact_stderr` flag. Returning the message
+    /// borrowed means it doesn't need to be redacted. Markers are never redacted. The values of
+    /// [`attach`]ments are passed through it as well, they are redacted on every output.
+    ///
+    /// Use [`Redacted`] or [`HashedDisplay`] to hide individual values at the call site instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// glog::new()
+    ///     .set_redactor(|message| match message.find("token=") {
+    ///         Some(start) => Cow::Owned(format!("{}token=[REDACTED]", &message[..start])),
+    ///         None => Cow::Borrowed(message),
+    ///     })
+    ///     .init(Flags::default())
+    ///     .unwrap();
+    ///
+    /// info!("Authenticated with token=hunter2");
+    /// // I0401 12:34:56.987654   123 doc.rs:12] Authenticated with token=[REDACTED]
+    /// ```
+    pub fn set_redactor<F>(mut self, redactor: F) -> Self
+    where
+        F: for<'a> Fn(&'a str) -> Cow<'a, str> + Send + Sync + 'static,
+    {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    /// Write a record to stderr only if `filter` returns `true` for it
+    ///
+    /// The log files and sinks still receive all records.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// glog::new()
+    ///     .with_stderr_output_filter(|record| !record.args().to_string().contains("heartbeat"))
+    ///     .init(Flags::default())
+    ///     .unwrap();
+    ///
+    /// info!("heartbeat");
+    /// info!("Disk full");
+    /// // I0401 12:34:56.987654   123 doc.rs:11] Disk full
+    /// ```
+    pub fn with_stderr_output_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Record) -> bool + Send + Sync + 'static,
+    {
+        self.stderr_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// The outputs of `record`, without stderr if the [stderr filter](Glog::with_stderr_output_filter) rejects it
+    fn output_plan(&self, flags: &Flags, record: &Record) -> OutputPlan {
+        if self.drain {
+            return OutputPlan::NOWHERE;
+        }
+        let mut plan = OutputPlan::new(flags, record.level());
+        if plan.stderr && !self.stderr_allows(record) {
+            plan.stderr = false;
+        }
+        plan
+    }
+
+    fn stderr_allows(&self, record: &Record) -> bool {
+        self.stderr_filter.as_ref().map_or(true, |filter| filter(record))
+    }
+
+    /// Write the records of `module_prefix` and its submodules to the file at `path` instead of the level log files
+    ///
+    /// `my_crate::db` matches the records of `my_crate::db` and `my_crate::db::pool` but not of
+    /// `my_crate::dbx`. If several prefixes match, the longest one wins. The file is appended to if it
+    /// exists and starts with the log file header otherwise. With the
+    /// [`module_logs_to_level_files`](Flags::module_logs_to_level_files) flag the records are written
+    /// to the level log files as well.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::Flags;
+    ///
+    /// glog::new()
+    ///     .with_module_log_file("my_crate::db", std::env::temp_dir().join("my_crate.db.log"))
+    ///     .init(Flags::default())
+    ///     .unwrap();
+    /// ```
+    pub fn with_module_log_file(mut self, module_prefix: &str, path: PathBuf) -> Self {
+        let log_file = Arc::new(Mutex::new(LogFile::pending(path.into_os_string())));
+        let position = self
+            .module_log_files
+            .iter()
+            .position(|(prefix, _)| prefix.len() < module_prefix.len())
+            .unwrap_or(self.module_log_files.len());
+        self.module_log_files.insert(position, (module_prefix.to_owned(), log_file));
+        self
+    }
+
+    /// Copy every byte written to the log files to `tee`, e.g. a serial console or a pipe to a supervisor
+    ///
+    /// The tee receives the exact byte stream of all log files including the headers, in the order
+    /// it is written. Failing writes to a tee never affect the log files, they are counted in
+    /// [`StatsSnapshot::tee_write_errors`]. Several tees can be added, they are flushed after the log files.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::Flags;
+    ///
+    /// glog::new().tee(Box::new(std::io::stdout())).init(Flags::default()).unwrap();
+    /// ```
+    pub fn tee(self, tee: Box<dyn Write + Send>) -> Self {
+        self.tees.add(tee);
+        self
+    }
+
+    /// [`tee`](Glog::tee) to the file descriptor `fd`
+    ///
+    /// The descriptor isn't closed by the logger and has to stay open while logging.
+    #[cfg(unix)]
+    pub fn tee_fd(self, fd: std::os::unix::io::RawFd) -> Self {
+        self.tee(Box::new(tee::FdWriter(fd)))
+    }
+
+    /// Additionally write all records to `sink`, see [`LogSink`]
+    pub fn with_sink<S: LogSink + 'static>(self, sink: S) -> Self {
+        self.add_sink_with(SinkConfig::default(), sink)
+    }
+
+    /// Write all records to `sink` instead of stderr and the log files, e.g. for test reporters
+    ///
+    /// No log files are created. Other sinks still receive their records.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::{Flags, RingBufferSink};
+    ///
+    /// let collected = RingBufferSink::new(1000);
+    /// glog::new().drain_to_sink(collected.clone()).init(Flags::default()).unwrap();
+    ///
+    /// info!("Only in the sink");
+    /// assert_eq!(collected.latest_seq(), 1);
+    /// ```
+    pub fn drain_to_sink<S: LogSink + 'static>(mut self, sink: S) -> Self {
+        self.drain = true;
+        self.with_sink(sink)
+    }
+
+    /// Keep writing to a log file after a thread panicked while writing to it
+    ///
+    /// Without it the lock of the file stays poisoned and every later record panics. With it the lock is
+    /// taken over and a warning is written to stderr once.
+    pub fn with_crash_recovery(mut self, recover: bool) -> Self {
+        self.crash_recovery = recover;
+        self
+    }
+
+    /// Additionally write the records selected by `config` to `sink`, formatted as requested
+    ///
+    /// A sink with a `min_level` more verbose than `minloglevel` receives these records without
+    /// them being written to stderr or the log files. Every format is created at most once per
+    /// record and shared by the sinks requesting it.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::{Flags, LogFormat, RingBufferSink, SinkConfig};
+    ///
+    /// let recent = RingBufferSink::new(1000);
+    /// glog::new()
+    ///     .add_sink_with(
+    ///         SinkConfig {
+    ///             min_level: Some(Level::Trace),
+    ///             format: LogFormat::Json,
+    ///             include_context: true,
+    ///         },
+    ///         recent.clone(),
+    ///     )
+    ///     .init(Flags::default())
+    ///     .unwrap();
+    ///
+    /// trace!("Only kept in memory");
+    /// ```
+    pub fn add_sink_with<S: LogSink + 'static>(self, config: SinkConfig, sink: S) -> Self {
+        self.sinks.add(config, Arc::new(sink));
+        self
+    }
+
+    /// Change the format of the messages passed to the sink added at `sink_idx`, counting from 0
+    ///
+    /// Sinks added with [`with_sink`](Glog::with_sink) and the other shortcuts get glog lines,
+    /// this switches one of them without spelling out its [`SinkConfig`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::{Flags, LogFormat, RingBufferSink};
+    ///
+    /// let lines = RingBufferSink::new(100);
+    /// let json = RingBufferSink::new(100);
+    /// glog::new()
+    ///     .with_sink(lines)
+    ///     .with_sink(json)
+    ///     .with_per_sink_format(1, LogFormat::Json)
+    ///     .init(Flags::default())
+    ///     .unwrap();
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// If fewer than `sink_idx + 1` sinks were added.
+    pub fn with_per_sink_format(self, sink_idx: usize, format: LogFormat) -> Self {
+        assert!(
+            self.sinks.set_format(sink_idx, format),
+            "no sink was added at index {}",
+            sink_idx
+        );
+        self
+    }
+
+    /// The most verbose level enabled by `minloglevel`, the target policy, an escalation or a sink
+    fn max_level(&self) -> Level {
+        let minloglevel = self.flags().minloglevel;
+        let policy_level = self.target_policy.read().unwrap().max_level();
+        let minloglevel = self
+            .adaptive
+            .lower(policy_level.map_or(minloglevel, |level| level.max(minloglevel)));
+        self.sinks.max_level().map_or(minloglevel, |level| level.max(minloglevel))
+    }
+
+    /// Whether `metadata` is enabled, by the level of its [target policy](Flags::target_policy) if it has one
+    fn enabled_by(&self, metadata: &Metadata, matched: Option<&Matched>) -> bool {
+        match matched {
+            Some(matched) => matched
+                .level()
+                .is_some_and(|level| metadata.level() <= self.adaptive.lower(level)),
+            None => metadata.level() <= self.max_level(),
+        }
+    }
+
+    /// Additionally send all records to the systemd journal, see [`JournaldSink`]
+    ///
+    /// Prints a warning to stderr and continues without the journal if `libsystemd` isn't available.
+    #[cfg(all(feature = "journald", target_os = "linux"))]
+    pub fn with_log_to_journald(self, log_to_journald: bool) -> Self {
+        if !log_to_journald {
+            return self;
+        }
+        match JournaldSink::new() {
+            Ok(sink) => self.with_sink(sink),
+            Err(why) => {
+                Glog::write_stderr_raw(&format!("glog: not logging to the systemd journal: {}\n", why));
+                self
+            }
+        }
+    }
+
+    /// Write the signal and a stack trace to stderr and the log files on `SIGSEGV` and `SIGBUS`
+    ///
+    /// The handler only uses async-signal-safe functions, so the stack trace consists of
+    /// instruction addresses without symbol names. The process is terminated by the signal afterwards.
+    #[cfg(unix)]
+    pub fn install_crash_handler(mut self) -> Self {
+        self.crash_fds = Some([-1; 5]);
+        self
+    }
+
+    /// Write the lines otherwise written to stderr to `writer`, see [`ConsoleWriter`]
+    ///
+    /// Colors of `colorlogtostderr` aren't applied to lines written to `writer`.
+    pub fn with_console_writer<W: ConsoleWriter + 'static>(mut self, writer: W) -> Self {
+        self.console_writer = Some(Arc::new(writer));
+        self
+    }
+
+    /// Only include stack frames in logged backtraces for which `filter` returns `true`
+    ///
+    /// `filter` is called with the demangled symbol name of each frame.
+    /// Frames without symbol information are always included.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::{Flags, Glog};
+    ///
+    /// glog::new()
+    ///     .with_backtrace_filter(Glog::default_backtrace_filter())
+    ///     .init(Flags {
+    ///         log_backtrace_at: Some("main.rs:42".to_owned()),
+    ///         ..Default::default()
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn with_backtrace_filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.backtrace_filter = Some(Arc::new(filter));
+        self
+    }
+
+    /// A backtrace filter hiding the frames of the Rust runtime, the backtrace capturing and glog itself
+    ///
+    /// Trait implementations of these are hidden as well, e.g. `<glog::Glog as log::Log>::log`.
+    /// See [`with_backtrace_filter`](Glog::with_backtrace_filter).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use glog::Glog;
+    ///
+    /// let filter = Glog::default_backtrace_filter();
+    /// assert!(!filter("std::rt::lang_start"));
+    /// assert!(!filter("glog::Glog::write_file"));
+    /// assert!(!filter("<glog::Glog as log::Log>::log"));
+    /// assert!(filter("my_crate::main"));
+    /// assert!(filter("<my_crate::Server as glog::LogSink>::write"));
+    /// ```
+    pub fn default_backtrace_filter() -> impl Fn(&str) -> bool + Send + Sync + 'static {
+        |symbol_name| {
+            let path = symbol_name.strip_prefix('<').unwrap_or(symbol_name);
+            !["std::rt", "std::sys", "backtrace::", "glog::"]
+                .iter()
+                .any(|prefix| path.starts_with(prefix))
+        }
+    }
+
+    /// Get the current logging statistics
+    ///
+    /// The statistics are shared with the logger registered by [`init`](Glog::init), so this can be
+    /// called on the [`Glog`] object used for initialization.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// let mut glog = glog::new();
+    /// glog.init(Flags {
+    ///     logtostderr: true,
+    ///     ..Default::default()
+    /// }).unwrap();
+    ///
+    /// info!("A log message");
+    /// warn!("A warning");
+    /// info!("Another log message");
+    ///
+    /// let stats = glog.stats_snapshot();
+    /// assert_eq!(stats.messages.info, 2);
+    /// assert_eq!(stats.messages.warn, 1);
+    /// assert_eq!(stats.messages.total(), 3);
+    /// ```
+    pub fn stats_snapshot(&self) -> StatsSnapshot {
+        self.stats.snapshot()
+    }
+
+    /// The `percentiles` of the time records took to be written to the log files since the last call
+    ///
+    /// The keys are the level of the records and the percentile, e.g. `"INFO/p99"`, levels without
+    /// writes are left out. Like the [statistics](Glog::stats_snapshot) the measurements are
+    /// shared with the registered logger.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// let mut glog = glog::new();
+    /// glog.init(Flags::default()).unwrap();
+    ///
+    /// info!("A log message");
+    ///
+    /// let latency = glog.measure_write_latency(&[50.0, 99.0]);
+    /// assert!(latency["INFO/p50"] <= latency["INFO/p99"]);
+    /// assert!(glog.measure_write_latency(&[99.0]).is_empty());
+    /// ```
+    #[cfg(feature = "metrics")]
+    pub fn measure_write_latency(&self, percentiles: &[f64]) -> HashMap<String, Duration> {
+        self.write_latency.measure(percentiles)
+    }
+
+    /// The longest time a record took to be written to the log files, not reset by [`Glog::measure_write_latency`]
+    #[cfg(feature = "metrics")]
+    pub fn max_write_latency(&self) -> Duration {
+        self.write_latency.max()
+    }
+
+    /// Up to `n` of the latest lines logged at `level` or a more severe level, oldest first
+    ///
+    /// Returns the lines written to the log file of `level` without reading it, e.g. to embed them
+    /// into a health endpoint or crash report. Only the last
+    /// [`tail_buffer_size`](Flags::tail_buffer_size) lines per level are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::*;
+    /// use glog::Flags;
+    ///
+    /// let mut glog = glog::new();
+    /// glog.init(Flags {
+    ///     logtostderr: true,
+    ///     tail_buffer_size: 100,
+    ///     ..Default::default()
+    /// }).unwrap();
+    ///
+    /// info!("Starting");
+    /// warn!("Disk almost full");
+    ///
+    /// let tail = glog.tail(Level::Info, 10);
+    /// assert_eq!(tail.len(), 2);
+    /// assert!(tail[1].ends_with("] Disk almost full"));
+    /// assert_eq!(glog.tail(Level::Warn, 10).len(), 1);
+    /// ```
+    pub fn tail(&self, level: Level, n: usize) -> Vec<String> {
+        self.tail.tail(level, n)
+    }
+
+    /// Call `callback` with the current logging statistics every `interval`
+    ///
+    /// The callback runs on a dedicated background thread until the returned [`StatsInterval`] is
+    /// dropped or stopped and can be used to push the statistics to a metric collector.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::{sync::mpsc, time::Duration};
+    ///
+    /// let glog = glog::new();
+    /// let (sender, receiver) = mpsc::channel();
+    /// let interval = glog.on_stats_interval(Duration::from_millis(10), move |stats| {
+    ///     let _ = sender.send(stats.messages.total());
+    /// });
+    /// assert_eq!(receiver.recv().unwrap(), 0);
+    /// interval.stop();
+    /// ```
+    pub fn on_stats_interval<F>(&self, interval: Duration, callback: F) -> StatsInterval
+    where
+        F: Fn(&StatsSnapshot) + Send + 'static,
+    {
+        StatsInterval::spawn(self.stats.clone(), interval, callback)
+    }
+
+    /// Write the pre-formatted `message` to stderr without any formatting or locking
+    ///
+    /// On Unix this is a single `write(2)` call, which makes it usable in emergencies where the
+    /// regular logging might be broken, e.g. because a mutex is poisoned.
+    /// No newline is appended to `message`. Without a console `message` follows the
+    /// [`console_fallback`](Flags::console_fallback) instead: it's dropped or, on Unix, written
+    /// to the console file with a single `write(2)` call as well.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// glog::Glog::write_stderr_raw("F0401 12:34:56.987654   123 main.rs:42] Out of memory\n");
+    /// ```
+    pub fn write_stderr_raw(message: &str) {
+        if console::write_raw(message) {
+            return;
+        }
+        #[cfg(target_family = "unix")]
+        let _ = nix::unistd::write(output::stderr_fd(), message.as_bytes());
+        #[cfg(not(target_family = "unix"))]
+        let _ = io::stderr().write_all(message.as_bytes());
+    }
+
+    /// Write the glog line of `record` followed by `extra_context` to stderr with [`Glog::write_stderr_raw`]
+    ///
+    /// Meant for panic hooks and crash handlers adding e.g. a stack trace or registers below the
+    /// line. Only the flags are read, even if their lock is poisoned, the stderr writer and the
+    /// other state of the logger aren't used.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use log::{Level, Record};
+    /// use glog::Flags;
+    ///
+    /// let handle = glog::new().try_init(Flags::default()).unwrap();
+    /// std::panic::set_hook(Box::new(move |info| {
+    ///     handle.write_stderr_with_context(
+    ///         &Record::builder().level(Level::Error).args(format_args!("{}", info)).build(),
+    ///         &std::backtrace::Backtrace::force_capture().to_string(),
+    ///     );
+    /// }));
+    /// ```
+    pub fn write_stderr_with_context(&self, record: &Record, extra_context: &str) {
+        let flags = self.flags.read().unwrap_or_else(PoisonError::into_inner);
+        let options = self.custom_level_options(flags.multiline_style);
+        let mut line = format::format_record(
+            &options,
+            self.wall_clock(),
+            record.level(),
+            get_tid(),
+            &format::source_file_name(record.file().unwrap_or(""), flags.trim_source_prefix.as_deref()),
+            record.line().unwrap_or(0),
+            record.args(),
+        );
+        drop(flags);
+        if !extra_context.is_empty() {
+            line.push('\n');
+            line.push_str(extra_context.trim_end_matches('\n'));
+        }
+        line.push('\n');
+        Glog::write_stderr_raw(&line);
+    }
+
+    fn flags(&self) -> RwLockReadGuard<'_, Flags> {
+        self.flags.read().unwrap()
+    }
+
+    fn match_level(&self, level: &Level) -> Level {
+        format::match_level(*level, self.compatible_verbosity)
+    }
+
+    /// Create the log files and their symlinks, failing with the path that couldn't be created
+    #[cfg(not(target_arch = "wasm32"))]
+    fn create_log_files(&mut self) -> Result<(), (OsString, io::Error)> {
+        let log_file_name = self.identity.log_file_name_prefix();
+        let log_file_suffix = self.identity.log_file_name_suffix();
+
+        let levels: &[Level] = if self.compatible_verbosity {
+            &[Level::Info, Level::Warn, Level::Error]
+        } else {
+            &[Level::Trace, Level::Debug, Level::Info, Level::Warn, Level::Error]
+        };
+        let logtostderr_if_no_log_dir = self.flags().logtostderr_if_no_log_dir;
+        let mut log_dirs: Vec<OsString> = levels.iter().map(|level| self.log_dir(level)).collect();
+        log_dirs.dedup();
+        for log_dir in &log_dirs {
+            self.remove_stale_in_progress_files(log_dir, &log_file_name);
+        }
+        let mut missing = None;
+        for level in levels {
+            let mut log_file_path = self.log_dir(level);
+            log_file_path.push(&log_file_name);
+            log_file_path.push(level.to_string().to_uppercase());
+            log_file_path.push(&log_file_suffix);
+            match self.write_file_header(&log_file_path, level) {
+                Ok(()) => {}
+                Err(why) if logtostderr_if_no_log_dir => {
+                    // Created on a later write if the directory appears
+                    self.insert_file_writer(*level, LogFile::pending(log_file_path.clone()));
+                    missing.get_or_insert((log_file_path, why));
+                    continue;
+                }
+                Err(why) => return Err((log_file_path, why)),
+            }
+
+            let symlink_file_name = self.symlink_path(level);
+            self.create_symlink(&log_file_path, &symlink_file_name)
+                .map_err(|why| (symlink_file_name, why))?;
+        }
+        self.log_file_name_prefix = log_file_name;
+        self.open_module_log_files()?;
+        if let Some((path, why)) = missing {
+            Glog::write_stderr_raw(&format!(
+                "glog: couldn't create {}: {}, logging to stderr as well\n",
+                Path::new(&path).display(),
+                why
+            ));
+            self.flags.write().unwrap().alsologtostderr = true;
+        }
+        Ok(())
+    }
+
+    /// Remove the in progress log files of this program in `log_dir` left behind by a crash
+    #[cfg(not(target_arch = "wasm32"))]
+    fn remove_stale_in_progress_files(&self, log_dir: &OsStr, log_file_name: &OsStr) {
+        let (Ok(paths), Some(log_file_name)) = (self.fs.read_dir(Path::new(log_dir)), log_file_name.to_str()) else {
+            return;
+        };
+        for path in paths {
+            let in_progress = path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .is_some_and(|name| name.starts_with(log_file_name) && name.ends_with(IN_PROGRESS_SUFFIX));
+            let stale = || {
+                self.fs
+                    .stat(&path)
+                    .is_ok_and(|stat| stat.modified.elapsed().is_ok_and(|age| age >= STALE_IN_PROGRESS_AGE))
+            };
+            if in_progress && stale() {
+                let _ = self.fs.remove(&path);
+            }
+        }
+    }
+
+    /// Open the files of [`Glog::with_module_log_file`], appending to existing files
+    #[cfg(not(target_arch = "wasm32"))]
+    fn open_module_log_files(&self) -> Result<(), (OsString, io::Error)> {
+        for (_, log_file) in &self.module_log_files {
+            let mut log_file = self.lock_file(log_file);
+            let path = log_file.path.clone();
+            let started = Instant::now();
+            // Never mapped, other processes may append to the file
+            let file = loop {
+                match self.publish_log_file(&path, None, None, None, None) {
+                    // Another process created the file or is still writing its header
+                    Err(why) if why.kind() == io::ErrorKind::AlreadyExists => {}
+                    Err(why) => break Err(why),
+                    Ok(()) => {}
+                }
+                match self.fs.open_append(Path::new(&path)) {
+                    Err(why) if why.kind() == io::ErrorKind::NotFound && started.elapsed() < IN_PROGRESS_PUBLISH_TIMEOUT => {
+                        thread::sleep(Duration::from_millis(1))
+                    }
+                    opened => break opened.map(|file| self.tee_file(file)),
+                }
+            };
+            *log_file = LogFile::new(file.map_err(|why| (path.clone(), why))?, path);
+        }
+        Ok(())
+    }
+
+    /// Replace the missing console as selected by `console_fallback`
+    ///
+    /// The replacement is process wide for [`Glog::write_stderr_raw`], as stderr is. Its raw writes
+    /// stay on stderr for a console file without a descriptor, which is everywhere but Unix.
+    fn apply_console_fallback(&mut self, flags: &Flags) -> Result<(), GlogError> {
+        let raw_target = match flags.console_fallback {
+            ConsoleFallback::Keep => return Ok(()),
+            ConsoleFallback::Disable => {
+                self.console_writer = Some(Arc::new(console::NoConsole));
+                RawTarget::Dropped
+            }
+            ConsoleFallback::RedirectToFile => {
+                let mut path = flags.log_dir.clone();
+                path.push(self.identity.program());
+                path.push(".console.log");
+                let path = PathBuf::from(path);
+                let file = match self.fs.open_append(&path) {
+                    Err(why) if why.kind() == io::ErrorKind::NotFound => self.fs.create_exclusive(&path),
+                    opened => opened,
+                }
+                .map_err(|why| GlogError::CreateLogFile(path, why))?;
+                #[cfg(unix)]
+                let raw_target = file.raw_fd().map_or(RawTarget::Stderr, RawTarget::File);
+                #[cfg(not(unix))]
+                let raw_target = RawTarget::Stderr;
+                self.console_writer = Some(Arc::new(console::FileConsole(Mutex::new(file))));
+                raw_target
+            }
+        };
+        console::redirect_raw(raw_target);
+        Ok(())
+    }
+
+    /// The stable name of the symlink pointing to the latest log file of `level`
+    #[cfg(not(target_arch = "wasm32"))]
+    fn symlink_path(&self, level: &Level) -> OsString {
+        let mut symlink_path = self.log_dir(level);
+        symlink_path.push(self.identity.program());
+        symlink_path.push(".");
+        symlink_path.push(level.to_string().to_uppercase());
+        symlink_path
+    }
+
+    #[cfg(unix)]
+    fn register_crash_handler(&mut self) {
+        let mut fds = [-1; 5];
+        for (fd, level) in fds
+            .iter_mut()
+            .zip([Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace].iter())
+        {
+            let raw_fd = self
+                .file_writer
+                .get(level)
+                .and_then(|log_file| self.lock_file(log_file).borrow().file.raw_fd());
+            if let Some(raw_fd) = raw_fd {
+                // The duplicate stays open when the log file is replaced
+                *fd = nix::unistd::dup(raw_fd).unwrap_or(-1);
+            }
+        }
+        self.crash_fds = Some(fds);
+        if let Err(why) = crash::install(fds) {
+            Glog::write_stderr_raw(&format!("glog: couldn't install the crash handler: {}\n", why));
+        }
+    }
+
+    /// The log files of this process, see [`GlogHandle::log_files`]
+    fn log_files(&self) -> Vec<LogFileInfo> {
+        // Holding all writers keeps the files from changing while the directories are scanned
+        let writers = self.lock_log_files(Level::Trace, Level::Error);
+        let active: Vec<PathBuf> = writers
+            .iter()
+            .map(|(_, log_file)| PathBuf::from(&log_file.borrow().path))
+            .collect();
+        let mut log_dirs: Vec<OsString> = writers.iter().map(|(level, _)| self.log_dir(level)).collect();
+        log_dirs.sort();
+        log_dirs.dedup();
+
+        let prefix = self.log_file_name_prefix.to_str().unwrap_or_default();
+        let mut log_files = Vec::new();
+        for path in log_dirs
+            .iter()
+            .filter_map(|dir| self.fs.read_dir(Path::new(dir)).ok())
+            .flatten()
+        {
+            let parsed = path
+                .file_name()
+                .and_then(OsStr::to_str)
+                .and_then(|file_name| log_file::parse_file_name(file_name, prefix, self.identity.pid));
+            // Files removed since the directory was read are skipped
+            if let (Some((level, created)), Ok(stat)) = (parsed, self.fs.stat(&path)) {
+                if stat.is_file {
+                    log_files.push(LogFileInfo {
+                        active: active.contains(&path),
+                        path,
+                        level,
+                        created,
+                        size: stat.len,
+                    });
+                }
+            }
+        }
+        // Levels compare by severity, list the least severe first like the files are created
+        log_files.sort_by(|a, b| {
+            b.level
+                .cmp(&a.level)
+                .then(a.created.cmp(&b.created))
+                .then(a.path.cmp(&b.path))
+        });
+        log_files
+    }
+
+    /// The directory for the log file of `level`, including a trailing path separator
+    fn log_dir(&self, level: &Level) -> OsString {
+        let flags = self.flags();
+        match flags
+            .log_dir_overrides
+            .iter()
+            .rev()
+            .find(|(override_level, _)| override_level == level)
+        {
+            Some((_, log_dir)) => log_dir.join("").into_os_string(),
+            None => flags.log_dir.clone(),
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn write_file_header(&mut self, file_path: &OsString, level: &Level) -> io::Result<()> {
+        let file = self.create_log_file(file_path, Some(*level), Some(0), None, None)?;
+        let (file, index) = self.index_log_file(file, file_path);
+        let mut log_file = LogFile::new(file, file_path.clone());
+        log_file.index = index;
+        self.insert_file_writer(*level, log_file);
+        Ok(())
+    }
+
+    /// Write to `log_file` for `level` from now on
+    ///
+    /// A file left over from an earlier initialization is flushed and reported, it would otherwise
+    /// stay open unnoticed until the last clone of the logger is dropped.
+    fn insert_file_writer(&mut self, level: Level, log_file: LogFile) {
+        let Some(replaced) = self.file_writer.insert(level, Arc::new(Mutex::new(RefCell::new(log_file)))) else {
+            return;
+        };
+        let replaced = self.lock_file(&replaced);
+        let mut replaced = replaced.borrow_mut();
+        let flushed = replaced.file.flush();
+        Glog::write_stderr_raw(&format!(
+            "glog: replaced the open {} log file {}{}\n",
+            level,
+            Path::new(&replaced.path).display(),
+            flushed.map_or_else(|why| format!(", flushing it failed: {}", why), |()| String::new())
+        ));
+    }
+
+    /// Start the [index](index) of the new level log file at `path` if `log_index_bytes` or `log_index_interval` is set
+    ///
+    /// Returns the file to write to, it counts the bytes for the offsets of the index. A sidecar left
+    /// behind by a removed log file at the same path is replaced.
+    fn index_log_file(&self, file: Box<dyn LogWriter>, path: &OsString) -> (Box<dyn LogWriter>, Option<IndexWriter>) {
+        let (every_bytes, every) = {
+            let flags = self.flags();
+            (flags.log_index_bytes.map(ByteSize::as_bytes), flags.log_index_interval)
+        };
+        if every_bytes.is_none() && every.is_none() {
+            return (file, None);
+        }
+        let sidecar_path = index::index_path(path);
+        let _ = self.fs.remove(&sidecar_path);
+        let (Ok(stat), Ok(sidecar)) = (self.fs.stat(Path::new(path)), self.fs.create_exclusive(&sidecar_path)) else {
+            return (file, None);
+        };
+        let (file, index) = IndexWriter::new(file, stat.len, sidecar, every_bytes, every, self.index_micros());
+        (file, Some(index))
+    }
+
+    /// The time a line is written at for the index, never earlier than a logged timestamp
+    fn index_micros(&self) -> i64 {
+        self.wall_clock().timestamp_micros().max(self.monotonic_guard.latest_micros())
+    }
+
+    /// Create the log file at `file_path` with [`Glog::publish_log_file`] and open it for appending
+    fn create_log_file(
+        &self,
+        file_path: &OsString,
+        level: Option<Level>,
+        previous_segments: Option<u64>,
+        previous: Option<&Path>,
+        note: Option<&str>,
+    ) -> io::Result<Box<dyn LogWriter>> {
+        self.publish_log_file(file_path, level, previous_segments, previous, note)?;
+        self.open_log_file(Path::new(file_path))
+    }
+
+    /// Open the log file at `path` of this process for appending, through a mapping with [`Glog::with_mmap_writer`]
+    fn open_log_file(&self, path: &Path) -> io::Result<Box<dyn LogWriter>> {
+        #[cfg(all(unix, feature = "mmap"))]
+        if let Some(chunk) = self.mmap_chunk {
+            return Ok(self.tee_file(self.fs.open_mapped(path, chunk.as_bytes())?));
+        }
+        Ok(self.tee_file(self.fs.open_append(path)?))
+    }
+
+    /// Create the log file at `file_path` starting with the file header followed by an optional `note`
+    ///
+    /// The header is written to an in progress file which only gets the final name once it is
+    /// complete, so a crash never leaves a log file with a partial header behind. Files of this
+    /// process pass the number of files created at `file_path` before for `header_include_stats`
+    /// and the envelope, which is only written for the `level` log files and links to the
+    /// `previous` segment.
+    fn publish_log_file(
+        &self,
+        file_path: &OsString,
+        level: Option<Level>,
+        previous_segments: Option<u64>,
+        previous: Option<&Path>,
+        note: Option<&str>,
+    ) -> io::Result<()> {
+        let mut in_progress_path = file_path.clone();
+        in_progress_path.push(IN_PROGRESS_SUFFIX);
+        let (file_path, in_progress_path) = (Path::new(file_path), Path::new(&in_progress_path));
+        if self.fs.stat(file_path).is_ok() {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        let mut file = self.tee_file(self.fs.create_exclusive(in_progress_path)?);
+
+        if let Some(gid) = self.log_file_group {
+            file.set_group(gid)?;
+        }
+
+        let (preallocate_log_bytes, log_crate_version, header_include_stats) = {
+            let flags = self.flags();
+            (
+                flags.preallocate_log_bytes,
+                flags.log_crate_version,
+                flags.header_include_stats,
+            )
+        };
+        if let Some(bytes) = preallocate_log_bytes.map(ByteSize::as_bytes) {
+            // Preallocation is only an optimization, logging works without it and must not fill up the disk
+            let dir = file_path.parent().unwrap_or_else(|| Path::new("."));
+            if self.fs.free_space(dir).is_ok_and(|free| free >= bytes) {
+                // Without support for it the file grows as it's written like without the flag
+                let _ = file.preallocate(bytes);
+            }
+        }
+
+        let created = self
+            .timestamp_fn
+            .as_ref()
+            .map_or_else(|| self.identity.now(), |timestamp| timestamp());
+        // A fake clock may be set before the start of the process
+        let running_duration = (created - self.identity.start_time).max(chrono::Duration::zero());
+
+        self.write_locked(&mut file, |file| {
+            file.write_all(self.file_encoding.bom())?;
+            if let Some(level) = level.filter(|_| self.file_header == FileHeader::JsonEnvelope) {
+                let envelope = self.envelope(level, previous_segments.unwrap_or(0), previous, created);
+                self.file_encoding.write_fmt(file, format_args!("{}\n", envelope.to_json()))?;
+            } else {
+                // todo(#3): integrate UTC
+                self.file_encoding.write_fmt(
+                    file,
+                    format_args!("Log file created at:\n{}\nRunning on machine: {}\n{}{}{}Running duration (h:mm:ss): {}:{:02}:{:02}\nLog line format: {}\n",
+                        created.format("%Y/%m/%d %H:%M:%S"),
+                        self.identity.header_hostname(),
+                        self.machine_id.as_ref().map(|id| format!("Machine ID: {}\n", id)).unwrap_or_default(),
+                        if self.application_fingerprint.is_some() { format!("Application fingerprint: {}\n", self.application_fingerprint.clone().unwrap()) } else { String::new() },
+                        if log_crate_version { concat!("glog-rs version: ", env!("CARGO_PKG_VERSION"), "\n") } else { "" },
+                        running_duration.num_hours(),
+                        running_duration.num_minutes() % 60,
+                        running_duration.num_seconds() % 60,
+                        self.log_line_format(),
+                    )
+                )?;
+            }
+            if let Some(previous_segments) = previous_segments.filter(|_| header_include_stats) {
+                self.file_encoding.write_fmt(file, format_args!("{}\n", self.header_stats(previous_segments)))?;
+            }
+            for line in self.extra_headers.iter().map(String::as_str).chain(note) {
+                self.file_encoding.write_fmt(file, format_args!("{}\n", line))?;
+            }
+            file.flush()
+        })?;
+        file.sync()?;
+        drop(file);
+
+        let published = match self.fs.hard_link(in_progress_path, file_path) {
+            // File systems without hard links, e.g. FAT, fail with EPERM on Unix. Renaming replaces
+            // an existing file, so the final name is only checked right before.
+            Err(why) if matches!(why.kind(), io::ErrorKind::Unsupported | io::ErrorKind::PermissionDenied) => {
+                if self.fs.stat(file_path).is_ok() {
+                    Err(io::ErrorKind::AlreadyExists.into())
+                } else {
+                    self.fs.rename(in_progress_path, file_path)
+                }
+            }
+            published => published,
+        };
+        // Without the final name the file is only removed as stale after a crash
+        let _ = self.fs.remove(in_progress_path);
+        published
+    }
+
+    /// The layout of the records for the file header, e.g. `[IWE]mmdd hh:mm:ss.uuuuuu threadid file:line] msg`
+    fn log_line_format(&self) -> String {
+        format!(
+            "[{}]{}mmdd hh:mm:ss.uuuuuu threadid file:line] msg",
+            self.header_level_letters(),
+            if self.compatible_date { "" } else { "yyyy" }
+        )
+    }
+
+    /// The [`Envelope`] of a log file created at `created` after `segment` others, the last of them at `previous`
+    fn envelope(&self, level: Level, segment: u64, previous: Option<&Path>, created: DateTime<Local>) -> Envelope {
+        Envelope {
+            glog_envelope: envelope::SCHEMA_VERSION,
+            program: self.identity.program().to_string_lossy().into_owned(),
+            host: self.identity.header_hostname().to_owned(),
+            user: self.identity.username.clone(),
+            pid: self.identity.pid,
+            session: format!("{}-{}", self.identity.pid, self.identity.start_time.timestamp_micros()),
+            created: envelope::timestamp(created),
+            level: level.to_string().to_uppercase(),
+            segment,
+            previous: previous
+                .and_then(Path::file_name)
+                .map(|file_name| file_name.to_string_lossy().into_owned()),
+            // The salt would let readers of the log files reverse the redaction
+            flags: flag_registry::snapshot(&self.flags())
+                .filter(|(name, _)| *name != "redaction_salt")
+                .map(|(name, value)| (name.to_owned(), value))
+                .chain([("console_available".to_owned(), self.console_available.to_string())])
+                .collect(),
+            format: self.log_line_format(),
+        }
+    }
+
+    /// Append the [`Footer`] to `log_file`, see [`GlogHandle::shutdown`]
+    fn write_footer(&self, log_file: &mut LogFile, end: DateTime<Local>) -> io::Result<()> {
+        let footer = Footer {
+            glog_footer: envelope::SCHEMA_VERSION,
+            end: envelope::timestamp(end),
+            records: log_file.records,
+            clean: !crash::crash_reported(),
+        };
+        self.write_locked(&mut log_file.file, |file| {
+            self.file_encoding.write_fmt(file, format_args!("{}\n", footer.to_json()))?;
+            file.flush()
+        })
+    }
+
+    /// Flush the output, end the level log files with a [`Footer`] if they have envelopes and cut off mapped files
+    fn shutdown(&self) {
+        self.flush();
+        if self.file_header == FileHeader::JsonEnvelope {
+            let end = self.wall_clock();
+            for (level, log_file) in self.lock_log_files(Level::Trace, Level::Error) {
+                let mut log_file = log_file.borrow_mut();
+                if !log_file.created {
+                    continue;
+                }
+                if let Err(why) = self.write_footer(&mut log_file, end) {
+                    self.handle_write_error(level, why);
+                }
+            }
+        }
+        // Other threads don't flush their files before the process exits
+        #[cfg(all(unix, feature = "mmap"))]
+        if self.mmap_chunk.is_some() {
+            mmap::close_all();
+        }
+    }
+
+    /// The letters of the levels written to the log files, from the least severe level
+    fn header_level_letters(&self) -> String {
+        let options = self.custom_level_options(MultilineStyle::Raw);
+        let levels: &[Level] = if self.compatible_verbosity {
+            &[Level::Info, Level::Warn, Level::Error]
+        } else {
+            &[Level::Trace, Level::Debug, Level::Info, Level::Warn, Level::Error]
+        };
+        levels.iter().map(|level| options.letter(*level)).collect()
+    }
+
+    /// The `header_include_stats` line with the messages logged so far, by the levels of the log files
+    fn header_stats(&self, previous_segments: u64) -> String {
+        let messages = self.stats.snapshot().messages;
+        let counts = if self.compatible_verbosity {
+            vec![(messages.trace + messages.debug + messages.info, "INFO")]
+        } else {
+            vec![(messages.trace, "TRACE"), (messages.debug, "DEBUG"), (messages.info, "INFO")]
+        };
+        let counts: Vec<_> = counts
+            .into_iter()
+            .chain([(messages.warn, "WARNING"), (messages.error, "ERROR")])
+            .map(|(count, level)| format!("{} {}", count, level))
+            .collect();
+        format!("Previous segments: {}, cumulative: {}", previous_segments, counts.join(" / "))
+    }
+
+    /// Call `write` while holding an exclusive lock on `file` if `lock_shared_files` is set
+    ///
+    /// Writes without the lock if it isn't acquired within [`SHARED_FILE_LOCK_TIMEOUT`] or the file
+    /// can't be locked.
+    fn write_locked(
+        &self,
+        file: &mut Box<dyn LogWriter>,
+        write: impl FnOnce(&mut Box<dyn LogWriter>) -> io::Result<()>,
+    ) -> io::Result<()> {
+        if !self.flags().lock_shared_files {
+            return write(file);
+        }
+        let deadline = Instant::now() + SHARED_FILE_LOCK_TIMEOUT;
+        let locked = loop {
+            match file.try_lock() {
+                Ok(false) if Instant::now() < deadline => thread::sleep(Duration::from_millis(1)),
+                Ok(locked) => break Some(locked),
+                Err(why) if why.kind() == io::ErrorKind::Unsupported => {
+                    if !LOCKING_UNSUPPORTED.swap(true, Ordering::Relaxed) {
+                        Glog::write_stderr_raw(&format!(
+                            "glog: lock_shared_files is not supported for this log file, writing without the lock: {}\n",
+                            why
+                        ));
+                    }
+                    break None;
+                }
+                Err(_) => break Some(false),
+            }
+        };
+        if locked == Some(false) {
+            self.stats.record_lock_timeout();
+        }
+        let locked = locked == Some(true);
+        let written = write(file);
+        if locked {
+            let _ = file.unlock();
+        }
+        written
+    }
+
+    /// Copy everything written to `file` to the tees, if there are any
+    fn tee_file(&self, file: Box<dyn LogWriter>) -> Box<dyn LogWriter> {
+        if self.tees.is_empty() {
+            return file;
+        }
+        Box::new(TeeWriter {
+            file,
+            tees: self.tees.clone(),
+            stats: self.stats.clone(),
+        })
+    }
+
+    /// On supported platforms creates short stable named symlinks pointing to latest log file.
+    /// Example /tmp/main.INFO -> /tmp/main.hostname.username.log.INFO.<timestamp>
+    #[cfg(not(target_arch = "wasm32"))]
+    fn create_symlink(&self, long_name: &OsString, symlink_name: &OsString) -> io::Result<()> {
+        #[cfg(target_family = "unix")]
+        {
+            // Unconditionally remove any existing symlink
+            let _ = self.fs.remove(Path::new(symlink_name));
+            self.fs.symlink(Path::new(long_name), Path::new(symlink_name))?;
+        }
+        #[cfg(not(target_family = "unix"))]
+        let _ = (long_name, symlink_name);
+        Ok(())
+    }
+
+    /// A backtrace of the current thread rendered in the configured style
+    fn backtrace(&self) -> String {
+        let style = self.flags().backtrace_style;
+        trace::render_backtrace(&Backtrace::new(), style, self.backtrace_filter.as_ref())
+    }
+
+    fn should_log_backtrace(&self, record: &Record) -> bool {
+        !self.backtrace_at.is_empty()
+            && self
+                .backtrace_at
+                .contains(&call_site_file_name(record), record.line().unwrap_or(0))
+    }
+
+    fn record_to_file_name(&self, record: &Record) -> String {
+        format::source_file_name(record.file().unwrap_or(""), self.flags().trim_source_prefix.as_deref())
+    }
+
+    /// The current time of the clock, see [`Glog::with_timestamp_fn`]
+    fn wall_clock(&self) -> DateTime<Local> {
+        self.timestamp_fn.as_ref().map_or_else(Local::now, |timestamp| timestamp())
+    }
+
+    /// The minimum level, lowered by an [escalation](Flags::adaptive_error_threshold) and raised by
+    /// the active [schedule window](Glog::with_schedule)
+    fn min_level(&self, minloglevel: Level) -> Level {
+        self.adaptive
+            .lower(minloglevel)
+            .min(self.schedule.floor(|| self.wall_clock()))
+    }
+
+    /// Escalate or restore the minimum level after `record`, see [`Flags::adaptive_error_threshold`]
+    fn adapt(&self, record: &Record) {
+        if record.target() == redact::INTERNAL_TARGET {
+            return;
+        }
+        let config = || {
+            let flags = self.flags();
+            flags.adaptive_error_threshold.map(|threshold| AdaptiveConfig {
+                threshold,
+                window: flags.adaptive_error_window,
+                level: flags.adaptive_min_level,
+                duration: flags.adaptive_duration,
+            })
+        };
+        let Some(transition) = self
+            .adaptive
+            .observe(record.level(), config, || self.wall_clock().timestamp_micros())
+        else {
+            return;
+        };
+        log::set_max_level(self.max_level().to_level_filter());
+        let (minloglevel, lowered, window, duration, dump) = {
+            let flags = self.flags();
+            (
+                flags.minloglevel,
+                flags.adaptive_min_level,
+                flags.adaptive_error_window,
+                flags.adaptive_duration,
+                flags.adaptive_dump_ring_buffer,
+            )
+        };
+        let message = match transition {
+            Transition::Escalated(count) => format!(
+                "glog: {} WARNING and ERROR records within {:?}, logging {} records until none happen for {:?}",
+                count, window, lowered, duration
+            ),
+            Transition::Restored => format!("glog: error burst subsided, logging {} records again", minloglevel),
+        };
+        self.log_record(
+            &Record::builder()
+                .level(Level::Warn)
+                .target(redact::INTERNAL_TARGET)
+                .args(format_args!("{}", message))
+                .build(),
+        );
+        let Some(buffer) = &self.adaptive_buffer else {
+            return;
+        };
+        if transition == Transition::Restored || !dump {
+            // Records logged while escalated are in the log files already
+            self.adaptive.set_dumped_seq(buffer.latest_seq());
+            return;
+        }
+        let events = buffer
+            .query(&QueryParams {
+                min_level: lowered,
+                since_seq: self.adaptive.dumped_seq(),
+                ..Default::default()
+            })
+            .events;
+        if let Some(last) = events.last() {
+            self.adaptive.set_dumped_seq(last.seq);
+        }
+        for event in events.iter().filter(|event| event.level > minloglevel) {
+            self.write_file(
+                &Record::builder().level(event.level).target(&event.target).build(),
+                &event.line,
+            );
+        }
+    }
+
+    /// The timestamp for a log record
+    fn now(&self) -> DateTime<Local> {
+        let now = self.wall_clock();
+        if !self.flags().monotonic_timestamps {
+            return now;
+        }
+        match self.monotonic_guard.guard(now) {
+            Ok(now) => now,
+            Err((last, microseconds_backwards)) => {
+                if self.stats.record_clock_step_backwards() == 0 {
+                    Glog::write_stderr_raw(&format!(
+                        "glog: wall clock went backwards by {}us, keeping log timestamps monotonic\n",
+                        microseconds_backwards
+                    ));
+                }
+                last
+            }
+        }
+    }
+
+    fn format_options(&self) -> FormatOptions {
+        self.custom_level_options(self.flags().multiline_style)
+    }
+
+    /// The [`FormatOptions`] of the logger with `multiline_style`
+    fn custom_level_options(&self, multiline_style: MultilineStyle) -> FormatOptions {
+        let options = FormatOptions::default()
+            .with_year(!self.compatible_date)
+            .reduced_log_levels(self.compatible_verbosity)
+            .multiline_style(multiline_style);
+        self.custom_level_names
+            .iter()
+            .fold(options, |options, (level, name)| match name.chars().next() {
+                Some(letter) => options.level_letter(*level, letter),
+                None => options,
+            })
+    }
+
+    fn build_log_message(&self, record: &Record) -> String {
+        self.format_message(record, record.args(), self.now())
+    }
+
+    /// The log line of `record` with `args` as message, logged at `timestamp`
+    fn format_message(&self, record: &Record, args: &fmt::Arguments, timestamp: DateTime<Local>) -> String {
+        let options = self.format_options();
+        let formatter = GlogFormatter::new(&self.flags()).with_options(options);
+        formatter.format_message(record, args, timestamp)
+    }
+
+    /// The message of `record` replaced by the redactor, `None` if it doesn't need to be redacted
+    fn redact(&self, record: &Record) -> Option<String> {
+        let redactor = self.redactor.as_ref()?;
+        if record.target() == redact::INTERNAL_TARGET || record.target() == MARKER_TARGET {
+            return None;
+        }
+        match redactor(&record.args().to_string()) {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(redacted) => Some(redacted),
+        }
+    }
+
+    /// Write `message`, the line of `record` logged at `timestamp`, to stderr
+    fn write_stderr(&self, record: &Record, message: &str, timestamp: DateTime<Local>) {
+        let restamped;
+        let message = match &self.stderr_timestamp_format {
+            Some(format) => {
+                restamped = self.restamp(record.level(), message, format, timestamp);
+                &restamped
+            }
+            None => message,
+        };
+        let prefixed_message;
+        let message = if self.flags().stderr_verbose_level {
+            prefixed_message = format!("[{}] {}", self.match_level(&record.level()), message);
+            &prefixed_message
+        } else {
+            message
+        };
+
+        if let Some(console_writer) = &self.console_writer {
+            console_writer.write_line(record.level(), message);
+            if self.should_log_backtrace(record) {
+                console_writer.write_line(record.level(), &self.backtrace());
+            }
+            return;
+        }
+
+        let mut stderr_writer = LineWriter::new(self.stderr_color_writer());
+        let (colorlogtostderr, color_reset_on_newline) = {
+            let flags = self.flags();
+            (flags.colorlogtostderr, flags.color_reset_on_newline)
+        };
+
+        if colorlogtostderr {
+            stderr_writer
+                .get_mut()
+                .set_color(ColorSpec::new().set_fg(match record.metadata().level() {
+                    Level::Error => Some(Color::Red),
+                    Level::Warn => Some(Color::Yellow),
+                    _ => None,
+                }))
+                .expect("failed to set color");
+        }
+
+        if colorlogtostderr && color_reset_on_newline {
+            write!(stderr_writer, "{}", message).expect("couldn't write log message");
+            // The reset bypasses the line buffer
+            stderr_writer.flush().expect("couldn't write log message");
+            stderr_writer.get_mut().reset().expect("failed to reset color");
+            writeln!(stderr_writer).expect("couldn't write log message");
+        } else {
+            writeln!(stderr_writer, "{}", message).expect("couldn't write log message");
+            if colorlogtostderr {
+                stderr_writer.get_mut().reset().expect("failed to reset color");
+            }
+        }
+
+        if self.should_log_backtrace(record) {
+            let colored = colorlogtostderr && io::stderr().is_terminal();
+            let style = self.flags().backtrace_style;
+            trace::write_backtrace(stderr_writer.get_mut(), style, self.backtrace_filter.as_ref(), colored)
+                .expect("Couldn't write backtrace");
+        }
+    }
+
+    /// `message` with the glog timestamps starting its lines replaced by `timestamp` written in `format`
+    ///
+    /// Returns `message` as is if it doesn't start with a glog timestamp, e.g. for raw lines. The
+    /// glog timestamp isn't parsed back, it's ambiguous when the clocks are turned back.
+    fn restamp(&self, level: Level, message: &str, format: &str, timestamp: DateTime<Local>) -> String {
+        let letter = self.format_options().letter(level);
+        let layout = if self.compatible_date {
+            "mmdd hh:mm:ss.uuuuuu"
+        } else {
+            "yyyymmdd hh:mm:ss.uuuuuu"
+        };
+        let Some(stamp) = message.strip_prefix(letter).and_then(|rest| rest.get(..layout.len())) else {
+            return message.to_owned();
+        };
+        let is_timestamp = stamp.bytes().zip(layout.bytes()).all(|(byte, expected)| {
+            if expected.is_ascii_alphabetic() {
+                byte.is_ascii_digit()
+            } else {
+                byte == expected
+            }
+        });
+        if !is_timestamp {
+            return message.to_owned();
+        }
+        // With `MultilineStyle::Prefixed` every line starts with the timestamp
+        let prefix = format!("{}{}", letter, stamp);
+        let replacement = format!("{}{}", letter, timestamp.format(format));
+        let lines: Vec<_> = message
+            .split('\n')
+            .map(|line| match line.strip_prefix(&prefix) {
+                Some(rest) => format!("{}{}", replacement, rest),
+                None => line.to_owned(),
+            })
+            .collect();
+        lines.join("\n")
+    }
+
+    /// The colored stderr, the original one while fd 2 is captured
+    fn stderr_color_writer(&self) -> Box<dyn WriteColor + '_> {
+        #[cfg(unix)]
+        if let Some(stderr) = output::captured_stderr() {
+            return match nix::unistd::isatty(stderr.0) {
+                Ok(true) => Box::new(Ansi::new(stderr)),
+                _ => Box::new(NoColor::new(stderr)),
+            };
+        }
+        Box::new(self.stderr_writer.lock())
+    }
+
+    /// Fast path for plain stderr output without color or backtraces
+    ///
+    /// Formats `record` into a reused thread local buffer and writes it with a single call.
+    /// Returns the length of the log message or `None` if the buffer is in use because formatting
+    /// the record logged another record, in which case the general path has to be used.
+    fn write_stderr_fast<W: Write>(&self, record: &Record, out: &mut W) -> Option<usize> {
+        thread_local! {
+            static BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+        }
+        let (sanitize, attachment_max_bytes, log_target) = {
+            let flags = self.flags();
+            (flags.sanitize_messages, flags.attachment_max_bytes, flags.log_target)
+        };
+        BUFFER.with(|buffer| {
+            let mut buffer = buffer.try_borrow_mut().ok()?;
+            buffer.clear();
+            format::write_record(
+                &mut *buffer,
+                &self.format_options(),
+                self.now(),
+                record.level(),
+                get_tid(),
+                &self.record_to_file_name(record),
+                record.line().unwrap_or(0),
+                &format_args!(
+                    "{}",
+                    Sanitized::new(
+                        format_args!(
+                            "{}{}{}{}",
+                            format::Target(log_target.then(|| record.target())),
+                            context::Current,
+                            record.args(),
+                            attach::Rendered {
+                                max_bytes: attachment_max_bytes
+                            }
+                        ),
+                        sanitize
+                    )
+                ),
+            )
+            .expect("couldn't format log message");
+            let len = buffer.len();
+            buffer.push('\n');
+            out.write_all(buffer.as_bytes()).expect("couldn't write log message");
+            Some(len)
+        })
+    }
+
+    /// Fast path for records only written to files
+    ///
+    /// Formats the prefix and the message of `record` into separate reused thread local buffers,
+    /// which are written to the files without concatenating them, see [`LogWriter::write_line`].
+    /// Returns the length of the log message or `None` if the buffers are in use or the message
+    /// has to be prefixed line by line, in which case the general path has to be used.
+    fn write_file_fast(&self, record: &Record) -> Option<usize> {
+        thread_local! {
+            static BUFFERS: RefCell<(String, String)> = const { RefCell::new((String::new(), String::new())) };
+        }
+        let (sanitize, attachment_max_bytes, log_target, multiline_style) = {
+            let flags = self.flags();
+            (
+                flags.sanitize_messages,
+                flags.attachment_max_bytes,
+                flags.log_target,
+                flags.multiline_style,
+            )
+        };
+        if multiline_style != MultilineStyle::Raw {
+            return None;
+        }
+        BUFFERS.with(|buffers| {
+            let mut buffers = buffers.try_borrow_mut().ok()?;
+            let (prefix, message) = &mut *buffers;
+            prefix.clear();
+            message.clear();
+            format::write_prefix(
+                prefix,
+                &self.format_options(),
+                self.now(),
+                record.level(),
+                get_tid(),
+                &self.record_to_file_name(record),
+                record.line().unwrap_or(0),
+            )
+            .expect("couldn't format log message");
+            fmt::Write::write_fmt(
+                message,
+                format_args!(
+                    "{}",
+                    Sanitized::new(
+                        format_args!(
+                            "{}{}{}{}",
+                            format::Target(log_target.then(|| record.target())),
+                            context::Current,
+                            record.args(),
+                            attach::Rendered {
+                                max_bytes: attachment_max_bytes
+                            }
+                        ),
+                        sanitize
+                    )
+                ),
+            )
+            .expect("couldn't format log message");
+            self.write_file_parts(record, prefix, message);
+            Some(prefix.len() + message.len())
+        })
+    }
+
+    /// Write `prefix`, `message` and a newline to `file` in the [`FileEncoding`]
+    fn write_file_line(&self, file: &mut Box<dyn LogWriter>, prefix: &str, message: &str) -> io::Result<()> {
+        match self.file_encoding {
+            FileEncoding::Utf8 => file.write_line(prefix.as_bytes(), message.as_bytes()),
+            encoding => file.write_all(&encoding.encode(&format!("{}{}\n", prefix, message))),
+        }
+    }
+
+    fn write_file(&self, record: &Record, message: &str) {
+        self.write_file_parts(record, "", message);
+    }
+
+    /// Write the log line `prefix` followed by `message` to the files of `record`
+    fn write_file_parts(&self, record: &Record, prefix: &str, message: &str) {
+        #[cfg(feature = "metrics")]
+        let _timer = self.write_latency.time(record.level());
+        let (minloglevel, detect_deleted_files, module_logs_to_level_files, file_per_thread, sync) = {
+            let flags = self.flags();
+            (
+                // Records of targets with a more verbose policy only go to the files of their level
+                self.adaptive.lower(flags.minloglevel).max(record.level()),
+                flags.detect_deleted_files,
+                flags.module_logs_to_level_files,
+                flags.file_per_thread,
+                flags.fsync_on.is_some_and(|fsync_on| record.level() <= fsync_on),
+            )
+        };
+        if let Some(module_log_file) = record.module_path().and_then(|module| self.module_log_file(module)) {
+            let written = self.write_synced(&mut self.lock_file(module_log_file).file, prefix, message, sync);
+            if let Err(why) = written {
+                self.handle_write_error(record.level(), why);
+            }
+            if !module_logs_to_level_files {
+                return;
+            }
+        }
+        if file_per_thread {
+            self.write_thread_files(minloglevel, record.level(), prefix, message, sync);
+        } else {
+            self.write_cascaded_files(minloglevel, record.level(), prefix, message, detect_deleted_files, sync);
+        }
+
+        if self.should_log_backtrace(record) {
+            let level = self.match_level(&minloglevel);
+            let written = {
+                let file_write_guard = self.lock_file(self.file_writer.get(&level).unwrap());
+                let mut log_file = (*file_write_guard).borrow_mut();
+                let _stamp = self.write_watchdog.as_ref().map(|watchdog| watchdog.begin(level));
+                self.write_locked(&mut log_file.file, |file| self.write_file_line(file, "", &self.backtrace()))
+            };
+            if let Err(why) = written {
+                self.handle_write_error(level, why);
+            }
+        }
+    }
+
+    /// Write the line like [`Glog::write_file_line`], then sync the file to disk if `sync` is set
+    fn write_synced(&self, file: &mut Box<dyn LogWriter>, prefix: &str, message: &str, sync: bool) -> io::Result<()> {
+        self.write_locked(file, |file| self.write_file_line(file, prefix, message))?;
+        if sync {
+            file.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Write `prefix` and `message` to the shared log files from `level` down to `least_severe`
+    fn write_cascaded_files(
+        &self,
+        least_severe: Level,
+        level: Level,
+        prefix: &str,
+        message: &str,
+        detect_deleted_files: bool,
+        sync: bool,
+    ) {
+        self.write_cascaded(least_severe, level, |level, log_file| {
+            if !log_file.created {
+                // Pending with logtostderr_if_no_log_dir, the record is on stderr until the file can be created
+                match self.create_log_file(&log_file.path, Some(level), Some(log_file.previous_segments), None, None) {
+                    Ok(file) => {
+                        let (file, index) = self.index_log_file(file, &log_file.path);
+                        *log_file = LogFile::new(file, log_file.path.clone());
+                        log_file.index = index;
+                    }
+                    Err(_) => return Ok(()),
+                }
+                let _ = self.create_symlink(&log_file.path, &self.symlink_path(&level));
+            }
+            if detect_deleted_files && log_file.liveness_check_due(self.wall_clock()) && log_file.is_removed(&*self.fs) {
+                // Keep writing to the old file if it can't be recreated, maybe it's just being rotated
+                // The record is only counted once it is written, so it isn't part of the header stats
+                let previous_segments = log_file.previous_segments + 1;
+                let note = Some("Log file recreated after external removal");
+                // Moved away by a rotation or already removed without a name to link to
+                let previous = log_file.file.current_path();
+                let recreated = match self.create_log_file(
+                    &log_file.path,
+                    Some(level),
+                    Some(previous_segments),
+                    previous.as_deref(),
+                    note,
+                ) {
+                    // Replaced by another file, e.g. by a copying rotation, which is continued without a header
+                    Err(why) if why.kind() == io::ErrorKind::AlreadyExists => self.open_log_file(Path::new(&log_file.path)),
+                    recreated => recreated,
+                };
+                if let Ok(file) = recreated {
+                    if self.file_header == FileHeader::JsonEnvelope {
+                        // The removed file may have been moved away by a rotation, so it is kept consistent
+                        let _ = self.write_footer(log_file, self.wall_clock());
+                    }
+                    (log_file.file, log_file.index) = self.index_log_file(file, &log_file.path);
+                    log_file.previous_segments = previous_segments;
+                    log_file.records = 0;
+                    self.stats.record_rotation();
+                    self.run_rotation_hook(level, Path::new(&log_file.path));
+                }
+            }
+            if let Some(index) = &mut log_file.index {
+                index.before_line(self.index_micros());
+            }
+            self.write_synced(&mut log_file.file, prefix, message, sync)?;
+            log_file.records += 1;
+            Ok(())
+        });
+    }
+
+    /// Call the [rotation hook](Glog::with_log_rotation_hook), warning if it is slow
+    fn run_rotation_hook(&self, level: Level, path: &Path) {
+        let Some(hook) = &self.rotation_hook else {
+            return;
+        };
+        let started = Instant::now();
+        hook(level, path);
+        let elapsed = started.elapsed();
+        if elapsed > ROTATION_HOOK_WARNING {
+            Glog::write_stderr_raw(&format!(
+                "glog: the log rotation hook took {:?} for {}, it blocks logging\n",
+                elapsed,
+                path.display()
+            ));
+        }
+    }
+
+    /// Write `prefix` and `message` to the files of the current thread from `level` down to `least_severe`
+    fn write_thread_files(&self, least_severe: Level, level: Level, prefix: &str, message: &str, sync: bool) {
+        let levels = self.match_level(&level)..=self.match_level(&least_severe);
+        let failed: Vec<(Level, io::Error)> = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace]
+            .iter()
+            .filter(|level| levels.contains(*level) && self.file_writer.contains_key(*level))
+            .filter_map(|&level| {
+                let _stamp = self.write_watchdog.as_ref().map(|watchdog| watchdog.begin(level));
+                self.thread_files
+                    .write(
+                        level,
+                        || self.create_thread_log_file(level),
+                        |log_file| self.write_synced(&mut log_file.file, prefix, message, sync),
+                    )
+                    .err()
+                    .map(|why| (level, why))
+            })
+            .collect();
+        for (level, why) in failed {
+            self.handle_write_error(level, why);
+        }
+    }
+
+    /// The path of the log file of `level` for the current thread
+    fn thread_log_file_path(&self, level: Level) -> OsString {
+        let mut path = self.log_dir(&level);
+        path.push(&self.log_file_name_prefix);
+        path.push(level.to_string().to_uppercase());
+        path.push(self.identity.log_file_name_suffix());
+        path.push(format!(".{}", get_tid()));
+        path
+    }
+
+    /// Create the log file of `level` for the current thread, see [`Flags::file_per_thread`]
+    fn create_thread_log_file(&self, level: Level) -> io::Result<LogFile> {
+        let path = self.thread_log_file_path(level);
+        let file = match self.create_log_file(&path, None, Some(0), None, None) {
+            // Thread ids are reused after threads exit
+            Err(why) if why.kind() == io::ErrorKind::AlreadyExists => self.open_log_file(Path::new(&path)),
+            created => created,
+        }?;
+        Ok(LogFile::new(file, path))
+    }
+
+    /// The log file of the longest module prefix matching `module`
+    fn module_log_file(&self, module: &str) -> Option<&Mutex<LogFile>> {
+        self.module_log_files
+            .iter()
+            .find(|(prefix, _)| matches_module_prefix(module, prefix))
+            .map(|(_, log_file)| &**log_file)
+    }
+
+    /// Lock the log files from `most_severe` down to `least_severe`, in this order
+    ///
+    /// Everything holding more than one log file has to lock them through this to avoid deadlocks.
+    fn lock_log_files(&self, least_severe: Level, most_severe: Level) -> Vec<(Level, MutexGuard<'_, RefCell<LogFile>>)> {
+        let levels = self.match_level(&most_severe)..=self.match_level(&least_severe);
+        [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace]
+            .iter()
+            .filter(|level| levels.contains(*level))
+            .filter_map(|level| Some((*level, self.lock_file(self.file_writer.get(level)?))))
+            .collect()
+    }
+
+    /// Lock a log file, taking it over from a thread which panicked with [`Glog::with_crash_recovery`]
+    fn lock_file<'a, T>(&self, log_file: &'a Mutex<T>) -> MutexGuard<'a, T> {
+        log_file.lock().unwrap_or_else(|poisoned| {
+            if !self.crash_recovery {
+                panic!("{}", poisoned);
+            }
+            if !POISON_RECOVERED.swap(true, Ordering::Relaxed) {
+                Glog::write_stderr_raw("glog: recovered a log file left locked by a panicking thread\n");
+            }
+            poisoned.into_inner()
+        })
+    }
+
+    /// Call `write` for the file of `level` and every less severe file down to `least_severe`
+    ///
+    /// The most severe file is written first and all files stay locked until the record is in every
+    /// one of them. Records therefore appear in the same order in all files, and a less severe file
+    /// is at most behind a more severe one: after a crash it can only miss the last records of the
+    /// more severe file, never records in between.
+    ///
+    /// With [`Flags::log_dir_overrides`] the record is only written to the files in the directory of its level.
+    fn write_cascaded(&self, least_severe: Level, level: Level, mut write: impl FnMut(Level, &mut LogFile) -> io::Result<()>) {
+        let log_dir = (!self.flags().log_dir_overrides.is_empty()).then(|| self.log_dir(&self.match_level(&level)));
+        let failed: Vec<(Level, io::Error)> = self
+            .lock_log_files(least_severe, level)
+            .into_iter()
+            .filter(|(level, _)| log_dir.as_ref().map_or(true, |log_dir| self.log_dir(level) == *log_dir))
+            .filter_map(|(level, log_file)| {
+                let _stamp = self.write_watchdog.as_ref().map(|watchdog| watchdog.begin(level));
+                write(level, &mut log_file.borrow_mut()).err().map(|why| (level, why))
+            })
+            .collect();
+        // The files aren't locked anymore in case the handler logs
+        for (level, why) in failed {
+            self.handle_write_error(level, why);
+        }
+    }
+
+    fn handle_write_error(&self, level: Level, why: io::Error) {
+        match &self.write_error_handler {
+            Some(handler) => handler(level, why),
+            None => Glog::write_stderr_raw(&format!(
+                "glog: couldn't write to the {} log file, dropping the record: {}\n",
+                level, why
+            )),
+        }
+    }
+
+    /// The log line of a template record with the human readable message, if there is one
+    ///
+    /// The rendered message is passed through the redactor if `redact` is set.
+    fn render_template(&self, record: &Record, redact: bool) -> Option<String> {
+        if record.target() != TEMPLATE_TARGET {
+            return None;
+        }
+        let rendered = template::render(self.template_renderer.as_deref()?, &record.args().to_string())?;
+        let rendered = match self.redactor.as_ref().filter(|_| redact) {
+            Some(redactor) => redactor(&rendered).into_owned(),
+            None => rendered,
+        };
+        Some(
+            self.build_log_message(
+                &Record::builder()
+                    .metadata(record.metadata().clone())
+                    .file(record.file())
+                    .line(record.line())
+                    .args(format_args!("{}", rendered))
+                    .build(),
+            ),
+        )
+    }
+
+    /// Write `record` to all outputs, see [`Log::log`]
+    fn log_record(&self, record: &Record) {
+        let is_marker = record.target() == MARKER_TARGET;
+        // Markers and the audit records of glog itself are logged at any minimum level
+        let unfiltered = is_marker || record.target() == redact::INTERNAL_TARGET;
+        let policy = self.target_policy.read().unwrap().clone();
+        let matched = policy.lookup(record.target()).filter(|_| !unfiltered);
+        if !unfiltered && !self.enabled_by(record.metadata(), matched.as_ref()) {
+            return;
+        }
+        if !unfiltered
+            && self
+                .suppressions
+                .suppress(|| call_site_file_name(record), record.line().unwrap_or(0))
+        {
+            self.stats.record_suppressed();
+            return;
+        }
+        if let Some(Err(rejection)) = matched
+            .as_ref()
+            .map(|matched| matched.admit(|| self.wall_clock().timestamp_micros()))
+        {
+            self.stats.record_rejection(rejection);
+            return;
+        }
+        if !is_marker {
+            self.adapt(record);
+        }
+
+        let (plan, redact_stderr, tail_buffer_size, minloglevel, attachment_ttl) = {
+            let flags = self.flags();
+            (
+                self.output_plan(&flags, record),
+                flags.redact_stderr,
+                flags.tail_buffer_size,
+                flags.minloglevel,
+                flags.attachment_ttl,
+            )
+        };
+        // Markers are logged by the logger itself, not by the thread the attachment is meant for
+        let _attachment = (!is_marker).then(|| {
+            let redactor = self.redactor.as_ref().filter(|_| record.target() != redact::INTERNAL_TARGET);
+            attach::CurrentGuard::take(attachment_ttl, redactor)
+        });
+        let minloglevel = self.min_level(matched.and_then(|matched| matched.level()).unwrap_or(minloglevel));
+        if !is_marker && record.level() > minloglevel {
+            // Only enabled for sinks with a more verbose `min_level`
+            self.write_sinks(record, None, self.redact(record).as_deref(), minloglevel, false);
+            return;
+        }
+        let fast = !is_marker && self.sinks.is_empty() && (self.template_renderer.is_none() || record.target() != TEMPLATE_TARGET);
+        if fast && self.fast_paths.stderr && plan == OutputPlan::STDERR_ONLY {
+            if let Some(len) = self.write_stderr_fast(record, &mut output::Stderr::lock()) {
+                self.stats.record_message(record.level(), len);
+                return;
+            }
+        }
+        if fast && self.fast_paths.files && plan == OutputPlan::FILES_ONLY && !output::fatal_redirected_to_stderr(record.level()) {
+            if let Some(len) = self.write_file_fast(record) {
+                self.stats.record_message(record.level(), len);
+                return;
+            }
+        }
+
+        let redacted = self.redact(record);
+        let now = self.now();
+        let message = match &redacted {
+            Some(redacted) => self.format_message(record, &format_args!("{}", redacted), now),
+            None => self.format_message(record, record.args(), now),
+        };
+        let rendered = self.render_template(record, redact_stderr);
+        plan.dispatch(
+            || {
+                let unredacted;
+                let message = if redacted.is_some() && !redact_stderr {
+                    unredacted = self.format_message(record, record.args(), now);
+                    &unredacted
+                } else {
+                    &message
+                };
+                self.write_stderr(record, rendered.as_deref().unwrap_or(message), now)
+            },
+            || self.write_file(record, &message),
+        );
+        if !plan.stderr && !self.drain && output::fatal_redirected_to_stderr(record.level()) && self.stderr_allows(record) {
+            Glog::write_stderr_raw(&format!("{}\n", message));
+        }
+        self.write_sinks(record, Some(&message), redacted.as_deref(), minloglevel, is_marker);
+        self.tail.push(record.level(), &message, tail_buffer_size);
+        self.stats.record_message(record.level(), message.len());
+
+        if is_marker {
+            // Flushed by the threads waiting for the marker, see `GlogHandle::wait_for_marker`
+            if let Some(seq) = marker::parse_marker_sequence(&record.args().to_string()) {
+                self.markers.written(seq);
+            }
+        }
+    }
+
+    /// Write `record` to the sinks accepting it, `message` is its glog line if it was already formatted
+    fn write_sinks(&self, record: &Record, message: Option<&str>, redacted: Option<&str>, minloglevel: Level, any_level: bool) {
+        let errors = self.sinks.write(
+            record,
+            minloglevel,
+            any_level,
+            |format, include_context| {
+                if let (LogFormat::Glog, true, Some(message)) = (format, include_context, message) {
+                    return Cow::Borrowed(message);
+                }
+                let format_with = |args: &fmt::Arguments| match format {
+                    LogFormat::Glog => {
+                        let options = self.format_options();
+                        let formatter = GlogFormatter::new(&self.flags()).with_options(options);
+                        let formatter = if include_context {
+                            formatter
+                        } else {
+                            formatter.without_context()
+                        };
+                        formatter.format_message(record, args, self.now())
+                    }
+                    LogFormat::Json => {
+                        let level = self
+                            .custom_level_names
+                            .get(&record.level())
+                            .map_or(record.level().as_str(), String::as_str);
+                        format::format_json(
+                            record,
+                            level,
+                            self.now(),
+                            &self.record_to_file_name(record),
+                            args,
+                            include_context,
+                        )
+                    }
+                };
+                Cow::Owned(match redacted {
+                    Some(redacted) => format_with(&format_args!("{}", redacted)),
+                    None => format_with(record.args()),
+                })
+            },
+            &self.sink_error_handler,
+        );
+        self.stats.record_sink_errors(errors);
+    }
+
+    /// Write the lines of a batch as one contiguous block, see [`GlogHandle::log_batch`]
+    fn write_batch(&self, record: &Record, f: impl FnOnce(&mut BatchWriter<'_>)) {
+        let policy = self.target_policy.read().unwrap().clone();
+        let matched = policy.lookup(record.target());
+        if !self.enabled_by(record.metadata(), matched.as_ref()) {
+            return;
+        }
+        if self
+            .suppressions
+            .suppress(|| call_site_file_name(record), record.line().unwrap_or(0))
+        {
+            self.stats.record_suppressed();
+            return;
+        }
+        // The block is sampled and rate limited as a whole
+        if let Some(Err(rejection)) = matched
+            .as_ref()
+            .map(|matched| matched.admit(|| self.wall_clock().timestamp_micros()))
+        {
+            self.stats.record_rejection(rejection);
+            return;
+        }
+        let (plan, sanitize, minloglevel, log_target, redact_stderr, tail_buffer_size) = {
+            let flags = self.flags();
+            (
+                self.output_plan(&flags, record),
+                flags.sanitize_messages,
+                flags.minloglevel,
+                flags.log_target,
+                flags.redact_stderr,
+                flags.tail_buffer_size,
+            )
+        };
+        let minloglevel = self.min_level(matched.and_then(|matched| matched.level()).unwrap_or(minloglevel));
+        // Only enabled for sinks with a more verbose `min_level`
+        let sinks_only = record.level() > minloglevel;
+        let file_name = self.record_to_file_name(record);
+        let mut batch = BatchWriter {
+            options: self.format_options(),
+            timestamp: self.now(),
+            level: record.level(),
+            tid: get_tid(),
+            file: &file_name,
+            line: record.line().unwrap_or(0),
+            sanitize,
+            target: log_target.then(|| record.target()),
+            redactor: self.redactor.as_ref(),
+            lines: Vec::new(),
+            stderr_lines: (self.redactor.is_some() && plan.stderr && !redact_stderr).then(Vec::new),
+            messages: if self.sinks.is_empty() { None } else { Some(Vec::new()) },
+        };
+        f(&mut batch);
+        if batch.lines.is_empty() {
+            return;
+        }
+
+        if !sinks_only {
+            plan.dispatch(
+                || {
+                    // Keeps other threads from writing to stderr in the middle of the block
+                    let _stderr = io::stderr().lock();
+                    for line in batch.stderr_lines.as_ref().unwrap_or(&batch.lines) {
+                        self.write_stderr(record, line, batch.timestamp);
+                    }
+                },
+                || self.write_file(record, &batch.lines.join("\n")),
+            );
+            if !plan.stderr && !self.drain && output::fatal_redirected_to_stderr(record.level()) && self.stderr_allows(record) {
+                Glog::write_stderr_raw(&format!("{}\n", batch.lines.join("\n")));
+            }
+        }
+        for (line, message) in batch.lines.iter().zip(batch.messages.iter().flatten()) {
+            self.write_sinks(
+                &Record::builder()
+                    .metadata(record.metadata().clone())
+                    .file(record.file())
+                    .line(record.line())
+                    .module_path(record.module_path())
+                    .args(format_args!("{}", message))
+                    .build(),
+                Some(line),
+                None,
+                minloglevel,
+                false,
+            );
+        }
+        if !sinks_only {
+            for line in &batch.lines {
+                self.tail.push(record.level(), line, tail_buffer_size);
+                self.stats.record_message(record.level(), line.len());
+            }
+        }
+        // Every line counts towards an error burst, after the block so it isn't interrupted
+        for _ in &batch.lines {
+            self.adapt(record);
+        }
+    }
+
+    /// Log a tagged record at every enabled level and check that it reached the files and sinks, see [`GlogHandle::self_test`]
+    fn self_test(&self) -> SelfTestReport {
+        let tag = self_test::unique_tag();
+        let (minloglevel, logtostderr, file_per_thread) = {
+            let flags = self.flags();
+            (flags.minloglevel, flags.logtostderr, flags.file_per_thread)
+        };
+        let minloglevel = self.min_level(minloglevel);
+        let levels: Vec<Level> = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace]
+            .iter()
+            .copied()
+            .filter(|level| *level <= minloglevel)
+            .collect();
+        let message = |level: Level| format!("{} {}", tag, level);
+        for &level in &levels {
+            self.log(
+                &Record::builder()
+                    .level(level)
+                    .target(redact::INTERNAL_TARGET)
+                    .args(format_args!("{}", message(level)))
+                    .build(),
+            );
+        }
+        self.flush();
+
+        let mut checks = Vec::new();
+        let mut file_levels: Vec<_> = if logtostderr {
+            Vec::new()
+        } else {
+            self.file_writer.keys().copied().collect()
+        };
+        file_levels.sort();
+        for file_level in file_levels {
+            let path = if file_per_thread {
+                self.thread_log_file_path(file_level)
+            } else {
+                self.lock_file(&self.file_writer[&file_level]).borrow().path.clone()
+            };
+            let error = match self.fs.read(Path::new(&path)) {
+                Ok(content) => levels
+                    .iter()
+                    .find(|level| {
+                        let expected = self.file_encoding.encode(&message(**level));
+                        self.match_level(level) <= file_level && !content.windows(expected.len()).any(|window| window == expected)
+                    })
+                    .map(|level| format!("the {} record is missing in {}", level, Path::new(&path).display())),
+                Err(why) => Some(format!("couldn't read {}: {}", Path::new(&path).display(), why)),
+            };
+            checks.push(SelfTestCheck {
+                component: format!("log file {}", file_level),
+                error,
+            });
+        }
+        for (index, sink) in self.sinks.list().into_iter().enumerate() {
+            let ping = format!("{} sink {}", tag, index);
+            checks.push(SelfTestCheck {
+                component: format!("sink {}", index),
+                error: self_test::ping_sink(sink, ping, self_test::SINK_TIMEOUT).err(),
+            });
+        }
+        SelfTestReport {
+            tag,
+            checks,
+            console_available: self.console_available,
+        }
+    }
+}
+
+impl Log for Glog {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let policy = self.target_policy.read().unwrap().clone();
+        self.enabled_by(metadata, policy.lookup(metadata.target()).as_ref())
+    }
+
+    fn log(&self, record: &Record) {
+        // Formatting calls Display impls of the caller, their panics must neither lose the event nor reach the caller
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(|| self.log_record(record))) {
+            self.stats.record_format_panic();
+            let reason = panic_message(&*payload);
+            // The substitute is formatted with the same prefix, context and hooks, which may panic as well
+            let substituted = panic::catch_unwind(AssertUnwindSafe(|| {
+                self.log_record(
+                    &Record::builder()
+                        .metadata(Metadata::builder().level(Level::Error).target(record.target()).build())
+                        .file(record.file())
+                        .line(record.line())
+                        .module_path(record.module_path())
+                        .args(format_args!("<message formatting panicked: {}>", reason))
+                        .build(),
+                )
+            }));
+            if substituted.is_err() {
+                Glog::write_stderr_raw(&format!(
+                    "glog: formatting a record and its substitute panicked, dropping it: {}\n",
+                    reason
+                ));
+            }
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr_writer.lock().flush().ok();
+        self.sinks.flush();
+
+        let failed: Vec<(Level, io::Error)> = self
+            .lock_log_files(Level::Trace, Level::Error)
+            .into_iter()
+            .filter_map(|(level, log_file)| log_file.borrow_mut().file.flush().err().map(|why| (level, why)))
+            .chain(self.thread_files.flush())
+            .collect();
+        for (level, why) in failed {
+            self.handle_write_error(level, why);
+        }
+        for (_, log_file) in &self.module_log_files {
+            let flushed = self.lock_file(log_file).file.flush();
+            if let Err(why) = flushed {
+                self.handle_write_error(Level::Info, why);
+            }
+        }
+        self.stats.record_tee_write_errors(self.tees.flush());
+        self.stats.record_flush();
+    }
+}
+
+/// The message of a caught panic
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown panic payload")
+}
+
+/// Whether `module` is the module `prefix` or one of its submodules
+pub(crate) fn matches_module_prefix(module: &str, prefix: &str) -> bool {
+    module
+        .strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// The default sink error handler, printing every distinct error of a sink to stderr once
+fn report_sink_errors_once() -> SinkErrorHandler {
+    let reported = Mutex::new(HashSet::new());
+    Arc::new(move |index, _message, why| {
+        let why = why.to_string();
+        if reported.lock().unwrap().insert((index, why.clone())) {
+            Glog::write_stderr_raw(&format!("glog: log sink {} failed: {}\n", index, why));
+        }
+    })
+}
+
+#[cfg(target_os = "macos")]
+fn get_tid() -> u64 {
+    nix::sys::pthread::pthread_self().try_into().unwrap()
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn get_tid() -> u64 {
+    nix::unistd::gettid().as_raw().try_into().unwrap()
+}
+
+#[cfg(target_os = "windows")]
 mod bindings {
     windows::include_bindings!();
 }
@@ -511,31 +3235,1714 @@ fn get_tid() -> u64 {
     win_tid.try_into().unwrap()
 }
 
-impl Clone for Glog {
-    fn clone(&self) -> Glog {
-        Glog {
-            stderr_writer: ThreadLocal::new(),
-            flags: self.flags.clone(),
-            application_fingerprint: self.application_fingerprint.clone(),
-            file_writer: self.file_writer.clone(),
-            level_integers: self.level_integers.clone(),
-            ..*self
+/// `wasm32` has a single thread
+#[cfg(target_arch = "wasm32")]
+fn get_tid() -> u64 {
+    0
+}
+
+/// Synthetic thread id for other targets without native thread ids
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "linux",
+    target_os = "android",
+    target_os = "windows",
+    target_arch = "wasm32"
+)))]
+fn get_tid() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT_TID: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static TID: u64 = NEXT_TID.fetch_add(1, Ordering::Relaxed);
+    }
+    TID.with(|tid| *tid)
+}
+
+impl Clone for Glog {
+    fn clone(&self) -> Glog {
+        Glog {
+            stderr_writer: self.stderr_writer.clone(),
+            flags: self.flags.clone(),
+            application_fingerprint: self.application_fingerprint.clone(),
+            file_writer: self.file_writer.clone(),
+            module_log_files: self.module_log_files.clone(),
+            stats: self.stats.clone(),
+            #[cfg(feature = "metrics")]
+            write_latency: self.write_latency.clone(),
+            tail: self.tail.clone(),
+            backtrace_filter: self.backtrace_filter.clone(),
+            backtrace_at: self.backtrace_at.clone(),
+            suppressions: self.suppressions.clone(),
+            markers: self.markers.clone(),
+            monotonic_guard: self.monotonic_guard.clone(),
+            identity: self.identity.clone(),
+            log_file_name_prefix: self.log_file_name_prefix.clone(),
+            console_writer: self.console_writer.clone(),
+            write_error_handler: self.write_error_handler.clone(),
+            custom_level_names: self.custom_level_names.clone(),
+            rotation_hook: self.rotation_hook.clone(),
+            sinks: self.sinks.clone(),
+            sink_error_handler: self.sink_error_handler.clone(),
+            template_renderer: self.template_renderer.clone(),
+            stderr_timestamp_format: self.stderr_timestamp_format.clone(),
+            extra_headers: self.extra_headers.clone(),
+            redactor: self.redactor.clone(),
+            stderr_filter: self.stderr_filter.clone(),
+            timestamp_fn: self.timestamp_fn.clone(),
+            machine_id: self.machine_id.clone(),
+            fs: self.fs.clone(),
+            write_watchdog: self.write_watchdog.clone(),
+            tees: self.tees.clone(),
+            schedule: self.schedule.clone(),
+            adaptive: self.adaptive.clone(),
+            target_policy: self.target_policy.clone(),
+            adaptive_buffer: self.adaptive_buffer.clone(),
+            ..*self
+        }
+    }
+}
+
+impl Default for Glog {
+    fn default() -> Self {
+        Glog::new()
+    }
+}
+
+/// Create a new Glog instance
+pub fn new() -> Glog {
+    Glog::new()
+}
+
+/// Log to stderr only, in color if it is a terminal
+///
+/// A shortcut for [`new`] and [`Glog::try_init`], which has all other settings.
+///
+/// # Example
+///
+/// ```
+/// use log::*;
+///
+/// glog::init_stderr().unwrap();
+/// info!("Started");
+/// assert!(glog::init_stderr().is_err());
+/// ```
+pub fn init_stderr() -> Result<GlogHandle, GlogError> {
+    new().try_init(Flags {
+        logtostderr: true,
+        colorlogtostderr: io::stderr().is_terminal(),
+        ..Default::default()
+    })
+}
+
+/// Log to files in `dir`, which is created if it doesn't exist, with the default flags
+///
+/// Errors are copied to stderr, as with [`Flags::default`]. A shortcut for [`new`] and
+/// [`Glog::try_init`], which has all other settings.
+///
+/// # Example
+///
+/// ```no_run
+/// use log::*;
+///
+/// glog::init_files("/var/log/server").unwrap();
+/// info!("Started");
+/// ```
+pub fn init_files(dir: impl AsRef<Path>) -> Result<GlogHandle, GlogError> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir).map_err(|why| GlogError::CreateLogFile(dir.to_owned(), why))?;
+    new().try_init(Flags {
+        log_dir: dir.join("").into_os_string(),
+        ..Default::default()
+    })
+}
+
+/// Set the flags from the `GLOG_<name>` environment variables and the command line
+///
+/// Arguments which aren't logging flags are left to the application, see [`FlagRegistry`] to
+/// parse them as well. Arguments which aren't valid UTF-8 are skipped instead of panicking like
+/// [`env::args`]. A shortcut for [`new`] and [`Glog::try_init`], which has all other settings.
+///
+/// # Example
+///
+/// ```no_run
+/// use log::*;
+///
+/// // GLOG_logtostderr=1 server --minloglevel=WARNING
+/// glog::init_from_env().unwrap();
+/// warn!("Started");
+/// ```
+pub fn init_from_env() -> Result<GlogHandle, GlogError> {
+    let mut registry = FlagRegistry::new().with_unknown_flags(UnknownFlags::PassThrough);
+    registry.parse_env()?;
+    registry.parse_args(env::args_os().skip(1).filter_map(|arg| arg.into_string().ok()))?;
+    new().try_init(registry.into_flags())
+}
+
+/// Flush all log output and terminate the process with `code`
+///
+/// [`std::process::exit`] doesn't run any destructors, so use this instead to make sure everything
+/// logged so far has been handed to the log files and stderr before the process ends.
+/// The logger registered by [`Glog::try_init`] is shut down like with [`GlogHandle::shutdown`],
+/// so envelope files end with their [`Footer`](envelope::Footer) and a capture of fd 2 is released.
+/// This is bounded by a timeout so a stuck writer can't prevent the process from exiting.
+///
+/// For exits glog doesn't see, like `process::exit` in a dependency or a crash of the machine, set
+/// [`fsync_on`](Flags::fsync_on) to `ERROR`: the records leading up to the exit are then on disk
+/// as soon as they are logged.
+///
+/// # Example
+///
+/// ```no_run
+/// use log::*;
+/// use glog::Flags;
+///
+/// glog::new().init(Flags::default()).unwrap();
+///
+/// error!("Unrecoverable error, exiting");
+/// glog::exit(1);
+/// ```
+pub fn exit(code: i32) -> ! {
+    // Without threads there is no way to bound the shutdown
+    #[cfg(target_arch = "wasm32")]
+    shutdown_registered();
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let (flushed_sender, flushed_receiver) = mpsc::channel();
+        thread::spawn(move || {
+            shutdown_registered();
+            let _ = flushed_sender.send(());
+        });
+        if flushed_receiver.recv_timeout(EXIT_FLUSH_TIMEOUT).is_err() {
+            Glog::write_stderr_raw("glog: timed out flushing the log output before exiting\n");
+        }
+    }
+    std::process::exit(code)
+}
+
+/// Shut down the logger registered by [`Glog::try_init`], or flush the one registered otherwise
+fn shutdown_registered() {
+    let registered = REGISTERED.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    match registered {
+        Some(handle) => handle.shutdown(),
+        None => {
+            log::logger().flush();
+            // The flush only reaches the mapped files of this thread
+            #[cfg(all(unix, feature = "mmap"))]
+            mmap::close_all();
+        }
+    }
+}
+
+/// The file name of the call site of `record` as matched by `log_backtrace_at` and `suppress_at`
+///
+/// Without the directories [`trim_source_prefix`](Flags::trim_source_prefix) keeps in the output,
+/// so the flags don't change meaning with it.
+fn call_site_file_name(record: &Record) -> String {
+    format::source_file_name(record.file().unwrap_or(""), None)
+}
+
+/// Log the pre-formatted `message` at `level` with the source location `file` and `line`
+///
+/// This is meant for bindings to other languages, which have a finished message instead of
+/// [`fmt::Arguments`]. The message is written as is, apart from [`sanitize_messages`](Flags::sanitize_messages).
+///
+/// # Example
+///
+/// ```
+/// use log::Level;
+/// use glog::Flags;
+///
+/// glog::new().init(Flags::default()).unwrap();
+///
+/// glog::log_str(Level::Warn, "worker.c", 42, "queue is full");
+/// ```
+pub fn log_str(level: Level, file: &str, line: u32, message: &str) {
+    if level <= log::max_level() {
+        log::logger().log(
+            &Record::builder()
+                .level(level)
+                .file(Some(file))
+                .line(Some(line))
+                .args(format_args!("{}", message))
+                .build(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicI64;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::log_fs::MemFs;
+
+    fn with_record<R>(f: impl FnOnce(&Record) -> R) -> R {
+        f(&Record::builder()
+            .level(Level::Warn)
+            .file(Some("src/main.rs"))
+            .line(Some(42))
+            .args(format_args!("a {}", "message"))
+            .build())
+    }
+
+    /// Drop the timestamp which differs between two calls
+    fn without_timestamp(line: &str) -> String {
+        let mut parts = line.splitn(3, ' ');
+        let (level_and_date, _time, rest) = (parts.next().unwrap(), parts.next(), parts.next().unwrap());
+        format!("{} {}", &level_and_date[..1], rest)
+    }
+
+    #[test]
+    fn fast_stderr_path_matches_general_path() {
+        let glog = Glog::new().reduced_log_levels(false);
+        let mut fast = Vec::new();
+        let len = with_record(|record| glog.write_stderr_fast(record, &mut fast)).unwrap();
+        let general = with_record(|record| glog.build_log_message(record));
+
+        let fast = String::from_utf8(fast).unwrap();
+        assert_eq!(len, general.len());
+        assert!(fast.ends_with('\n'));
+        assert_eq!(without_timestamp(&fast), without_timestamp(&format!("{}\n", general)));
+    }
+
+    #[test]
+    fn fast_file_path_writes_the_same_bytes_as_general_path() {
+        use std::{env, fs};
+
+        let long = "x".repeat(16 * 1024);
+        let messages = ["short", "multi\nline", long.as_str()];
+        for encoding in [FileEncoding::Utf8, FileEncoding::Utf16Le].iter() {
+            let files: Vec<Vec<u8>> = [false, true]
+                .iter()
+                .map(|fast| {
+                    let dir = env::temp_dir().join(format!("glog-fast-file-test-{}-{}", std::process::id(), fast));
+                    let mut glog = Glog::new()
+                        .with_identity(test_identity())
+                        .with_timestamp_fn(|| Local.with_ymd_and_hms(2021, 4, 1, 12, 34, 56).unwrap())
+                        .with_log_file_encoding(*encoding);
+                    {
+                        let mut flags = glog.flags.write().unwrap();
+                        flags.log_dir = dir.join("").into_os_string();
+                        flags.log_target = true;
+                        // The tail buffer needs the whole line, so it forces the general path
+                        flags.tail_buffer_size = if *fast { 0 } else { 1 };
+                    }
+                    fs::create_dir_all(&dir).unwrap();
+                    glog.create_log_files().unwrap();
+                    let write = |record: &Record| {
+                        if *fast {
+                            assert!(glog.write_file_fast(record).is_some());
+                        } else {
+                            glog.log_record(record);
+                        }
+                    };
+                    for message in messages.iter() {
+                        write(
+                            &Record::builder()
+                                .level(Level::Warn)
+                                .file(Some("src/main.rs"))
+                                .line(Some(42))
+                                .args(format_args!("{}", message))
+                                .build(),
+                        );
+                    }
+                    let content = fs::read(dir.join("server.host.alice.log.INFO.20210401-123456.42")).unwrap();
+                    fs::remove_dir_all(&dir).unwrap();
+                    content
+                })
+                .collect();
+            assert!(files[0].len() > 16 * 1024);
+            assert_eq!(files[0], files[1], "{:?}", encoding);
+        }
+    }
+
+    struct Collect(Arc<Mutex<Vec<(Level, String)>>>);
+
+    impl ConsoleWriter for Collect {
+        fn write_line(&self, level: Level, line: &str) {
+            self.0.lock().unwrap().push((level, line.to_owned()));
+        }
+    }
+
+    #[test]
+    fn console_writer_receives_stderr_lines() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let glog = Glog::new().with_console_writer(Collect(lines.clone()));
+        let message = with_record(|record| glog.build_log_message(record));
+        with_record(|record| glog.write_stderr(record, &message, glog.now()));
+
+        assert_eq!(*lines.lock().unwrap(), [(Level::Warn, message)]);
+    }
+
+    /// A console writer of a process without a console
+    struct Detached(Collect);
+
+    impl ConsoleWriter for Detached {
+        fn write_line(&self, level: Level, line: &str) {
+            self.0.write_line(level, line);
+        }
+
+        fn is_available(&self) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn console_fallback() {
+        for fallback in [
+            ConsoleFallback::Keep,
+            ConsoleFallback::Disable,
+            ConsoleFallback::RedirectToFile,
+        ] {
+            let lines = Arc::new(Mutex::new(Vec::new()));
+            let (glog, fs) = with_mem_fs();
+            let mut glog = glog.with_console_writer(Detached(Collect(lines.clone())));
+            glog.configure(Flags {
+                logtostderr: true,
+                colorlogtostderr: true,
+                console_fallback: fallback,
+                log_dir: OsString::from("/logs/"),
+                ..Default::default()
+            })
+            .unwrap();
+            with_record(|record| glog.log(record));
+            // Dropped, or left on stderr without the descriptor of a real console file
+            let raw_written = console::write_raw("");
+            // The fallback is process wide, other tests may write raw lines as well until it's reset
+            console::redirect_raw(RawTarget::Stderr);
+            assert_eq!(raw_written, fallback == ConsoleFallback::Disable, "{:?}", fallback);
+
+            assert!(!glog.flags().colorlogtostderr);
+            assert_eq!(glog.stats_snapshot().messages.warn, 1, "{:?}", fallback);
+            let lines = lines.lock().unwrap().clone();
+            let console_log = fs.contents(Path::new("/logs/server.console.log"));
+            assert!(!glog.self_test().console_available);
+            match fallback {
+                ConsoleFallback::Keep => assert!(lines[0].1.ends_with("] a message"), "{:?}", lines),
+                ConsoleFallback::Disable => assert!(lines.is_empty() && console_log.is_none()),
+                ConsoleFallback::RedirectToFile => {
+                    assert!(lines.is_empty());
+                    let console_log = console_log.unwrap();
+                    assert!(console_log.ends_with("] a message\n"), "{:?}", console_log);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn call_sites_are_matched_by_file_name_with_trim_source_prefix() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let mut glog = Glog::new().with_console_writer(Collect(lines.clone()));
+        glog.configure(Flags {
+            logtostderr: true,
+            trim_source_prefix: Some("/ci/project/".to_owned()),
+            log_backtrace_at: Some("main.rs:42".to_owned()),
+            suppress_at: vec!["main.rs:7".to_owned()],
+            ..Default::default()
+        })
+        .unwrap();
+        for line in [42, 7] {
+            glog.log(
+                &Record::builder()
+                    .level(Level::Warn)
+                    .file(Some("/ci/project/src/main.rs"))
+                    .line(Some(line))
+                    .args(format_args!("at {}", line))
+                    .build(),
+            );
         }
+
+        let lines = lines.lock().unwrap().clone();
+        assert_eq!(lines.len(), 2, "{:?}", lines);
+        assert!(lines[0].1.ends_with(" src/main.rs:42] at 42"), "{:?}", lines);
+        assert_eq!(glog.stats_snapshot().suppressed, 1);
     }
-}
 
-impl Default for Glog {
-    fn default() -> Self {
-        Glog::new()
+    /// Log the lines `0..lines` as one batch at `level`
+    fn log_batch(glog: &Glog, level: Level, lines: usize) {
+        glog.write_batch(
+            &Record::builder()
+                .level(level)
+                .file(Some("src/main.rs"))
+                .line(Some(42))
+                .args(format_args!(""))
+                .build(),
+            |batch| {
+                for i in 0..lines {
+                    batch.line(format_args!("line {}", i));
+                }
+            },
+        );
     }
-}
 
-/// Create a new Glog instance
-pub fn new() -> Glog {
-    Glog::new()
-}
+    #[test]
+    fn batches_reach_the_tail() {
+        let mut glog = Glog::new().with_console_writer(Collect(Arc::new(Mutex::new(Vec::new()))));
+        glog.configure(Flags {
+            logtostderr: true,
+            tail_buffer_size: 10,
+            ..Default::default()
+        })
+        .unwrap();
+        log_batch(&glog, Level::Warn, 3);
 
-#[cfg(test)]
-mod tests {
-    // todo(#6): Fill with tests
+        let tail = glog.tail(Level::Info, 10);
+        assert_eq!(tail.len(), 3, "{:?}", tail);
+        assert!(tail[2].ends_with("main.rs:42] line 2"), "{:?}", tail);
+        assert_eq!(glog.tail(Level::Warn, 10), tail);
+    }
+
+    #[test]
+    fn batches_have_a_target() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let mut glog = Glog::new().with_console_writer(Collect(lines.clone()));
+        glog.configure(Flags {
+            logtostderr: true,
+            log_target: true,
+            target_policy: "noisy=warn".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+        let handle = GlogHandle::new(glog.clone());
+        handle.log_batch(Level::Info, "noisy", |batch| batch.line(format_args!("dropped")));
+        handle.log_batch(Level::Info, "app::db", |batch| batch.line(format_args!("connected")));
+
+        let lines = lines.lock().unwrap().clone();
+        assert_eq!(lines.len(), 1, "{:?}", lines);
+        assert!(lines[0].1.ends_with("] (target: app::db) connected"), "{:?}", lines);
+    }
+
+    #[test]
+    fn batched_errors_escalate_the_minimum_level() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let mut glog = Glog::new().with_console_writer(Collect(lines.clone()));
+        glog.configure(Flags {
+            logtostderr: true,
+            adaptive_error_threshold: Some(2),
+            ..Default::default()
+        })
+        .unwrap();
+        log_batch(&glog, Level::Error, 3);
+
+        let lines = lines.lock().unwrap().clone();
+        assert_eq!(lines.len(), 4, "{:?}", lines);
+        assert!(lines[3].1.contains("] glog: 3 WARNING and ERROR records within"), "{:?}", lines);
+        assert_eq!(glog.min_level(Level::Info), Level::Debug);
+    }
+
+    #[test]
+    fn stderr_verbose_level() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let glog = Glog::new().with_console_writer(Collect(lines.clone()));
+        glog.flags.write().unwrap().stderr_verbose_level = true;
+        let message = with_record(|record| glog.build_log_message(record));
+        with_record(|record| glog.write_stderr(record, &message, glog.now()));
+
+        assert_eq!(*lines.lock().unwrap(), [(Level::Warn, format!("[WARN] {}", message))]);
+    }
+
+    #[test]
+    fn timestamp_fn() {
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog.with_timestamp_fn(|| Local.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap());
+        glog.create_log_files().unwrap();
+        glog.log(&Record::builder().level(Level::Info).args(format_args!("fake clock")).build());
+
+        let info_log = fs
+            .files()
+            .into_iter()
+            .find(|path| path.to_str().unwrap().contains(".INFO."))
+            .unwrap();
+        let content = fs.contents(&info_log).unwrap();
+        assert!(
+            content.starts_with("Log file created at:\n2020/01/02 03:04:05\n"),
+            "{}",
+            content
+        );
+        assert!(content.contains("\nI0102 03:04:05.000000 "), "{}", content);
+    }
+
+    #[test]
+    fn stderr_output_filter() {
+        let stderr = Arc::new(Mutex::new(Vec::new()));
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog
+            .with_console_writer(Collect(stderr.clone()))
+            .with_stderr_output_filter(|record| !record.args().to_string().contains("heartbeat"));
+        glog.flags.write().unwrap().alsologtostderr = true;
+        glog.create_log_files().unwrap();
+
+        for message in ["heartbeat", "disk full"].iter() {
+            glog.log(&Record::builder().level(Level::Info).args(format_args!("{}", message)).build());
+        }
+
+        let stderr = stderr.lock().unwrap();
+        assert_eq!(stderr.len(), 1);
+        assert!(stderr[0].1.ends_with("] disk full"));
+        let info_log = fs
+            .files()
+            .into_iter()
+            .find(|path| path.to_str().unwrap().contains(".INFO."))
+            .unwrap();
+        let content = fs.contents(&info_log).unwrap();
+        assert!(content.contains("] heartbeat\n"));
+        assert!(content.ends_with("] disk full\n"));
+    }
+
+    #[test]
+    fn write_error_handler() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let mut glog = {
+            let errors = errors.clone();
+            Glog::new().with_write_error_handler(move |level, why| errors.lock().unwrap().push((level, why.kind())))
+        };
+        let fs = MemFs::default();
+        let file = fs.create_exclusive(Path::new("/logs/INFO")).unwrap();
+        glog.file_writer.insert(
+            Level::Info,
+            Arc::new(Mutex::new(RefCell::new(LogFile::new(file, OsString::from("/logs/INFO"))))),
+        );
+        fs.fail_nth_write(1);
+
+        let record = Record::builder().level(Level::Info).args(format_args!("lost")).build();
+        glog.write_file(&record, &glog.build_log_message(&record));
+
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, Level::Info);
+    }
+
+    /// A process started at 11:00:00 creating its log files at 12:34:56
+    fn test_identity() -> Identity {
+        Identity {
+            program: OsString::from("server"),
+            hostname: OsString::from("host"),
+            username: "alice".to_owned(),
+            pid: 42,
+            start_time: Local.with_ymd_and_hms(2021, 4, 1, 11, 0, 0).unwrap(),
+            fixed_time: Some(Local.with_ymd_and_hms(2021, 4, 1, 12, 34, 56).unwrap()),
+        }
+    }
+
+    /// A clock at the fixed time of the [`test_identity`] plus the seconds in the returned counter
+    fn fake_clock() -> (Arc<AtomicI64>, impl Fn() -> DateTime<Local> + Send + Sync + 'static) {
+        let seconds = Arc::new(AtomicI64::new(0));
+        let start = test_identity().fixed_time.unwrap();
+        (seconds.clone(), move || {
+            start + chrono::Duration::seconds(seconds.load(Ordering::Relaxed))
+        })
+    }
+
+    /// A logger with the [`test_identity`] creating its log files in `/logs/` of a fresh [`MemFs`]
+    fn with_mem_fs() -> (Glog, MemFs) {
+        let fs = MemFs::default();
+        let glog = Glog::new().with_fs(fs.clone()).with_identity(test_identity());
+        glog.flags.write().unwrap().log_dir = OsString::from("/logs/");
+        (glog, fs)
+    }
+
+    #[test]
+    fn creates_a_log_file_with_header_per_level() {
+        for (compatible, levels, letters) in [
+            (true, &["ERROR", "INFO", "WARN"][..], "IWE"),
+            (false, &["DEBUG", "ERROR", "INFO", "TRACE", "WARN"][..], "TDIWE"),
+        ]
+        .iter()
+        {
+            for (with_year, date) in [(false, "mmdd"), (true, "yyyymmdd")].iter() {
+                let (glog, fs) = with_mem_fs();
+                let mut glog = glog
+                    .reduced_log_levels(*compatible)
+                    .with_year(*with_year)
+                    .with_machine_id("0123abcd")
+                    .with_log_file_group(100);
+                glog.create_log_files().unwrap();
+
+                let expected: Vec<_> = levels
+                    .iter()
+                    .map(|level| PathBuf::from(format!("/logs/server.host.alice.log.{}.20210401-123456.42", level)))
+                    .collect();
+                assert_eq!(fs.files(), expected);
+                for (level, path) in levels.iter().zip(&expected) {
+                    assert_eq!(fs.group(path), Some(100));
+                    assert_eq!(
+                        fs.contents(path).unwrap(),
+                        format!(
+                            "Log file created at:\n\
+                             2021/04/01 12:34:56\n\
+                             Running on machine: host\n\
+                             Machine ID: 0123abcd\n\
+                             Running duration (h:mm:ss): 1:34:56\n\
+                             Log line format: [{}]{} hh:mm:ss.uuuuuu threadid file:line] msg\n",
+                            letters, date
+                        )
+                    );
+                    #[cfg(unix)]
+                    assert_eq!(
+                        fs.symlink_target(&Path::new("/logs").join(format!("server.{}", level)))
+                            .as_ref(),
+                        Some(path)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn log_files_are_renamed_without_hard_links() {
+        let (mut glog, fs) = with_mem_fs();
+        fs.disable_hard_links();
+        glog.create_log_files().unwrap();
+        with_record(|record| glog.log(record));
+
+        assert_eq!(
+            fs.files(),
+            ["ERROR", "INFO", "WARN"]
+                .iter()
+                .map(|level| PathBuf::from(format!("/logs/server.host.alice.log.{}.20210401-123456.42", level)))
+                .collect::<Vec<_>>()
+        );
+        let content = fs
+            .contents(Path::new("/logs/server.host.alice.log.WARN.20210401-123456.42"))
+            .unwrap();
+        assert!(content.starts_with("Log file created at:\n"), "{}", content);
+        assert!(content.ends_with("] a message\n"), "{}", content);
+    }
+
+    #[test]
+    fn running_duration_of_a_clock_before_the_start() {
+        let (seconds, clock) = fake_clock();
+        seconds.store(-2 * 60 * 60, Ordering::Relaxed);
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog.with_timestamp_fn(clock);
+        glog.create_log_files().unwrap();
+
+        let header = fs
+            .contents(Path::new("/logs/server.host.alice.log.INFO.20210401-123456.42"))
+            .unwrap();
+        assert!(header.contains("\nRunning duration (h:mm:ss): 0:00:00\n"), "{}", header);
+    }
+
+    #[test]
+    fn crate_version_in_header() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.flags.write().unwrap().log_crate_version = true;
+        glog.create_log_files().unwrap();
+
+        let header = fs
+            .contents(Path::new("/logs/server.host.alice.log.INFO.20210401-123456.42"))
+            .unwrap();
+        assert!(
+            header.contains(&format!("\nglog-rs version: {}\nRunning duration", env!("CARGO_PKG_VERSION"))),
+            "{}",
+            header
+        );
+    }
+
+    #[cfg(all(unix, feature = "mmap"))]
+    #[test]
+    fn mapped_log_files_are_cut_to_their_contents_on_flush() {
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog.with_mmap_writer(ByteSize::kib(1));
+        glog.create_log_files().unwrap();
+        let path = Path::new("/logs/server.host.alice.log.INFO.20210401-123456.42");
+
+        let record = Record::builder().level(Level::Info).args(format_args!("")).build();
+        for line in 0..100 {
+            glog.write_file(&record, &format!("line {}", line));
+        }
+        let mapped = fs.contents(path).unwrap();
+        assert_eq!(mapped.len() % 1024, 0);
+        assert!(mapped.ends_with('\0'));
+
+        glog.flush();
+        let content = fs.contents(path).unwrap();
+        assert!(!content.contains('\0'));
+        assert_eq!(content.matches("\nline ").count(), 100);
+        assert!(content.ends_with("\nline 98\nline 99\n"), "{}", content);
+    }
+
+    #[test]
+    fn clone_with_flags_has_its_own_log_files() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.create_log_files().unwrap();
+        let variant = glog
+            .clone_with_flags(Flags {
+                log_dir: OsString::from("/other/"),
+                minloglevel: Level::Warn,
+                ..Default::default()
+            })
+            .unwrap();
+
+        for level in [Level::Info, Level::Warn].iter() {
+            variant.log(&Record::builder().level(*level).args(format_args!("{} record", level)).build());
+        }
+        let content = |path: &str| fs.contents(Path::new(path)).unwrap();
+        let other_warn = content("/other/server.host.alice.log.WARN.20210401-123456.42");
+        assert!(other_warn.ends_with("] WARN record\n"), "{}", other_warn);
+        assert!(!content("/other/server.host.alice.log.INFO.20210401-123456.42").contains("record"));
+        assert!(!content("/logs/server.host.alice.log.WARN.20210401-123456.42").contains("record"));
+        assert_eq!(glog.flags().minloglevel, Level::Info);
+    }
+
+    #[test]
+    fn recreated_log_files_replace_the_open_ones() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.create_log_files().unwrap();
+        with_record(|record| glog.log(record));
+        let replaced = Arc::downgrade(&glog.file_writer[&Level::Warn]);
+
+        glog.flags.write().unwrap().log_dir = OsString::from("/other/");
+        glog.create_log_files().unwrap();
+        with_record(|record| glog.log(record));
+
+        assert!(replaced.upgrade().is_none());
+        for dir in ["/logs", "/other"] {
+            let content = fs
+                .contents(&Path::new(dir).join("server.host.alice.log.WARN.20210401-123456.42"))
+                .unwrap();
+            assert_eq!(content.matches("] a message\n").count(), 1, "{}", content);
+        }
+    }
+
+    #[test]
+    fn fsync_on_syncs_the_files_of_severe_records() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.flags.write().unwrap().fsync_on = Some(Level::Error);
+        glog.create_log_files().unwrap();
+        let syncs = fs.syncs();
+
+        glog.log(&Record::builder().level(Level::Warn).args(format_args!("not synced")).build());
+        assert_eq!(fs.syncs(), syncs);
+        glog.log(&Record::builder().level(Level::Error).args(format_args!("synced")).build());
+        // The ERROR, WARNING and INFO files
+        assert_eq!(fs.syncs(), syncs + 3);
+    }
+
+    #[test]
+    fn crash_recovery_takes_over_poisoned_log_files() {
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog.with_crash_recovery(true);
+        glog.create_log_files().unwrap();
+        let info_log = glog.file_writer[&Level::Info].clone();
+        thread::spawn(move || {
+            let _locked = info_log.lock().unwrap();
+            panic!("while writing");
+        })
+        .join()
+        .unwrap_err();
+        assert!(glog.file_writer[&Level::Info].is_poisoned());
+
+        let record = Record::builder().level(Level::Info).args(format_args!("")).build();
+        glog.write_file(&record, "after the panic");
+        let content = fs
+            .contents(Path::new("/logs/server.host.alice.log.INFO.20210401-123456.42"))
+            .unwrap();
+        assert!(content.ends_with("\nafter the panic\n"), "{}", content);
+    }
+
+    #[test]
+    fn records_are_written_to_their_file_and_less_severe_ones() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.create_log_files().unwrap();
+
+        let record = Record::builder().level(Level::Warn).args(format_args!("a warning")).build();
+        let message = glog.build_log_message(&record);
+        glog.write_file(&record, &message);
+        let written: Vec<_> = fs
+            .files()
+            .iter()
+            .map(|path| fs.contents(path).unwrap().ends_with(&format!("{}\n", message)))
+            .collect();
+        assert_eq!(written, [false, true, true]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_point_to_the_latest_log_files() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.create_log_files().unwrap();
+
+        for level in ["INFO", "WARN", "ERROR"].iter() {
+            let target = fs
+                .symlink_target(&Path::new("/logs").join(format!("server.{}", level)))
+                .unwrap();
+            assert_eq!(
+                target,
+                Path::new(&format!("/logs/server.host.alice.log.{}.20210401-123456.42", level))
+            );
+            assert!(fs.contents(&target).is_some());
+        }
+    }
+
+    #[test]
+    fn crashes_never_leave_partial_log_files() {
+        for crash_after_steps in 0.. {
+            let (mut glog, fs) = with_mem_fs();
+            fs.crash_after_steps(crash_after_steps);
+            let created = glog.create_log_files().is_ok();
+
+            let complete = |path: &Path| fs.contents(path).is_some_and(|content| content.ends_with("msg\n"));
+            for path in fs.files() {
+                let in_progress = path.to_str().unwrap().ends_with(IN_PROGRESS_SUFFIX);
+                assert!(
+                    in_progress || complete(&path),
+                    "{:?} is partial after {} steps",
+                    path,
+                    crash_after_steps
+                );
+                assert!(!(created && in_progress), "{:?} is left behind", path);
+            }
+            for level in ["INFO", "WARN", "ERROR"].iter() {
+                if let Some(target) = fs.symlink_target(&Path::new("/logs").join(format!("server.{}", level))) {
+                    assert!(complete(&target), "{:?} is partial after {} steps", target, crash_after_steps);
+                }
+            }
+            if created {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn stale_in_progress_files_are_removed() {
+        let (mut glog, fs) = with_mem_fs();
+        let stale = Path::new("/logs/server.host.alice.log.INFO.20210401-000000.41.inprogress");
+        let fresh = Path::new("/logs/server.host.alice.log.INFO.20210401-123000.43.inprogress");
+        let other_program = Path::new("/logs/client.host.alice.log.INFO.20210401-000000.41.inprogress");
+        for path in [stale, fresh, other_program].iter() {
+            fs.create_exclusive(path)
+                .unwrap()
+                .write_all(b"Log file created at:\n")
+                .unwrap();
+        }
+        let an_hour_ago = std::time::SystemTime::now() - Duration::from_secs(60 * 60);
+        fs.set_modified(stale, an_hour_ago);
+        fs.set_modified(other_program, an_hour_ago);
+        glog.create_log_files().unwrap();
+
+        assert!(fs.contents(stale).is_none());
+        assert!(fs.contents(fresh).is_some());
+        assert!(fs.contents(other_program).is_some());
+    }
+
+    #[test]
+    fn file_per_thread() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.flags.write().unwrap().file_per_thread = true;
+        glog.create_log_files().unwrap();
+
+        let messages = ["first", "second"];
+        let threads: Vec<_> = messages
+            .iter()
+            .map(|&message| {
+                let glog = glog.clone();
+                thread::spawn(move || {
+                    glog.log(&Record::builder().level(Level::Warn).args(format_args!("{}", message)).build());
+                    glog.flush();
+                    get_tid()
+                })
+            })
+            .collect();
+        let tids: Vec<u64> = threads.into_iter().map(|thread| thread.join().unwrap()).collect();
+
+        let shared = fs
+            .contents(Path::new("/logs/server.host.alice.log.WARN.20210401-123456.42"))
+            .unwrap();
+        assert!(shared.ends_with("msg\n"), "{}", shared);
+        for (tid, message) in tids.iter().zip(messages.iter()) {
+            let thread_file = |level: &str| {
+                fs.contents(Path::new(&format!(
+                    "/logs/server.host.alice.log.{}.20210401-123456.42.{}",
+                    level, tid
+                )))
+            };
+            for level in ["INFO", "WARN"].iter() {
+                let content = thread_file(level).unwrap();
+                assert!(content.starts_with("Log file created at:\n"), "{}", content);
+                assert!(content.ends_with(&format!("] {}\n", message)), "{}", content);
+            }
+            assert!(thread_file("ERROR").is_none());
+        }
+    }
+
+    #[test]
+    fn failed_writes_dont_stop_the_cascade() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let (glog, fs) = with_mem_fs();
+        let mut glog = {
+            let errors = errors.clone();
+            glog.with_write_error_handler(move |level, why| errors.lock().unwrap().push((level, why.to_string())))
+        };
+        glog.create_log_files().unwrap();
+
+        // The error file is written first, the less severe files still get the record
+        fs.fail_nth_write(1);
+        let record = Record::builder().level(Level::Error).args(format_args!("an error")).build();
+        glog.write_file(&record, &glog.build_log_message(&record));
+        glog.flush();
+
+        assert_eq!(*errors.lock().unwrap(), [(Level::Error, "injected write failure".to_owned())]);
+        let written: Vec<_> = fs
+            .files()
+            .iter()
+            .map(|path| fs.contents(path).unwrap().ends_with("an error\n"))
+            .collect();
+        assert_eq!(written, [false, true, true]);
+    }
+
+    #[test]
+    fn preallocation_needs_enough_free_space() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.flags.write().unwrap().preallocate_log_bytes = Some(ByteSize::mib(1));
+        fs.set_free_space(1 << 19);
+        glog.create_log_files().unwrap();
+        assert!(fs.files().iter().all(|path| fs.preallocated(path) == Some(0)));
+
+        let (mut glog, fs) = with_mem_fs();
+        glog.flags.write().unwrap().preallocate_log_bytes = Some(ByteSize::mib(1));
+        glog.create_log_files().unwrap();
+        assert!(fs.files().iter().all(|path| fs.preallocated(path) == Some(1 << 20)));
+    }
+
+    #[test]
+    fn log_file_creation_errors_are_returned() {
+        let (mut glog, fs) = with_mem_fs();
+        fs.fail_nth_write(1);
+        let (path, why) = glog.create_log_files().unwrap_err();
+        assert!(path.to_str().unwrap().contains(".log.INFO."));
+        assert_eq!(why.to_string(), "injected write failure");
+    }
+
+    #[test]
+    fn headers_count_the_messages_before_the_file() {
+        let (seconds, clock) = fake_clock();
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog.with_timestamp_fn(clock);
+        {
+            let mut flags = glog.flags.write().unwrap();
+            flags.header_include_stats = true;
+            flags.detect_deleted_files = true;
+        }
+        glog.create_log_files().unwrap();
+        let path = Path::new("/logs/server.host.alice.log.INFO.20210401-123456.42");
+        let log = |level, message: &str| glog.log(&Record::builder().level(level).args(format_args!("{}", message)).build());
+        let stats_line = |content: String| {
+            content
+                .lines()
+                .find(|line| line.starts_with("Previous segments"))
+                .unwrap()
+                .to_owned()
+        };
+        assert_eq!(
+            stats_line(fs.contents(path).unwrap()),
+            "Previous segments: 0, cumulative: 0 INFO / 0 WARNING / 0 ERROR"
+        );
+
+        for (level, message) in [
+            (Level::Info, "one"),
+            (Level::Debug, "hidden"),
+            (Level::Info, "two"),
+            (Level::Warn, "three"),
+        ] {
+            log(level, message);
+        }
+        fs.remove(path).unwrap();
+        seconds.fetch_add(2, Ordering::Relaxed);
+        log(Level::Error, "rotates");
+        let content = fs.contents(path).unwrap();
+        assert_eq!(
+            stats_line(content.clone()),
+            "Previous segments: 1, cumulative: 2 INFO / 1 WARNING / 0 ERROR"
+        );
+        assert!(content.ends_with("] rotates\n"), "{}", content);
+
+        fs.remove(path).unwrap();
+        seconds.fetch_add(2, Ordering::Relaxed);
+        log(Level::Info, "rotates again");
+        assert_eq!(
+            stats_line(fs.contents(path).unwrap()),
+            "Previous segments: 2, cumulative: 2 INFO / 1 WARNING / 1 ERROR"
+        );
+    }
+
+    #[test]
+    fn rotation_hook_gets_recreated_files() {
+        let rotated = Arc::new(Mutex::new(Vec::new()));
+        let (seconds, clock) = fake_clock();
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog.with_timestamp_fn(clock).with_log_rotation_hook({
+            let rotated = rotated.clone();
+            move |level, path| rotated.lock().unwrap().push((level, path.to_owned()))
+        });
+        glog.flags.write().unwrap().detect_deleted_files = true;
+        glog.create_log_files().unwrap();
+        let path = Path::new("/logs/server.host.alice.log.WARN.20210401-123456.42");
+
+        fs.remove(path).unwrap();
+        seconds.fetch_add(2, Ordering::Relaxed);
+        glog.log(&Record::builder().level(Level::Warn).args(format_args!("rotated")).build());
+        assert_eq!(*rotated.lock().unwrap(), [(Level::Warn, path.to_owned())]);
+        assert!(fs.contents(path).unwrap().ends_with("] rotated\n"));
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct BrokenPipe;
+
+    impl Write for BrokenPipe {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+    }
+
+    #[test]
+    fn tees_receive_the_bytes_of_all_log_files_in_write_order() {
+        let (first, second) = (SharedBuffer::default(), SharedBuffer::default());
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog
+            .tee(Box::new(first.clone()))
+            .tee(Box::new(BrokenPipe))
+            .tee(Box::new(second.clone()));
+        glog.create_log_files().unwrap();
+
+        let record = Record::builder().level(Level::Error).args(format_args!("an error")).build();
+        let line = format!("{}\n", glog.build_log_message(&record));
+        glog.write_file(&record, line.trim_end());
+        glog.flush();
+
+        // Headers in creation order, then the record from the most to the least severe file
+        let contents = |level: &str| {
+            let path = fs
+                .files()
+                .into_iter()
+                .find(|path| path.to_str().unwrap().contains(level))
+                .unwrap();
+            fs.contents(&path).unwrap()
+        };
+        let mut expected = String::new();
+        for level in [".INFO.", ".WARN.", ".ERROR."].iter() {
+            expected.push_str(contents(level).strip_suffix(&line).unwrap());
+        }
+        expected.push_str(&line.repeat(3));
+        assert_eq!(String::from_utf8(first.0.lock().unwrap().clone()).unwrap(), expected);
+        assert_eq!(*first.0.lock().unwrap(), *second.0.lock().unwrap());
+        assert!(glog.stats_snapshot().tee_write_errors > 3);
+    }
+
+    #[test]
+    fn module_log_files() {
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog
+            .with_module_log_file("app", PathBuf::from("/logs/app.log"))
+            .with_module_log_file("app::db", PathBuf::from("/logs/db.log"));
+        fs.create_exclusive(Path::new("/logs/app.log"))
+            .unwrap()
+            .write_all(b"older content\n")
+            .unwrap();
+        glog.create_log_files().unwrap();
+
+        for (module, message) in [
+            ("app::db::pool", "pool"),
+            ("app::dbx", "dbx"),
+            ("app", "app"),
+            ("other", "other"),
+        ]
+        .iter()
+        {
+            let record = Record::builder().level(Level::Warn).module_path(Some(module)).build();
+            glog.write_file(&record, message);
+        }
+        let warn_log = fs
+            .files()
+            .into_iter()
+            .find(|path| path.to_str().unwrap().contains(".WARN."))
+            .unwrap();
+        assert_eq!(fs.contents(Path::new("/logs/app.log")).unwrap(), "older content\ndbx\napp\n");
+        assert!(fs.contents(Path::new("/logs/db.log")).unwrap().ends_with("msg\npool\n"));
+        assert!(fs.contents(&warn_log).unwrap().ends_with("msg\nother\n"));
+
+        glog.flags.write().unwrap().module_logs_to_level_files = true;
+        let record = Record::builder()
+            .level(Level::Warn)
+            .module_path(Some("app::db"))
+            .args(format_args!("both"))
+            .build();
+        glog.write_file(&record, "both");
+        assert!(fs.contents(Path::new("/logs/db.log")).unwrap().ends_with("pool\nboth\n"));
+        assert!(fs.contents(&warn_log).unwrap().ends_with("other\nboth\n"));
+    }
+
+    #[test]
+    fn files_without_locks_are_written_unlocked() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.flags.write().unwrap().lock_shared_files = true;
+        glog.create_log_files().unwrap();
+
+        with_record(|record| glog.log(record));
+        let content = fs
+            .contents(Path::new("/logs/server.host.alice.log.WARN.20210401-123456.42"))
+            .unwrap();
+        assert!(content.ends_with("] a message\n"), "{}", content);
+        assert_eq!(glog.stats_snapshot().lock_timeouts, 0);
+    }
+
+    #[test]
+    fn locked_writes_to_shared_files_dont_interleave() {
+        use std::{env, fs};
+
+        let dir = env::temp_dir().join(format!("glog-shared-files-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("shared.log");
+        let start = Arc::new(std::sync::Barrier::new(2));
+        let writers: Vec<_> = ["a", "b"]
+            .iter()
+            .map(|letter| {
+                let glog = Glog::new().with_module_log_file("shared", path.clone());
+                glog.flags.write().unwrap().lock_shared_files = true;
+                glog.open_module_log_files().unwrap();
+                let line = letter.repeat(8 * 1024);
+                let start = start.clone();
+                thread::spawn(move || {
+                    start.wait();
+                    let record = Record::builder().level(Level::Info).module_path(Some("shared")).build();
+                    for _ in 0..500 {
+                        glog.write_file(&record, &line);
+                    }
+                    glog.stats_snapshot().lock_timeouts
+                })
+            })
+            .collect();
+        for writer in writers {
+            assert_eq!(writer.join().unwrap(), 0);
+        }
+        let content = fs::read_to_string(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let lines: Vec<_> = content
+            .lines()
+            .filter(|line| !line.contains(' ') && !line.is_empty())
+            .collect();
+        assert_eq!(lines.len(), 1000);
+        for line in lines {
+            assert!(line == "a".repeat(8 * 1024) || line == "b".repeat(8 * 1024), "torn line");
+        }
+    }
+
+    #[test]
+    fn cascaded_writes_keep_less_severe_files_behind() {
+        let mut glog = Glog::new();
+        let fs = MemFs::default();
+        for level in [Level::Info, Level::Warn, Level::Error].iter() {
+            let path = format!("/logs/{}", level);
+            let file = fs.create_exclusive(Path::new(&path)).unwrap();
+            glog.file_writer.insert(
+                *level,
+                Arc::new(Mutex::new(RefCell::new(LogFile::new(file, OsString::from(path))))),
+            );
+        }
+
+        // Global order of (file level, record) writes, recorded instead of writing to the files
+        let writes = Arc::new(Mutex::new(Vec::new()));
+        let threads: Vec<_> = (0..4)
+            .map(|thread| {
+                let (glog, writes) = (glog.clone(), writes.clone());
+                thread::spawn(move || {
+                    for i in 0..200 {
+                        let level = [Level::Info, Level::Warn, Level::Error, Level::Debug][i % 4];
+                        glog.write_cascaded(Level::Trace, level, |file_level, _| {
+                            writes.lock().unwrap().push((file_level, (thread, i)));
+                            Ok(())
+                        });
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let writes = writes.lock().unwrap();
+        let file = |level| {
+            writes
+                .iter()
+                .enumerate()
+                .filter(move |(_, (file_level, _))| *file_level == level)
+        };
+        for (more_severe, less_severe) in [
+            (Level::Error, Level::Warn),
+            (Level::Warn, Level::Info),
+            (Level::Error, Level::Info),
+        ]
+        .iter()
+        {
+            let records: Vec<_> = file(*more_severe).collect();
+            let copies: Vec<_> = file(*less_severe)
+                .filter(|(_, (_, record))| records.iter().any(|(_, (_, copied))| copied == record))
+                .collect();
+            assert_eq!(records.len(), copies.len());
+            for (i, ((written, (_, record)), (copied, (_, copy)))) in records.iter().zip(&copies).enumerate() {
+                // Same order in both files, the copy is written after the original and before the next record
+                assert_eq!(record, copy);
+                assert!(written < copied);
+                if let Some((next, _)) = records.get(i + 1) {
+                    assert!(copied < next);
+                }
+            }
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct CollectSink(Arc<Mutex<Vec<String>>>);
+
+    impl LogSink for CollectSink {
+        fn write(&self, _record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+            self.0.lock().unwrap().push(message.to_owned());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn custom_level_names() {
+        let json = CollectSink::default();
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog
+            .reduced_log_levels(false)
+            .with_custom_level_name(Level::Error, "CRITICAL")
+            .with_custom_level_name(Level::Debug, "VERBOSE")
+            .add_sink_with(
+                SinkConfig {
+                    format: LogFormat::Json,
+                    ..Default::default()
+                },
+                json.clone(),
+            );
+        glog.flags.write().unwrap().minloglevel = Level::Debug;
+        glog.create_log_files().unwrap();
+        for level in [Level::Debug, Level::Warn, Level::Error] {
+            glog.log(&Record::builder().level(level).args(format_args!("message")).build());
+        }
+
+        let content = fs
+            .contents(Path::new("/logs/server.host.alice.log.DEBUG.20210401-123456.42"))
+            .unwrap();
+        assert!(content.contains("\nLog line format: [TVIWC]mmdd "), "{}", content);
+        let letters: String = content
+            .lines()
+            .filter(|line| line.ends_with("] message"))
+            .map(|line| &line[..1])
+            .collect();
+        assert_eq!(letters, "VWC");
+        let levels: Vec<_> = json
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|json| json.split("\"level\":").nth(1).unwrap().split(',').next().unwrap().to_owned())
+            .collect();
+        assert_eq!(levels, ["\"VERBOSE\"", "\"WARN\"", "\"CRITICAL\""]);
+    }
+
+    #[test]
+    fn per_sink_formats() {
+        let (lines, json) = (CollectSink::default(), CollectSink::default());
+        let (glog, _fs) = with_mem_fs();
+        let glog = glog
+            .with_sink(lines.clone())
+            .with_sink(json.clone())
+            .with_per_sink_format(1, LogFormat::Json);
+        glog.log(&Record::builder().level(Level::Info).args(format_args!("message")).build());
+
+        assert!(lines.0.lock().unwrap()[0].ends_with("] message"));
+        assert!(json.0.lock().unwrap()[0].starts_with("{\"timestamp\":"));
+    }
+
+    struct JsonOnly(CollectSink);
+
+    impl LogSink for JsonOnly {
+        fn write(&self, record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+            self.0.write(record, message)
+        }
+
+        fn required_format(&self) -> Option<LogFormat> {
+            Some(LogFormat::Json)
+        }
+    }
+
+    #[test]
+    fn required_sink_formats_override_the_config() {
+        let json = CollectSink::default();
+        let (glog, _fs) = with_mem_fs();
+        let glog = glog
+            .with_sink(JsonOnly(json.clone()))
+            .with_per_sink_format(0, LogFormat::Glog);
+        glog.log(&Record::builder().level(Level::Info).args(format_args!("message")).build());
+
+        assert!(json.0.lock().unwrap()[0].starts_with("{\"timestamp\":"));
+    }
+
+    #[test]
+    #[should_panic(expected = "no sink was added at index 1")]
+    fn per_sink_format_of_a_missing_sink() {
+        let _ = new()
+            .with_sink(CollectSink::default())
+            .with_per_sink_format(1, LogFormat::Json);
+    }
+
+    #[test]
+    fn template_rendered_on_stderr_only() {
+        let stderr = Arc::new(Mutex::new(Vec::new()));
+        let sink = CollectSink::default();
+        let glog = Glog::new()
+            .with_console_writer(Collect(stderr.clone()))
+            .with_sink(sink.clone())
+            .set_template_renderer(|id, params| match id {
+                "disk_low" => Some(format!("Disk {} is {}% full", params[1].1, params[0].1)),
+                _ => None,
+            });
+        glog.flags.write().unwrap().logtostderr = true;
+
+        let location = std::panic::Location::caller();
+        let disk_low = [("used", "95".to_owned()), ("mount", "/data".to_owned())];
+        template::log_template_to(&glog, Level::Info, "disk_low", &disk_low, location);
+        template::log_template_to(&glog, Level::Info, "unknown", &[("id", "7".to_owned())], location);
+        glog.log(
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("template:disk_low used=1"))
+                .build(),
+        );
+
+        let stderr: Vec<String> = stderr.lock().unwrap().iter().map(|(_, line)| line.clone()).collect();
+        let sink = sink.0.lock().unwrap();
+        let messages = |lines: &[String]| {
+            lines
+                .iter()
+                .map(|line| line.split("] ").nth(1).unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            messages(&stderr),
+            ["Disk /data is 95% full", "template:unknown id=7", "template:disk_low used=1"]
+        );
+        assert_eq!(
+            messages(&sink),
+            [
+                "template:disk_low used=95 mount=/data",
+                "template:unknown id=7",
+                "template:disk_low used=1"
+            ]
+        );
+    }
+
+    #[test]
+    fn panicking_substitute_records_dont_reach_the_caller() {
+        let glog = Glog::new()
+            .with_console_writer(Collect(Arc::default()))
+            .with_timestamp_fn(|| panic!("broken clock"));
+        glog.flags.write().unwrap().logtostderr = true;
+
+        with_record(|record| glog.log(record));
+        assert_eq!(glog.stats_snapshot().format_panics, 1);
+    }
+
+    #[test]
+    fn rendered_template_redacted_on_stderr() {
+        let stderr = Arc::new(Mutex::new(Vec::new()));
+        let glog = Glog::new()
+            .with_console_writer(Collect(stderr.clone()))
+            .set_template_renderer(|id, params| match id {
+                "login" => Some(format!("Logged in with password {}", params[0].1)),
+                _ => None,
+            })
+            .set_redactor(|message| match message.find("password ") {
+                Some(start) => Cow::Owned(format!("{}password [REDACTED]", &message[..start])),
+                None => Cow::Borrowed(message),
+            });
+        glog.flags.write().unwrap().logtostderr = true;
+
+        let location = std::panic::Location::caller();
+        let login = [("password", "hunter2".to_owned())];
+        template::log_template_to(&glog, Level::Info, "login", &login, location);
+        glog.flags.write().unwrap().redact_stderr = true;
+        template::log_template_to(&glog, Level::Info, "login", &login, location);
+
+        let stderr = stderr.lock().unwrap();
+        assert_eq!(stderr.len(), 2);
+        assert!(stderr[0].1.ends_with("] Logged in with password hunter2"), "{}", stderr[0].1);
+        assert!(
+            stderr[1].1.ends_with("] Logged in with password [REDACTED]"),
+            "{}",
+            stderr[1].1
+        );
+    }
+
+    struct FailingSink;
+
+    impl LogSink for FailingSink {
+        fn write(&self, _record: &Record, _message: &str) -> Result<(), Box<dyn Error>> {
+            Err("disk full".into())
+        }
+    }
+
+    struct PanickingSink;
+
+    impl LogSink for PanickingSink {
+        fn write(&self, _record: &Record, _message: &str) -> Result<(), Box<dyn Error>> {
+            panic!("broken sink")
+        }
+    }
+
+    #[test]
+    fn sink_errors_are_handled() {
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let sink = CollectSink::default();
+        let reported = errors.clone();
+        let glog = Glog::new()
+            .with_console_writer(Collect(Arc::default()))
+            .with_sink(FailingSink)
+            .with_sink(PanickingSink)
+            .with_sink(sink.clone())
+            .with_sink_error_handler(move |index, message, why| {
+                reported
+                    .lock()
+                    .unwrap()
+                    .push((index, message.split("] ").nth(1).unwrap().to_owned(), why.to_string()))
+            });
+        glog.flags.write().unwrap().logtostderr = true;
+
+        glog.log(&Record::builder().level(Level::Info).args(format_args!("hello")).build());
+
+        assert_eq!(sink.0.lock().unwrap().len(), 1);
+        assert_eq!(
+            *errors.lock().unwrap(),
+            [
+                (0, "hello".to_owned(), "disk full".to_owned()),
+                (1, "hello".to_owned(), "sink panicked: broken sink".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn stats_follow_a_scripted_sequence() {
+        let (seconds, clock) = fake_clock();
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog
+            .with_timestamp_fn(clock)
+            .with_sink(FailingSink)
+            .with_sink_error_handler(|_, _, _| ());
+        glog.flags.write().unwrap().detect_deleted_files = true;
+        glog.create_log_files().unwrap();
+        *glog.target_policy.write().unwrap() = Arc::new(ActivePolicy::new(
+            "sampled=info@sample:0.5, limited=info@rate:1/m".parse().unwrap(),
+        ));
+        glog.suppressions
+            .call_sites
+            .write()
+            .unwrap()
+            .insert("noisy.rs".to_owned(), Some(7));
+        let log = |level: Level, target: &str, file: &str| {
+            glog.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .file(Some(file))
+                    .line(Some(7))
+                    .args(format_args!("message"))
+                    .build(),
+            )
+        };
+        let before = glog.stats_snapshot();
+
+        log(Level::Info, "app", "main.rs");
+        log(Level::Warn, "app", "main.rs");
+        log(Level::Info, "app", "noisy.rs");
+        log(Level::Info, "sampled", "main.rs");
+        log(Level::Info, "sampled", "main.rs");
+        log(Level::Info, "limited", "main.rs");
+        log(Level::Info, "limited", "main.rs");
+        let warn_file = Path::new("/logs/server.host.alice.log.WARN.20210401-123456.42");
+        fs.remove(warn_file).unwrap();
+        seconds.fetch_add(2, Ordering::Relaxed);
+        log(Level::Error, "app", "main.rs");
+        glog.flush();
+
+        let stats = glog.stats_snapshot();
+        assert_eq!(
+            (stats.messages.info, stats.messages.warn, stats.messages.error),
+            (before.messages.info + 3, before.messages.warn + 1, before.messages.error + 1)
+        );
+        assert_eq!(stats.suppressed, 1);
+        assert_eq!(stats.sampled_out, 1);
+        assert_eq!(stats.rate_limited, 1);
+        // The ERROR record recreates the INFO, WARN and ERROR files, only WARN was removed
+        assert_eq!(stats.rotations, 1);
+        assert_eq!(stats.sink_errors, 5);
+        assert_eq!(stats.flushes, before.flushes + 1);
+        assert!(fs.contents(warn_file).unwrap().ends_with("] message\n"));
+    }
+
+    #[test]
+    fn replaced_log_files_are_continued() {
+        let (seconds, clock) = fake_clock();
+        let (glog, fs) = with_mem_fs();
+        let mut glog = glog.with_timestamp_fn(clock);
+        glog.flags.write().unwrap().detect_deleted_files = true;
+        glog.create_log_files().unwrap();
+        with_record(|record| glog.log(record));
+
+        let warn_file = Path::new("/logs/server.host.alice.log.WARN.20210401-123456.42");
+        fs.remove(warn_file).unwrap();
+        fs.create_exclusive(warn_file).unwrap().write_all(b"replaced\n").unwrap();
+        seconds.fetch_add(2, Ordering::Relaxed);
+        with_record(|record| glog.log(record));
+
+        let content = fs.contents(warn_file).unwrap();
+        assert!(content.starts_with("replaced\n"), "{}", content);
+        assert_eq!(content.matches("] a message\n").count(), 1, "{}", content);
+        assert_eq!(glog.stats_snapshot().rotations, 1);
+    }
+
+    #[test]
+    fn stats_interval_stops_when_dropped() {
+        let glog = Glog::new().with_console_writer(Collect(Arc::default()));
+        let (sender, receiver) = mpsc::channel();
+        let interval = glog.on_stats_interval(Duration::from_millis(1), move |stats| {
+            let _ = sender.send(stats.messages.total());
+        });
+        glog.log(&Record::builder().level(Level::Warn).args(format_args!("counted")).build());
+        assert!(receiver.iter().any(|total| total == 1));
+
+        drop(interval);
+        // The sender was dropped with the thread, so the channel is drained and disconnected
+        while receiver.try_recv().is_ok() {}
+        assert_eq!(receiver.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn fast_paths_are_chosen_once_and_skipped_for_runtime_sinks() {
+        let (mut glog, fs) = with_mem_fs();
+        glog.configure(Flags {
+            log_dir: OsString::from("/logs/"),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(glog.fast_paths.files);
+
+        let sink = CollectSink::default();
+        glog.sinks.add(SinkConfig::default(), Arc::new(sink.clone()));
+        with_record(|record| glog.log(record));
+        assert_eq!(sink.0.lock().unwrap().len(), 1);
+        let warn_file = Path::new("/logs/server.host.alice.log.WARN.20210401-123456.42");
+        assert!(fs.contents(warn_file).unwrap().ends_with("] a message\n"));
+
+        let mut glog = Glog::new().with_console_writer(Collect(Arc::default()));
+        glog.configure(Flags {
+            logtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!glog.fast_paths.stderr);
+        let mut glog = Glog::new();
+        glog.configure(Flags {
+            logtostderr: true,
+            stderr_verbose_level: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(!glog.fast_paths.stderr);
+        glog.configure(Flags {
+            logtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(glog.fast_paths.stderr);
+    }
+
+    #[test]
+    fn sanitize_messages() {
+        let glog = Glog::new();
+        let message = |glog: &Glog| glog.build_log_message(&Record::builder().args(format_args!("\x1b[31m{}", "red")).build());
+        assert!(message(&glog).ends_with("] \x1b[31mred"));
+
+        glog.flags.write().unwrap().sanitize_messages = true;
+        assert!(message(&glog).ends_with("] \\x1b[31mred"));
+    }
+
+    #[test]
+    fn log_target() {
+        let glog = Glog::new();
+        glog.flags.write().unwrap().log_target = true;
+        let record = Record::builder()
+            .target("app::db")
+            .file(Some("src/db.rs"))
+            .line(Some(7))
+            .args(format_args!("connected"))
+            .build();
+        let general = glog.build_log_message(&record);
+        assert!(general.ends_with(" db.rs:7] (target: app::db) connected"), "{}", general);
+
+        let mut fast = Vec::new();
+        glog.write_stderr_fast(&record, &mut fast).unwrap();
+        assert_eq!(
+            without_timestamp(&String::from_utf8(fast).unwrap()),
+            without_timestamp(&format!("{}\n", general))
+        );
+    }
+
+    #[test]
+    fn stderr_timestamp_format() {
+        let lines = Arc::new(Mutex::new(Vec::new()));
+        let glog = Glog::new()
+            .with_console_writer(Collect(lines.clone()))
+            .with_timestamp_fn(|| Local.with_ymd_and_hms(2021, 1, 2, 3, 4, 5).unwrap())
+            .with_stderr_timestamp_format("%Y-%m-%dT%H:%M:%S%.3f");
+        glog.flags.write().unwrap().multiline_style = MultilineStyle::Prefixed;
+        let record = Record::builder()
+            .level(Level::Warn)
+            .file(Some("src/main.rs"))
+            .line(Some(3))
+            .args(format_args!("two\nlines"))
+            .build();
+        let message = glog.build_log_message(&record);
+        assert!(message.starts_with("W0102 03:04:05.000000 "), "{}", message);
+        glog.write_stderr(&record, &message, glog.now());
+        glog.write_stderr(&record, "raw line", glog.now());
+
+        let tid = get_tid();
+        assert_eq!(
+            *lines.lock().unwrap(),
+            [
+                (
+                    Level::Warn,
+                    format!(
+                        "W2021-01-02T03:04:05.000 {:5} main.rs:3] two\nW2021-01-02T03:04:05.000 {:5} main.rs:3] lines",
+                        tid, tid
+                    )
+                ),
+                (Level::Warn, "raw line".to_owned()),
+            ]
+        );
+        let invalid = Glog::new().with_stderr_timestamp_format("%Q").try_init(Flags::default());
+        assert!(matches!(invalid, Err(GlogError::InvalidTimestampFormat(_))));
+    }
 }
@@ -0,0 +1,210 @@
+use std::{
+    ffi::OsString,
+    fmt,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use log::Level;
+
+use crate::{
+    index::IndexWriter,
+    log_fs::{FileStat, LogFs, LogWriter},
+};
+
+/// Format of the creation timestamp in log file names
+pub(crate) const FILE_NAME_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Minimum time between two checks whether a log file was removed
+const LIVENESS_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Character encoding of the log files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FileEncoding {
+    /// UTF-8 without a byte order mark
+    #[default]
+    Utf8,
+    /// UTF-16 little endian starting with a byte order mark
+    Utf16Le,
+    /// UTF-16 big endian starting with a byte order mark
+    Utf16Be,
+}
+
+impl FileEncoding {
+    /// The byte order mark written at the start of a new file
+    pub(crate) fn bom(self) -> &'static [u8] {
+        match self {
+            FileEncoding::Utf8 => &[],
+            FileEncoding::Utf16Le => &[0xff, 0xfe],
+            FileEncoding::Utf16Be => &[0xfe, 0xff],
+        }
+    }
+
+    pub(crate) fn encode(self, text: &str) -> Vec<u8> {
+        match self {
+            FileEncoding::Utf8 => text.as_bytes().to_vec(),
+            FileEncoding::Utf16Le => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+            FileEncoding::Utf16Be => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        }
+    }
+
+    /// Write `args` to `writer` in this encoding, UTF-8 is written without an intermediate buffer
+    pub(crate) fn write_fmt(self, writer: &mut impl Write, args: fmt::Arguments) -> io::Result<()> {
+        match self {
+            FileEncoding::Utf8 => writer.write_fmt(args),
+            _ => writer.write_all(&self.encode(&args.to_string())),
+        }
+    }
+}
+
+/// An open log file and the path it was created at
+pub(crate) struct LogFile {
+    pub(crate) file: Box<dyn LogWriter>,
+    pub(crate) path: OsString,
+    /// Whether `file` was created, see [`LogFile::pending`]
+    pub(crate) created: bool,
+    /// Number of files created at `path` before `file`, e.g. because they were removed
+    pub(crate) previous_segments: u64,
+    /// Number of records written to `file`
+    pub(crate) records: u64,
+    /// Maintains the sidecar of the level log files with `log_index_bytes` or `log_index_interval`
+    pub(crate) index: Option<IndexWriter>,
+    /// The time of the clock of the logger at the last liveness check, `None` before the first one
+    last_liveness_check: Option<DateTime<Local>>,
+}
+
+impl LogFile {
+    pub(crate) fn new(file: Box<dyn LogWriter>, path: OsString) -> Self {
+        LogFile {
+            file,
+            path,
+            created: true,
+            previous_segments: 0,
+            records: 0,
+            index: None,
+            last_liveness_check: None,
+        }
+    }
+
+    /// A log file at `path` that couldn't be created yet, records written to it are dropped
+    pub(crate) fn pending(path: OsString) -> Self {
+        LogFile {
+            created: false,
+            ..LogFile::new(Box::new(Pending), path)
+        }
+    }
+
+    /// Whether enough time passed since the last liveness check to do another one
+    ///
+    /// `now` is taken from the clock of the logger, so a fake clock drives the checks in tests.
+    /// A clock stepped backwards makes the check due.
+    pub(crate) fn liveness_check_due(&mut self, now: DateTime<Local>) -> bool {
+        let due = self.last_liveness_check.map_or(true, |last| {
+            (now - last)
+                .to_std()
+                .map_or(true, |elapsed| elapsed >= LIVENESS_CHECK_INTERVAL)
+        });
+        if due {
+            self.last_liveness_check = Some(now);
+        }
+        due
+    }
+
+    /// Whether the open file was removed or replaced by another file at its path
+    pub(crate) fn is_removed(&self, fs: &dyn LogFs) -> bool {
+        match fs.stat(Path::new(&self.path)) {
+            Err(_) => true,
+            #[cfg(unix)]
+            Ok(on_disk) => self.file.stat().is_ok_and(|open| open.id != on_disk.id),
+            #[cfg(not(unix))]
+            Ok(_) => false,
+        }
+    }
+}
+
+/// The writer of a [pending](LogFile::pending) log file
+struct Pending;
+
+impl Write for Pending {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl LogWriter for Pending {
+    fn stat(&self) -> io::Result<FileStat> {
+        Err(io::ErrorKind::NotFound.into())
+    }
+
+    fn set_group(&self, _gid: u32) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn preallocate(&self, _bytes: u64) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<std::os::unix::io::RawFd> {
+        None
+    }
+}
+
+/// A log file created by the logger, see [`GlogHandle::log_files`](crate::GlogHandle::log_files)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct LogFileInfo {
+    /// Path of the log file
+    pub path: PathBuf,
+    /// Level of the records in the log file
+    pub level: Level,
+    /// Creation time as encoded in the file name
+    pub created: DateTime<Local>,
+    /// Size in bytes when the listing was taken
+    pub size: u64,
+    /// Whether the logger is currently writing to this file
+    pub active: bool,
+}
+
+/// Parse the level and creation time from the name of a log file
+///
+/// Log file names are `<prefix><LEVEL>.<yyyymmdd-hhmmss>.<pid>`, other names yield `None`.
+pub(crate) fn parse_file_name(file_name: &str, prefix: &str, pid: u32) -> Option<(Level, DateTime<Local>)> {
+    let mut parts = file_name.strip_prefix(prefix)?.splitn(3, '.');
+    let (level, created, file_pid) = (parts.next()?, parts.next()?, parts.next()?);
+    if level != level.to_uppercase() || file_pid != pid.to_string() {
+        return None;
+    }
+    let created = NaiveDateTime::parse_from_str(created, FILE_NAME_TIMESTAMP_FORMAT).ok()?;
+    Some((Level::from_str(level).ok()?, Local.from_local_datetime(&created).earliest()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodings() {
+        assert_eq!(FileEncoding::Utf8.encode("aä\n"), "aä\n".as_bytes());
+        assert_eq!(FileEncoding::Utf16Le.encode("aä\n"), [0x61, 0x00, 0xe4, 0x00, 0x0a, 0x00]);
+        assert_eq!(FileEncoding::Utf16Be.encode("aä\n"), [0x00, 0x61, 0x00, 0xe4, 0x00, 0x0a]);
+        assert_eq!(FileEncoding::Utf16Le.encode("😀"), [0x3d, 0xd8, 0x00, 0xde]);
+
+        let mut written = Vec::new();
+        FileEncoding::Utf16Be
+            .write_fmt(&mut written, format_args!("{}\n", 42))
+            .unwrap();
+        assert_eq!(written, [0x00, b'4', 0x00, b'2', 0x00, b'\n']);
+    }
+}
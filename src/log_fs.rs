@@ -0,0 +1,720 @@
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::{
+    cell::RefCell,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Type, size and identity of a file, the identity tells whether a path still refers to an open file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FileStat {
+    pub(crate) is_file: bool,
+    pub(crate) len: u64,
+    pub(crate) id: (u64, u64),
+    pub(crate) modified: SystemTime,
+}
+
+/// The file system operations of the logger, replaced by an in-memory fake in tests
+pub(crate) trait LogFs: Send + Sync {
+    /// Create the file at `path` for appending, failing if it already exists
+    fn create_exclusive(&self, path: &Path) -> io::Result<Box<dyn LogWriter>>;
+    /// Open the existing file at `path` for appending
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn LogWriter>>;
+    /// Information about `path` itself, symlinks aren't followed
+    fn stat(&self, path: &Path) -> io::Result<FileStat>;
+    fn remove(&self, path: &Path) -> io::Result<()>;
+    /// Give the file `original` the additional name `link`, failing if `link` already exists
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()>;
+    /// Move the file `from` to `to`, replacing `to` if it exists
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    /// Create a symlink at `link` pointing to `target`, only supported on Unix
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()>;
+    /// Bytes available to unprivileged users on the file system of `dir`
+    fn free_space(&self, dir: &Path) -> io::Result<u64>;
+    /// The paths of the entries of `dir`
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    /// Open the existing file at `path` for appending through a mapping extended in chunks of `chunk` bytes
+    #[cfg(all(unix, feature = "mmap"))]
+    fn open_mapped(&self, path: &Path, chunk: u64) -> io::Result<Box<dyn LogWriter>>;
+}
+
+/// An open log file of a [`LogFs`]
+pub(crate) trait LogWriter: Write + Send {
+    fn stat(&self) -> io::Result<FileStat>;
+    /// Change the group of the file, ignored on platforms other than Unix
+    fn set_group(&self, gid: u32) -> io::Result<()>;
+    /// Reserve `bytes` on disk without changing the size
+    ///
+    /// Fails with [`io::ErrorKind::Unsupported`] on platforms without a way to do so.
+    fn preallocate(&self, bytes: u64) -> io::Result<()>;
+    /// Write the contents of the file to disk
+    fn sync(&self) -> io::Result<()>;
+    /// The descriptor of the file, if it is backed by one
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<RawFd>;
+    /// The path the open file has now, e.g. after it was moved away by a rotation
+    ///
+    /// `None` once the file was removed or where the path of a descriptor can't be looked up.
+    fn current_path(&self) -> Option<PathBuf> {
+        #[cfg(target_os = "linux")]
+        if let Some(path) = self
+            .raw_fd()
+            .and_then(|fd| fs::read_link(format!("/proc/self/fd/{}", fd)).ok())
+        {
+            return Some(path).filter(|path| !path.to_string_lossy().ends_with(" (deleted)"));
+        }
+        None
+    }
+    /// Take an exclusive advisory lock shared with other processes without blocking
+    ///
+    /// Returns whether the lock was taken, fails with [`io::ErrorKind::Unsupported`] for writers
+    /// which can't be locked.
+    fn try_lock(&self) -> io::Result<bool> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+    /// Release the lock taken with [`LogWriter::try_lock`]
+    fn unlock(&self) -> io::Result<()> {
+        Err(io::ErrorKind::Unsupported.into())
+    }
+    /// Write `prefix`, `message` and a newline as one record
+    ///
+    /// Concatenates the parts into a single write, so writers copying or mapping what is written see
+    /// the whole record at once.
+    fn write_line(&mut self, prefix: &[u8], message: &[u8]) -> io::Result<()> {
+        with_line_buffer(|line| {
+            line.extend_from_slice(prefix);
+            line.extend_from_slice(message);
+            line.push(b'\n');
+            self.write_all(line)
+        })
+    }
+}
+
+/// Call `f` with an empty buffer reused by the calls of the current thread
+///
+/// A call from within `f`, e.g. by a writer logging itself, gets a new buffer.
+fn with_line_buffer<R>(f: impl FnOnce(&mut Vec<u8>) -> R) -> R {
+    thread_local! {
+        static LINE: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+    }
+    LINE.with(|line| match line.try_borrow_mut() {
+        Ok(mut line) => {
+            line.clear();
+            f(&mut line)
+        }
+        Err(_) => f(&mut Vec::new()),
+    })
+}
+
+/// Write `prefix`, `message` and a newline to `out` with a single `write_vectored`
+///
+/// The rest of a partial write is concatenated and written like by [`LogWriter::write_line`].
+#[cfg(unix)]
+fn write_line_vectored<W: Write + ?Sized>(out: &mut W, prefix: &[u8], message: &[u8]) -> io::Result<()> {
+    let parts = [io::IoSlice::new(prefix), io::IoSlice::new(message), io::IoSlice::new(b"\n")];
+    let len = prefix.len() + message.len() + 1;
+    let written = loop {
+        match out.write_vectored(&parts) {
+            Err(why) if why.kind() == io::ErrorKind::Interrupted => continue,
+            written => break written?,
+        }
+    };
+    if written == len {
+        return Ok(());
+    }
+    with_line_buffer(|rest| {
+        rest.extend(prefix.iter().chain(message).chain(b"\n").skip(written));
+        out.write_all(rest)
+    })
+}
+
+/// The file system of the operating system
+pub(crate) struct RealFs;
+
+impl LogFs for RealFs {
+    fn create_exclusive(&self, path: &Path) -> io::Result<Box<dyn LogWriter>> {
+        Ok(Box::new(OpenOptions::new().append(true).create_new(true).open(path)?))
+    }
+
+    fn open_append(&self, path: &Path) -> io::Result<Box<dyn LogWriter>> {
+        Ok(Box::new(OpenOptions::new().append(true).open(path)?))
+    }
+
+    fn stat(&self, path: &Path) -> io::Result<FileStat> {
+        Ok(file_stat(&fs::symlink_metadata(path)?))
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()> {
+        fs::hard_link(original, link)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(target, link);
+        #[cfg(not(unix))]
+        {
+            let _ = (target, link);
+            Err(io::ErrorKind::Unsupported.into())
+        }
+    }
+
+    fn free_space(&self, dir: &Path) -> io::Result<u64> {
+        #[cfg(unix)]
+        {
+            let stat = nix::sys::statvfs::statvfs(dir).map_err(io::Error::other)?;
+            #[allow(clippy::useless_conversion)] // The field types differ between platforms
+            Ok(u64::from(stat.blocks_available()) * u64::from(stat.fragment_size()))
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = dir;
+            Ok(u64::MAX)
+        }
+    }
+
+    fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        fs::read_dir(dir)?.map(|entry| Ok(entry?.path())).collect()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    #[cfg(all(unix, feature = "mmap"))]
+    fn open_mapped(&self, path: &Path, chunk: u64) -> io::Result<Box<dyn LogWriter>> {
+        Ok(Box::new(crate::mmap::MappedFile::open(path, chunk)?))
+    }
+}
+
+impl LogWriter for File {
+    fn stat(&self) -> io::Result<FileStat> {
+        Ok(file_stat(&self.metadata()?))
+    }
+
+    fn set_group(&self, gid: u32) -> io::Result<()> {
+        #[cfg(unix)]
+        nix::unistd::fchown(self.as_raw_fd(), None, Some(nix::unistd::Gid::from_raw(gid))).map_err(io::Error::other)?;
+        #[cfg(not(unix))]
+        let _ = gid;
+        Ok(())
+    }
+
+    fn preallocate(&self, bytes: u64) -> io::Result<()> {
+        preallocate_file(self, bytes)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.sync_data()
+    }
+
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<RawFd> {
+        Some(self.as_raw_fd())
+    }
+
+    /// `flock` on Unix and `LockFileEx` on Windows
+    fn try_lock(&self) -> io::Result<bool> {
+        match File::try_lock(self) {
+            Ok(()) => Ok(true),
+            Err(fs::TryLockError::WouldBlock) => Ok(false),
+            Err(fs::TryLockError::Error(why)) => Err(why),
+        }
+    }
+
+    fn unlock(&self) -> io::Result<()> {
+        File::unlock(self)
+    }
+
+    /// Writes the parts with a single `writev` without concatenating them
+    ///
+    /// The rest of a partial write is concatenated and written like on other platforms.
+    #[cfg(unix)]
+    fn write_line(&mut self, prefix: &[u8], message: &[u8]) -> io::Result<()> {
+        write_line_vectored(self, prefix, message)
+    }
+}
+
+/// Reserve `bytes` on disk for `file`, see [`LogWriter::preallocate`]
+///
+/// `fallocate` on Linux keeps the size of the file. Windows can only reserve the space by moving
+/// the end of the file with `SetEndOfFile`, so the end is moved back afterwards: the records are
+/// appended to it.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn preallocate_file(file: &File, bytes: u64) -> io::Result<()> {
+    use std::convert::TryInto;
+
+    use nix::fcntl::{fallocate, FallocateFlags};
+
+    let len = bytes
+        .try_into()
+        .map_err(|why| io::Error::new(io::ErrorKind::InvalidInput, why))?;
+    fallocate(file.as_raw_fd(), FallocateFlags::FALLOC_FL_KEEP_SIZE, 0, len).map_err(io::Error::other)
+}
+
+#[cfg(windows)]
+fn preallocate_file(file: &File, bytes: u64) -> io::Result<()> {
+    // set_len is SetFilePointerEx and SetEndOfFile on the handle
+    let end = file.metadata()?.len();
+    if bytes <= end {
+        return Ok(());
+    }
+    file.set_len(bytes)?;
+    file.set_len(end)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", windows)))]
+fn preallocate_file(_file: &File, _bytes: u64) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+pub(crate) fn file_stat(metadata: &fs::Metadata) -> FileStat {
+    #[cfg(unix)]
+    let id = {
+        use std::os::unix::fs::MetadataExt;
+
+        (metadata.dev(), metadata.ino())
+    };
+    #[cfg(not(unix))]
+    let id = (0, 0);
+    FileStat {
+        is_file: metadata.is_file(),
+        len: metadata.len(),
+        id,
+        modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+    }
+}
+
+#[cfg(test)]
+pub(crate) use fake::MemFs;
+
+#[cfg(test)]
+mod fake {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    /// An in-memory [`LogFs`] with knobs to inject failures
+    #[derive(Clone, Default)]
+    pub(crate) struct MemFs {
+        state: Arc<Mutex<State>>,
+    }
+
+    #[derive(Default)]
+    struct State {
+        files: HashMap<PathBuf, Arc<Mutex<MemFile>>>,
+        symlinks: HashMap<PathBuf, PathBuf>,
+        next_id: u64,
+        writes: usize,
+        failing_write: Option<usize>,
+        free_space: Option<u64>,
+        steps: usize,
+        crash_after_steps: Option<usize>,
+        syncs: usize,
+        without_hard_links: bool,
+    }
+
+    impl State {
+        /// Count a change of the file system, failing all of them after the simulated crash
+        fn step(&mut self) -> io::Result<()> {
+            self.steps += 1;
+            match self.crash_after_steps {
+                Some(steps) if self.steps > steps => Err(io::Error::other("simulated crash")),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    struct MemFile {
+        id: u64,
+        data: Vec<u8>,
+        group: Option<u32>,
+        preallocated: u64,
+        modified: SystemTime,
+    }
+
+    struct MemWriter {
+        state: Arc<Mutex<State>>,
+        file: Arc<Mutex<MemFile>>,
+    }
+
+    /// Like a mapped file the data is extended with NULs in chunks and cut to the contents on flush
+    #[cfg(all(unix, feature = "mmap"))]
+    struct MemMappedWriter {
+        writer: MemWriter,
+        len: usize,
+        chunk: usize,
+    }
+
+    impl MemFs {
+        /// Let the `n`th write from now on fail, counting from 1
+        pub(crate) fn fail_nth_write(&self, n: usize) {
+            let mut state = self.state.lock().unwrap();
+            state.failing_write = Some(state.writes + n);
+        }
+
+        /// Let hard links fail like on file systems without them
+        pub(crate) fn disable_hard_links(&self) {
+            self.state.lock().unwrap().without_hard_links = true;
+        }
+
+        pub(crate) fn set_free_space(&self, bytes: u64) {
+            self.state.lock().unwrap().free_space = Some(bytes);
+        }
+
+        /// Let every change of the file system after the next `steps` fail, like after a crash
+        ///
+        /// Changes are the creation, removal and linking of files, writes and syncs.
+        pub(crate) fn crash_after_steps(&self, steps: usize) {
+            let mut state = self.state.lock().unwrap();
+            state.crash_after_steps = Some(state.steps + steps);
+        }
+
+        pub(crate) fn set_modified(&self, path: &Path, modified: SystemTime) {
+            self.state.lock().unwrap().files[path].lock().unwrap().modified = modified;
+        }
+
+        /// The number of syncs of all files
+        pub(crate) fn syncs(&self) -> usize {
+            self.state.lock().unwrap().syncs
+        }
+
+        pub(crate) fn contents(&self, path: &Path) -> Option<String> {
+            let state = self.state.lock().unwrap();
+            let file = state.files.get(path)?.lock().unwrap();
+            Some(String::from_utf8_lossy(&file.data).into_owned())
+        }
+
+        pub(crate) fn preallocated(&self, path: &Path) -> Option<u64> {
+            Some(self.state.lock().unwrap().files.get(path)?.lock().unwrap().preallocated)
+        }
+
+        pub(crate) fn group(&self, path: &Path) -> Option<u32> {
+            self.state.lock().unwrap().files.get(path)?.lock().unwrap().group
+        }
+
+        pub(crate) fn symlink_target(&self, link: &Path) -> Option<PathBuf> {
+            self.state.lock().unwrap().symlinks.get(link).cloned()
+        }
+
+        /// All regular files, sorted by path
+        pub(crate) fn files(&self) -> Vec<PathBuf> {
+            let mut files: Vec<_> = self.state.lock().unwrap().files.keys().cloned().collect();
+            files.sort();
+            files
+        }
+    }
+
+    impl LogFs for MemFs {
+        fn create_exclusive(&self, path: &Path) -> io::Result<Box<dyn LogWriter>> {
+            let mut state = self.state.lock().unwrap();
+            if state.files.contains_key(path) || state.symlinks.contains_key(path) {
+                return Err(io::ErrorKind::AlreadyExists.into());
+            }
+            state.step()?;
+            state.next_id += 1;
+            let file = Arc::new(Mutex::new(MemFile {
+                id: state.next_id,
+                data: Vec::new(),
+                group: None,
+                preallocated: 0,
+                modified: SystemTime::now(),
+            }));
+            state.files.insert(path.to_owned(), file.clone());
+            Ok(Box::new(MemWriter {
+                state: self.state.clone(),
+                file,
+            }))
+        }
+
+        fn open_append(&self, path: &Path) -> io::Result<Box<dyn LogWriter>> {
+            let file = self.state.lock().unwrap().files.get(path).cloned();
+            Ok(Box::new(MemWriter {
+                state: self.state.clone(),
+                file: file.ok_or(io::ErrorKind::NotFound)?,
+            }))
+        }
+
+        fn stat(&self, path: &Path) -> io::Result<FileStat> {
+            let state = self.state.lock().unwrap();
+            if state.symlinks.contains_key(path) {
+                return Ok(FileStat {
+                    is_file: false,
+                    len: 0,
+                    id: (1, 0),
+                    modified: UNIX_EPOCH,
+                });
+            }
+            state
+                .files
+                .get(path)
+                .map(|file| file.lock().unwrap().stat())
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        }
+
+        fn remove(&self, path: &Path) -> io::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            state.step()?;
+            match (state.files.remove(path), state.symlinks.remove(path)) {
+                (None, None) => Err(io::ErrorKind::NotFound.into()),
+                _ => Ok(()),
+            }
+        }
+
+        fn hard_link(&self, original: &Path, link: &Path) -> io::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            if state.without_hard_links {
+                return Err(io::ErrorKind::Unsupported.into());
+            }
+            if state.files.contains_key(link) || state.symlinks.contains_key(link) {
+                return Err(io::ErrorKind::AlreadyExists.into());
+            }
+            let file = state.files.get(original).cloned().ok_or(io::ErrorKind::NotFound)?;
+            state.step()?;
+            state.files.insert(link.to_owned(), file);
+            Ok(())
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            let file = state.files.get(from).cloned().ok_or(io::ErrorKind::NotFound)?;
+            state.step()?;
+            state.files.remove(from);
+            state.symlinks.remove(to);
+            state.files.insert(to.to_owned(), file);
+            Ok(())
+        }
+
+        fn symlink(&self, target: &Path, link: &Path) -> io::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            if state.files.contains_key(link) || state.symlinks.contains_key(link) {
+                return Err(io::ErrorKind::AlreadyExists.into());
+            }
+            state.step()?;
+            state.symlinks.insert(link.to_owned(), target.to_owned());
+            Ok(())
+        }
+
+        fn free_space(&self, _dir: &Path) -> io::Result<u64> {
+            Ok(self.state.lock().unwrap().free_space.unwrap_or(u64::MAX))
+        }
+
+        #[cfg(all(unix, feature = "mmap"))]
+        fn open_mapped(&self, path: &Path, chunk: u64) -> io::Result<Box<dyn LogWriter>> {
+            let file = self
+                .state
+                .lock()
+                .unwrap()
+                .files
+                .get(path)
+                .cloned()
+                .ok_or(io::ErrorKind::NotFound)?;
+            let len = file.lock().unwrap().data.len();
+            Ok(Box::new(MemMappedWriter {
+                writer: MemWriter {
+                    state: self.state.clone(),
+                    file,
+                },
+                len,
+                chunk: chunk as usize,
+            }))
+        }
+
+        fn read_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+            let state = self.state.lock().unwrap();
+            Ok(state
+                .files
+                .keys()
+                .chain(state.symlinks.keys())
+                .filter(|path| path.parent() == Some(dir))
+                .cloned()
+                .collect())
+        }
+
+        fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+            let state = self.state.lock().unwrap();
+            let file = state.files.get(path).ok_or(io::ErrorKind::NotFound)?;
+            let data = file.lock().unwrap().data.clone();
+            Ok(data)
+        }
+    }
+
+    impl MemFile {
+        fn stat(&self) -> FileStat {
+            FileStat {
+                is_file: true,
+                len: self.data.len() as u64,
+                id: (0, self.id),
+                modified: self.modified,
+            }
+        }
+    }
+
+    impl Write for MemWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            {
+                let mut state = self.state.lock().unwrap();
+                state.writes += 1;
+                if state.failing_write == Some(state.writes) {
+                    return Err(io::Error::other("injected write failure"));
+                }
+                state.step()?;
+            }
+            self.file.lock().unwrap().data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl LogWriter for MemWriter {
+        fn stat(&self) -> io::Result<FileStat> {
+            Ok(self.file.lock().unwrap().stat())
+        }
+
+        fn set_group(&self, gid: u32) -> io::Result<()> {
+            self.file.lock().unwrap().group = Some(gid);
+            Ok(())
+        }
+
+        fn preallocate(&self, bytes: u64) -> io::Result<()> {
+            self.file.lock().unwrap().preallocated = bytes;
+            Ok(())
+        }
+
+        fn sync(&self) -> io::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            state.syncs += 1;
+            state.step()
+        }
+
+        #[cfg(unix)]
+        fn raw_fd(&self) -> Option<RawFd> {
+            None
+        }
+
+        /// The first of the paths linked to the file
+        fn current_path(&self) -> Option<PathBuf> {
+            let state = self.state.lock().unwrap();
+            let paths = state.files.iter().filter(|(_, file)| Arc::ptr_eq(file, &self.file));
+            paths.map(|(path, _)| path.clone()).min()
+        }
+    }
+    #[cfg(all(unix, feature = "mmap"))]
+    impl Write for MemMappedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let end = self.len + buf.len();
+            if self.writer.file.lock().unwrap().data.len() < end {
+                self.writer.state.lock().unwrap().step()?;
+                self.writer
+                    .file
+                    .lock()
+                    .unwrap()
+                    .data
+                    .resize(end.div_ceil(self.chunk) * self.chunk, 0);
+            }
+            self.writer.file.lock().unwrap().data[self.len..end].copy_from_slice(buf);
+            self.len = end;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.writer.file.lock().unwrap().data.truncate(self.len);
+            Ok(())
+        }
+    }
+
+    #[cfg(all(unix, feature = "mmap"))]
+    impl LogWriter for MemMappedWriter {
+        fn stat(&self) -> io::Result<FileStat> {
+            Ok(FileStat {
+                len: self.len as u64,
+                ..self.writer.stat()?
+            })
+        }
+
+        fn set_group(&self, gid: u32) -> io::Result<()> {
+            self.writer.set_group(gid)
+        }
+
+        fn preallocate(&self, bytes: u64) -> io::Result<()> {
+            self.writer.preallocate(bytes)
+        }
+
+        fn sync(&self) -> io::Result<()> {
+            self.writer.sync()
+        }
+
+        fn raw_fd(&self) -> Option<RawFd> {
+            None
+        }
+
+        fn current_path(&self) -> Option<PathBuf> {
+            self.writer.current_path()
+        }
+    }
+
+    #[cfg(all(unix, feature = "mmap"))]
+    impl Drop for MemMappedWriter {
+        fn drop(&mut self) {
+            let _ = self.flush();
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    /// Takes at most `max` bytes per write after failing the first one with `EINTR`
+    struct ShortWrites {
+        data: Vec<u8>,
+        max: usize,
+        interrupted: bool,
+    }
+
+    impl Write for ShortWrites {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.interrupted {
+                self.interrupted = true;
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let len = buf.len().min(self.max);
+            self.data.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn partial_vectored_writes_are_completed() {
+        for max in [1, 4, 6, 12, 13, 100] {
+            let mut out = ShortWrites {
+                data: Vec::new(),
+                max,
+                interrupted: false,
+            };
+            write_line_vectored(&mut out, b"I0401 x.rs:1] ", b"message").unwrap();
+            assert_eq!(out.data, b"I0401 x.rs:1] message\n", "{}", max);
+        }
+    }
+}
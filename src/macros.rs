@@ -0,0 +1,155 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static EXIT_ON_DFATAL: AtomicBool = AtomicBool::new(false);
+
+pub(crate) fn set_exit_on_dfatal(exit_on_dfatal: bool) {
+    EXIT_ON_DFATAL.store(exit_on_dfatal, Ordering::Relaxed);
+}
+
+#[doc(hidden)]
+pub fn __exit_on_dfatal() -> bool {
+    EXIT_ON_DFATAL.load(Ordering::Relaxed)
+}
+
+#[doc(hidden)]
+pub fn __abort() -> ! {
+    crate::crash::log_fatal_report();
+    log::logger().flush();
+    crate::Glog::write_stderr_raw("*** Aborting after a fatal error, see the log for details ***\n");
+    std::process::abort()
+}
+
+/// Log an error and abort the process in debug builds, analogous to `DFATAL` in [`glog`]
+///
+/// In release builds only the error is logged, unless [`exit_on_dfatal`](crate::Flags::exit_on_dfatal) is set.
+///
+/// [`glog`]: https://github.com/google/glog
+///
+/// # Example
+///
+/// ```no_run
+/// use glog::{dfatal, Flags};
+///
+/// glog::new().init(Flags::default()).unwrap();
+///
+/// dfatal!("Reached an inconsistent state: {}", 42);
+/// ```
+#[macro_export]
+macro_rules! dfatal {
+    ($($arg:tt)+) => {{
+        $crate::__log::error!($($arg)+);
+        if cfg!(debug_assertions) || $crate::__exit_on_dfatal() {
+            $crate::__abort();
+        }
+    }};
+}
+
+/// Log an error and abort the process if `condition` is false, analogous to `CHECK` in [`glog`]
+///
+/// An additional message can be given in the same format as for the logging macros.
+///
+/// [`glog`]: https://github.com/google/glog
+///
+/// # Example
+///
+/// ```
+/// use glog::{check, Flags};
+///
+/// glog::new().init(Flags::default()).unwrap();
+///
+/// let answer = 42;
+/// check!(answer == 42);
+/// check!(answer > 0, "answer has to be positive but is {}", answer);
+/// ```
+#[macro_export]
+macro_rules! check {
+    ($condition:expr $(,)?) => {
+        if !$condition {
+            $crate::__log::error!("Check failed: {}", stringify!($condition));
+            $crate::__abort();
+        }
+    };
+    ($condition:expr, $($arg:tt)+) => {
+        if !$condition {
+            $crate::__log::error!("Check failed: {} {}", stringify!($condition), format_args!($($arg)+));
+            $crate::__abort();
+        }
+    };
+}
+
+/// Like [`check!`] in debug builds, does nothing in release builds, analogous to `DCHECK` in [`glog`]
+///
+/// The condition is not evaluated in release builds.
+///
+/// [`glog`]: https://github.com/google/glog
+#[macro_export]
+macro_rules! dcheck {
+    ($($arg:tt)+) => {
+        if cfg!(debug_assertions) {
+            $crate::check!($($arg)+);
+        }
+    };
+}
+
+/// Log an error followed by its chain of sources, one per line
+///
+/// The message is given in the same format as for the logging macros. The error may be any
+/// [`Error`](std::error::Error), a reference to one or a boxed trait object like
+/// `Box<dyn Error + Send + Sync>`. See [`log_error_chain`](crate::log_error_chain).
+///
+/// # Example
+///
+/// ```
+/// use std::fs::File;
+/// use log::*;
+/// use glog::{error_chain, Flags};
+///
+/// glog::new().init(Flags::default()).unwrap();
+///
+/// if let Err(why) = File::open("/does/not/exist") {
+///     error_chain!(Level::Error, why, "couldn't open {}", "/does/not/exist");
+/// }
+/// // E0401 12:34:56.987654   123 doc.rs:9] couldn't open /does/not/exist: No such file or directory (os error 2)
+///
+/// let boxed: Box<dyn std::error::Error + Send + Sync> = "invalid port".into();
+/// error_chain!(Level::Warn, boxed, "couldn't parse the config");
+/// ```
+#[macro_export]
+macro_rules! error_chain {
+    ($level:expr, $error:expr, $($arg:tt)+) => {
+        {
+            #[allow(unused_imports)]
+            use $crate::__error_chain::{ViaBox as _, ViaError as _};
+            $crate::log_error_chain(
+                $level,
+                (&$crate::__error_chain::ErrorRef(&$error)).as_dyn_error(),
+                format_args!($($arg)+),
+            )
+        }
+    };
+}
+
+/// Log a message template with named parameters
+///
+/// The log files receive the machine readable form `template:<id> key=value ...` with the parameters
+/// in the given order, while stderr shows the message rendered by
+/// [`set_template_renderer`](crate::Glog::set_template_renderer). Use
+/// [`parse_template`](crate::parse_template) to read the template back from a log file.
+///
+/// # Example
+///
+/// ```
+/// use log::*;
+/// use glog::{tmpl, Flags};
+///
+/// glog::new().init(Flags::default()).unwrap();
+///
+/// tmpl!(Level::Info, "disk_low", used = 95, mount = "/data");
+/// // I0401 12:34:56.987654   123 doc.rs:8] template:disk_low used=95 mount=/data
+/// ```
+#[macro_export]
+macro_rules! tmpl {
+    ($level:expr, $id:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        $crate::log_template($level, $id, &[$((stringify!($key), ($value).to_string())),*])
+    };
+}
@@ -0,0 +1,119 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Condvar,
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use chrono::Local;
+use log::{Level, Record};
+
+/// Target of the records emitted by [`marker`]
+pub const MARKER_TARGET: &str = "glog::marker";
+
+static MARKER_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Log a marker record named `name` and return its sequence number
+///
+/// Markers delimit phases in the log output, e.g. for tests or log analysis tools.
+/// They are logged with the target [`MARKER_TARGET`] and the message `===MARK <name> seq=<sequence> ts=<timestamp>===`
+/// independent of the configured minimum log level.
+///
+/// Use [`GlogHandle::wait_for_marker`](crate::GlogHandle::wait_for_marker) to wait until everything
+/// logged before the marker has been written.
+///
+/// # Example
+///
+/// ```
+/// use log::*;
+/// use std::time::Duration;
+/// use glog::Flags;
+///
+/// let handle = glog::new().try_init(Flags::default()).unwrap();
+///
+/// info!("Setting up the scenario");
+/// let seq = glog::marker("begin scenario");
+/// assert!(handle.wait_for_marker(seq, Duration::from_secs(1)));
+/// ```
+pub fn marker(name: &str) -> u64 {
+    let seq = MARKER_SEQUENCE.fetch_add(1, Ordering::SeqCst) + 1;
+    log::logger().log(
+        &Record::builder()
+            .level(Level::Info)
+            .target(MARKER_TARGET)
+            .file(Some(file!()))
+            .line(Some(line!()))
+            .args(format_args!(
+                "===MARK {} seq={} ts={}===",
+                name,
+                seq,
+                Local::now().format("%Y-%m-%dT%H:%M:%S%.6f")
+            ))
+            .build(),
+    );
+    seq
+}
+
+/// The sequence number of a marker message
+pub(crate) fn parse_marker_sequence(message: &str) -> Option<u64> {
+    let fields = message.strip_prefix("===MARK ")?.strip_suffix("===")?;
+    let (_, seq) = fields.rsplit_once(" seq=")?;
+    seq.split(' ').next()?.parse().ok()
+}
+
+/// Tracks the markers written by the logger
+#[derive(Debug, Default)]
+pub(crate) struct Markers {
+    written: Mutex<u64>,
+    condvar: Condvar,
+}
+
+impl Markers {
+    pub(crate) fn written(&self, seq: u64) {
+        let mut written = self.written.lock().unwrap();
+        *written = (*written).max(seq);
+        self.condvar.notify_all();
+    }
+
+    pub(crate) fn wait(&self, seq: u64, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut written = self.written.lock().unwrap();
+        while *written < seq {
+            let remaining = match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) => remaining,
+                None => return false,
+            };
+            written = self.condvar.wait_timeout(written, remaining).unwrap().0;
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_sequence_of_markers_only() {
+        assert_eq!(
+            parse_marker_sequence("===MARK phase seq=3 ts=2021-04-01T12:34:56.000000==="),
+            Some(3)
+        );
+        assert_eq!(
+            parse_marker_sequence("===MARK seq=4 seq=5 ts=2021-04-01T12:34:56.000000==="),
+            Some(5)
+        );
+        assert_eq!(parse_marker_sequence("MARK phase seq=3"), None);
+    }
+
+    #[test]
+    fn waiting_times_out_before_the_marker_is_written() {
+        let markers = Markers::default();
+        assert!(!markers.wait(1, Duration::from_millis(1)));
+        markers.written(2);
+        assert!(markers.wait(1, Duration::ZERO));
+        assert!(markers.wait(2, Duration::ZERO));
+    }
+}
@@ -0,0 +1,365 @@
+//! Merging of log files into one chronological stream, e.g. for support bundles
+//!
+//! Every file is read line by line, only its next record is kept in memory. A record is a line in
+//! the glog format with the continuation lines of a multi-line message following it. The lines
+//! before the first record are the header, its creation time supplies the year of files written
+//! without [`Glog::with_year`](crate::Glog::with_year). Records are written in the order of their
+//! timestamps, records with the same timestamp in the order of `paths` and within a file in the
+//! order they were written. The first line of a record gets the severity of its file as a
+//! `[INFO] ` prefix, taken from the name of the file like `server.log.INFO.20210401-123456.42`.
+//!
+//! Files written with [`FileHeader::JsonEnvelope`](crate::FileHeader::JsonEnvelope) start with an
+//! envelope line instead of the header and may end with a footer line. Both are left out of the
+//! output and kept in the [`FileStats`] of the file.
+//!
+//! The level log files cascade, so the `INFO` file already contains the records of the `WARNING`
+//! and `ERROR` files. Merge the files of several processes or the least severe file of each.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::path::PathBuf;
+//!
+//! let paths = [PathBuf::from("/tmp/server.log.INFO"), PathBuf::from("/tmp/worker.log.INFO")];
+//! let stats = glog::merge::merge_files(&paths, std::io::stdout()).unwrap();
+//! for file in &stats.files {
+//!     eprintln!("{}: {} records, {} unparseable lines", file.path.display(), file.records, file.unparseable.len());
+//! }
+//! ```
+
+use std::{
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::envelope::{ENVELOPE_PREFIX, FOOTER_PREFIX};
+
+/// The counts of a merged file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStats {
+    pub path: PathBuf,
+    /// The number of records written to the output
+    pub records: u64,
+    /// The numbers of the lines, starting at 1, which looked like records but couldn't be parsed
+    ///
+    /// They are kept as continuation lines of the record before them, lines before the first
+    /// record are left out of the output.
+    pub unparseable: Vec<u64>,
+    /// The JSON [`Envelope`](crate::envelope::Envelope) line the file starts with
+    pub envelope: Option<String>,
+    /// The JSON [`Footer`](crate::envelope::Footer) line
+    pub footer: Option<String>,
+}
+
+impl FileStats {
+    /// Whether the file has an envelope but no footer, because its process crashed or still runs
+    pub fn crashed(&self) -> bool {
+        self.envelope.is_some() && self.footer.is_none()
+    }
+}
+
+/// The result of [`merge_files`], with the files in the order of `paths`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeStats {
+    pub files: Vec<FileStats>,
+}
+
+/// Whether `line` starts with a glog prefix like `I0401 12:34:56.987654   123 main.rs:42] `
+///
+/// The level letter is followed by the date with or without the year, the time with microseconds,
+/// the thread id and the `file:line`. The values aren't checked, see [`parse_prefix`].
+fn starts_like_record(line: &str) -> bool {
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit());
+    let mut chars = line.chars();
+    if !chars.next().is_some_and(char::is_alphabetic) {
+        return false;
+    }
+    let Some((date, rest)) = chars.as_str().split_once(' ') else {
+        return false;
+    };
+    let Some((time, rest)) = rest.split_once(' ') else {
+        return false;
+    };
+    let Some((tid, rest)) = rest.trim_start().split_once(' ') else {
+        return false;
+    };
+    let Some((file_line, message)) = rest.split_once(']') else {
+        return false;
+    };
+    let time_shape = time.len() == 15
+        && time.bytes().enumerate().all(|(i, byte)| match i {
+            2 | 5 => byte == b':',
+            8 => byte == b'.',
+            _ => byte.is_ascii_digit(),
+        });
+    (date.len() == 4 || date.len() == 8)
+        && digits(date)
+        && time_shape
+        && digits(tid)
+        && file_line.rsplit_once(':').is_some_and(|(_, line)| digits(line))
+        && (message.is_empty() || message.starts_with(' '))
+}
+
+/// The timestamp of a glog line, `None` if the line doesn't start like a record
+///
+/// `Some(None)` is a line starting like a record which can't be parsed, like one with the date `0431`. Lines without a year get
+/// the one of `previous` or `year`, the next one if the date went backwards.
+fn parse_timestamp(line: &str, year: i32, previous: Option<NaiveDateTime>) -> Option<Option<NaiveDateTime>> {
+    if !starts_like_record(line) {
+        return None;
+    }
+    let letter = line.chars().next()?;
+    Some(parse_prefix(&line[letter.len_utf8()..], year, previous))
+}
+
+/// The timestamp of the prefix following the level letter
+fn parse_prefix(line: &str, year: i32, previous: Option<NaiveDateTime>) -> Option<NaiveDateTime> {
+    let (date, rest) = line.split_once(' ')?;
+    let (time, rest) = rest.split_once(' ')?;
+    let (_tid, file_line) = rest.trim_start().split_once(' ')?;
+    if !file_line.contains("] ") && !file_line.ends_with(']') {
+        return None;
+    }
+    let time = NaiveTime::parse_from_str(time, "%H:%M:%S%.6f").ok()?;
+    let date = match date.len() {
+        8 => NaiveDate::parse_from_str(date, "%Y%m%d").ok()?,
+        4 => {
+            let year = previous.map_or(year, |previous| previous.year());
+            let month_day = |year| NaiveDate::parse_from_str(&format!("{}{}", year, date), "%Y%m%d").ok();
+            match month_day(year) {
+                // The year changed since the previous record
+                Some(day) if previous.is_some_and(|previous| day < previous.date()) => month_day(year + 1)?,
+                Some(day) => day,
+                None => month_day(year + 1)?,
+            }
+        }
+        _ => return None,
+    };
+    Some(date.and_time(time))
+}
+
+/// The year of the `Log file created at:` header
+fn header_year(header: &[String]) -> Option<i32> {
+    let created = header.iter().position(|line| line == "Log file created at:")?;
+    let date = header.get(created + 1)?.split(' ').next()?;
+    NaiveDate::parse_from_str(date, "%Y/%m/%d").ok().map(|date| date.year())
+}
+
+/// The year of the `created` time of an envelope line
+fn envelope_year(envelope: &str) -> Option<i32> {
+    let (_, created) = envelope.split_once("\"created\":\"")?;
+    let (created, _) = created.split_once('"')?;
+    DateTime::parse_from_rfc3339(created).ok().map(|created| created.year())
+}
+
+/// The severity in the name of a log file, its file name if there is none
+fn severity(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    match name.split_once(".log.") {
+        Some((_, rest)) => rest.split('.').next().unwrap_or_default().to_owned(),
+        None => name.into_owned(),
+    }
+}
+
+/// A record with its continuation lines
+struct Record {
+    timestamp: NaiveDateTime,
+    lines: Vec<String>,
+}
+
+/// Reads the records of a file one by one
+struct Reader {
+    file: BufReader<File>,
+    line_number: u64,
+    /// The year of the first record if it has none
+    year: i32,
+    /// The lines before the first record, `None` once it was read
+    header: Option<Vec<String>>,
+    /// The timestamp of the latest record
+    latest: Option<NaiveDateTime>,
+    /// The record after the one returned last, started by the line ending that one
+    next: Option<Record>,
+    stats: FileStats,
+}
+
+impl Reader {
+    fn open(path: &Path) -> io::Result<Reader> {
+        Ok(Reader {
+            file: BufReader::new(File::open(path)?),
+            line_number: 0,
+            year: Local::now().year(),
+            header: Some(Vec::new()),
+            latest: None,
+            next: None,
+            stats: FileStats {
+                path: path.to_owned(),
+                records: 0,
+                unparseable: Vec::new(),
+                envelope: None,
+                footer: None,
+            },
+        })
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = Vec::new();
+        if self.file.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        self.line_number += 1;
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches('\n').trim_end_matches('\r');
+        Ok(Some(match self.line_number {
+            1 => line.trim_start_matches('\u{feff}').to_owned(),
+            _ => line.to_owned(),
+        }))
+    }
+
+    /// The next record, `None` at the end of the file
+    fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let mut record = self.next.take();
+        while let Some(line) = self.read_line()? {
+            if self.line_number == 1 && line.starts_with(ENVELOPE_PREFIX) {
+                self.year = envelope_year(&line).unwrap_or(self.year);
+                self.stats.envelope = Some(line);
+                continue;
+            }
+            if line.starts_with(FOOTER_PREFIX) {
+                self.stats.footer = Some(line);
+                continue;
+            }
+            if let Some(header) = self.header.as_ref().filter(|_| starts_like_record(&line)) {
+                self.year = header_year(header).unwrap_or(self.year);
+                self.header = None;
+            }
+            match parse_timestamp(&line, self.year, self.latest) {
+                Some(Some(timestamp)) => {
+                    self.latest = Some(timestamp);
+                    let started = Record {
+                        timestamp,
+                        lines: vec![line],
+                    };
+                    if record.is_some() {
+                        self.next = Some(started);
+                        break;
+                    }
+                    record = Some(started);
+                }
+                parsed => {
+                    if parsed.is_some() {
+                        self.stats.unparseable.push(self.line_number);
+                    }
+                    match (&mut self.header, &mut record) {
+                        (Some(header), _) => header.push(line),
+                        // A message line may look like a record
+                        (None, Some(record)) => record.lines.push(line),
+                        (None, None) if parsed.is_some() => {}
+                        (None, None) => self.stats.unparseable.push(self.line_number),
+                    }
+                }
+            }
+        }
+        if record.is_some() {
+            self.stats.records += 1;
+        }
+        Ok(record)
+    }
+}
+
+/// Merge the records of the log files at `paths` into `out`, see the [module](self) documentation
+///
+/// Fails if a file can't be read or `out` can't be written.
+pub fn merge_files(paths: &[PathBuf], mut out: impl Write) -> io::Result<MergeStats> {
+    let mut readers = paths.iter().map(|path| Reader::open(path)).collect::<io::Result<Vec<_>>>()?;
+    let severities: Vec<String> = paths.iter().map(|path| severity(path)).collect();
+    // The next record of every file, ordered by its timestamp and then the position of the file
+    let mut heads = BinaryHeap::new();
+    let mut pending = Vec::with_capacity(readers.len());
+    for (i, reader) in readers.iter_mut().enumerate() {
+        let record = reader.next_record()?;
+        if let Some(record) = &record {
+            heads.push(Reverse((record.timestamp, i)));
+        }
+        pending.push(record);
+    }
+    while let Some(Reverse((_, i))) = heads.pop() {
+        let record = pending[i].take().unwrap();
+        let mut lines = record.lines.iter();
+        if let Some(first) = lines.next() {
+            writeln!(out, "[{}] {}", severities[i], first)?;
+        }
+        for line in lines {
+            writeln!(out, "{}", line)?;
+        }
+        pending[i] = readers[i].next_record()?;
+        if let Some(record) = &pending[i] {
+            heads.push(Reverse((record.timestamp, i)));
+        }
+    }
+    out.flush()?;
+    Ok(MergeStats {
+        files: readers.into_iter().map(|reader| reader.stats).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(date: (i32, u32, u32), time: (u32, u32, u32, u32)) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+            .unwrap()
+            .and_hms_micro_opt(time.0, time.1, time.2, time.3)
+            .unwrap()
+    }
+
+    #[test]
+    fn timestamps() {
+        let parse = |line| parse_timestamp(line, 2021, None);
+        assert_eq!(
+            parse("I0401 12:00:00.000001  1234 main.rs:42] message"),
+            Some(Some(at((2021, 4, 1), (12, 0, 0, 1))))
+        );
+        assert_eq!(
+            parse("E20200401 12:00:00.000001 1 main.rs:42] "),
+            Some(Some(at((2020, 4, 1), (12, 0, 0, 1))))
+        );
+        assert_eq!(parse("W0431 12:00:00.000001 1 main.rs:42] message"), Some(None));
+        assert_eq!(parse("I0401 25:00:00.000001 1 main.rs:42] message"), Some(None));
+        assert_eq!(parse("I0401 12:xx:00.000001 1 main.rs:42] message"), None);
+        assert_eq!(parse("I0401 12:00:00.000001 1 main.rs] message"), None);
+        assert_eq!(parse("W4 retries left"), None);
+        assert_eq!(parse("Log file created at:"), None);
+        assert_eq!(parse("2021/04/01 12:00:00"), None);
+        assert_eq!(parse("  continued"), None);
+
+        let december = Some(at((2021, 12, 31), (23, 59, 59, 0)));
+        assert_eq!(
+            parse_timestamp("I0101 00:00:00.000000 1 main.rs:1] new year", 2021, december),
+            Some(Some(at((2022, 1, 1), (0, 0, 0, 0))))
+        );
+    }
+
+    #[test]
+    fn envelope_years() {
+        assert_eq!(
+            envelope_year("{\"glog_envelope\":1,\"created\":\"2021-04-01T12:34:56.000000+02:00\",\"level\":\"INFO\"}"),
+            Some(2021)
+        );
+        assert_eq!(envelope_year("{\"glog_envelope\":1}"), None);
+    }
+
+    #[test]
+    fn severities_of_file_names() {
+        assert_eq!(
+            severity(Path::new("/logs/server.host.user.log.WARNING.20210401-120000.42")),
+            "WARNING"
+        );
+        assert_eq!(severity(Path::new("/logs/server.INFO")), "server.INFO");
+    }
+}
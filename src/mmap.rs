@@ -0,0 +1,396 @@
+use std::{
+    convert::TryFrom,
+    fs::{File, OpenOptions},
+    hint,
+    io::{self, Write},
+    mem,
+    os::unix::io::{AsRawFd, RawFd},
+    path::Path,
+    ptr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Sender},
+        Arc,
+        Mutex,
+        Weak,
+    },
+    thread,
+};
+
+use nix::{
+    sys::mman::{self, MapFlags, MsFlags, ProtFlags},
+    unistd::{self, SysconfVar},
+};
+
+use crate::log_fs::{file_stat, FileStat, LogWriter};
+
+/// Set in [`Shared::reserved`] while the file is cut to its contents
+const SEALED: u64 = 1 << 63;
+
+/// The sender of requests to the background thread, started with the first request
+static ROLLER: Mutex<Option<Sender<Request>>> = Mutex::new(None);
+
+/// Every mapped file of the process, to cut them to their contents at shutdown
+static OPEN: Mutex<Vec<Weak<Shared>>> = Mutex::new(Vec::new());
+
+/// A log file extended and mapped in chunks, see [`Glog::with_mmap_writer`](crate::Glog::with_mmap_writer)
+///
+/// Lines are copied into the mapping behind an atomic bump pointer, so writes don't need system
+/// calls or locks. A background thread extends the file and maps the next chunk ahead of the
+/// writer and writes back and unmaps the chunks the writer is done with. The file is longer than
+/// its contents while lines are written, flushing, dropping and [`close_all`] cut off the unused end.
+pub(crate) struct MappedFile {
+    shared: Arc<Shared>,
+    /// The chunk lines are copied into, `None` until the first write
+    map: Option<Mapping>,
+    /// Offset of the mapping in the file
+    map_offset: u64,
+}
+
+/// The state of a [`MappedFile`] shared with the background thread and [`close_all`]
+struct Shared {
+    file: File,
+    /// Size of the mappings, a multiple of the page size
+    chunk: usize,
+    /// End of the reserved bytes, the bump pointer, with [`SEALED`] set while the file is cut to its contents
+    reserved: AtomicU64,
+    /// End of the bytes copied into the mapping, behind `reserved` while a line is copied
+    committed: AtomicU64,
+    state: Mutex<State>,
+}
+
+/// The length of a [`MappedFile`], only changed while the lock is held
+struct State {
+    /// Length of the file
+    len: u64,
+    /// Whether lines are written to the descriptor instead of the mapping, set by [`close_all`]
+    closed: bool,
+    /// The chunk after the mapping of the writer and its offset, mapped ahead by the background thread
+    next: Option<(u64, Mapping)>,
+}
+
+/// A mapping of `len` bytes at `addr`
+#[derive(Clone, Copy)]
+struct Mapping {
+    addr: usize,
+    len: usize,
+}
+
+/// Work for the background thread
+enum Request {
+    /// Map the chunk at the offset as the next chunk of the file, extending the file unless it's sealed
+    Prepare(Arc<Shared>, u64),
+    /// Schedule writing back the mapping
+    Sync(Mapping),
+    /// Write back and unmap a mapping the writer is done with
+    Retire(Mapping),
+}
+
+impl Mapping {
+    fn new(file: &File, offset: u64, len: usize) -> io::Result<Self> {
+        let offset = i64::try_from(offset).map_err(|why| io::Error::new(io::ErrorKind::InvalidInput, why))?;
+        // SAFETY: maps `len` bytes of the file, they are only accessed once the file reaches over them
+        let map = unsafe {
+            mman::mmap(
+                ptr::null_mut(),
+                len,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+                file.as_raw_fd(),
+                offset,
+            )
+        };
+        Ok(Mapping {
+            addr: map.map_err(io::Error::other)? as usize,
+            len,
+        })
+    }
+
+    fn sync(self, flags: MsFlags) -> io::Result<()> {
+        // SAFETY: the mapping is unmapped only after the requests sent before
+        unsafe { mman::msync(self.addr as *mut _, self.len, flags) }.map_err(io::Error::other)
+    }
+
+    fn unmap(self) -> io::Result<()> {
+        // SAFETY: the writer is done with the mapping
+        unsafe { mman::munmap(self.addr as *mut _, self.len) }.map_err(io::Error::other)
+    }
+}
+
+impl Request {
+    fn handle(self) {
+        match self {
+            Request::Prepare(shared, offset) => {
+                let _ = shared.prepare(offset);
+            }
+            Request::Sync(map) => {
+                let _ = map.sync(MsFlags::MS_ASYNC);
+            }
+            Request::Retire(map) => {
+                let _ = map.sync(MsFlags::MS_ASYNC);
+                let _ = map.unmap();
+            }
+        }
+    }
+}
+
+/// Hand `request` to the background thread, or handle it right away if the thread can't be started
+fn in_background(request: Request) {
+    let sender = {
+        let mut roller = ROLLER.lock().unwrap();
+        if roller.is_none() {
+            let (sender, requests) = mpsc::channel::<Request>();
+            let spawned = thread::Builder::new()
+                .name("glog-mmap".to_owned())
+                .spawn(move || requests.into_iter().for_each(Request::handle));
+            if spawned.is_ok() {
+                *roller = Some(sender);
+            }
+        }
+        roller.clone()
+    };
+    // Requests are handled in order, a mapping is never unmapped before it's synced
+    let unsent = match sender {
+        Some(sender) => sender.send(request).err().map(|mpsc::SendError(request)| request),
+        None => Some(request),
+    };
+    if let Some(request) = unsent {
+        request.handle();
+    }
+}
+
+/// Cut every mapped file of the process to its contents and write the following lines to the descriptors
+///
+/// Covers the files of other threads with [`Flags::file_per_thread`](crate::Flags::file_per_thread),
+/// which are only flushed by their own threads.
+pub(crate) fn close_all() {
+    let open = mem::take(&mut *OPEN.lock().unwrap());
+    for shared in open.iter().filter_map(Weak::upgrade) {
+        let _ = shared.trim(true);
+    }
+}
+
+impl Shared {
+    /// Map the chunk at `offset` ahead of the writer
+    fn prepare(&self, offset: u64) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Ok(());
+        }
+        let end = offset + self.chunk as u64;
+        if self.reserved.load(Ordering::Acquire) & SEALED == 0 && state.len < end {
+            self.file.set_len(end)?;
+            state.len = end;
+        }
+        let map = Mapping::new(&self.file, offset, self.chunk)?;
+        if let Some((_, stale)) = state.next.replace((offset, map)) {
+            in_background(Request::Retire(stale));
+        }
+        Ok(())
+    }
+
+    /// Cut the file to its contents, and keep it that way if `close` is set
+    fn trim(&self, close: bool) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            // Lines go to the descriptor, the file ends with them
+            return Ok(());
+        }
+        let len = self.reserved.fetch_or(SEALED, Ordering::AcqRel) & !SEALED;
+        // The owning thread may be copying a line into a reserved range
+        while self.committed.load(Ordering::Acquire) != len {
+            hint::spin_loop();
+        }
+        if close {
+            state.closed = true;
+            if let Some((_, next)) = state.next.take() {
+                in_background(Request::Retire(next));
+            }
+        }
+        if state.len != len {
+            self.file.set_len(len)?;
+            state.len = len;
+        }
+        Ok(())
+    }
+}
+
+impl MappedFile {
+    /// Map the file at `path` for appending in chunks of at least `chunk` bytes
+    pub(crate) fn open(path: &Path, chunk: u64) -> io::Result<Self> {
+        // Appending keeps writes of the crash handler to the descriptor from overwriting lines
+        let file = OpenOptions::new().read(true).append(true).open(path)?;
+        let page = unistd::sysconf(SysconfVar::PAGE_SIZE)
+            .map_err(io::Error::other)?
+            .and_then(|page| usize::try_from(page).ok())
+            .unwrap_or(4096);
+        let chunk = usize::try_from(chunk)
+            .map_err(|why| io::Error::new(io::ErrorKind::InvalidInput, why))?
+            .max(1);
+        let chunk = chunk.div_ceil(page) * page;
+        let len = file.metadata()?.len();
+        let shared = Arc::new(Shared {
+            file,
+            chunk,
+            // Nothing is mapped yet, the first write extends the file
+            reserved: AtomicU64::new(len | SEALED),
+            committed: AtomicU64::new(len),
+            state: Mutex::new(State {
+                len,
+                closed: false,
+                next: None,
+            }),
+        });
+        let mut open = OPEN.lock().unwrap();
+        open.retain(|shared| shared.strong_count() > 0);
+        open.push(Arc::downgrade(&shared));
+        Ok(MappedFile {
+            shared,
+            map: None,
+            map_offset: len - len % page as u64,
+        })
+    }
+
+    fn chunk_end(&self) -> u64 {
+        self.map_offset + self.shared.chunk as u64
+    }
+
+    /// Extend the file over the mapping after it was cut to its contents, `false` if it's closed
+    fn unseal(&mut self) -> io::Result<bool> {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closed {
+            return Ok(false);
+        }
+        if self.map.is_none() {
+            self.map = Some(Mapping::new(&self.shared.file, self.map_offset, self.shared.chunk)?);
+            in_background(Request::Prepare(Arc::clone(&self.shared), self.chunk_end()));
+        }
+        if state.len < self.chunk_end() {
+            self.shared.file.set_len(self.chunk_end())?;
+            state.len = self.chunk_end();
+        }
+        self.shared.reserved.fetch_and(!SEALED, Ordering::AcqRel);
+        Ok(true)
+    }
+
+    /// Switch to the chunk after the full mapping
+    fn roll(&mut self) -> io::Result<()> {
+        let offset = self.chunk_end();
+        let end = offset + self.shared.chunk as u64;
+        let mut state = self.shared.state.lock().unwrap();
+        let next = match state.next.take() {
+            Some((next_offset, next)) if next_offset == offset => next,
+            stale => {
+                if let Some((_, stale)) = stale {
+                    in_background(Request::Retire(stale));
+                }
+                Mapping::new(&self.shared.file, offset, self.shared.chunk)?
+            }
+        };
+        // A sealed file is extended by the next write
+        if self.shared.reserved.load(Ordering::Acquire) & SEALED == 0 && state.len < end {
+            if let Err(why) = self.shared.file.set_len(end) {
+                in_background(Request::Retire(next));
+                return Err(why);
+            }
+            state.len = end;
+        }
+        drop(state);
+        if let Some(full) = self.map.replace(next) {
+            in_background(Request::Retire(full));
+        }
+        self.map_offset = offset;
+        in_background(Request::Prepare(Arc::clone(&self.shared), end));
+        Ok(())
+    }
+}
+
+impl Write for MappedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            let reserved = self.shared.reserved.load(Ordering::Acquire);
+            if reserved & SEALED != 0 {
+                if !self.unseal()? {
+                    return (&self.shared.file).write(buf);
+                }
+                continue;
+            }
+            if reserved == self.chunk_end() {
+                self.roll()?;
+                continue;
+            }
+            let len = buf.len().min((self.chunk_end() - reserved) as usize);
+            // Fails if the file was sealed by `close_all` in the meantime
+            if self
+                .shared
+                .reserved
+                .compare_exchange(reserved, reserved + len as u64, Ordering::AcqRel, Ordering::Acquire)
+                .is_err()
+            {
+                continue;
+            }
+            let map = self.map.expect("an unsealed file is mapped");
+            // SAFETY: the range is inside the mapping, the file reaches its end until `committed` catches up
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    buf.as_ptr(),
+                    (map.addr as *mut u8).add((reserved - self.map_offset) as usize),
+                    len,
+                )
+            };
+            self.shared.committed.store(reserved + len as u64, Ordering::Release);
+            return Ok(len);
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.shared.trim(false)?;
+        if let Some(map) = self.map {
+            in_background(Request::Sync(map));
+        }
+        Ok(())
+    }
+}
+
+impl LogWriter for MappedFile {
+    fn stat(&self) -> io::Result<FileStat> {
+        let stat = file_stat(&self.shared.file.metadata()?);
+        let reserved = self.shared.reserved.load(Ordering::Acquire);
+        // A sealed file is cut to its contents
+        if reserved & SEALED != 0 {
+            return Ok(stat);
+        }
+        Ok(FileStat {
+            len: reserved,
+            ..stat
+        })
+    }
+
+    fn set_group(&self, gid: u32) -> io::Result<()> {
+        self.shared.file.set_group(gid)
+    }
+
+    fn preallocate(&self, bytes: u64) -> io::Result<()> {
+        self.shared.file.preallocate(bytes)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        if let Some(map) = self.map {
+            map.sync(MsFlags::MS_SYNC)?;
+        }
+        self.shared.file.sync_data()
+    }
+
+    fn raw_fd(&self) -> Option<RawFd> {
+        Some(self.shared.file.as_raw_fd())
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        let _ = self.shared.trim(true);
+        if let Some(map) = self.map.take() {
+            in_background(Request::Retire(map));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+#[cfg(unix)]
+use std::{os::unix::io::RawFd, sync::atomic::AtomicI32};
+
+use log::Level;
+
+#[cfg(unix)]
+use crate::tee::FdWriter;
+use crate::Flags;
+
+static FATAL_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// The descriptor the logger writes its own stderr output to, the original stderr while fd 2 is captured
+#[cfg(unix)]
+pub(crate) static STDERR_FD: AtomicI32 = AtomicI32::new(2);
+
+#[cfg(unix)]
+pub(crate) fn stderr_fd() -> RawFd {
+    STDERR_FD.load(Ordering::Relaxed)
+}
+
+/// The original stderr if fd 2 is captured by `capture_raw_stderr`
+#[cfg(unix)]
+pub(crate) fn captured_stderr() -> Option<FdWriter> {
+    Some(stderr_fd()).filter(|fd| *fd != 2).map(FdWriter)
+}
+
+/// Locked stderr of the logger, which bypasses fd 2 while it is captured
+pub(crate) enum Stderr {
+    Std(io::StderrLock<'static>),
+    #[cfg(unix)]
+    Fd(FdWriter),
+}
+
+impl Stderr {
+    pub(crate) fn lock() -> Self {
+        #[cfg(unix)]
+        if let Some(fd) = captured_stderr() {
+            return Stderr::Fd(fd);
+        }
+        Stderr::Std(io::stderr().lock())
+    }
+}
+
+impl Write for Stderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stderr::Std(stderr) => stderr.write(buf),
+            #[cfg(unix)]
+            Stderr::Fd(fd) => fd.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stderr::Std(stderr) => stderr.flush(),
+            #[cfg(unix)]
+            Stderr::Fd(fd) => fd.flush(),
+        }
+    }
+}
+
+/// Write all `Error` records to stderr as well, independent of `logtostderr` and `stderrthreshold`
+///
+/// Meant for environments without a writable file system. The records are written with
+/// [`Glog::write_stderr_raw`](crate::Glog::write_stderr_raw), skipping those already written to
+/// stderr or rejected by the [stderr filter](crate::Glog::with_stderr_output_filter).
+/// [`Glog::init`](crate::Glog::init) calls it if neither `logtostderr` nor `log_dir` is set.
+pub fn redirect_fatal_to_stderr() {
+    FATAL_TO_STDERR.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn fatal_redirected_to_stderr(level: Level) -> bool {
+    level == Level::Error && FATAL_TO_STDERR.load(Ordering::Relaxed)
+}
+
+/// Outputs a single record is written to
+///
+/// Follows the decision of C++ glog:
+///
+/// | `logtostderr` | `alsologtostderr` | level at or above `stderrthreshold` | stderr | files |
+/// |---------------|-------------------|-------------------------------------|--------|-------|
+/// | `true`        | any               | any                                 | yes    | no    |
+/// | `false`       | `true`            | any                                 | yes    | yes   |
+/// | `false`       | `false`           | yes                                 | yes    | yes   |
+/// | `false`       | `false`           | no                                  | no     | yes   |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct OutputPlan {
+    pub(crate) stderr: bool,
+    pub(crate) files: bool,
+}
+
+impl OutputPlan {
+    pub(crate) const FILES_ONLY: OutputPlan = OutputPlan {
+        stderr: false,
+        files: true,
+    };
+    pub(crate) const NOWHERE: OutputPlan = OutputPlan {
+        stderr: false,
+        files: false,
+    };
+    pub(crate) const STDERR_ONLY: OutputPlan = OutputPlan {
+        stderr: true,
+        files: false,
+    };
+
+    pub(crate) fn new(flags: &Flags, level: Level) -> Self {
+        if flags.logtostderr {
+            return OutputPlan::STDERR_ONLY;
+        }
+        OutputPlan {
+            stderr: flags.alsologtostderr || flags.stderrthreshold.is_some_and(|threshold| level <= threshold),
+            files: true,
+        }
+    }
+
+    /// Call the writer of every output in the plan, stderr first
+    pub(crate) fn dispatch(&self, write_stderr: impl FnOnce(), write_files: impl FnOnce()) {
+        if self.stderr {
+            write_stderr();
+        }
+        if self.files {
+            write_files();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const STDERR: (bool, bool) = (true, false);
+    const FILES: (bool, bool) = (false, true);
+    const BOTH: (bool, bool) = (true, true);
+
+    /// Which of an injected stderr and files writer received the record
+    fn outputs(flags: &Flags, level: Level) -> (bool, bool) {
+        let (mut stderr, mut files) = (Vec::new(), Vec::new());
+        OutputPlan::new(flags, level).dispatch(|| writeln!(stderr, "record").unwrap(), || writeln!(files, "record").unwrap());
+        (!stderr.is_empty(), !files.is_empty())
+    }
+
+    #[test]
+    fn decision_table() {
+        let (t, f) = (true, false);
+        let warn = Some(Level::Warn);
+        #[rustfmt::skip]
+        let cases = [
+            // logtostderr, alsologtostderr, stderrthreshold, level, (stderr, files)
+            (f, f, None, Level::Info, FILES),
+            (f, f, None, Level::Warn, FILES),
+            (f, f, None, Level::Error, FILES),
+            (f, f, warn, Level::Info, FILES),
+            (f, f, warn, Level::Warn, BOTH),
+            (f, f, warn, Level::Error, BOTH),
+            (f, t, None, Level::Info, BOTH),
+            (f, t, None, Level::Warn, BOTH),
+            (f, t, None, Level::Error, BOTH),
+            (f, t, warn, Level::Info, BOTH),
+            (f, t, warn, Level::Warn, BOTH),
+            (f, t, warn, Level::Error, BOTH),
+            (t, f, None, Level::Info, STDERR),
+            (t, f, None, Level::Warn, STDERR),
+            (t, f, None, Level::Error, STDERR),
+            (t, f, warn, Level::Info, STDERR),
+            (t, f, warn, Level::Warn, STDERR),
+            (t, f, warn, Level::Error, STDERR),
+            (t, t, None, Level::Info, STDERR),
+            (t, t, None, Level::Warn, STDERR),
+            (t, t, None, Level::Error, STDERR),
+            (t, t, warn, Level::Info, STDERR),
+            (t, t, warn, Level::Warn, STDERR),
+            (t, t, warn, Level::Error, STDERR),
+        ];
+        for (logtostderr, alsologtostderr, stderrthreshold, level, expected) in cases.iter() {
+            let flags = Flags {
+                logtostderr: *logtostderr,
+                alsologtostderr: *alsologtostderr,
+                stderrthreshold: *stderrthreshold,
+                ..Default::default()
+            };
+            assert_eq!(
+                outputs(&flags, *level),
+                *expected,
+                "logtostderr={} alsologtostderr={} stderrthreshold={:?} level={}",
+                logtostderr,
+                alsologtostderr,
+                stderrthreshold,
+                level
+            );
+        }
+    }
+}
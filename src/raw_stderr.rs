@@ -0,0 +1,163 @@
+//! Capture of everything written to fd 2, by C libraries and child processes too
+
+use std::{
+    io::{self, Write},
+    os::unix::io::RawFd,
+    sync::{
+        atomic::{AtomicI32, Ordering},
+        mpsc::{self, Receiver},
+        Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::{Level, Record};
+use nix::{
+    fcntl::{fcntl, FcntlArg, FdFlag},
+    unistd,
+};
+
+use crate::{format::Sanitized, output};
+
+/// Target of the records logged for the lines written to the captured fd 2 by default
+pub const RAW_STDERR_TARGET: &str = "raw_stderr";
+
+/// Lines longer than this are logged in pieces
+const MAX_LINE: usize = 64 * 1024;
+
+/// Appended to a line which was not terminated when the capture ended
+const PARTIAL_MARKER: &str = " [partial line]";
+
+struct Capture {
+    /// The original fd 2
+    saved: RawFd,
+    /// Signalled when the reader has logged everything
+    done: Receiver<()>,
+}
+
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+/// The duplicate of the original fd 2, -1 until the first capture
+///
+/// It's never closed, threads which loaded it from [`output::STDERR_FD`] before a release may
+/// still write to it. The next capture points it at the current fd 2 again.
+static SAVED: AtomicI32 = AtomicI32::new(-1);
+
+fn nix_error(error: nix::Error) -> io::Error {
+    io::Error::other(error)
+}
+
+fn set_cloexec(fd: RawFd) -> nix::Result<()> {
+    fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).map(drop)
+}
+
+fn log_line(level: Level, target: &str, line: &[u8], partial: bool) {
+    let line = String::from_utf8_lossy(line);
+    log::logger().log(
+        &Record::builder()
+            .level(level)
+            .target(target)
+            .args(format_args!(
+                "{}{}",
+                Sanitized::new(line.trim_end_matches('\r'), true),
+                if partial { PARTIAL_MARKER } else { "" }
+            ))
+            .build(),
+    );
+}
+
+/// Logs the lines read from `fd` until every write end of the pipe is closed
+fn read_lines(fd: RawFd, level: Level, target: &str) {
+    let mut pending = Vec::new();
+    let mut buf = [0; 8192];
+    loop {
+        let read = match unistd::read(fd, &mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => continue,
+            Err(_) => break,
+        };
+        pending.extend_from_slice(&buf[..read]);
+        while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+            log_line(level, target, &pending[..end], false);
+            pending.drain(..=end);
+        }
+        while pending.len() >= MAX_LINE {
+            log_line(level, target, &pending[..MAX_LINE], false);
+            pending.drain(..MAX_LINE);
+        }
+    }
+    if !pending.is_empty() {
+        log_line(level, target, &pending, true);
+    }
+    let _ = unistd::close(fd);
+}
+
+/// Replace fd 2 with a pipe and log every line written to it at `level` with `target`
+///
+/// Writes of C libraries to `stderr` and of child processes which inherit fd 2 are logged like
+/// the records of the process, [`RAW_STDERR_TARGET`] is the conventional target. The output of
+/// the logger to stderr goes to the original fd 2 in the meantime. Bytes which aren't UTF-8 and
+/// control characters are escaped. Undone by [`release_raw_stderr`].
+///
+/// Fails if fd 2 is already captured or the pipe can't be set up.
+pub fn capture_raw_stderr(level: Level, target: &'static str) -> io::Result<()> {
+    let mut capture = CAPTURE.lock().unwrap();
+    if capture.is_some() {
+        return Err(io::Error::other("stderr is already captured"));
+    }
+    let saved = match SAVED.load(Ordering::Relaxed) {
+        -1 => fcntl(2, FcntlArg::F_DUPFD_CLOEXEC(3)).map_err(nix_error)?,
+        saved => {
+            unistd::dup2(2, saved).and_then(|_| set_cloexec(saved)).map_err(nix_error)?;
+            saved
+        }
+    };
+    SAVED.store(saved, Ordering::Relaxed);
+    let (read, write) = unistd::pipe().map_err(nix_error)?;
+    let replaced = set_cloexec(read).and_then(|()| {
+        let _ = io::stderr().flush();
+        unistd::dup2(write, 2)
+    });
+    let _ = unistd::close(write);
+    if let Err(error) = replaced {
+        let _ = unistd::close(read);
+        return Err(nix_error(error));
+    }
+    output::STDERR_FD.store(saved, Ordering::Relaxed);
+
+    let (done_tx, done) = mpsc::channel();
+    let spawned = thread::Builder::new().name("glog-raw-stderr".to_owned()).spawn(move || {
+        read_lines(read, level, target);
+        let _ = done_tx.send(());
+    });
+    if let Err(error) = spawned {
+        let _ = unistd::dup2(saved, 2);
+        output::STDERR_FD.store(2, Ordering::Relaxed);
+        let _ = unistd::close(read);
+        return Err(error);
+    }
+    *capture = Some(Capture {
+        saved,
+        done,
+    });
+    Ok(())
+}
+
+/// Restore the fd 2 replaced by [`capture_raw_stderr`]
+///
+/// Waits a moment for the lines already written to be logged, a line without a newline is logged
+/// with a ` [partial line]` marker. The lines of child processes which still hold the pipe are
+/// logged once they exit. Does nothing if fd 2 isn't captured. Called by [`GlogHandle::shutdown`](crate::GlogHandle::shutdown).
+pub fn release_raw_stderr() -> io::Result<()> {
+    let Some(capture) = CAPTURE.lock().unwrap().take() else {
+        return Ok(());
+    };
+    let _ = io::stderr().flush();
+    let restored = unistd::dup2(capture.saved, 2).map_err(nix_error);
+    // Other threads may still write to the saved fd, so it stays open
+    output::STDERR_FD.store(2, Ordering::Relaxed);
+    let _ = capture.done.recv_timeout(Duration::from_secs(1));
+    restored.map(drop)
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+
+use log::{Level, Log, Record};
+
+/// A log record built at runtime instead of by a `log!` macro, e.g. by an HTTP or audit middleware
+///
+/// Unlike [`log::Record`] it owns its fields, so it can be built step by step and kept around.
+///
+/// # Example
+///
+/// ```
+/// use glog::{Flags, GlogRecord};
+/// use log::Level;
+///
+/// glog::new().init(Flags::default()).unwrap();
+///
+/// GlogRecord::builder()
+///     .level(Level::Warn)
+///     .target("http::access")
+///     .file("proxy/upstream.rs")
+///     .line(12)
+///     .args(format_args!("GET /health took {}ms", 1200))
+///     .log();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlogRecord {
+    level: Level,
+    target: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    message: String,
+}
+
+/// Builds a [`GlogRecord`], see [`GlogRecord::builder`]
+#[derive(Debug, Clone)]
+pub struct GlogRecordBuilder {
+    record: GlogRecord,
+}
+
+impl GlogRecord {
+    /// A record at [`Level::Info`] with an empty target and message and without a source location
+    pub fn builder() -> GlogRecordBuilder {
+        GlogRecordBuilder {
+            record: GlogRecord {
+                level: Level::Info,
+                target: String::new(),
+                module_path: None,
+                file: None,
+                line: None,
+                message: String::new(),
+            },
+        }
+    }
+
+    /// Call `f` with the record as a [`log::Record`]
+    pub fn with_record<R>(&self, f: impl FnOnce(&Record) -> R) -> R {
+        f(&Record::builder()
+            .level(self.level)
+            .target(&self.target)
+            .module_path(self.module_path.as_deref())
+            .file(self.file.as_deref())
+            .line(self.line)
+            .args(format_args!("{}", self.message))
+            .build())
+    }
+
+    /// Log the record with the global logger, if its level is enabled
+    pub fn log(&self) {
+        self.log_to(log::logger());
+    }
+
+    /// Log the record with `logger`, if its level is enabled
+    pub fn log_to(&self, logger: &dyn Log) {
+        self.with_record(|record| {
+            if logger.enabled(record.metadata()) {
+                logger.log(record);
+            }
+        })
+    }
+}
+
+impl GlogRecordBuilder {
+    /// The severity, [`Level::Info`] by default
+    pub fn level(mut self, level: Level) -> Self {
+        self.record.level = level;
+        self
+    }
+
+    /// The target, e.g. `http::access`, matched by the [`target_policy`](crate::Flags::target_policy)
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.record.target = target.into();
+        self
+    }
+
+    /// The module which logs the record, selects its [module log file](crate::Glog::with_module_log_file)
+    pub fn module_path(mut self, module_path: impl Into<String>) -> Self {
+        self.record.module_path = Some(module_path.into());
+        self
+    }
+
+    /// The source file, shown without its directories like for the macros
+    pub fn file(mut self, file: impl Into<String>) -> Self {
+        self.record.file = Some(file.into());
+        self
+    }
+
+    /// The line in the source [file](GlogRecordBuilder::file), 0 in the log line without one
+    pub fn line(mut self, line: u32) -> Self {
+        self.record.line = Some(line);
+        self
+    }
+
+    /// The message, formatted right away
+    pub fn args(mut self, args: impl fmt::Display) -> Self {
+        self.record.message = args.to_string();
+        self
+    }
+
+    /// The record, to be logged later, maybe more than once
+    pub fn build(self) -> GlogRecord {
+        self.record
+    }
+
+    /// Build the record and [log](GlogRecord::log) it with the global logger
+    pub fn log(self) {
+        self.build().log();
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    borrow::Cow,
+    fmt::{self, Write as _},
+    sync::{Arc, RwLock},
+};
+
+/// Target of records logged by glog itself, which are never redacted
+pub(crate) const INTERNAL_TARGET: &str = "glog::internal";
+
+pub(crate) type Redactor = Arc<dyn Fn(&str) -> Cow<'_, str> + Send + Sync>;
+
+static SALT: RwLock<String> = RwLock::new(String::new());
+
+pub(crate) fn set_salt(salt: &str) {
+    *SALT.write().unwrap() = salt.to_owned();
+}
+
+/// Displays `[REDACTED]` instead of the wrapped value
+///
+/// ```
+/// use glog::Redacted;
+///
+/// let token = "hunter2";
+/// assert_eq!(format!("token {}", Redacted(token)), "token [REDACTED]");
+/// ```
+#[derive(Clone, Copy)]
+pub struct Redacted<T>(pub T);
+
+impl<T> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl<T> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+/// Displays a stable 8 character hash of the wrapped value instead of the value
+///
+/// Equal values log the same hash, so records can still be correlated. The hash is salted with the
+/// [`redaction_salt`](crate::Flags::redaction_salt) flag. It's not a cryptographic hash, values
+/// from a small set like user ids can be recovered by hashing all candidates.
+///
+/// ```
+/// use glog::HashedDisplay;
+///
+/// let email = "alice@example.com";
+/// assert_eq!(HashedDisplay(email).to_string().len(), 8);
+/// assert_eq!(HashedDisplay(email).to_string(), HashedDisplay(email).to_string());
+/// ```
+#[derive(Clone, Copy)]
+pub struct HashedDisplay<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for HashedDisplay<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut hasher = Fnv1a::default();
+        hasher.write_str(&SALT.read().unwrap())?;
+        write!(hasher, "{}", self.0)?;
+        write!(f, "{:08x}", (hasher.0 >> 32) as u32 ^ hasher.0 as u32)
+    }
+}
+
+impl<T: fmt::Display> fmt::Debug for HashedDisplay<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// 64 bit FNV-1a, stable across Rust versions unlike the hashers of `std`
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl fmt::Write for Fnv1a {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a() {
+        let mut hasher = Fnv1a::default();
+        hasher.write_str("a").unwrap();
+        assert_eq!(hasher.0, 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn hashed_display_is_stable() {
+        let hashed = HashedDisplay("alice@example.com").to_string();
+        assert_eq!(hashed.len(), 8);
+        assert!(hashed.bytes().all(|byte| byte.is_ascii_hexdigit()));
+        assert_eq!(HashedDisplay(String::from("alice@example.com")).to_string(), hashed);
+        assert_ne!(HashedDisplay("bob@example.com").to_string(), hashed);
+        assert_eq!(format!("{:?}", Redacted("secret")), "[REDACTED]");
+    }
+}
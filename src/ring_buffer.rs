@@ -0,0 +1,276 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+use log::{Level, Record};
+
+use crate::{attach, Attachment, LogSink};
+
+/// A [`LogSink`] keeping the last records in memory, e.g. to serve them from a debug endpoint
+///
+/// Clones share the same buffer, keep one to [`query`](RingBufferSink::query) the records.
+///
+/// # Example
+///
+/// ```
+/// use log::*;
+/// use glog::{Flags, QueryParams, RingBufferSink};
+///
+/// let recent = RingBufferSink::new(1000);
+/// glog::new().with_sink(recent.clone()).init(Flags::default()).unwrap();
+///
+/// warn!("Disk almost full");
+/// let response = recent.query(&QueryParams {
+///     min_level: Level::Warn,
+///     limit: 200,
+///     ..Default::default()
+/// });
+/// assert!(response.events[0].line.ends_with("] Disk almost full"));
+/// ```
+#[derive(Clone)]
+pub struct RingBufferSink {
+    capacity: usize,
+    buffer: Arc<Mutex<Buffer>>,
+}
+
+struct Buffer {
+    events: VecDeque<Arc<LogEvent>>,
+    latest_seq: u64,
+}
+
+/// A record stored by a [`RingBufferSink`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct LogEvent {
+    /// Position of the record among all records written to the sink, starting at 1
+    pub seq: u64,
+    pub level: Level,
+    pub target: String,
+    /// The formatted glog line
+    pub line: String,
+    /// The [attachment](crate::attach) of the record, with its whole value
+    pub attachment: Option<Attachment>,
+}
+
+impl LogEvent {
+    /// The message of the [`line`](LogEvent::line) without the glog prefix
+    pub fn message(&self) -> &str {
+        self.line.split_once("] ").map_or(&self.line, |(_, message)| message)
+    }
+}
+
+/// Which events [`RingBufferSink::query`] returns
+#[derive(Debug, Clone)]
+pub struct QueryParams {
+    /// Only events at this level or more severe ones
+    pub min_level: Level,
+    /// Only events after this sequence number, usually the [`latest_seq`](RingBufferSink::latest_seq) of the previous poll
+    pub since_seq: u64,
+    /// At most this many of the latest matching events
+    pub limit: usize,
+    /// Only events with a target starting with this prefix
+    pub target_prefix: Option<String>,
+}
+
+impl Default for QueryParams {
+    fn default() -> Self {
+        QueryParams {
+            min_level: Level::Trace,
+            since_seq: 0,
+            limit: usize::MAX,
+            target_prefix: None,
+        }
+    }
+}
+
+/// The result of a [`RingBufferSink::query`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct QueryResponse {
+    /// Matching events, oldest first
+    pub events: Vec<LogEvent>,
+    /// Whether events after `since_seq` were evicted before they could be returned
+    pub gap: bool,
+}
+
+impl RingBufferSink {
+    /// Keep the last `capacity` records
+    pub fn new(capacity: usize) -> Self {
+        RingBufferSink {
+            capacity,
+            buffer: Arc::new(Mutex::new(Buffer {
+                events: VecDeque::with_capacity(capacity),
+                latest_seq: 0,
+            })),
+        }
+    }
+
+    /// Sequence number of the latest written record, 0 if none was written
+    pub fn latest_seq(&self) -> u64 {
+        self.buffer.lock().unwrap().latest_seq
+    }
+
+    /// Remove all events, the sequence numbers continue
+    pub fn clear(&self) {
+        self.buffer.lock().unwrap().events.clear();
+    }
+
+    /// The events of the records written while `scope` runs, from any thread
+    ///
+    /// The logger is flushed before the events are collected, so records of a
+    /// [`BatchWriter`](crate::BatchWriter) are included.
+    pub fn scope(&self, scope: impl FnOnce()) -> Vec<LogEvent> {
+        let since_seq = self.latest_seq();
+        scope();
+        log::logger().flush();
+        self.query(&QueryParams {
+            since_seq,
+            ..Default::default()
+        })
+        .events
+    }
+
+    /// The events matching `params`
+    pub fn query(&self, params: &QueryParams) -> QueryResponse {
+        // Only the shared events are copied while writers are blocked, the strings are cloned afterwards
+        let (events, gap): (Vec<Arc<LogEvent>>, bool) = {
+            let buffer = self.buffer.lock().unwrap();
+            let oldest_seq = buffer.events.front().map_or(buffer.latest_seq + 1, |event| event.seq);
+            let events = buffer
+                .events
+                .iter()
+                .rev()
+                .take_while(|event| event.seq > params.since_seq)
+                .filter(|event| event.level <= params.min_level)
+                .filter(|event| {
+                    params
+                        .target_prefix
+                        .as_ref()
+                        .map_or(true, |prefix| event.target.starts_with(prefix.as_str()))
+                })
+                .take(params.limit)
+                .cloned()
+                .collect();
+            (events, oldest_seq > params.since_seq + 1)
+        };
+        QueryResponse {
+            events: events.iter().rev().map(|event| LogEvent::clone(event)).collect(),
+            gap,
+        }
+    }
+}
+
+impl LogSink for RingBufferSink {
+    fn write(&self, record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut buffer = self.buffer.lock().unwrap();
+        buffer.latest_seq += 1;
+        let event = Arc::new(LogEvent {
+            seq: buffer.latest_seq,
+            level: record.level(),
+            target: record.target().to_owned(),
+            line: message.to_owned(),
+            attachment: attach::current_attachment().map(|attachment| Attachment::clone(&attachment)),
+        });
+        if buffer.events.len() == self.capacity {
+            buffer.events.pop_front();
+        }
+        buffer.events.push_back(event);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(sink: &RingBufferSink, level: Level, target: &str, message: &str) {
+        sink.write(&Record::builder().level(level).target(target).build(), message)
+            .unwrap();
+    }
+
+    fn lines(response: &QueryResponse) -> Vec<(u64, &str)> {
+        response.events.iter().map(|event| (event.seq, event.line.as_str())).collect()
+    }
+
+    #[test]
+    fn keeps_the_latest_events_in_order() {
+        let sink = RingBufferSink::new(3);
+        assert_eq!(sink.latest_seq(), 0);
+        assert!(!sink.query(&QueryParams::default()).gap);
+        for i in 1..=5 {
+            write(&sink, Level::Info, "app", &format!("line {}", i));
+        }
+
+        assert_eq!(sink.latest_seq(), 5);
+        let response = sink.query(&QueryParams::default());
+        assert_eq!(lines(&response), [(3, "line 3"), (4, "line 4"), (5, "line 5")]);
+        assert!(response.gap);
+    }
+
+    #[test]
+    fn gap_detection() {
+        let sink = RingBufferSink::new(3);
+        write(&sink, Level::Info, "app", "line 1");
+        write(&sink, Level::Info, "app", "line 2");
+        let since_seq = sink.latest_seq();
+        write(&sink, Level::Info, "app", "line 3");
+
+        let poll = |since_seq| {
+            sink.query(&QueryParams {
+                since_seq,
+                ..Default::default()
+            })
+        };
+        let response = poll(since_seq);
+        assert_eq!(lines(&response), [(3, "line 3")]);
+        assert!(!response.gap);
+
+        for i in 4..=7 {
+            write(&sink, Level::Info, "app", &format!("line {}", i));
+        }
+        let response = poll(3);
+        assert_eq!(lines(&response), [(5, "line 5"), (6, "line 6"), (7, "line 7")]);
+        assert!(response.gap);
+        assert!(!poll(4).gap);
+        assert!(!poll(7).gap);
+        assert!(poll(7).events.is_empty());
+    }
+
+    #[test]
+    fn filtering() {
+        let sink = RingBufferSink::new(10);
+        write(&sink, Level::Warn, "app::db", "warn db");
+        write(&sink, Level::Info, "app::db", "info db");
+        write(&sink, Level::Error, "app::http", "error http");
+        write(&sink, Level::Error, "app::db", "error db");
+        write(&sink, Level::Debug, "app::db", "debug db");
+
+        let response = sink.query(&QueryParams {
+            min_level: Level::Warn,
+            ..Default::default()
+        });
+        assert_eq!(lines(&response), [(1, "warn db"), (3, "error http"), (4, "error db")]);
+
+        let response = sink.query(&QueryParams {
+            min_level: Level::Warn,
+            target_prefix: Some("app::db".to_owned()),
+            limit: 1,
+            ..Default::default()
+        });
+        assert_eq!(lines(&response), [(4, "error db")]);
+
+        let response = sink.query(&QueryParams {
+            since_seq: 2,
+            target_prefix: Some("app::db".to_owned()),
+            ..Default::default()
+        });
+        assert_eq!(lines(&response), [(4, "error db"), (5, "debug db")]);
+        assert!(!response.gap);
+    }
+}
@@ -0,0 +1,202 @@
+use std::sync::{
+    atomic::{AtomicI64, AtomicUsize, Ordering},
+    Mutex,
+};
+
+use chrono::{DateTime, Datelike, Duration, Local, LocalResult, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Weekday};
+use log::Level;
+
+/// A daily time window raising the minimum level, see [`Glog::with_schedule`](crate::Glog::with_schedule)
+///
+/// Windows are evaluated on the local wall clock: the start is inclusive, the end exclusive, so a
+/// record at the instant of a switch already gets the new floor. A window ending before it starts
+/// runs through midnight, one ending when it starts lasts the whole day. When daylight saving time
+/// skips the start or end of a window the switch happens at the first time after the gap, when
+/// it repeats a time the window follows the wall clock through both repetitions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleWindow {
+    /// Local time the window starts at
+    pub start: NaiveTime,
+    /// Local time the window ends at
+    pub end: NaiveTime,
+    /// Days the window starts on, every day if empty
+    pub weekdays: Vec<Weekday>,
+    /// The most verbose level logged during the window
+    pub floor: Level,
+}
+
+impl ScheduleWindow {
+    fn starts_on(&self, day: NaiveDate) -> bool {
+        self.weekdays.is_empty() || self.weekdays.contains(&day.weekday())
+    }
+
+    fn is_active(&self, now: NaiveDateTime) -> bool {
+        let (day, time) = (now.date(), now.time());
+        if self.start < self.end {
+            return self.starts_on(day) && self.start <= time && time < self.end;
+        }
+        (self.starts_on(day) && self.start <= time) || (time < self.end && day.pred_opt().is_some_and(|day| self.starts_on(day)))
+    }
+}
+
+/// The windows as `start-end days floor` separated by commas, for the audit record of a change
+pub(crate) fn describe(windows: &[ScheduleWindow]) -> String {
+    let windows: Vec<String> = windows
+        .iter()
+        .map(|window| {
+            let days: Vec<String> = window.weekdays.iter().map(Weekday::to_string).collect();
+            let days = if days.is_empty() { "daily".to_owned() } else { days.join("/") };
+            format!("{}-{} {} {}", window.start, window.end, days, window.floor)
+        })
+        .collect();
+    windows.join(", ")
+}
+
+/// The strictest floor of the windows active at the local time `now`
+fn active_floor(windows: &[ScheduleWindow], now: NaiveDateTime) -> Level {
+    windows
+        .iter()
+        .filter(|window| window.is_active(now))
+        .map(|window| window.floor)
+        .min()
+        .unwrap_or(Level::Trace)
+}
+
+/// The instants the local wall clock shows `time`, the end of the gap if it is skipped
+fn instants(time: NaiveDateTime) -> Vec<DateTime<Local>> {
+    match Local.from_local_datetime(&time) {
+        LocalResult::Single(instant) => vec![instant],
+        LocalResult::Ambiguous(first, second) => vec![first, second],
+        LocalResult::None => (1..=24 * 60)
+            .find_map(|minutes| Local.from_local_datetime(&(time + Duration::minutes(minutes))).earliest())
+            .into_iter()
+            .collect(),
+    }
+}
+
+/// The first instant after `now` at which the active floor may change
+///
+/// Every window switches at its start and end on every day, this includes switches which don't
+/// change anything on days the window doesn't start on.
+fn next_boundary(windows: &[ScheduleWindow], now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let today = now.date_naive();
+    (0..3)
+        .filter_map(|days| today.checked_add_signed(Duration::days(days)))
+        .flat_map(|day| {
+            windows
+                .iter()
+                .flat_map(move |window| [window.start, window.end].map(|time| day.and_time(time)))
+        })
+        .flat_map(instants)
+        .filter(|instant| *instant > now)
+        .min()
+}
+
+/// The windows of a logger with the floor cached until the next boundary
+pub(crate) struct Schedule {
+    windows: Mutex<Vec<ScheduleWindow>>,
+    /// Index of the floor in [`LEVELS`]
+    floor: AtomicUsize,
+    /// Microseconds since the epoch until which `floor` applies
+    next_boundary: AtomicI64,
+}
+
+const LEVELS: [Level; 5] = [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace];
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule {
+            windows: Mutex::new(Vec::new()),
+            floor: AtomicUsize::new(Schedule::index(Level::Trace)),
+            next_boundary: AtomicI64::new(i64::MAX),
+        }
+    }
+}
+
+impl Schedule {
+    fn index(level: Level) -> usize {
+        level as usize - 1
+    }
+
+    /// Replace the windows, they are evaluated on the next record, returns the previous ones
+    pub(crate) fn set(&self, windows: Vec<ScheduleWindow>) -> Vec<ScheduleWindow> {
+        let mut current = self.windows.lock().unwrap();
+        let next_boundary = if windows.is_empty() { i64::MAX } else { i64::MIN };
+        let previous = std::mem::replace(&mut *current, windows);
+        self.floor.store(Schedule::index(Level::Trace), Ordering::Relaxed);
+        self.next_boundary.store(next_boundary, Ordering::Release);
+        previous
+    }
+
+    /// The floor at the time returned by `now`, which is only called once the cached floor expired
+    pub(crate) fn floor(&self, now: impl FnOnce() -> DateTime<Local>) -> Level {
+        let valid_until = self.next_boundary.load(Ordering::Acquire);
+        if valid_until == i64::MAX {
+            return LEVELS[self.floor.load(Ordering::Relaxed)];
+        }
+        let now = now();
+        if now.timestamp_micros() < valid_until {
+            return LEVELS[self.floor.load(Ordering::Relaxed)];
+        }
+        let windows = self.windows.lock().unwrap();
+        let floor = active_floor(&windows, now.naive_local());
+        let valid_until = match next_boundary(&windows, now) {
+            Some(instant) => instant.timestamp_micros(),
+            // Evaluate every record if no boundary can be found
+            None if !windows.is_empty() => i64::MIN,
+            None => i64::MAX,
+        };
+        self.floor.store(Schedule::index(floor), Ordering::Relaxed);
+        self.next_boundary.store(valid_until, Ordering::Release);
+        floor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(start: &str, end: &str, weekdays: &[Weekday], floor: Level) -> ScheduleWindow {
+        ScheduleWindow {
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+            weekdays: weekdays.to_vec(),
+            floor,
+        }
+    }
+
+    fn at(time: &str) -> NaiveDateTime {
+        // 2021-04-05 is a Monday
+        NaiveDateTime::parse_from_str(&format!("2021-04-{}", time), "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn windows_are_active_from_start_until_end() {
+        let windows = [
+            window("17:00:00", "09:00:00", &[], Level::Info),
+            window("00:00:00", "00:00:00", &[Weekday::Sat, Weekday::Sun], Level::Warn),
+        ];
+        let cases = [
+            ("05 08:59:59", Level::Info),
+            ("05 09:00:00", Level::Trace),
+            ("05 16:59:59", Level::Trace),
+            ("05 17:00:00", Level::Info),
+            ("06 03:00:00", Level::Info),
+            ("10 12:00:00", Level::Warn),
+            ("11 23:00:00", Level::Warn),
+            ("12 08:00:00", Level::Info),
+        ];
+        for (time, floor) in cases.iter() {
+            assert_eq!(active_floor(&windows, at(time)), *floor, "{}", time);
+        }
+    }
+
+    #[test]
+    fn weekdays_are_the_days_a_window_starts_on() {
+        let windows = [window("22:00:00", "06:00:00", &[Weekday::Mon], Level::Error)];
+        assert_eq!(active_floor(&windows, at("05 23:00:00")), Level::Error);
+        assert_eq!(active_floor(&windows, at("06 05:00:00")), Level::Error);
+        assert_eq!(active_floor(&windows, at("06 23:00:00")), Level::Trace);
+        assert_eq!(active_floor(&windows, at("05 05:00:00")), Level::Trace);
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::{Level, Record};
+
+use crate::{redact::INTERNAL_TARGET, LogSink};
+
+/// Time a sink gets to accept the test record of [`GlogHandle::self_test`](crate::GlogHandle::self_test)
+pub(crate) const SINK_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Result of [`GlogHandle::self_test`](crate::GlogHandle::self_test)
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SelfTestReport {
+    /// Unique tag in the messages of the test records, which are logged with the `glog::internal` target
+    pub tag: String,
+    /// The checked components: the log file of every level from the most severe, then every sink
+    pub checks: Vec<SelfTestCheck>,
+    /// Whether a console was found at initialization, see [`console_available`](crate::console_available)
+    pub console_available: bool,
+}
+
+/// The check of a single component in a [`SelfTestReport`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SelfTestCheck {
+    /// The component, like `log file WARN` or `sink 0` with the index of the sink in the order they were added
+    pub component: String,
+    /// Why the check failed, `None` if it passed
+    pub error: Option<String>,
+}
+
+impl SelfTestReport {
+    /// Whether every check passed
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.error.is_none())
+    }
+
+    /// The checks which failed
+    pub fn failures(&self) -> impl Iterator<Item = &SelfTestCheck> {
+        self.checks.iter().filter(|check| check.error.is_some())
+    }
+}
+
+/// A tag unique to this process and self test
+pub(crate) fn unique_tag() -> String {
+    static RUNS: AtomicU64 = AtomicU64::new(0);
+    format!(
+        "glog-self-test-{}-{}",
+        std::process::id(),
+        RUNS.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Write a test record formatted as `message` to `sink` on another thread, waiting at most `timeout`
+pub(crate) fn ping_sink(sink: Arc<dyn LogSink>, message: String, timeout: Duration) -> Result<(), String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("glog-self-test".to_owned())
+        .spawn(move || {
+            let written = panic::catch_unwind(AssertUnwindSafe(|| {
+                sink.write(
+                    &Record::builder()
+                        .level(Level::Info)
+                        .target(INTERNAL_TARGET)
+                        .args(format_args!("{}", message))
+                        .build(),
+                    &message,
+                )
+                .map_err(|why| why.to_string())
+            }))
+            .unwrap_or_else(|payload| Err(format!("sink panicked: {}", crate::panic_message(&*payload))));
+            let _ = sender.send(written);
+        })
+        .map_err(|why| format!("couldn't spawn a thread: {}", why))?;
+    receiver
+        .recv_timeout(timeout)
+        .unwrap_or_else(|_| Err(format!("no acknowledgment within {:?}", timeout)))
+}
@@ -0,0 +1,219 @@
+use std::{
+    borrow::Cow,
+    error::Error,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+        RwLock,
+    },
+};
+
+use log::{Level, Record};
+
+/// Called with the index of the sink, the message and the error if a sink fails
+pub(crate) type SinkErrorHandler = Arc<dyn Fn(usize, &str, Box<dyn Error>) + Send + Sync>;
+
+/// An additional destination for log records, next to stderr and the log files
+///
+/// Sinks receive every record which is logged, independent of `logtostderr`.
+/// Add them with [`Glog::with_sink`](crate::Glog::with_sink). Errors and panics of a sink are
+/// passed to the [sink error handler](crate::Glog::with_sink_error_handler).
+///
+/// # Example
+///
+/// ```
+/// use std::{error::Error, sync::Mutex};
+/// use log::*;
+/// use glog::{Flags, LogSink};
+///
+/// #[derive(Default)]
+/// struct Errors(Mutex<Vec<String>>);
+///
+/// impl LogSink for Errors {
+///     fn write(&self, record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+///         if record.level() == Level::Error {
+///             self.0.lock().unwrap().push(message.to_owned());
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// glog::new().with_sink(Errors::default()).init(Flags::default()).unwrap();
+/// ```
+pub trait LogSink: Send + Sync {
+    /// Write `record`, which was formatted as the glog line `message`
+    fn write(&self, record: &Record, message: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Flush records buffered by the sink
+    fn flush(&self) {}
+
+    /// The format the sink needs its messages in, overriding the [`SinkConfig::format`] it was added with
+    fn required_format(&self) -> Option<LogFormat> {
+        None
+    }
+}
+
+/// How the message passed to a [`LogSink`] is formatted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LogFormat {
+    /// The glog line written to the log files
+    #[default]
+    Glog,
+    /// A JSON object with the `timestamp`, `level`, `thread`, `file`, `line`, `target`, `context`
+    /// and `message` of the record
+    Json,
+}
+
+/// Which records a [`LogSink`] receives and how they are formatted
+///
+/// Used with [`Glog::add_sink_with`](crate::Glog::add_sink_with). The default matches
+/// [`Glog::with_sink`](crate::Glog::with_sink).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SinkConfig {
+    /// The least severe level the sink receives, even if it is below `minloglevel`.
+    /// `None` receives the records enabled by `minloglevel`.
+    pub min_level: Option<Level>,
+    /// The format of the message
+    pub format: LogFormat,
+    /// Whether the message contains the [context](crate::context) of the record
+    pub include_context: bool,
+}
+
+impl Default for SinkConfig {
+    fn default() -> Self {
+        SinkConfig {
+            min_level: None,
+            format: LogFormat::Glog,
+            include_context: true,
+        }
+    }
+}
+
+/// Identifies a sink to remove it with [`GlogHandle::remove_sink`](crate::GlogHandle::remove_sink)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SinkId(u64);
+
+#[derive(Clone)]
+struct Registered {
+    id: SinkId,
+    config: SinkConfig,
+    sink: Arc<dyn LogSink>,
+}
+
+/// The sinks of a logger, shared by all its clones
+///
+/// Writers take a snapshot of the list, so sinks may log themselves and be added or removed while
+/// records are written.
+#[derive(Default)]
+pub(crate) struct Sinks {
+    sinks: RwLock<Arc<Vec<Registered>>>,
+    next_id: AtomicU64,
+    /// The number of sinks, checked for every record without taking the lock
+    len: AtomicUsize,
+}
+
+impl Sinks {
+    fn snapshot(&self) -> Arc<Vec<Registered>> {
+        self.sinks.read().unwrap().clone()
+    }
+
+    pub(crate) fn add(&self, mut config: SinkConfig, sink: Arc<dyn LogSink>) -> SinkId {
+        config.format = sink.required_format().unwrap_or(config.format);
+        let id = SinkId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        let mut sinks = self.sinks.write().unwrap();
+        let mut updated = Vec::clone(&sinks);
+        updated.push(Registered {
+            id,
+            config,
+            sink,
+        });
+        self.len.store(updated.len(), Ordering::Relaxed);
+        *sinks = Arc::new(updated);
+        id
+    }
+
+    /// Returns whether the sink was registered
+    pub(crate) fn remove(&self, id: SinkId) -> bool {
+        let mut sinks = self.sinks.write().unwrap();
+        let updated: Vec<Registered> = sinks.iter().filter(|registered| registered.id != id).cloned().collect();
+        let removed = updated.len() != sinks.len();
+        self.len.store(updated.len(), Ordering::Relaxed);
+        *sinks = Arc::new(updated);
+        removed
+    }
+
+    /// Change the format of the sink at `index`, returns whether there is one
+    pub(crate) fn set_format(&self, index: usize, format: LogFormat) -> bool {
+        let mut sinks = self.sinks.write().unwrap();
+        let mut updated = Vec::clone(&sinks);
+        let Some(registered) = updated.get_mut(index) else {
+            return false;
+        };
+        registered.config.format = registered.sink.required_format().unwrap_or(format);
+        *sinks = Arc::new(updated);
+        true
+    }
+
+    /// All sinks in the order they were added
+    pub(crate) fn list(&self) -> Vec<Arc<dyn LogSink>> {
+        self.snapshot().iter().map(|registered| registered.sink.clone()).collect()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len.load(Ordering::Relaxed) == 0
+    }
+
+    /// The most verbose `min_level` of all sinks
+    pub(crate) fn max_level(&self) -> Option<Level> {
+        self.snapshot()
+            .iter()
+            .filter_map(|registered| registered.config.min_level)
+            .max()
+    }
+
+    /// Write `record` to every sink accepting its level, all sinks if `any_level`
+    ///
+    /// Every requested variant of the message is created once with `format`, called with the
+    /// format and whether to include the context. Errors and panics of the sinks are passed to
+    /// `on_error` with the index of the sink, returns the number of them.
+    pub(crate) fn write<'a>(
+        &self,
+        record: &Record,
+        minloglevel: Level,
+        any_level: bool,
+        mut format: impl FnMut(LogFormat, bool) -> Cow<'a, str>,
+        on_error: &SinkErrorHandler,
+    ) -> u64 {
+        let mut errors = 0;
+        let mut messages: [Option<Cow<'a, str>>; 4] = Default::default();
+        for (index, registered) in self.snapshot().iter().enumerate() {
+            let SinkConfig {
+                min_level,
+                format: log_format,
+                include_context,
+            } = registered.config;
+            if !any_level && record.level() > min_level.unwrap_or(minloglevel) {
+                continue;
+            }
+            let variant = match log_format {
+                LogFormat::Glog => 0,
+                LogFormat::Json => 2,
+            } + include_context as usize;
+            let message = messages[variant].get_or_insert_with(|| format(log_format, include_context));
+            let written = panic::catch_unwind(AssertUnwindSafe(|| registered.sink.write(record, message)))
+                .unwrap_or_else(|payload| Err(format!("sink panicked: {}", crate::panic_message(&*payload)).into()));
+            if let Err(why) = written {
+                errors += 1;
+                on_error(index, message, why);
+            }
+        }
+        errors
+    }
+
+    pub(crate) fn flush(&self) {
+        for registered in self.snapshot().iter() {
+            registered.sink.flush();
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::{
+    fmt::{self, Write as _},
+    panic::{RefUnwindSafe, UnwindSafe},
+};
+
+use crate::{Glog, GlogHandle};
+
+/// A [`slog::Drain`] writing records in the glog format through the registered logger
+///
+/// Key value pairs of the record and the logger are appended to the message as `key=value`.
+///
+/// # Example
+///
+/// ```
+/// use glog::{Flags, GlogFmtDrain};
+/// use slog::{info, o, Drain, Logger};
+///
+/// let handle = glog::new().try_init(Flags::default()).unwrap();
+/// let logger = Logger::root(GlogFmtDrain::new(&handle).fuse(), o!("version" => "1.0"));
+///
+/// info!(logger, "A slog message"; "user" => "alice");
+/// // I0401 12:34:56.987654   123 doc.rs:8] A slog message user=alice version=1.0
+/// ```
+pub struct GlogFmtDrain {
+    logger: Glog,
+}
+
+impl GlogFmtDrain {
+    /// Create a drain writing to the logger of `handle`
+    pub fn new(handle: &GlogHandle) -> Self {
+        GlogFmtDrain {
+            logger: handle.logger.clone(),
+        }
+    }
+}
+
+// Required by slog::Logger, the shared state of the logger is only changed behind locks which are
+// poisoned by a panic
+impl UnwindSafe for GlogFmtDrain {}
+impl RefUnwindSafe for GlogFmtDrain {}
+
+/// Map a [`slog::Level`] to the [`log::Level`] it is logged as
+pub fn slog_level_to_log(level: slog::Level) -> log::Level {
+    match level {
+        slog::Level::Critical | slog::Level::Error => log::Level::Error,
+        slog::Level::Warning => log::Level::Warn,
+        slog::Level::Info => log::Level::Info,
+        slog::Level::Debug => log::Level::Debug,
+        slog::Level::Trace => log::Level::Trace,
+    }
+}
+
+/// Collects key value pairs as ` key=value`
+struct KeyValues(String);
+
+impl slog::Serializer for KeyValues {
+    fn emit_arguments(&mut self, key: slog::Key, value: &fmt::Arguments) -> slog::Result {
+        write!(self.0, " {}={}", key, value)?;
+        Ok(())
+    }
+}
+
+impl slog::Drain for GlogFmtDrain {
+    type Err = slog::Never;
+    type Ok = ();
+
+    fn log(&self, record: &slog::Record, values: &slog::OwnedKVList) -> Result<(), slog::Never> {
+        let mut key_values = KeyValues(String::new());
+        // Serializing into a String can't fail
+        let _ = slog::KV::serialize(&record.kv(), record, &mut key_values);
+        let _ = slog::KV::serialize(values, record, &mut key_values);
+        log::Log::log(
+            &self.logger,
+            &log::Record::builder()
+                .level(slog_level_to_log(record.level()))
+                .target(record.module())
+                .file(Some(record.file()))
+                .line(Some(record.line()))
+                .args(format_args!("{}{}", record.msg(), key_values.0))
+                .build(),
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use log::Level;
+
+use crate::target_policy::Rejection;
+
+/// Counters of everything the logger did, shared between all clones of a [`Glog`](crate::Glog)
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    messages: [AtomicU64; 5],
+    bytes: [AtomicU64; 5],
+    flushes: AtomicU64,
+    clock_steps_backwards: AtomicU64,
+    tee_write_errors: AtomicU64,
+    format_panics: AtomicU64,
+    lock_timeouts: AtomicU64,
+    suppressed: AtomicU64,
+    sampled_out: AtomicU64,
+    rate_limited: AtomicU64,
+    rotations: AtomicU64,
+    sink_errors: AtomicU64,
+}
+
+impl Stats {
+    fn index(level: Level) -> usize {
+        level as usize - 1
+    }
+
+    pub(crate) fn record_message(&self, level: Level, bytes: usize) {
+        self.messages[Stats::index(level)].fetch_add(1, Ordering::Relaxed);
+        self.bytes[Stats::index(level)].fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_flush(&self) {
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the number of previously recorded steps
+    pub(crate) fn record_clock_step_backwards(&self) -> u64 {
+        self.clock_steps_backwards.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(crate) fn record_format_panic(&self) {
+        self.format_panics.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_suppressed(&self) {
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_rejection(&self, rejection: Rejection) {
+        match rejection {
+            Rejection::Sampled => &self.sampled_out,
+            Rejection::RateLimited => &self.rate_limited,
+        }
+        .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_rotation(&self) {
+        self.rotations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_sink_errors(&self, errors: u64) {
+        if errors > 0 {
+            self.sink_errors.fetch_add(errors, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_lock_timeout(&self) {
+        self.lock_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_tee_write_errors(&self, errors: u64) {
+        if errors > 0 {
+            self.tee_write_errors.fetch_add(errors, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> StatsSnapshot {
+        let load = |counters: &[AtomicU64; 5], level: Level| counters[Stats::index(level)].load(Ordering::Relaxed);
+        let per_level = |counters: &[AtomicU64; 5]| LevelCounters {
+            trace: load(counters, Level::Trace),
+            debug: load(counters, Level::Debug),
+            info: load(counters, Level::Info),
+            warn: load(counters, Level::Warn),
+            error: load(counters, Level::Error),
+        };
+        StatsSnapshot {
+            messages: per_level(&self.messages),
+            bytes: per_level(&self.bytes),
+            flushes: self.flushes.load(Ordering::Relaxed),
+            clock_steps_backwards: self.clock_steps_backwards.load(Ordering::Relaxed),
+            tee_write_errors: self.tee_write_errors.load(Ordering::Relaxed),
+            format_panics: self.format_panics.load(Ordering::Relaxed),
+            lock_timeouts: self.lock_timeouts.load(Ordering::Relaxed),
+            attachments_dropped: crate::attach::dropped(),
+            suppressed: self.suppressed.load(Ordering::Relaxed),
+            sampled_out: self.sampled_out.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            rotations: self.rotations.load(Ordering::Relaxed),
+            sink_errors: self.sink_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A counter for each log level
+///
+/// The counters are kept for the level of the record, independent of the level it is logged as
+/// when [`reduced_log_levels`](crate::Glog::reduced_log_levels) is active.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LevelCounters {
+    pub trace: u64,
+    pub debug: u64,
+    pub info: u64,
+    pub warn: u64,
+    pub error: u64,
+}
+
+impl LevelCounters {
+    /// The counter for `level`
+    pub fn get(&self, level: Level) -> u64 {
+        match level {
+            Level::Trace => self.trace,
+            Level::Debug => self.debug,
+            Level::Info => self.info,
+            Level::Warn => self.warn,
+            Level::Error => self.error,
+        }
+    }
+
+    /// Sum of the counters of all levels
+    pub fn total(&self) -> u64 {
+        self.trace + self.debug + self.info + self.warn + self.error
+    }
+}
+
+/// Point in time copy of the logging statistics
+///
+/// All counters are monotonically increasing since the logger was created, which makes them
+/// suitable to be exported as counters to metric collectors like prometheus.
+/// Existing fields are kept stable, new counters may be added in future versions.
+///
+/// The records dropped before they were written are counted by reason in `suppressed`,
+/// `sampled_out` and `rate_limited`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StatsSnapshot {
+    /// Number of logged messages
+    pub messages: LevelCounters,
+    /// Number of bytes of the formatted messages, without the trailing newline
+    pub bytes: LevelCounters,
+    /// Number of times the logger was flushed
+    pub flushes: u64,
+    /// Number of times the wall clock went backwards with `monotonic_timestamps` enabled
+    pub clock_steps_backwards: u64,
+    /// Number of failed writes and flushes of the streams added with [`Glog::tee`](crate::Glog::tee)
+    pub tee_write_errors: u64,
+    /// Number of records replaced by an error because formatting their message panicked
+    pub format_panics: u64,
+    /// Number of writes done without the lock because `lock_shared_files` couldn't take it in time
+    pub lock_timeouts: u64,
+    /// Number of [attachments](crate::attach) of all threads of the process dropped before a record consumed them
+    pub attachments_dropped: u64,
+    /// Number of records dropped because of `suppress_at`
+    pub suppressed: u64,
+    /// Number of records dropped by the sampling of the `target_policy`
+    pub sampled_out: u64,
+    /// Number of records dropped by the rate limits of the `target_policy`
+    pub rate_limited: u64,
+    /// Number of log files recreated after they were rotated away, see
+    /// [`Glog::with_log_rotation_hook`](crate::Glog::with_log_rotation_hook)
+    pub rotations: u64,
+    /// Number of failed writes of the sinks, see [`Glog::with_sink_error_handler`](crate::Glog::with_sink_error_handler)
+    pub sink_errors: u64,
+}
+
+/// Calls the callback of [`Glog::on_stats_interval`](crate::Glog::on_stats_interval) until it is dropped
+#[must_use = "the callback stops once the StatsInterval is dropped"]
+pub struct StatsInterval {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatsInterval {
+    pub(crate) fn spawn<F>(stats: Arc<Stats>, interval: Duration, callback: F) -> Self
+    where
+        F: Fn(&StatsSnapshot) + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("glog-stats".to_owned())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    callback(&stats.snapshot());
+                }
+            })
+            .expect("couldn't spawn statistics thread");
+        StatsInterval {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stop calling the callback, waiting for a running call to return
+    pub fn stop(self) {}
+}
+
+impl Drop for StatsInterval {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            // The callback may drop its own guard
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    io::{self, Write},
+    panic::Location,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use log::{Level, Log, Metadata, Record};
+
+use crate::{ByteSize, Glog};
+
+/// The group id of the next stream
+static NEXT_GROUP: AtomicU64 = AtomicU64::new(1);
+
+/// Logs the lines written to it, see [`GlogHandle::writer`](crate::GlogHandle::writer)
+///
+/// Every complete line is logged as its own record, prefixed with the name and group id of the
+/// stream and its part number, like `[report #3 37/…] `. A last line without a newline is logged
+/// when the writer is dropped, followed by a summary record with the number of lines and bytes.
+///
+/// Every writer buffers a single line of its own, so writers used by different threads at the
+/// same time don't mix their lines, though their records are interleaved in the log files.
+pub struct StreamWriter<'a> {
+    logger: &'a Glog,
+    level: Level,
+    name: String,
+    group: u64,
+    file: &'static str,
+    line: u32,
+    enabled: bool,
+    max_bytes: Option<u64>,
+    /// The bytes after the last newline
+    pending: Vec<u8>,
+    lines: u64,
+    bytes: u64,
+    truncated: bool,
+}
+
+impl<'a> StreamWriter<'a> {
+    pub(crate) fn new(logger: &'a Glog, level: Level, name: &str, location: &'static Location<'static>) -> Self {
+        let enabled = logger.enabled(&Metadata::builder().level(level).build());
+        StreamWriter {
+            logger,
+            level,
+            name: name.to_owned(),
+            group: NEXT_GROUP.fetch_add(1, Ordering::Relaxed),
+            file: location.file(),
+            line: location.line(),
+            enabled,
+            max_bytes: None,
+            pending: Vec::new(),
+            lines: 0,
+            bytes: 0,
+            truncated: false,
+        }
+    }
+
+    /// Stop the stream with a truncation record once it exceeds `max_bytes`
+    ///
+    /// The writes afterwards fail, so e.g. [`io::copy`] returns early.
+    pub fn with_max_bytes(mut self, max_bytes: ByteSize) -> Self {
+        self.max_bytes = Some(max_bytes.as_bytes());
+        self
+    }
+
+    /// The id shared by the records of this stream
+    pub fn group(&self) -> u64 {
+        self.group
+    }
+
+    fn log(&self, args: std::fmt::Arguments<'_>) {
+        self.logger.log(
+            &Record::builder()
+                .level(self.level)
+                .file(Some(self.file))
+                .line(Some(self.line))
+                .args(args)
+                .build(),
+        );
+    }
+
+    fn log_part(&mut self, line: &[u8]) {
+        self.lines += 1;
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        self.log(format_args!(
+            "[{} #{} {}/…] {}",
+            self.name,
+            self.group,
+            self.lines,
+            String::from_utf8_lossy(line)
+        ));
+    }
+
+    /// Log the complete lines of the pending bytes
+    fn log_lines(&mut self) {
+        let mut start = 0;
+        let pending = std::mem::take(&mut self.pending);
+        for end in pending
+            .iter()
+            .enumerate()
+            .filter(|(_, byte)| **byte == b'\n')
+            .map(|(end, _)| end)
+        {
+            self.log_part(&pending[start..end]);
+            start = end + 1;
+        }
+        self.pending = pending;
+        self.pending.drain(..start);
+    }
+
+    fn log_rest(&mut self) {
+        if !self.pending.is_empty() {
+            let rest = std::mem::take(&mut self.pending);
+            self.log_part(&rest);
+        }
+    }
+}
+
+impl Write for StreamWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.truncated {
+            return Err(io::Error::other(format!(
+                "log stream {} #{} was truncated",
+                self.name, self.group
+            )));
+        }
+        let room = self.max_bytes.map_or(u64::MAX, |max_bytes| max_bytes - self.bytes);
+        let accepted = (buf.len() as u64).min(room) as usize;
+        self.bytes += accepted as u64;
+        if self.enabled {
+            self.pending.extend_from_slice(&buf[..accepted]);
+            self.log_lines();
+        }
+        if accepted < buf.len() {
+            self.truncated = true;
+            self.log_rest();
+            self.log(format_args!(
+                "[{} #{} truncated] stopped after {} bytes",
+                self.name, self.group, self.bytes
+            ));
+            if accepted == 0 {
+                return self.write(buf);
+            }
+        }
+        Ok(accepted)
+    }
+
+    /// Flush the logger, a line without a newline stays buffered
+    fn flush(&mut self) -> io::Result<()> {
+        self.logger.flush();
+        Ok(())
+    }
+}
+
+impl Drop for StreamWriter<'_> {
+    fn drop(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        self.log_rest();
+        self.log(format_args!(
+            "[{} #{} done] {} lines, {} bytes",
+            self.name, self.group, self.lines, self.bytes
+        ));
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use log::Level;
+
+/// The last formatted lines of every level, see [`Glog::tail`](crate::Glog::tail)
+///
+/// Like the log files the buffer of a level also holds the lines of more severe levels.
+#[derive(Default)]
+pub(crate) struct TailBuffers {
+    levels: [Mutex<VecDeque<Arc<str>>>; 5],
+}
+
+impl TailBuffers {
+    fn index(level: Level) -> usize {
+        level as usize - 1
+    }
+
+    /// Add `line` to the buffers of `level` and all less severe levels, keeping at most `capacity` lines each
+    pub(crate) fn push(&self, level: Level, line: &str, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+        let line: Arc<str> = Arc::from(line);
+        for buffer in &self.levels[TailBuffers::index(level)..] {
+            let mut buffer = buffer.lock().unwrap();
+            while buffer.len() >= capacity {
+                buffer.pop_front();
+            }
+            buffer.push_back(line.clone());
+        }
+    }
+
+    /// Up to `n` of the latest lines of `level`, oldest first
+    pub(crate) fn tail(&self, level: Level, n: usize) -> Vec<String> {
+        let buffer = self.levels[TailBuffers::index(level)].lock().unwrap();
+        buffer
+            .iter()
+            .skip(buffer.len().saturating_sub(n))
+            .map(|line| line.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_lines_per_level() {
+        let buffers = TailBuffers::default();
+        buffers.push(Level::Info, "info 1", 2);
+        buffers.push(Level::Error, "error 1", 2);
+        buffers.push(Level::Info, "info 2", 2);
+
+        assert_eq!(buffers.tail(Level::Info, 5), ["error 1", "info 2"]);
+        assert_eq!(buffers.tail(Level::Info, 1), ["info 2"]);
+        assert_eq!(buffers.tail(Level::Error, 5), ["error 1"]);
+        assert_eq!(buffers.tail(Level::Trace, 5), ["error 1", "info 2"]);
+        assert!(buffers.tail(Level::Warn, 0).is_empty());
+
+        // Shrinking the capacity drops the oldest lines
+        buffers.push(Level::Warn, "warn 1", 1);
+        assert_eq!(buffers.tail(Level::Info, 5), ["warn 1"]);
+        assert_eq!(buffers.tail(Level::Error, 5), ["error 1"]);
+    }
+}
@@ -0,0 +1,124 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+
+use log::Level;
+
+use crate::{flags::TargetRule, TargetPolicy};
+
+/// A [`TargetPolicy`] with the state of its samplers and rate limiters
+#[derive(Default)]
+pub(crate) struct ActivePolicy {
+    policy: TargetPolicy,
+    /// The state of every rule in the order of [`TargetPolicy::rules`]
+    states: Vec<RuleState>,
+}
+
+#[derive(Default)]
+struct RuleState {
+    /// The number of records seen by the sampler
+    sampled: AtomicU64,
+    /// The start of the current rate limit window in microseconds and the records kept in it
+    window: Mutex<(i64, u32)>,
+}
+
+/// Why [`Matched::admit`] dropped a record
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Rejection {
+    Sampled,
+    RateLimited,
+}
+
+/// The rule of a target with its state
+pub(crate) struct Matched<'a> {
+    rule: &'a TargetRule,
+    state: &'a RuleState,
+}
+
+impl ActivePolicy {
+    pub(crate) fn new(policy: TargetPolicy) -> Self {
+        let states = policy.rules().map(|_| RuleState::default()).collect();
+        ActivePolicy {
+            policy,
+            states,
+        }
+    }
+
+    /// The rule of `target` with one lookup for its level, sampler and rate limiter
+    pub(crate) fn lookup(&self, target: &str) -> Option<Matched<'_>> {
+        let position = self.policy.position(target)?;
+        Some(Matched {
+            rule: self.policy.rules().nth(position)?,
+            state: &self.states[position],
+        })
+    }
+
+    /// The most verbose level of a rule, `None` if there are no rules or all are off
+    pub(crate) fn max_level(&self) -> Option<Level> {
+        self.policy.rules().filter_map(|rule| rule.level.to_level()).max()
+    }
+}
+
+impl Matched<'_> {
+    /// The minimum level of the target, `None` if it is off
+    pub(crate) fn level(&self) -> Option<Level> {
+        self.rule.level.to_level()
+    }
+
+    /// Whether the sampler and the rate limiter keep the record, `now_micros` is only called for a rate limit
+    pub(crate) fn admit(&self, now_micros: impl FnOnce() -> i64) -> Result<(), Rejection> {
+        if let Some(sample) = self.rule.sample {
+            // Keeps a record whenever the kept fraction of the seen ones reaches the next whole record
+            let seen = self.state.sampled.fetch_add(1, Ordering::Relaxed) as f64;
+            if ((seen + 1.0) * sample).floor() <= (seen * sample).floor() {
+                return Err(Rejection::Sampled);
+            }
+        }
+        let Some(rate) = self.rule.rate else {
+            return Ok(());
+        };
+        let now = now_micros();
+        let mut window = self.state.window.lock().unwrap();
+        let (start, kept) = &mut *window;
+        if *kept == 0 || now - *start >= rate.per.as_micros() as i64 || now < *start {
+            (*start, *kept) = (now, 0);
+        }
+        if *kept >= rate.count {
+            return Err(Rejection::RateLimited);
+        }
+        *kept += 1;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_and_rate_limits() {
+        let policy = ActivePolicy::new(
+            "a=info@sample:0.25, b=info@rate:2/s, c=info@sample:0.5@rate:1/m"
+                .parse()
+                .unwrap(),
+        );
+        let kept = |target: &str, now: i64| policy.lookup(target).unwrap().admit(|| now).is_ok();
+
+        let sampled: Vec<bool> = (0..8).map(|_| kept("a", 0)).collect();
+        assert_eq!(sampled, [false, false, false, true, false, false, false, true]);
+
+        let limited: Vec<bool> = [0, 1, 999_999, 1_000_000, 1_000_001, 1_500_000]
+            .iter()
+            .map(|now| kept("b::sub", *now))
+            .collect();
+        assert_eq!(limited, [true, true, false, true, true, false]);
+        assert_eq!(policy.lookup("b").unwrap().admit(|| 1_500_001), Err(Rejection::RateLimited));
+        assert_eq!(policy.lookup("a").unwrap().admit(|| 0), Err(Rejection::Sampled));
+
+        // Only records kept by the sampler count towards the rate limit
+        let both: Vec<bool> = (0..4).map(|_| kept("c", 0)).collect();
+        assert_eq!(both, [false, true, false, false]);
+        assert!(policy.lookup("d").is_none());
+    }
+}
@@ -0,0 +1,111 @@
+#[cfg(unix)]
+use std::os::unix::io::RawFd;
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    log_fs::{FileStat, LogWriter},
+    stats::Stats,
+};
+
+/// The streams receiving a copy of every byte written to the log files, see [`Glog::tee`](crate::Glog::tee)
+#[derive(Default)]
+pub(crate) struct Tees(Mutex<Vec<Box<dyn Write + Send>>>);
+
+impl Tees {
+    pub(crate) fn add(&self, tee: Box<dyn Write + Send>) {
+        self.0.lock().unwrap().push(tee);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+
+    /// Write `bytes` to every tee, returning the number of failed tees
+    fn write(&self, bytes: &[u8]) -> u64 {
+        self.0
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .filter_map(|tee| tee.write_all(bytes).err())
+            .count() as u64
+    }
+
+    /// Flush every tee, returning the number of failed tees
+    pub(crate) fn flush(&self) -> u64 {
+        self.0.lock().unwrap().iter_mut().filter_map(|tee| tee.flush().err()).count() as u64
+    }
+}
+
+/// A log file copying everything written to it to the tees
+pub(crate) struct TeeWriter {
+    pub(crate) file: Box<dyn LogWriter>,
+    pub(crate) tees: Arc<Tees>,
+    pub(crate) stats: Arc<Stats>,
+}
+
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.file.write(buf)?;
+        // Failing tees must not affect the log file, they are only counted
+        self.stats.record_tee_write_errors(self.tees.write(&buf[..written]));
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl LogWriter for TeeWriter {
+    fn stat(&self) -> io::Result<FileStat> {
+        self.file.stat()
+    }
+
+    fn set_group(&self, gid: u32) -> io::Result<()> {
+        self.file.set_group(gid)
+    }
+
+    fn preallocate(&self, bytes: u64) -> io::Result<()> {
+        self.file.preallocate(bytes)
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        self.file.sync()
+    }
+
+    #[cfg(unix)]
+    fn raw_fd(&self) -> Option<RawFd> {
+        self.file.raw_fd()
+    }
+
+    fn current_path(&self) -> Option<PathBuf> {
+        self.file.current_path()
+    }
+
+    fn try_lock(&self) -> io::Result<bool> {
+        self.file.try_lock()
+    }
+
+    fn unlock(&self) -> io::Result<()> {
+        self.file.unlock()
+    }
+}
+
+/// Writes to a file descriptor owned by someone else
+#[cfg(unix)]
+pub(crate) struct FdWriter(pub(crate) RawFd);
+
+#[cfg(unix)]
+impl Write for FdWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        nix::unistd::write(self.0, buf).map_err(io::Error::other)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
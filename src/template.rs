@@ -0,0 +1,154 @@
+use std::{fmt, panic::Location};
+
+use log::{Level, Log, Record};
+
+/// Target of the records emitted by [`tmpl!`](crate::tmpl)
+pub const TEMPLATE_TARGET: &str = "glog::template";
+
+pub(crate) type TemplateRenderer = dyn Fn(&str, &[(&str, String)]) -> Option<String> + Send + Sync;
+
+/// Displays a template as `template:<id> key=value ...`, quoting values where necessary
+struct Template<'a> {
+    id: &'a str,
+    params: &'a [(&'a str, String)],
+}
+
+impl fmt::Display for Template<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "template:{}", self.id)?;
+        for (key, value) in self.params {
+            write!(f, " {}=", key)?;
+            if !value.is_empty() && !value.contains(|c: char| c.is_whitespace() || c == '"' || c == '=' || c == '\\') {
+                write!(f, "{}", value)?;
+                continue;
+            }
+            write!(f, "\"")?;
+            for c in value.chars() {
+                match c {
+                    '"' | '\\' => write!(f, "\\{}", c)?,
+                    '\n' => write!(f, "\\n")?,
+                    _ => write!(f, "{}", c)?,
+                }
+            }
+            write!(f, "\"")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the template id and parameters from the message of a template record
+///
+/// Returns `None` if `message` wasn't written by [`tmpl!`](crate::tmpl).
+///
+/// # Example
+///
+/// ```
+/// let (id, params) = glog::parse_template(r#"template:disk_low used=95 mount="/my data""#).unwrap();
+/// assert_eq!(id, "disk_low");
+/// assert_eq!(params, [("used".to_owned(), "95".to_owned()), ("mount".to_owned(), "/my data".to_owned())]);
+/// ```
+pub fn parse_template(message: &str) -> Option<(String, Vec<(String, String)>)> {
+    let message = message.strip_prefix("template:")?;
+    let (id, mut rest) = message.split_once(' ').unwrap_or((message, ""));
+    let mut params = Vec::new();
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=')?;
+        let mut chars = value.chars();
+        let mut parsed = String::new();
+        if value.starts_with('"') {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '"' => break,
+                    '\\' => match chars.next()? {
+                        'n' => parsed.push('\n'),
+                        c => parsed.push(c),
+                    },
+                    c => parsed.push(c),
+                }
+            }
+        } else {
+            parsed = chars.by_ref().take_while(|c| *c != ' ').collect();
+        }
+        params.push((key.to_owned(), parsed));
+        rest = chars.as_str().trim_start_matches(' ');
+    }
+    Some((id.to_owned(), params))
+}
+
+/// The human readable form of a template record, if `renderer` knows its template
+pub(crate) fn render(renderer: &TemplateRenderer, message: &str) -> Option<String> {
+    let (id, params) = parse_template(message)?;
+    let params: Vec<(&str, String)> = params.iter().map(|(key, value)| (key.as_str(), value.clone())).collect();
+    renderer(&id, &params)
+}
+
+pub(crate) fn log_template_to(logger: &dyn Log, level: Level, id: &str, params: &[(&str, String)], location: &Location<'_>) {
+    logger.log(
+        &Record::builder()
+            .level(level)
+            .target(TEMPLATE_TARGET)
+            .file(Some(location.file()))
+            .line(Some(location.line()))
+            .args(format_args!(
+                "{}",
+                Template {
+                    id,
+                    params,
+                }
+            ))
+            .build(),
+    );
+}
+
+/// Log the template `id` with its `params`, usually called through [`tmpl!`](crate::tmpl)
+#[track_caller]
+pub fn log_template(level: Level, id: &str, params: &[(&str, String)]) {
+    if level <= log::max_level() {
+        log_template_to(log::logger(), level, id, params, Location::caller());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(params: &[(&str, String)]) -> String {
+        Template {
+            id: "disk_low",
+            params,
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn round_trip() {
+        let cases: &[&[(&str, String)]] = &[
+            &[],
+            &[("used", "95".to_owned()), ("mount", "/data".to_owned())],
+            &[("empty", String::new()), ("spaces", "a b".to_owned())],
+            &[
+                ("quoted", "say \"hi\"\\".to_owned()),
+                ("lines", "a\nb".to_owned()),
+                ("eq", "a=b".to_owned()),
+            ],
+        ];
+        for params in cases.iter() {
+            let (id, parsed) = parse_template(&format(params)).unwrap();
+            assert_eq!(id, "disk_low");
+            assert_eq!(
+                parsed.iter().map(|(k, v)| (k.as_str(), v.clone())).collect::<Vec<_>>(),
+                *params
+            );
+        }
+    }
+
+    #[test]
+    fn file_form() {
+        assert_eq!(
+            format(&[("used", "95".to_owned()), ("mount", "/my data".to_owned())]),
+            r#"template:disk_low used=95 mount="/my data""#
+        );
+        assert!(parse_template("disk_low used=95").is_none());
+    }
+}
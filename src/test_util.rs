@@ -0,0 +1,288 @@
+//! Assertions on the records captured by a [`RingBufferSink`], for tests of code which logs
+//!
+//! Capture the records with a [`RingBufferSink`] added to the logger with
+//! [`Glog::with_sink`](crate::Glog::with_sink) and assert on them with [`assert_logged!`](crate::assert_logged),
+//! [`assert_logged_n!`](crate::assert_logged_n) and [`assert_not_logged!`](crate::assert_not_logged).
+//! The messages are matched without the glog prefix of the lines, with `contains`, `exact` or, with
+//! the `regex` feature, `regex`. The assertions also accept the events returned by
+//! [`RingBufferSink::scope`] to check the records of one phase of a test.
+//!
+//! # Example
+//!
+//! ```
+//! use glog::{assert_logged, assert_logged_n, assert_not_logged, Flags, RingBufferSink};
+//! use log::*;
+//!
+//! let capture = RingBufferSink::new(100);
+//! glog::new().with_sink(capture.clone()).init(Flags::default()).unwrap();
+//!
+//! let connect = capture.scope(|| warn!("Connection timeout after 5s"));
+//! assert_logged!(connect, Level::Warn, contains "timeout");
+//! assert_logged_n!(connect, Level::Error, 0);
+//!
+//! error!("Login failed");
+//! assert_logged!(capture, exact "Login failed");
+//! assert_not_logged!(capture, contains "password");
+//! ```
+
+use std::fmt;
+
+use log::Level;
+
+use crate::{LogEvent, QueryParams, RingBufferSink};
+
+/// The number of captured lines closest to the expected one shown when an assertion fails
+const NEAREST_LINES: usize = 3;
+
+/// What the message of a captured record has to match
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    Contains(String),
+    Exact(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl Matcher {
+    /// Messages containing `text`
+    pub fn contains(text: impl Into<String>) -> Self {
+        Matcher::Contains(text.into())
+    }
+
+    /// Messages equal to `text`
+    pub fn exact(text: impl Into<String>) -> Self {
+        Matcher::Exact(text.into())
+    }
+
+    /// Messages matching `pattern`, panics if it isn't a valid regular expression
+    #[cfg(feature = "regex")]
+    pub fn regex(pattern: &str) -> Self {
+        Matcher::Regex(regex::Regex::new(pattern).unwrap_or_else(|why| panic!("invalid regex {:?}: {}", pattern, why)))
+    }
+
+    pub fn matches(&self, message: &str) -> bool {
+        match self {
+            Matcher::Contains(text) => message.contains(text.as_str()),
+            Matcher::Exact(text) => message == text,
+            #[cfg(feature = "regex")]
+            Matcher::Regex(regex) => regex.is_match(message),
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Matcher::Contains(text) | Matcher::Exact(text) => text,
+            #[cfg(feature = "regex")]
+            Matcher::Regex(regex) => regex.as_str(),
+        }
+    }
+}
+
+impl fmt::Display for Matcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Matcher::Contains(text) => write!(f, "containing {:?}", text),
+            Matcher::Exact(text) => write!(f, "equal to {:?}", text),
+            #[cfg(feature = "regex")]
+            Matcher::Regex(regex) => write!(f, "matching /{}/", regex),
+        }
+    }
+}
+
+/// The records an assertion is made on
+pub trait Captured {
+    /// The captured events, oldest first
+    fn captured_events(&self) -> Vec<LogEvent>;
+}
+
+impl Captured for RingBufferSink {
+    fn captured_events(&self) -> Vec<LogEvent> {
+        self.query(&QueryParams::default()).events
+    }
+}
+
+impl Captured for [LogEvent] {
+    fn captured_events(&self) -> Vec<LogEvent> {
+        self.to_vec()
+    }
+}
+
+fn matching<'a>(events: &'a [LogEvent], level: Option<Level>, matcher: Option<&'a Matcher>) -> impl Iterator<Item = &'a LogEvent> {
+    events
+        .iter()
+        .filter(move |event| level.map_or(true, |level| event.level == level))
+        .filter(move |event| matcher.map_or(true, |matcher| matcher.matches(event.message())))
+}
+
+/// The expected records in words, e.g. `a record at level WARN containing "timeout"`
+fn describe(records: &str, level: Option<Level>, matcher: Option<&Matcher>) -> String {
+    let level = level.map_or_else(String::new, |level| format!(" at level {}", level));
+    let matcher = matcher.map_or_else(String::new, |matcher| format!(" {}", matcher));
+    format!("{}{}{}", records, level, matcher)
+}
+
+/// The length of the longest common substring of `a` and `b`
+fn common_len(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut previous = vec![0; b.len() + 1];
+    let mut longest = 0;
+    for x in a {
+        let mut current = vec![0; b.len() + 1];
+        for (j, y) in b.iter().enumerate() {
+            if x == y {
+                current[j + 1] = previous[j] + 1;
+                longest = longest.max(current[j + 1]);
+            }
+        }
+        previous = current;
+    }
+    longest
+}
+
+/// The captured lines closest to the expected record, for the failure message
+fn nearest(events: &[LogEvent], level: Option<Level>, matcher: Option<&Matcher>) -> String {
+    if events.is_empty() {
+        return "no records were captured".to_owned();
+    }
+    let mut ranked: Vec<&LogEvent> = events.iter().collect();
+    ranked.sort_by_key(|event| {
+        let similarity = matcher.map_or(0, |matcher| common_len(matcher.text(), event.message()));
+        (
+            std::cmp::Reverse(similarity),
+            Some(event.level) != level,
+            std::cmp::Reverse(event.seq),
+        )
+    });
+    let lines: Vec<String> = ranked
+        .iter()
+        .take(NEAREST_LINES)
+        .map(|event| format!("  {}", event.line))
+        .collect();
+    format!(
+        "{} records were captured, the nearest are:\n{}",
+        events.len(),
+        lines.join("\n")
+    )
+}
+
+#[doc(hidden)]
+pub fn __assert_logged(events: &[LogEvent], level: Option<Level>, matcher: Option<&Matcher>) {
+    if matching(events, level, matcher).next().is_none() {
+        panic!(
+            "expected {}, {}",
+            describe("a record", level, matcher),
+            nearest(events, level, matcher)
+        );
+    }
+}
+
+#[doc(hidden)]
+pub fn __assert_logged_n(events: &[LogEvent], level: Option<Level>, matcher: Option<&Matcher>, expected: usize) {
+    let found: Vec<&LogEvent> = matching(events, level, matcher).collect();
+    if found.len() != expected {
+        let lines: Vec<String> = found.iter().map(|event| format!("  {}", event.line)).collect();
+        panic!(
+            "expected {}, found {}:\n{}",
+            describe(&format!("{} record(s)", expected), level, matcher),
+            found.len(),
+            lines.join("\n")
+        );
+    }
+}
+
+#[doc(hidden)]
+pub fn __assert_not_logged(events: &[LogEvent], level: Option<Level>, matcher: Option<&Matcher>) {
+    let found: Vec<String> = matching(events, level, matcher)
+        .map(|event| format!("  {}", event.line))
+        .collect();
+    if !found.is_empty() {
+        panic!(
+            "expected {}, found:\n{}",
+            describe("no record", level, matcher),
+            found.join("\n")
+        );
+    }
+}
+
+/// Assert that a captured record has the level and a message matching `contains`, `exact` or `regex`
+///
+/// See the [module documentation](crate::test_util) for an example.
+#[macro_export]
+macro_rules! assert_logged {
+    ($capture:expr, $level:expr, $kind:ident $pattern:expr $(,)?) => {
+        $crate::test_util::__assert_logged(
+            &{
+                use $crate::test_util::Captured as _;
+                $capture.captured_events()
+            },
+            Some($level),
+            Some(&$crate::test_util::Matcher::$kind($pattern)),
+        )
+    };
+    ($capture:expr, $kind:ident $pattern:expr $(,)?) => {
+        $crate::test_util::__assert_logged(
+            &{
+                use $crate::test_util::Captured as _;
+                $capture.captured_events()
+            },
+            None,
+            Some(&$crate::test_util::Matcher::$kind($pattern)),
+        )
+    };
+}
+
+/// Assert the number of captured records of a level, optionally only those with a matching message
+///
+/// See the [module documentation](crate::test_util) for an example.
+#[macro_export]
+macro_rules! assert_logged_n {
+    ($capture:expr, $level:expr, $kind:ident $pattern:expr, $expected:expr $(,)?) => {
+        $crate::test_util::__assert_logged_n(
+            &{
+                use $crate::test_util::Captured as _;
+                $capture.captured_events()
+            },
+            Some($level),
+            Some(&$crate::test_util::Matcher::$kind($pattern)),
+            $expected,
+        )
+    };
+    ($capture:expr, $level:expr, $expected:expr $(,)?) => {
+        $crate::test_util::__assert_logged_n(
+            &{
+                use $crate::test_util::Captured as _;
+                $capture.captured_events()
+            },
+            Some($level),
+            None,
+            $expected,
+        )
+    };
+}
+
+/// Assert that no captured record, optionally of a level, has a message matching the pattern
+///
+/// See the [module documentation](crate::test_util) for an example.
+#[macro_export]
+macro_rules! assert_not_logged {
+    ($capture:expr, $level:expr, $kind:ident $pattern:expr $(,)?) => {
+        $crate::test_util::__assert_not_logged(
+            &{
+                use $crate::test_util::Captured as _;
+                $capture.captured_events()
+            },
+            Some($level),
+            Some(&$crate::test_util::Matcher::$kind($pattern)),
+        )
+    };
+    ($capture:expr, $kind:ident $pattern:expr $(,)?) => {
+        $crate::test_util::__assert_not_logged(
+            &{
+                use $crate::test_util::Captured as _;
+                $capture.captured_events()
+            },
+            None,
+            Some(&$crate::test_util::Matcher::$kind($pattern)),
+        )
+    };
+}
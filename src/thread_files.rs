@@ -0,0 +1,66 @@
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    io::{self, Write},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use log::Level;
+
+use crate::log_file::LogFile;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// The log files of the current thread by logger and level
+    static FILES: RefCell<HashMap<(u64, Level), LogFile>> = RefCell::new(HashMap::new());
+}
+
+/// The per thread log files of a logger and its clones, see [`Flags::file_per_thread`](crate::Flags::file_per_thread)
+///
+/// The files live in thread locals, so writing and flushing never take a lock.
+#[derive(Clone, Copy)]
+pub(crate) struct ThreadFiles {
+    id: u64,
+}
+
+impl ThreadFiles {
+    pub(crate) fn new() -> Self {
+        ThreadFiles {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    /// Call `write` with the file of `level` of the current thread, created with `create` on first use
+    pub(crate) fn write(
+        &self,
+        level: Level,
+        create: impl FnOnce() -> io::Result<LogFile>,
+        write: impl FnOnce(&mut LogFile) -> io::Result<()>,
+    ) -> io::Result<()> {
+        FILES
+            .try_with(|files| {
+                let mut files = files.borrow_mut();
+                let log_file = match files.entry((self.id, level)) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(create()?),
+                };
+                write(log_file)
+            })
+            .unwrap_or_else(|_| Err(io::Error::other("thread is exiting")))
+    }
+
+    /// Flush the files of the current thread, returning the levels which failed
+    pub(crate) fn flush(&self) -> Vec<(Level, io::Error)> {
+        FILES
+            .try_with(|files| {
+                files
+                    .borrow_mut()
+                    .iter_mut()
+                    .filter(|((id, _), _)| *id == self.id)
+                    .filter_map(|((_, level), log_file)| log_file.file.flush().err().map(|why| (*level, why)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
@@ -0,0 +1,157 @@
+use std::{env, fmt::Write as _, io, sync::Arc};
+
+use backtrace::{Backtrace, BacktraceFrame};
+#[cfg(feature = "color-backtrace")]
+use color_backtrace::Verbosity;
+use termcolor::WriteColor;
+
+pub(crate) type BacktraceFilter = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// How much detail logged backtraces contain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacktraceStyle {
+    /// Function name and source location of the frames passing the
+    /// [backtrace filter](crate::Glog::with_backtrace_filter)
+    Short,
+    /// Address, function name and source location of all frames
+    Full,
+    /// [`Full`](BacktraceStyle::Full) if `RUST_BACKTRACE` is `full`, [`Short`](BacktraceStyle::Short) otherwise
+    Auto,
+}
+
+impl BacktraceStyle {
+    /// Replace [`Auto`](BacktraceStyle::Auto) with the style selected by `RUST_BACKTRACE`
+    pub(crate) fn resolve(self) -> Self {
+        match self {
+            BacktraceStyle::Auto if env::var("RUST_BACKTRACE").is_ok_and(|value| value == "full") => BacktraceStyle::Full,
+            BacktraceStyle::Auto => BacktraceStyle::Short,
+            style => style,
+        }
+    }
+}
+
+/// The frames of `backtrace` passing `filter`, frames without symbol names always pass
+pub(crate) fn filter_frames(backtrace: &Backtrace, filter: &BacktraceFilter) -> Vec<BacktraceFrame> {
+    backtrace
+        .frames()
+        .iter()
+        .filter(|frame| {
+            let mut names = frame.symbols().iter().filter_map(|symbol| symbol.name()).peekable();
+            names.peek().is_none() || names.any(|name| filter(&name.to_string()))
+        })
+        .cloned()
+        .collect()
+}
+
+/// The frames of `backtrace` shown in `style`, only short backtraces are filtered
+fn select_frames(backtrace: &Backtrace, style: BacktraceStyle, filter: Option<&BacktraceFilter>) -> Vec<BacktraceFrame> {
+    match filter {
+        Some(filter) if style == BacktraceStyle::Short => filter_frames(backtrace, filter),
+        _ => backtrace.frames().to_vec(),
+    }
+}
+
+/// Write a backtrace of the caller in `style` followed by a newline to `out`, colorized if `colored`
+/// is set and the `color-backtrace` feature is enabled
+pub(crate) fn write_backtrace<W: WriteColor>(
+    out: &mut W,
+    style: BacktraceStyle,
+    filter: Option<&BacktraceFilter>,
+    colored: bool,
+) -> io::Result<()> {
+    let backtrace = Backtrace::new();
+    #[cfg(feature = "color-backtrace")]
+    if colored {
+        let style = style.resolve();
+        let verbosity = match style {
+            BacktraceStyle::Full => Verbosity::Full,
+            _ => Verbosity::Medium,
+        };
+        return color_backtrace::BacktracePrinter::new()
+            .verbosity(verbosity)
+            .print_trace(&select_frames(&backtrace, style, filter).into(), out);
+    }
+    #[cfg(not(feature = "color-backtrace"))]
+    let _ = colored;
+    writeln!(out, "{}", render_backtrace(&backtrace, style, filter))
+}
+
+/// Render `backtrace` in `style` without a trailing newline
+pub(crate) fn render_backtrace(backtrace: &Backtrace, style: BacktraceStyle, filter: Option<&BacktraceFilter>) -> String {
+    let style = style.resolve();
+    let frames = select_frames(backtrace, style, filter);
+    let mut rendered = String::new();
+    let mut index = 0;
+    for frame in &frames {
+        let symbols = frame.symbols();
+        if symbols.is_empty() {
+            render_frame(&mut rendered, style, index, frame, None, None);
+            index += 1;
+        }
+        for symbol in symbols {
+            let name = symbol.name().map(|name| name.to_string());
+            let location = symbol
+                .filename()
+                .map(|file| format!("{}:{}", file.display(), symbol.lineno().unwrap_or(0)));
+            render_frame(&mut rendered, style, index, frame, name, location);
+            index += 1;
+        }
+    }
+    rendered.truncate(rendered.trim_end().len());
+    rendered
+}
+
+fn render_frame(
+    out: &mut String,
+    style: BacktraceStyle,
+    index: usize,
+    frame: &BacktraceFrame,
+    name: Option<String>,
+    location: Option<String>,
+) {
+    let name = name.as_deref().unwrap_or("<unknown>");
+    // Writing into a String can't fail
+    let _ = match style {
+        BacktraceStyle::Full => writeln!(out, "{:4}: {:?} - {}", index, frame.ip(), name),
+        _ => writeln!(out, "{:4}: {}", index, name),
+    };
+    if let Some(location) = location {
+        let _ = writeln!(out, "             at {}", location);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[inline(never)]
+    fn capture() -> Backtrace {
+        Backtrace::new()
+    }
+
+    #[test]
+    fn styles() {
+        let backtrace = capture();
+        let filter: BacktraceFilter = Arc::new(|name| !name.contains("trace::tests::styles"));
+        let short = render_backtrace(&backtrace, BacktraceStyle::Short, Some(&filter));
+        let full = render_backtrace(&backtrace, BacktraceStyle::Full, Some(&filter));
+
+        assert!(short.contains("trace::tests::capture"));
+        assert!(full.contains("trace::tests::capture"));
+        assert!(!short.contains(" 0x"));
+        assert!(full
+            .lines()
+            .filter(|line| !line.trim_start().starts_with("at "))
+            .all(|line| line.contains(": 0x")));
+        assert!(!short.contains("trace::tests::styles"));
+        assert!(full.contains("trace::tests::styles"));
+        assert!(short.lines().count() < full.lines().count());
+    }
+
+    #[test]
+    fn auto_resolves_to_short_or_full() {
+        assert_ne!(BacktraceStyle::Auto.resolve(), BacktraceStyle::Auto);
+        assert_eq!(BacktraceStyle::Full.resolve(), BacktraceStyle::Full);
+        assert_eq!(BacktraceStyle::Short.resolve(), BacktraceStyle::Short);
+    }
+}
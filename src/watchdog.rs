@@ -0,0 +1,125 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::Level;
+
+/// Detects writes to the log files that don't finish, see [`Glog::with_write_guard`](crate::Glog::with_write_guard)
+pub(crate) struct WriteWatchdog {
+    limit: Duration,
+    epoch: Instant,
+    /// Per log file the milliseconds since `epoch` plus one at which the current write started, 0 if idle
+    started: [AtomicU64; 5],
+}
+
+/// Marks a write to a log file as finished when dropped
+pub(crate) struct WriteStamp<'a>(&'a AtomicU64);
+
+impl Drop for WriteStamp<'_> {
+    fn drop(&mut self) {
+        self.0.store(0, Ordering::Relaxed);
+    }
+}
+
+impl WriteWatchdog {
+    pub(crate) fn new(limit: Duration) -> Self {
+        WriteWatchdog {
+            limit,
+            epoch: Instant::now(),
+            started: Default::default(),
+        }
+    }
+
+    /// Record the start of a write to the log file of `level`, lasting until the stamp is dropped
+    pub(crate) fn begin(&self, level: Level) -> WriteStamp<'_> {
+        let started = &self.started[level as usize - 1];
+        started.store(self.epoch.elapsed().as_millis() as u64 + 1, Ordering::Relaxed);
+        WriteStamp(started)
+    }
+
+    /// The level and duration of a write running for longer than the limit
+    fn stuck(&self) -> Option<(Level, Duration)> {
+        let now = self.epoch.elapsed();
+        [Level::Error, Level::Warn, Level::Info, Level::Debug, Level::Trace]
+            .iter()
+            .filter_map(|level| match self.started[*level as usize - 1].load(Ordering::Relaxed) {
+                0 => None,
+                started => Some((*level, now.saturating_sub(Duration::from_millis(started - 1)))),
+            })
+            .find(|(_, running)| *running > self.limit)
+    }
+
+    /// Check the writes every half limit on a background thread which calls `report` once per stuck write
+    pub(crate) fn spawn(self: &Arc<Self>, report: impl Fn(Level, io::Error) + Send + 'static) {
+        let watchdog = self.clone();
+        thread::Builder::new()
+            .name("glog-write-guard".to_owned())
+            .spawn(move || {
+                let mut reported = [0; 5];
+                loop {
+                    thread::sleep(watchdog.limit / 2);
+                    let Some((level, running)) = watchdog.stuck() else {
+                        continue;
+                    };
+                    let started = watchdog.started[level as usize - 1].load(Ordering::Relaxed);
+                    if std::mem::replace(&mut reported[level as usize - 1], started) == started {
+                        continue;
+                    }
+                    report(
+                        level,
+                        io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!(
+                                "writing to the {} log file is stuck for {:?}, longer than the write guard of {:?}",
+                                level, running, watchdog.limit
+                            ),
+                        ),
+                    );
+                }
+            })
+            .expect("couldn't spawn write guard thread");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn detects_writes_running_too_long() {
+        let watchdog = WriteWatchdog::new(Duration::from_millis(10));
+        assert_eq!(watchdog.stuck(), None);
+
+        let stamp = watchdog.begin(Level::Warn);
+        assert_eq!(watchdog.stuck(), None);
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(watchdog.stuck().map(|(level, _)| level), Some(Level::Warn));
+        assert!(watchdog.stuck().unwrap().1 >= Duration::from_millis(20));
+
+        drop(stamp);
+        assert_eq!(watchdog.stuck(), None);
+    }
+
+    #[test]
+    fn reports_a_stuck_write_once() {
+        let watchdog = Arc::new(WriteWatchdog::new(Duration::from_millis(10)));
+        let (sender, receiver) = mpsc::channel();
+        watchdog.spawn(move |level, why| {
+            let _ = sender.send((level, why.kind()));
+        });
+
+        let stamp = watchdog.begin(Level::Error);
+        let reported = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(reported, (Level::Error, io::ErrorKind::TimedOut));
+        assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+        drop(stamp);
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use chrono::{Local, TimeZone};
+use glog::{Flags, RingBufferSink, SinkConfig};
+use log::*;
+
+mod common;
+
+#[test]
+fn error_bursts_lower_the_minimum_level_for_a_while() {
+    let log_dir = common::temp_log_dir("adaptive");
+    let start = Local.with_ymd_and_hms(2021, 4, 1, 12, 0, 0).unwrap();
+    let seconds = Arc::new(AtomicI64::new(0));
+    let recent = RingBufferSink::new(100);
+    let handle = {
+        let seconds = seconds.clone();
+        glog::new()
+            .with_timestamp_fn(move || start + chrono::Duration::seconds(seconds.load(Ordering::Relaxed)))
+            .add_sink_with(
+                SinkConfig {
+                    min_level: Some(Level::Debug),
+                    ..Default::default()
+                },
+                recent.clone(),
+            )
+            .with_adaptive_buffer(recent)
+            .try_init(Flags {
+                log_dir: log_dir.join("").into_os_string(),
+                adaptive_error_threshold: Some(2),
+                adaptive_error_window: Duration::from_secs(10),
+                adaptive_duration: Duration::from_secs(30),
+                adaptive_dump_ring_buffer: true,
+                ..Default::default()
+            })
+            .unwrap()
+    };
+    let at = |second: i64| seconds.store(second, Ordering::Relaxed);
+
+    debug!("context 1");
+    at(1);
+    error!("error 1");
+    debug!("context 2");
+    // Outside of the window of the third error
+    at(20);
+    error!("error 2");
+    error!("error 3");
+    at(25);
+    debug!("context 3");
+    error!("error 4");
+    debug!("escalated");
+
+    // Another burst extends the escalation
+    at(40);
+    for i in 5..8 {
+        error!("error {}", i);
+    }
+    at(69);
+    debug!("still escalated");
+    at(70);
+    info!("restored");
+    debug!("context 4");
+
+    // Changing the minimum level ends an escalation
+    at(100);
+    for i in 8..11 {
+        error!("error {}", i);
+    }
+    debug!("escalated again");
+    handle.set_min_level(Level::Info);
+    debug!("dropped again");
+    log::logger().flush();
+
+    let content = fs::read_to_string(&handle.log_files()[0].path).unwrap();
+    let messages: Vec<&str> = content
+        .lines()
+        .skip_while(|line| !line.starts_with("Log line format"))
+        .skip(1)
+        .filter_map(|line| line.split_once("] ").map(|(_, message)| message))
+        .collect();
+    let burst = "glog: 3 WARNING and ERROR records within 10s, logging DEBUG records until none happen for 30s";
+    let subsided = "glog: error burst subsided, logging INFO records again";
+    assert_eq!(
+        messages,
+        [
+            "error 1",
+            "error 2",
+            "error 3",
+            burst,
+            // The buffered records below the minimum level, later bursts don't write them again
+            "context 1",
+            "context 2",
+            "context 3",
+            "error 4",
+            "escalated",
+            "error 5",
+            "error 6",
+            "error 7",
+            "still escalated",
+            subsided,
+            "restored",
+            "error 8",
+            "error 9",
+            burst,
+            "context 4",
+            "error 10",
+            "escalated again",
+        ],
+        "{}",
+        content
+    );
+}
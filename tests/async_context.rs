@@ -0,0 +1,60 @@
+#![cfg(feature = "tokio-context")]
+
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use glog::{context::FutureExt as _, ConsoleWriter, Flags};
+use log::*;
+
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl ConsoleWriter for Collect {
+    fn write_line(&self, _level: Level, line: &str) {
+        self.0.lock().unwrap().push(line.to_owned());
+    }
+}
+
+#[test]
+fn context_follows_tasks_migrating_threads() {
+    let lines = Arc::new(Mutex::new(Vec::new()));
+    glog::new()
+        .with_console_writer(Collect(lines.clone()))
+        .init(Flags {
+            logtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+    let runtime = tokio::runtime::Builder::new_multi_thread().worker_threads(2).build().unwrap();
+
+    let (started_tx, started_rx) = mpsc::channel();
+    let (resume_tx, resume_rx) = mpsc::channel::<()>();
+    let (done_tx, done_rx) = mpsc::channel::<()>();
+    let task = async move {
+        let before = thread::current().id();
+        // Goes into the LIFO slot of this worker, which can't be stolen, and blocks it until the
+        // task is done, so the woken task is picked up by the other worker
+        let blocker = tokio::spawn(async move {
+            started_tx.send(thread::current().id()).unwrap();
+            let _ = done_rx.recv_timeout(Duration::from_secs(10));
+        });
+        tokio::task::spawn_blocking(move || resume_rx.recv().unwrap()).await.unwrap();
+        info!("after await");
+        let after = thread::current().id();
+        done_tx.send(()).unwrap();
+        blocker.await.unwrap();
+        (before, after)
+    }
+    .with_log_context(&[("request_id", "7")]);
+    let handle = runtime.spawn(task);
+    let blocked = started_rx.recv().unwrap();
+    resume_tx.send(()).unwrap();
+    let (before, after) = runtime.block_on(handle).unwrap();
+
+    assert_eq!(blocked, before);
+    assert_ne!(before, after, "the task didn't move to another thread");
+    let last = lines.lock().unwrap().pop().unwrap();
+    assert!(last.ends_with("] [request_id=7] after await"), "{}", last);
+}
@@ -0,0 +1,59 @@
+use std::{thread, time::Duration};
+
+use glog::{Attachment, Flags, QueryParams, RingBufferSink};
+use log::*;
+
+#[test]
+fn attachments_belong_to_the_next_record_of_their_thread() {
+    let recent = RingBufferSink::new(100);
+    let handle = glog::new()
+        .with_sink(recent.clone())
+        .try_init(Flags {
+            logtostderr: true,
+            attachment_max_bytes: 16,
+            attachment_ttl: Duration::from_millis(200),
+            ..Default::default()
+        })
+        .unwrap();
+    let events = || recent.query(&QueryParams::default()).events;
+
+    glog::attach("dump", "first line\nsecond line");
+    error!("failed");
+    info!("unrelated");
+    let logged = events();
+    assert!(
+        logged[0]
+            .line
+            .ends_with("] failed [attached: dump=22 bytes]\n    first line\n    secon\n    [truncated 6 bytes]"),
+        "{:?}",
+        logged[0].line
+    );
+    assert_eq!(
+        logged[0].attachment,
+        Some(Attachment {
+            key: "dump".to_owned(),
+            value: "first line\nsecond line".to_owned(),
+        })
+    );
+    assert!(logged[1].line.ends_with("] unrelated"), "{:?}", logged[1].line);
+    assert_eq!(logged[1].attachment, None);
+
+    glog::attach("replaced", "");
+    glog::attach("expired", "");
+    thread::sleep(Duration::from_millis(300));
+    info!("too late");
+    thread::spawn(|| glog::attach("ended", "")).join().unwrap();
+    thread::spawn(|| {
+        glog::attach("other thread", "value");
+        info!("from another thread");
+    })
+    .join()
+    .unwrap();
+
+    let logged = events();
+    assert!(logged[2].line.ends_with("] too late"), "{:?}", logged[2].line);
+    assert!(logged[3]
+        .line
+        .ends_with("] from another thread [attached: other thread=5 bytes]\n    value"));
+    assert_eq!(handle.stats_snapshot().attachments_dropped, 3);
+}
@@ -0,0 +1,68 @@
+use std::fs;
+
+use chrono::{NaiveTime, Weekday};
+use glog::{Flags, ScheduleWindow};
+use log::*;
+
+mod common;
+
+#[test]
+fn runtime_changes_are_audited() {
+    let log_dir = common::temp_log_dir("audit-trail");
+
+    let handle = glog::new()
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    info!("before");
+    handle.set_min_level_with_reason(Level::Error, "incident-1234");
+    warn!("hidden");
+    handle.set_min_level(Level::Error);
+    handle.set_min_level(Level::Info);
+    info!("after");
+    handle.set_target_policy_with_reason("audit_trail=warn", "noisy").unwrap();
+    info!("filtered");
+    handle.add_suppression("vendored.rs:120").unwrap();
+    handle.add_suppression("vendored.rs:*").unwrap();
+    // Unchanged, so not audited
+    handle.add_suppression("vendored.rs:*").unwrap();
+    assert!(handle.remove_suppression("vendored.rs:*"));
+    handle.set_schedule(vec![ScheduleWindow {
+        start: NaiveTime::from_hms_opt(22, 0, 0).unwrap(),
+        end: NaiveTime::from_hms_opt(6, 0, 0).unwrap(),
+        weekdays: vec![Weekday::Sat],
+        floor: Level::Warn,
+    }]);
+    log::logger().flush();
+
+    let info_file = handle
+        .log_files()
+        .into_iter()
+        .find(|log_file| log_file.level == Level::Info)
+        .unwrap();
+    let content = fs::read_to_string(&info_file.path).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    let messages: Vec<&str> = content
+        .lines()
+        .filter(|line| line.starts_with(['I', 'W', 'E']) && line.contains("] "))
+        .map(|line| &line[line.find("] ").unwrap() + 2..])
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "before",
+            "minloglevel changed from INFO to ERROR: incident-1234",
+            "minloglevel changed from ERROR to INFO",
+            "after",
+            "target_policy changed from \"\" to \"audit_trail=warn\": noisy",
+            "suppress_at changed from \"\" to \"vendored.rs:120\"",
+            "suppress_at changed from \"vendored.rs:120\" to \"vendored.rs:*, vendored.rs:120\"",
+            "suppress_at changed from \"vendored.rs:*, vendored.rs:120\" to \"vendored.rs:120\"",
+            "schedule changed from \"\" to \"22:00:00-06:00:00 Sat WARN\"",
+        ]
+    );
+}
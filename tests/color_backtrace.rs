@@ -0,0 +1,79 @@
+#![cfg(all(unix, feature = "color-backtrace"))]
+
+use std::{
+    env,
+    fs::File,
+    io::Read,
+    os::unix::io::FromRawFd,
+    process::{Command, Stdio},
+};
+
+use glog::Flags;
+use log::*;
+
+const CHILD: &str = "GLOG_COLOR_BACKTRACE_TEST_CHILD";
+
+/// Stderr of a child logging a warning with a backtrace, written to a terminal if `terminal` is set
+fn backtrace_stderr(terminal: bool) -> String {
+    let mut command = Command::new(env::current_exe().unwrap());
+    command
+        .args(["--exact", "colored_backtraces_on_terminals", "--nocapture"])
+        .env(CHILD, "1")
+        .env("TERM", "xterm")
+        .env_remove("NO_COLOR")
+        .env_remove("RUST_BACKTRACE")
+        .stdout(Stdio::null());
+    if !terminal {
+        let output = command.output().unwrap();
+        assert!(output.status.success());
+        return String::from_utf8_lossy(&output.stderr).into_owned();
+    }
+
+    let pty = nix::pty::openpty(None, None).unwrap();
+    // SAFETY: the descriptors were just opened and are owned by nothing else
+    let mut child = command.stderr(unsafe { Stdio::from_raw_fd(pty.slave) }).spawn().unwrap();
+    // Closes the parent's copy of the terminal, so reading ends once the child exited
+    drop(command);
+    let mut master = unsafe { File::from_raw_fd(pty.master) };
+    let mut stderr = Vec::new();
+    // Fails with EIO once the child closed the terminal, everything written before is kept
+    let _ = master.read_to_end(&mut stderr);
+    assert!(child.wait().unwrap().success());
+    String::from_utf8_lossy(&stderr).replace("\r\n", "\n")
+}
+
+#[test]
+fn colored_backtraces_on_terminals() {
+    if env::var_os(CHILD).is_some() {
+        glog::new()
+            .init(Flags {
+                logtostderr: true,
+                colorlogtostderr: true,
+                log_backtrace_at: Some("traced.rs:7".to_owned()),
+                ..Default::default()
+            })
+            .unwrap();
+        log::logger().log(
+            &Record::builder()
+                .level(Level::Warn)
+                .file(Some("src/traced.rs"))
+                .line(Some(7))
+                .args(format_args!("traced"))
+                .build(),
+        );
+        return;
+    }
+
+    // The color of the warning is reset after the message, any other escape sequence is part of the backtrace
+    let backtrace_of = |stderr: &str| stderr.split_once("] traced").unwrap().1.replace("\x1b[0m", "");
+
+    let colored = backtrace_stderr(true);
+    let backtrace = backtrace_of(&colored);
+    assert!(backtrace.contains("colored_backtraces_on_terminals"), "{:?}", colored);
+    assert!(backtrace.contains("\x1b["), "{:?}", colored);
+
+    let plain = backtrace_stderr(false);
+    let backtrace = backtrace_of(&plain);
+    assert!(backtrace.contains("colored_backtraces_on_terminals"), "{:?}", plain);
+    assert!(!backtrace.contains('\x1b'), "{:?}", plain);
+}
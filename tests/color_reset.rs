@@ -0,0 +1,40 @@
+use std::{env, process::Command};
+
+use glog::Flags;
+use log::*;
+
+const CHILD_RESET_ON_NEWLINE: &str = "GLOG_COLOR_RESET_TEST_ON_NEWLINE";
+
+/// Stderr of a child logging a colored warning
+fn colored_stderr(color_reset_on_newline: bool) -> String {
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "colors_are_reset_before_the_newline", "--nocapture"])
+        .env(CHILD_RESET_ON_NEWLINE, color_reset_on_newline.to_string())
+        .env("TERM", "xterm")
+        .env_remove("NO_COLOR")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn colors_are_reset_before_the_newline() {
+    if let Some(color_reset_on_newline) = env::var_os(CHILD_RESET_ON_NEWLINE) {
+        glog::new()
+            .init(Flags {
+                logtostderr: true,
+                colorlogtostderr: true,
+                color_reset_on_newline: color_reset_on_newline == "true",
+                ..Default::default()
+            })
+            .unwrap();
+        warn!("a warning");
+        return;
+    }
+
+    let stderr = colored_stderr(true);
+    assert!(stderr.contains("] a warning\x1b[0m\n"), "{:?}", stderr);
+    let stderr = colored_stderr(false);
+    assert!(stderr.contains("] a warning\n\x1b[0m"), "{:?}", stderr);
+}
@@ -0,0 +1,33 @@
+//! Helpers shared by the integration tests, each test file only uses some of them
+#![allow(dead_code)]
+
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+use log::Level;
+
+/// The path of the temporary directory for the test `name` of this process, which isn't created
+pub fn temp_log_dir_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("glog-{}-test-{}", name, process::id()))
+}
+
+/// Create the temporary directory for the log files of the test `name` of this process
+pub fn temp_log_dir(name: &str) -> PathBuf {
+    let dir = temp_log_dir_path(name);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The log file of `level` in `dir`, like `server.host.alice.log.INFO.20210401-123456.42`
+pub fn find_log_file(dir: &Path, level: Level) -> PathBuf {
+    let infix = format!(".log.{}.", level);
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.to_string_lossy().contains(&infix))
+        .unwrap_or_else(|| panic!("no {} log file was written to {}", level, dir.display()))
+}
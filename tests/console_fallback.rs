@@ -0,0 +1,54 @@
+#![cfg(unix)]
+
+use std::{
+    env,
+    fs,
+    process::{Command, Stdio},
+};
+
+use glog::{ConsoleFallback, Flags, Glog};
+use log::*;
+
+mod common;
+
+const CHILD: &str = "GLOG_CONSOLE_FALLBACK_TEST_CHILD";
+
+#[test]
+fn raw_writes_follow_the_console_fallback() {
+    if let Some(log_dir) = env::var_os(CHILD) {
+        glog::new()
+            .init(Flags {
+                logtostderr: true,
+                // Conflicts with logtostderr, warned about during the initialization
+                log_dir,
+                console_fallback: ConsoleFallback::RedirectToFile,
+                ..Default::default()
+            })
+            .unwrap();
+        error!("a record");
+        Glog::write_stderr_raw("a raw line\n");
+        return;
+    }
+
+    let log_dir = common::temp_log_dir("console-fallback");
+    // A daemon's stderr leads to /dev/null
+    let status = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "raw_writes_follow_the_console_fallback", "--nocapture"])
+        .env(CHILD, log_dir.join(""))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let console_log = fs::read_dir(&log_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.to_string_lossy().ends_with(".console.log"))
+        .unwrap();
+    let content = fs::read_to_string(console_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+    assert!(content.contains("no log files are written to log_dir"), "{}", content);
+    assert!(content.contains("] a record\n"), "{}", content);
+    assert!(content.ends_with("\na raw line\n"), "{}", content);
+}
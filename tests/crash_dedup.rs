@@ -0,0 +1,60 @@
+#![cfg(unix)]
+
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    os::unix::process::ExitStatusExt,
+    path::MAIN_SEPARATOR,
+    process::Command,
+};
+
+use glog::{check, Flags};
+use log::Level;
+
+mod common;
+
+const CHILD_LOG_DIR: &str = "GLOG_CRASH_DEDUP_TEST_LOG_DIR";
+
+#[test]
+fn check_failure_is_reported_once() {
+    if let Some(log_dir) = env::var_os(CHILD_LOG_DIR) {
+        glog::new()
+            .install_crash_handler()
+            .init(Flags {
+                log_dir,
+                ..Default::default()
+            })
+            .unwrap();
+        let answer = 41;
+        check!(answer == 42);
+        unreachable!("check! didn't abort");
+    }
+
+    let log_dir = common::temp_log_dir("crash-dedup");
+    let mut child_log_dir = OsString::from(&log_dir);
+    child_log_dir.push(MAIN_SEPARATOR.to_string());
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "check_failure_is_reported_once", "--nocapture"])
+        .env(CHILD_LOG_DIR, child_log_dir)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.signal(), Some(libc::SIGABRT));
+
+    let error_log = common::find_log_file(&log_dir, Level::Error);
+    let content = fs::read_to_string(error_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    assert!(content.contains("] Check failed: answer == 42\n"), "{}", content);
+    assert_eq!(content.matches("stack trace: ***").count(), 1, "{}", content);
+    assert_eq!(content.matches("*** Fatal error stack trace: ***").count(), 1, "{}", content);
+    assert_eq!(
+        content
+            .matches("*** suppressed duplicate crash report from the SIGABRT handler ***\n")
+            .count(),
+        1,
+        "{}",
+        content
+    );
+}
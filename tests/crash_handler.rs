@@ -0,0 +1,57 @@
+#![cfg(unix)]
+
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    os::unix::process::ExitStatusExt,
+    path::MAIN_SEPARATOR,
+    process::{Command, Stdio},
+};
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+const CHILD_LOG_DIR: &str = "GLOG_CRASH_HANDLER_TEST_LOG_DIR";
+
+#[test]
+fn crash_is_logged_to_stderr_and_files() {
+    if let Some(log_dir) = env::var_os(CHILD_LOG_DIR) {
+        glog::new()
+            .install_crash_handler()
+            .init(Flags {
+                log_dir,
+                ..Default::default()
+            })
+            .unwrap();
+        error!("last message before the crash");
+        log::logger().flush();
+        // SAFETY: raising a signal is sound, the process is expected to die from it
+        unsafe { libc::raise(libc::SIGSEGV) };
+        unreachable!("the crash handler didn't terminate the process");
+    }
+
+    let log_dir = common::temp_log_dir("crash-handler");
+    let mut child_log_dir = OsString::from(&log_dir);
+    child_log_dir.push(MAIN_SEPARATOR.to_string());
+
+    let child = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "crash_is_logged_to_stderr_and_files", "--nocapture"])
+        .env(CHILD_LOG_DIR, child_log_dir)
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let header = format!("*** SIGSEGV received by PID {}; stack trace: ***\n", child.id());
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.signal(), Some(libc::SIGSEGV));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&header));
+
+    let error_log = common::find_log_file(&log_dir, Level::Error);
+    let content = fs::read_to_string(error_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+    let crash = &content[content.find("] last message before the crash\n").expect("message missing")..];
+    assert!(crash.contains(&header), "{}", content);
+    assert!(crash.contains("\n    @ 0x"), "{}", content);
+}
@@ -0,0 +1,35 @@
+#![cfg(unix)]
+
+use std::{fs, thread, time::Duration};
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+#[test]
+fn removed_log_file_is_recreated() {
+    let log_dir = common::temp_log_dir("deleted-log-file");
+
+    glog::new()
+        .init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            detect_deleted_files: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    info!("before removal");
+    let info_log = common::find_log_file(&log_dir, Level::Info);
+    fs::remove_file(&info_log).unwrap();
+
+    thread::sleep(Duration::from_millis(1100));
+    info!("after removal");
+
+    let content = fs::read_to_string(&info_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+    assert!(content.starts_with("Log file created at:\n"));
+    assert!(content.contains("Log file recreated after external removal\n"));
+    assert!(!content.contains("before removal"));
+    assert!(content.ends_with("] after removal\n"));
+}
@@ -0,0 +1,53 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use glog::{ConsoleWriter, Flags, QueryParams, RingBufferSink};
+use log::*;
+
+mod common;
+
+#[derive(Clone, Default)]
+struct Console(Arc<Mutex<Vec<String>>>);
+
+impl ConsoleWriter for Console {
+    fn write_line(&self, _level: Level, line: &str) {
+        self.0.lock().unwrap().push(line.to_owned());
+    }
+}
+
+#[test]
+fn drained_records_only_reach_the_sinks() {
+    let log_dir = common::temp_log_dir("drain");
+    let (console, drained, other) = (Console::default(), RingBufferSink::new(10), RingBufferSink::new(10));
+    glog::new()
+        .with_console_writer(console.clone())
+        .with_sink(other.clone())
+        .drain_to_sink(drained.clone())
+        .init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            alsologtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    info!("drained");
+    error!("drained too");
+    log::logger().flush();
+
+    let lines = |sink: &RingBufferSink| -> Vec<String> {
+        sink.query(&QueryParams::default())
+            .events
+            .into_iter()
+            .map(|event| event.line)
+            .collect()
+    };
+    let drained = lines(&drained);
+    assert_eq!(drained.len(), 2);
+    assert!(drained[0].ends_with("] drained"));
+    assert!(drained[1].ends_with("] drained too"));
+    assert_eq!(lines(&other), drained);
+    assert!(console.0.lock().unwrap().is_empty());
+    assert_eq!(fs::read_dir(&log_dir).unwrap().count(), 0);
+}
@@ -0,0 +1,38 @@
+use std::{env, panic, process::Command};
+
+use glog::Flags;
+use log::{Level, Record};
+
+const CHILD: &str = "GLOG_EMERGENCY_STDERR_TEST_CHILD";
+
+#[test]
+fn panic_hooks_write_lines_with_context() {
+    if env::var_os(CHILD).is_some() {
+        let handle = glog::new().try_init(Flags::default()).unwrap();
+        panic::set_hook(Box::new(move |info| {
+            let message = info.payload().downcast_ref::<&str>().copied().unwrap_or_default();
+            handle.write_stderr_with_context(
+                &Record::builder()
+                    .level(Level::Error)
+                    .file(Some("src/main.rs"))
+                    .line(Some(7))
+                    .args(format_args!("panicked: {}", message))
+                    .build(),
+                "registers:\n  rip 0x1234\n",
+            );
+        }));
+        panic!("broken state");
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "panic_hooks_write_lines_with_context", "--nocapture"])
+        .env(CHILD, "1")
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let line = stderr.lines().position(|line| line.starts_with('E')).unwrap();
+    let lines: Vec<_> = stderr.lines().skip(line).take(3).collect();
+    assert!(lines[0].ends_with(" main.rs:7] panicked: broken state"), "{}", stderr);
+    assert_eq!(lines[1..], ["registers:", "  rip 0x1234"], "{}", stderr);
+}
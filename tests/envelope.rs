@@ -0,0 +1,135 @@
+#![cfg(unix)]
+
+use std::{env, fs, path::PathBuf, process::Command, thread, time::Duration};
+
+use glog::{merge::merge_files, FileHeader, Flags};
+use log::*;
+
+mod common;
+
+const CHILD_LOG_DIR: &str = "GLOG_ENVELOPE_EXIT_TEST_LOG_DIR";
+
+#[test]
+fn segments_are_chained_and_closed() {
+    let log_dir = common::temp_log_dir("envelope");
+    let handle = glog::new()
+        .with_file_header(FileHeader::JsonEnvelope)
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            detect_deleted_files: true,
+            redaction_salt: "secret pepper".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let info_log = common::find_log_file(&log_dir, Level::Info);
+    let rotated = |n: u32| PathBuf::from(format!("{}.{}", info_log.display(), n));
+    // Rotated like logrotate, the open file is moved away
+    for (n, message) in [(1, "first"), (2, "second")] {
+        info!("{}", message);
+        fs::rename(&info_log, rotated(n)).unwrap();
+        thread::sleep(Duration::from_millis(1100));
+    }
+    info!("third");
+    handle.shutdown();
+
+    let segments: Vec<Vec<String>> = [rotated(1), rotated(2), info_log.clone()]
+        .iter()
+        .map(|path| fs::read_to_string(path).unwrap().lines().map(str::to_owned).collect())
+        .collect();
+    for (segment, lines) in segments.iter().enumerate() {
+        let envelope = &lines[0];
+        assert!(envelope.starts_with("{\"glog_envelope\":1,"), "{}", envelope);
+        assert!(envelope.contains(&format!(",\"level\":\"INFO\",\"segment\":{},", segment)));
+        // The previous segment is linked by the name it was moved to
+        match segment {
+            0 => assert!(envelope.contains(",\"previous\":null,")),
+            _ if cfg!(target_os = "linux") => {
+                let previous = rotated(segment as u32);
+                let previous = previous.file_name().unwrap().to_str().unwrap();
+                assert!(envelope.contains(&format!(",\"previous\":\"{}\",", previous)), "{}", envelope);
+            }
+            _ => {}
+        }
+        assert!(envelope.contains("\"detect_deleted_files\":\"true\""));
+        assert!(envelope.contains("\"console_available\":\"true\""), "{}", envelope);
+        assert!(
+            !envelope.contains("redaction_salt") && !envelope.contains("secret pepper"),
+            "{}",
+            envelope
+        );
+        let footer = lines.last().unwrap();
+        assert!(footer.starts_with("{\"glog_footer\":1,"), "{}", footer);
+        assert!(footer.ends_with(",\"records\":1,\"clean\":true}"));
+    }
+    assert!(segments[2].iter().any(|line| line.ends_with("] third")));
+
+    let stats = merge_files(&[rotated(1), rotated(2), info_log.clone()], std::io::sink()).unwrap();
+    assert!(stats.files.iter().all(|file| file.records == 1 && !file.crashed()));
+    assert_eq!(stats.files[0].envelope.as_ref(), Some(&segments[0][0]));
+
+    // Without the footer the file looks like the process crashed
+    let content = fs::read_to_string(&info_log).unwrap();
+    let without_footer = &content[..content.trim_end().rfind('\n').unwrap() + 1];
+    fs::write(&info_log, without_footer).unwrap();
+    let stats = merge_files(std::slice::from_ref(&info_log), std::io::sink()).unwrap();
+    assert!(stats.files[0].crashed());
+
+    #[cfg(feature = "serde")]
+    round_trip(&segments[1]);
+    fs::remove_dir_all(&log_dir).unwrap();
+}
+
+#[test]
+fn exit_closes_the_envelope() {
+    if let Some(log_dir) = env::var_os(CHILD_LOG_DIR) {
+        let _handle = glog::new()
+            .with_file_header(FileHeader::JsonEnvelope)
+            .try_init(Flags {
+                log_dir,
+                ..Default::default()
+            })
+            .unwrap();
+        warn!("last message before exit");
+        glog::exit(0);
+    }
+
+    let log_dir = common::temp_log_dir("envelope-exit");
+    let status = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "exit_closes_the_envelope", "--nocapture"])
+        .env(CHILD_LOG_DIR, log_dir.join(""))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    for level in [Level::Info, Level::Warn] {
+        let content = fs::read_to_string(common::find_log_file(&log_dir, level)).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert!(lines[lines.len() - 2].ends_with("] last message before exit"), "{}", content);
+        let footer = lines.last().unwrap();
+        assert!(footer.starts_with("{\"glog_footer\":1,"), "{}", content);
+        assert!(footer.ends_with(",\"records\":1,\"clean\":true}"), "{}", content);
+    }
+    fs::remove_dir_all(&log_dir).unwrap();
+}
+
+#[cfg(feature = "serde")]
+fn round_trip(lines: &[String]) {
+    use glog::envelope::{Envelope, Footer};
+    use serde_json::Value;
+
+    let envelope: Envelope = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(envelope.segment, 1);
+    assert_eq!(envelope.level, "INFO");
+    assert_eq!(envelope.flags["detect_deleted_files"], "true");
+    assert_eq!(
+        serde_json::to_value(&envelope).unwrap(),
+        serde_json::from_str::<Value>(&lines[0]).unwrap()
+    );
+    let footer: Footer = serde_json::from_str(lines.last().unwrap()).unwrap();
+    assert_eq!((footer.records, footer.clean), (1, true));
+    assert_eq!(
+        serde_json::to_value(&footer).unwrap(),
+        serde_json::from_str::<Value>(lines.last().unwrap()).unwrap()
+    );
+}
@@ -0,0 +1,44 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::MAIN_SEPARATOR,
+    process::Command,
+};
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+const CHILD_LOG_DIR: &str = "GLOG_EXIT_TEST_LOG_DIR";
+
+#[test]
+fn exit_flushes_log_files() {
+    if let Some(log_dir) = env::var_os(CHILD_LOG_DIR) {
+        glog::new()
+            .init(Flags {
+                log_dir,
+                ..Default::default()
+            })
+            .unwrap();
+        error!("last message before exit");
+        glog::exit(3);
+    }
+
+    let log_dir = common::temp_log_dir("exit");
+    let mut child_log_dir = OsString::from(&log_dir);
+    child_log_dir.push(MAIN_SEPARATOR.to_string());
+
+    let status = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "exit_flushes_log_files", "--nocapture"])
+        .env(CHILD_LOG_DIR, child_log_dir)
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(3));
+
+    let error_log = common::find_log_file(&log_dir, Level::Error);
+    let content = fs::read_to_string(error_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+    assert!(content.ends_with("] last message before exit\n"), "{}", content);
+}
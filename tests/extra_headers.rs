@@ -0,0 +1,36 @@
+use std::fs;
+
+use glog::{Flags, GlogError};
+use log::*;
+
+mod common;
+
+#[test]
+fn extra_headers_follow_the_log_line_format() {
+    let invalid = glog::new()
+        .with_extra_headers(vec!["Build: 1234".to_owned(), "Config:\nhash".to_owned()])
+        .try_init(Flags::default());
+    assert!(matches!(invalid, Err(GlogError::InvalidHeader(line)) if line == "Config:\nhash"));
+
+    let log_dir = common::temp_log_dir("extra-headers");
+    glog::new()
+        .with_extra_headers(vec!["Build: 1234".to_owned(), "Deployed at: 2021-04-01".to_owned()])
+        .init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    info!("a message");
+    log::logger().flush();
+
+    let info_log = common::find_log_file(&log_dir, Level::Info);
+    let content = fs::read_to_string(&info_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    let lines: Vec<&str> = content
+        .lines()
+        .skip_while(|line| !line.starts_with("Log line format: "))
+        .collect();
+    assert_eq!(lines[1..3], ["Build: 1234", "Deployed at: 2021-04-01"]);
+    assert!(lines[3].ends_with("] a message"));
+}
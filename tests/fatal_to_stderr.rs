@@ -0,0 +1,54 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::MAIN_SEPARATOR,
+    process::Command,
+};
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+const CHILD_LOG_DIR: &str = "GLOG_FATAL_TO_STDERR_TEST_LOG_DIR";
+
+#[test]
+fn errors_are_written_to_stderr_when_logging_to_files() {
+    if let Some(log_dir) = env::var_os(CHILD_LOG_DIR) {
+        glog::redirect_fatal_to_stderr();
+        let handle = glog::new()
+            .try_init(Flags {
+                log_dir,
+                ..Default::default()
+            })
+            .unwrap();
+        warn!("a warning");
+        error!("an error");
+        handle.log_batch(Level::Error, module_path!(), |batch| {
+            batch.line(format_args!("a batched error"));
+            batch.line(format_args!("another batched error"));
+        });
+        return;
+    }
+
+    let log_dir = common::temp_log_dir("fatal-to-stderr");
+    let mut child_log_dir = OsString::from(&log_dir);
+    child_log_dir.push(MAIN_SEPARATOR.to_string());
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "errors_are_written_to_stderr_when_logging_to_files", "--nocapture"])
+        .env(CHILD_LOG_DIR, child_log_dir)
+        .output()
+        .unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let logged: Vec<_> = stderr.lines().filter(|line| line.contains("] ")).collect();
+    assert_eq!(logged.len(), 3, "{}", stderr);
+    assert!(logged.iter().all(|line| line.starts_with('E')), "{}", stderr);
+    assert!(logged[0].ends_with("] an error"), "{}", stderr);
+    assert!(logged[1].ends_with("] a batched error"), "{}", stderr);
+    assert!(logged[2].ends_with("] another batched error"), "{}", stderr);
+}
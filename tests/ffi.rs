@@ -0,0 +1,63 @@
+#![cfg(feature = "ffi")]
+
+use std::{fs, os::raw::c_char, ptr};
+
+use glog::{
+    ffi::{glog_flush, glog_log, GLOG_ERROR, GLOG_INFO, GLOG_WARNING},
+    Flags,
+};
+use log::Level;
+
+mod common;
+
+#[test]
+fn extern_functions_write_to_the_log_files() {
+    let log_dir = common::temp_log_dir("ffi");
+    glog::new()
+        .init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let c = |bytes: &'static [u8]| bytes.as_ptr().cast::<c_char>();
+    unsafe {
+        glog_log(GLOG_INFO, c(b"worker.c\0"), 42, c(b"before\0after\0"));
+        glog_log(GLOG_WARNING, c(b"worker.c\0"), -1, c(b"invalid \xff utf-8\0"));
+        glog_log(GLOG_ERROR, ptr::null(), 7, c(b"unknown file\0"));
+        glog_log(GLOG_INFO, c(b"worker.c\0"), 1, ptr::null());
+        glog_log(17, c(b"worker.c\0"), 2, c(b"unknown severity\0"));
+    }
+    glog_flush();
+
+    let info = common::find_log_file(&log_dir, Level::Info);
+    let content = fs::read_to_string(info).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    let lines: Vec<_> = content
+        .lines()
+        .skip_while(|line| !line.starts_with("Log line format"))
+        .skip(1)
+        .collect();
+    assert_eq!(lines.len(), 4, "{}", content);
+    assert!(
+        lines[0].starts_with('I') && lines[0].ends_with(" worker.c:42] before"),
+        "{}",
+        lines[0]
+    );
+    assert!(
+        lines[1].starts_with('W') && lines[1].ends_with(" worker.c:0] invalid \u{fffd} utf-8"),
+        "{}",
+        lines[1]
+    );
+    assert!(
+        lines[2].starts_with('E') && lines[2].ends_with(" :7] unknown file"),
+        "{}",
+        lines[2]
+    );
+    assert!(
+        lines[3].starts_with('E') && lines[3].ends_with(" worker.c:2] unknown severity"),
+        "{}",
+        lines[3]
+    );
+}
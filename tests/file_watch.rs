@@ -0,0 +1,49 @@
+#![cfg(feature = "file-watch")]
+
+use std::{
+    fs,
+    io::Write,
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::Duration,
+};
+
+use glog::{FileWatchSink, Flags};
+use log::*;
+
+mod common;
+
+fn next(lines: &Receiver<String>) -> String {
+    lines.recv_timeout(Duration::from_secs(5)).expect("no line received")
+}
+
+#[test]
+fn streams_lines_of_new_files_and_records() {
+    let watch_dir = common::temp_log_dir("file-watch");
+    fs::write(watch_dir.join("old.log"), "already there\n").unwrap();
+
+    let sink = FileWatchSink::new(watch_dir.clone()).unwrap();
+    let lines = sink.receiver();
+    glog::new()
+        .with_sink(sink)
+        .init(Flags {
+            logtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    info!("logged directly");
+    assert!(next(&lines).ends_with("] logged directly"));
+
+    let mut first = fs::File::create(watch_dir.join("service.log.1")).unwrap();
+    first.write_all(b"first line\nsecond ").unwrap();
+    assert_eq!(next(&lines), "first line");
+    first.write_all(b"line\n").unwrap();
+    assert_eq!(next(&lines), "second line");
+
+    // Rotated
+    fs::write(watch_dir.join("service.log.2"), "after rotation\n").unwrap();
+    assert_eq!(next(&lines), "after rotation");
+
+    fs::remove_dir_all(&watch_dir).unwrap();
+    assert_eq!(lines.recv_timeout(Duration::from_millis(200)), Err(RecvTimeoutError::Timeout));
+}
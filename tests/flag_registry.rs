@@ -0,0 +1,66 @@
+use std::{env, time::Duration};
+
+use glog::{FlagRegistry, UnknownFlags};
+use log::Level;
+
+#[test]
+fn logging_and_application_flags_share_the_command_line() {
+    env::set_var("GLOG_retry_after", "2m");
+    env::set_var("GLOG_minloglevel", "ERROR");
+    let mut registry = FlagRegistry::new().with_unknown_flags(UnknownFlags::PassThrough);
+    let retry_after = registry.register("retry_after", Duration::from_secs(30), "Wait this long before retrying");
+    let verbose = registry.register("verbose", false, "Print progress");
+    registry.parse_env().unwrap();
+    assert_eq!(retry_after.get(), Duration::from_secs(120));
+
+    let rest = registry
+        .parse_args([
+            "--alsologtostderr",
+            "--minloglevel=warning",
+            "-retry_after",
+            "1500ms",
+            "input.txt",
+            "--other=1",
+            "--verbose",
+            "--",
+            "--logtostderr",
+        ])
+        .unwrap();
+    assert_eq!(rest, ["input.txt", "--other=1", "--logtostderr"]);
+    assert_eq!(retry_after.get(), Duration::from_millis(1500));
+    assert!(verbose.get());
+    let flags = registry.into_flags();
+    assert!(flags.alsologtostderr);
+    assert!(!flags.logtostderr);
+    assert_eq!(flags.minloglevel, Level::Warn);
+}
+
+#[test]
+fn unknown_flags_and_invalid_values_are_errors() {
+    let mut registry = FlagRegistry::new();
+    let retry_after = registry.register("retry_after", Duration::from_secs(30), "Wait this long before retrying");
+    let error = registry.parse_args(["--other=1"]).unwrap_err();
+    assert_eq!((error.flag(), error.value()), ("other", "1"));
+    assert_eq!(
+        registry.parse_args(["--retry_after=soon"]).unwrap_err().to_string(),
+        "invalid value \"soon\" for retry_after: expected a whole number with an optional unit"
+    );
+    assert_eq!(retry_after.get(), Duration::from_secs(30));
+}
+
+#[test]
+fn describe_lists_defaults_and_descriptions() {
+    let mut registry = FlagRegistry::new();
+    registry.register("retry_after", Duration::from_secs(30), "Wait this long before retrying");
+    let description = registry.describe();
+    assert!(description.starts_with("  --colorlogtostderr (default: false)\n      Colorize levels"));
+    assert!(description.contains("  --minloglevel (default: INFO)\n"));
+    assert!(description.contains("  --log_backtrace_at\n      Log a backtrace at this file:line\n"));
+    assert!(description.ends_with("  --retry_after (default: 30s)\n      Wait this long before retrying\n"));
+}
+
+#[test]
+#[should_panic(expected = "the flag logtostderr is already registered")]
+fn logging_flags_cant_be_registered_again() {
+    FlagRegistry::new().register("logtostderr", true, "");
+}
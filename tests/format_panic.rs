@@ -0,0 +1,55 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use glog::{Flags, LogSink};
+use log::*;
+
+#[derive(Clone, Default)]
+struct Capture(Arc<Mutex<Vec<(Level, String)>>>);
+
+impl LogSink for Capture {
+    fn write(&self, record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+        self.0.lock().unwrap().push((record.level(), message.to_owned()));
+        Ok(())
+    }
+}
+
+struct Broken;
+
+impl fmt::Display for Broken {
+    fn fmt(&self, _f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        panic!("broken Display impl")
+    }
+}
+
+#[test]
+fn panicking_display_impls_are_replaced_by_an_error() {
+    let capture = Capture::default();
+    let handle = glog::new()
+        .with_sink(capture.clone())
+        .try_init(Flags {
+            logtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    info!("before");
+    let line = line!() + 1;
+    info!("state: {}", Broken);
+    info!("after");
+
+    let captured = capture.0.lock().unwrap();
+    assert_eq!(captured.len(), 3);
+    assert!(captured[0].1.ends_with("] before"));
+    assert_eq!(captured[1].0, Level::Error);
+    assert!(captured[1].1.starts_with('E'));
+    assert!(captured[1].1.ends_with(&format!(
+        " format_panic.rs:{}] <message formatting panicked: broken Display impl>",
+        line
+    )));
+    assert!(captured[2].1.ends_with("] after"));
+    assert_eq!(handle.stats_snapshot().format_panics, 1);
+}
@@ -0,0 +1,37 @@
+use glog::{Flags, GlogRecord, QueryParams, RingBufferSink};
+use log::*;
+
+#[test]
+fn synthetic_records_are_logged_like_macro_records() {
+    let sink = RingBufferSink::new(10);
+    glog::new()
+        .with_sink(sink.clone())
+        .init(Flags {
+            logtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let record = GlogRecord::builder()
+        .level(Level::Warn)
+        .target("http::access")
+        .module_path("proxy::upstream")
+        .file("src/proxy/upstream.rs")
+        .line(12)
+        .args(format_args!("GET /health took {}ms", 1200))
+        .build();
+    record.log();
+    record.log();
+    GlogRecord::builder().level(Level::Trace).args("filtered").log();
+
+    let events = sink.query(&QueryParams::default()).events;
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].level, Level::Warn);
+    assert_eq!(events[0].target, "http::access");
+    assert!(events[0].line.starts_with('W'), "{}", events[0].line);
+    assert!(
+        events[0].line.ends_with(" upstream.rs:12] GET /health took 1200ms"),
+        "{}",
+        events[0].line
+    );
+}
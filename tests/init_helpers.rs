@@ -0,0 +1,74 @@
+use std::{env, fs, process::Command};
+
+use glog::GlogError;
+use log::*;
+
+mod common;
+
+const CHILD: &str = "GLOG_INIT_HELPERS_TEST_CHILD";
+
+/// Run `test` in a child process, the global logger can only be initialized once per process
+fn run_child(test: &str, envs: &[(&str, &str)]) -> String {
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", test, "--nocapture"])
+        .env(CHILD, "1")
+        .envs(envs.iter().copied())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(output.status.success(), "{}", stderr);
+    stderr
+}
+
+#[test]
+fn init_stderr() {
+    if env::var_os(CHILD).is_some() {
+        let _handle = glog::init_stderr().unwrap();
+        assert!(matches!(glog::init_stderr(), Err(GlogError::AlreadyInitialized)));
+        assert!(matches!(
+            glog::init_files(env::temp_dir()),
+            Err(GlogError::AlreadyInitialized)
+        ));
+        warn!("to stderr");
+        return;
+    }
+
+    let stderr = run_child("init_stderr", &[]);
+    assert!(stderr.contains("] to stderr\n"), "{}", stderr);
+    assert!(!stderr.contains('\x1b'), "{}", stderr);
+}
+
+#[test]
+fn init_files() {
+    let dir = common::temp_log_dir_path("init-files");
+    if env::var_os(CHILD).is_some() {
+        let dir = env::var_os("GLOG_INIT_FILES_DIR").unwrap();
+        let _handle = glog::init_files(dir).unwrap();
+        info!("to a file");
+        return;
+    }
+
+    let stderr = run_child("init_files", &[("GLOG_INIT_FILES_DIR", dir.to_str().unwrap())]);
+    assert!(!stderr.contains("] to a file"), "{}", stderr);
+    let info_log = common::find_log_file(&dir, Level::Info);
+    let content = fs::read_to_string(info_log).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(content.contains("] to a file\n"), "{}", content);
+}
+
+#[test]
+fn init_from_env() {
+    if env::var_os(CHILD).is_some() {
+        let _handle = glog::init_from_env().unwrap();
+        info!("below minloglevel");
+        warn!("from the environment");
+        return;
+    }
+
+    let stderr = run_child(
+        "init_from_env",
+        &[("GLOG_logtostderr", "true"), ("GLOG_minloglevel", "WARNING")],
+    );
+    assert!(stderr.contains("] from the environment\n"), "{}", stderr);
+    assert!(!stderr.contains("below minloglevel"), "{}", stderr);
+}
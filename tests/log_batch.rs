@@ -0,0 +1,62 @@
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+const BATCH_LINES: usize = 2000;
+
+#[test]
+fn batch_is_not_interleaved_with_other_threads() {
+    let log_dir = common::temp_log_dir("log-batch");
+
+    let handle = glog::new()
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let done = Arc::new(AtomicBool::new(false));
+    let foreign = {
+        let done = done.clone();
+        thread::spawn(move || {
+            while !done.load(Ordering::Relaxed) {
+                info!("foreign line");
+            }
+        })
+    };
+    handle.log_batch(Level::Info, module_path!(), |batch| {
+        for i in 0..BATCH_LINES {
+            batch.line(format_args!("batch line {}", i));
+        }
+    });
+    done.store(true, Ordering::Relaxed);
+    foreign.join().unwrap();
+    log::logger().flush();
+
+    let info_file = handle
+        .log_files()
+        .into_iter()
+        .find(|log_file| log_file.level == Level::Info)
+        .unwrap();
+    let content = fs::read_to_string(&info_file.path).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    let lines: Vec<&str> = content.lines().collect();
+    let first = lines.iter().position(|line| line.ends_with("] batch line 0")).unwrap();
+    let block = &lines[first..first + BATCH_LINES];
+    for (i, line) in block.iter().enumerate() {
+        assert!(line.ends_with(&format!("] batch line {}", i)), "{}", line);
+    }
+    let timestamp = |line: &str| line[..21].to_owned();
+    assert!(block.iter().all(|line| timestamp(line) == timestamp(block[0])));
+}
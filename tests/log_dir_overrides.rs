@@ -0,0 +1,51 @@
+use std::{fs, path::Path};
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+fn read_log_files(dir: &Path) -> Vec<(String, String)> {
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().contains(".log."))
+        .map(|path| (path.to_string_lossy().into_owned(), fs::read_to_string(&path).unwrap()))
+        .collect()
+}
+
+#[test]
+fn error_logs_in_separate_directory() {
+    let base_dir = common::temp_log_dir_path("log-dir-overrides");
+    let ephemeral_dir = base_dir.join("ephemeral");
+    let persistent_dir = base_dir.join("persistent");
+    fs::create_dir_all(&ephemeral_dir).unwrap();
+    fs::create_dir_all(&persistent_dir).unwrap();
+
+    glog::new()
+        .init(Flags {
+            log_dir: ephemeral_dir.join("").into_os_string(),
+            log_dir_overrides: vec![(Level::Error, persistent_dir.clone())],
+            ..Default::default()
+        })
+        .unwrap();
+
+    info!("an info message");
+    error!("an error message");
+    log::logger().flush();
+
+    let ephemeral_files = read_log_files(&ephemeral_dir);
+    let persistent_files = read_log_files(&persistent_dir);
+    fs::remove_dir_all(&base_dir).unwrap();
+
+    assert_eq!(ephemeral_files.len(), 2);
+    assert!(ephemeral_files.iter().all(|(path, _)| !path.contains(".log.ERROR.")));
+    let (_, info_content) = ephemeral_files.iter().find(|(path, _)| path.contains(".log.INFO.")).unwrap();
+    assert!(info_content.contains("] an info message\n"));
+    assert!(!info_content.contains("an error message"));
+    assert_eq!(persistent_files.len(), 1);
+    let (path, content) = &persistent_files[0];
+    assert!(path.contains(".log.ERROR."));
+    assert!(content.contains("] an error message\n"));
+    assert!(!content.contains("an info message"));
+}
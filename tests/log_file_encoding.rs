@@ -0,0 +1,34 @@
+use std::fs;
+
+use glog::{FileEncoding, Flags};
+use log::*;
+
+mod common;
+
+#[test]
+fn utf16_log_files_start_with_a_bom() {
+    let log_dir = common::temp_log_dir("log-file-encoding");
+
+    glog::new()
+        .with_log_file_encoding(FileEncoding::Utf16Le)
+        .init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    info!("grüße");
+    log::logger().flush();
+
+    let info_log = common::find_log_file(&log_dir, Level::Info);
+    let bytes = fs::read(&info_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    assert_eq!(bytes[..2], [0xff, 0xfe]);
+    let units: Vec<u16> = bytes[2..]
+        .chunks(2)
+        .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+        .collect();
+    let content = String::from_utf16(&units).unwrap();
+    assert!(content.starts_with("Log file created at:\n"));
+    assert!(content.ends_with("] grüße\n"));
+}
@@ -0,0 +1,64 @@
+use std::{fs, path::PathBuf};
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+/// Name of a log file of `level` created by this process at `timestamp`
+fn log_file_name(active: &str, level: &str, timestamp: &str, pid: u32) -> String {
+    let prefix = &active[..active.find(".log.").unwrap() + ".log.".len()];
+    format!("{}{}.{}.{}", prefix, level, timestamp, pid)
+}
+
+#[test]
+fn lists_active_and_older_files_of_this_process() {
+    let log_dir = common::temp_log_dir("log-files");
+
+    let handle = glog::new()
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    warn!("a warning");
+    log::logger().flush();
+
+    let active = handle.log_files();
+    assert_eq!(
+        active.iter().map(|log_file| log_file.level).collect::<Vec<_>>(),
+        [Level::Info, Level::Warn, Level::Error]
+    );
+    assert!(active.iter().all(|log_file| log_file.active));
+    let info_file = active[0].path.file_name().unwrap().to_str().unwrap().to_owned();
+
+    let pid = std::process::id();
+    let older_info = log_dir.join(log_file_name(&info_file, "INFO", "20210401-123456", pid));
+    let older_warn = log_dir.join(log_file_name(&info_file, "WARN", "20210402-000000", pid));
+    let other_process = log_dir.join(log_file_name(&info_file, "INFO", "20210401-123456", pid + 1));
+    let other_program = log_dir.join(format!("other.{}", log_file_name(&info_file, "INFO", "20210401-123456", pid)));
+    for path in [&older_info, &older_warn, &other_process, &other_program].iter() {
+        fs::write(path, "older content\n").unwrap();
+    }
+
+    let log_files = handle.log_files();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    let listed: Vec<(Level, PathBuf, bool)> = log_files
+        .iter()
+        .map(|log_file| (log_file.level, log_file.path.clone(), log_file.active))
+        .collect();
+    assert_eq!(
+        listed,
+        [
+            (Level::Info, older_info, false),
+            (Level::Info, active[0].path.clone(), true),
+            (Level::Warn, older_warn, false),
+            (Level::Warn, active[1].path.clone(), true),
+            (Level::Error, active[2].path.clone(), true),
+        ]
+    );
+    assert_eq!(log_files[0].created.format("%Y%m%d-%H%M%S").to_string(), "20210401-123456");
+    assert_eq!(log_files[0].size, "older content\n".len() as u64);
+    assert!(log_files[3].size > 0);
+}
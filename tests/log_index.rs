@@ -0,0 +1,96 @@
+use std::{
+    fs,
+    io::BufRead,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+};
+
+use chrono::{Local, TimeZone};
+use glog::{
+    index::{self, Seekable},
+    ByteSize,
+    Flags,
+};
+use log::*;
+
+mod common;
+
+const GRANULE: u64 = 16 * 1024;
+const RECORDS: i64 = 20_000;
+
+/// The number of the record in a line logged by the test
+fn record_number(line: &str) -> Option<i64> {
+    line.rsplit("] record ").next()?.split(' ').next()?.parse().ok()
+}
+
+#[test]
+fn lookups_land_within_a_granule_of_the_target() {
+    let log_dir = common::temp_log_dir("log-index");
+    // Record i is logged at start + 10ms * i
+    let start = Local.with_ymd_and_hms(2021, 4, 1, 12, 0, 0).unwrap();
+    let clock = Arc::new(AtomicI64::new(0));
+    let handle = {
+        let clock = clock.clone();
+        glog::new()
+            .with_timestamp_fn(move || start + chrono::Duration::milliseconds(clock.load(Ordering::Relaxed)))
+            .try_init(Flags {
+                log_dir: log_dir.join("").into_os_string(),
+                log_index_bytes: Some(ByteSize::b(GRANULE)),
+                ..Default::default()
+            })
+            .unwrap()
+    };
+    for i in 0..RECORDS {
+        clock.store(i * 10, Ordering::Relaxed);
+        info!("record {} {}", i, "padding".repeat(i as usize % 16));
+    }
+    log::logger().flush();
+
+    let info_path = handle.log_files()[0].path.clone();
+    let content = fs::read_to_string(&info_path).unwrap();
+    let offsets: Vec<(usize, i64)> = content
+        .match_indices('\n')
+        .map(|(newline, _)| newline + 1)
+        .filter_map(|offset| Some((offset, record_number(content[offset..].lines().next()?)?)))
+        .collect();
+    assert_eq!(offsets.len() as i64, RECORDS);
+    for target in [-5, 0, 1, 4321, 10_000, 19_999, 25_000] {
+        let timestamp = start + chrono::Duration::milliseconds(target * 10);
+        let offset = index::lookup(&info_path, timestamp).unwrap() as usize;
+        assert!(content.is_char_boundary(offset) && (offset == 0 || content.as_bytes()[offset - 1] == b'\n'));
+        let first = offsets
+            .iter()
+            .find(|(_, number)| *number >= target)
+            .map_or(content.len(), |(offset, _)| *offset);
+        assert!(offset <= first, "record {} is before offset {}", target, offset);
+        // Entries are at least a granule apart, the next one is at most a line longer
+        assert!(
+            ((first - offset) as u64) < GRANULE + 256,
+            "{} bytes before record {}",
+            first - offset,
+            target
+        );
+
+        let mut lines = Seekable::open_at(&info_path, timestamp).unwrap().lines();
+        let line = lines.next().unwrap().unwrap();
+        assert!(
+            record_number(&line).map_or(offset == 0, |number| number <= target.max(0)),
+            "{}",
+            line
+        );
+    }
+
+    // A damaged sidecar makes readers start at the beginning instead of skipping records
+    let sidecar_path = index::index_path(&info_path);
+    let mut sidecar = fs::read_to_string(&sidecar_path).unwrap();
+    let digit = sidecar.rfind(|c: char| c.is_ascii_digit()).unwrap();
+    sidecar.replace_range(digit..=digit, if &sidecar[digit..=digit] == "1" { "2" } else { "1" });
+    fs::write(&sidecar_path, sidecar).unwrap();
+    assert_eq!(index::lookup(&info_path, start + chrono::Duration::seconds(100)).unwrap(), 0);
+    fs::remove_file(&sidecar_path).unwrap();
+    assert_eq!(index::lookup(&info_path, start + chrono::Duration::seconds(100)).unwrap(), 0);
+
+    fs::remove_dir_all(&log_dir).unwrap();
+}
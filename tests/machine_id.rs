@@ -0,0 +1,28 @@
+use std::fs;
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+#[test]
+fn machine_id_in_file_header() {
+    let log_dir = common::temp_log_dir("machine-id");
+    glog::new()
+        .with_machine_id("pod-7f9c")
+        .init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    info!("a message");
+    log::logger().flush();
+
+    let info_log = common::find_log_file(&log_dir, Level::Info);
+    let content = fs::read_to_string(&info_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    let lines: Vec<&str> = content.lines().collect();
+    assert!(lines[2].starts_with("Running on machine: "));
+    assert_eq!(lines[3], "Machine ID: pod-7f9c");
+}
@@ -0,0 +1,88 @@
+use std::{
+    error::Error,
+    fs,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+        Mutex,
+    },
+    time::Duration,
+};
+
+use glog::{Flags, LogSink};
+use log::*;
+
+mod common;
+
+#[derive(Clone, Default)]
+struct Recorder {
+    messages: Arc<Mutex<Vec<String>>>,
+    flushes: Arc<AtomicUsize>,
+}
+
+impl LogSink for Recorder {
+    fn write(&self, _record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+        self.messages.lock().unwrap().push(message.to_owned());
+        Ok(())
+    }
+
+    fn flush(&self) {
+        self.flushes.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn markers_delimit_phases_and_flush_for_waiters() {
+    let log_dir = common::temp_log_dir("marker");
+    let recorder = Recorder::default();
+
+    let handle = glog::new()
+        .with_sink(recorder.clone())
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    info!("first phase");
+    let flushes = recorder.flushes.load(Ordering::SeqCst);
+    let first = glog::marker("phase two");
+    // Nobody waits for the marker, so it isn't flushed
+    assert_eq!(recorder.flushes.load(Ordering::SeqCst), flushes);
+    info!("second phase");
+    let second = glog::marker("done");
+    assert_eq!(second, first + 1);
+
+    assert!(handle.wait_for_marker(second, Duration::from_secs(1)));
+    assert!(recorder.flushes.load(Ordering::SeqCst) > flushes);
+    assert!(!handle.wait_for_marker(second + 1, Duration::from_millis(10)));
+
+    let info_file = handle
+        .log_files()
+        .into_iter()
+        .find(|log_file| log_file.level == Level::Info)
+        .unwrap();
+    let content = fs::read_to_string(&info_file.path).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    // The markers are in order with the other records in the files and the sinks
+    let expected = [
+        "] first phase".to_owned(),
+        format!("] ===MARK phase two seq={} ts=", first),
+        "] second phase".to_owned(),
+        format!("] ===MARK done seq={} ts=", second),
+    ];
+    for lines in [
+        content.lines().collect::<Vec<_>>(),
+        recorder.messages.lock().unwrap().iter().map(String::as_str).collect(),
+    ] {
+        let phases: Vec<&str> = lines
+            .into_iter()
+            .filter(|line| line.contains("phase") || line.contains("===MARK"))
+            .collect();
+        assert_eq!(phases.len(), expected.len(), "{:?}", phases);
+        for (line, expected) in phases.iter().zip(&expected) {
+            assert!(line.contains(expected.as_str()), "{:?} doesn't contain {:?}", line, expected);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::{fs, path::PathBuf};
+
+use glog::merge::merge_files;
+
+mod common;
+
+fn header(created: &str, letters: &str) -> String {
+    format!(
+        "Log file created at:\n{}\nRunning on machine: host\nRunning duration (h:mm:ss): 0:00:00\n\
+         Log line format: [{}]mmdd hh:mm:ss.uuuuuu threadid file:line] msg\n",
+        created, letters
+    )
+}
+
+#[test]
+fn merges_by_timestamp() {
+    let dir = common::temp_log_dir("merge");
+    let write = |name: &str, content: String| {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    };
+    let paths: Vec<PathBuf> = vec![
+        write(
+            "server.host.user.log.INFO.20211231-235900.1",
+            header("2021/12/31 23:59:00", "IWE")
+                + "I1231 23:59:58.000000     1 main.rs:1] last of the year\n\
+                   I1231 23:59:59.500000     1 main.rs:2] multi\n\
+                   line\n\
+                   W4 retries left\n\
+                   I0101 00:00:01.000000     1 main.rs:3] first of the year\n",
+        ),
+        write(
+            "worker.host.user.log.WARNING.20211231-235900.2",
+            header("2021/12/31 23:59:00", "IWE")
+                + "W1231 23:59:59.000000    22 worker.rs:7] retrying\n\
+                   W1231 23:59:59.5xxxxx    22 worker.rs:8] corrupt\n\
+                   W1231 25:00:00.000000    22 worker.rs:8] invalid time\n\
+                   W1231 23:59:59.500000    22 worker.rs:9] same time as multi\n",
+        ),
+        write(
+            "batch.host.user.log.ERROR.20211231-235900.3",
+            header("2021/12/31 23:59:00", "TDIWE")
+                + "Extra header line\n\
+                   E20211231 23:59:57.000000   333 batch.rs:1] with year\n\
+                   E20220101 00:00:00.000000   333 batch.rs:2] midnight\n  \
+                   indented continuation\n",
+        ),
+    ];
+
+    let mut merged = Vec::new();
+    let stats = merge_files(&paths, &mut merged).unwrap();
+    assert_eq!(
+        String::from_utf8(merged).unwrap(),
+        "[ERROR] E20211231 23:59:57.000000   333 batch.rs:1] with year\n\
+         [INFO] I1231 23:59:58.000000     1 main.rs:1] last of the year\n\
+         [WARNING] W1231 23:59:59.000000    22 worker.rs:7] retrying\n\
+         W1231 23:59:59.5xxxxx    22 worker.rs:8] corrupt\n\
+         W1231 25:00:00.000000    22 worker.rs:8] invalid time\n\
+         [INFO] I1231 23:59:59.500000     1 main.rs:2] multi\n\
+         line\n\
+         W4 retries left\n\
+         [WARNING] W1231 23:59:59.500000    22 worker.rs:9] same time as multi\n\
+         [ERROR] E20220101 00:00:00.000000   333 batch.rs:2] midnight\n  \
+         indented continuation\n\
+         [INFO] I0101 00:00:01.000000     1 main.rs:3] first of the year\n"
+    );
+    let counts: Vec<(u64, Vec<u64>)> = stats
+        .files
+        .iter()
+        .map(|file| (file.records, file.unparseable.clone()))
+        .collect();
+    assert_eq!(counts, [(3, vec![]), (2, vec![8]), (2, vec![])]);
+    assert_eq!(stats.files[1].path, paths[1]);
+}
@@ -0,0 +1,95 @@
+#![cfg(all(unix, feature = "mmap"))]
+
+use std::{env, fs, process::Command, sync::mpsc, thread};
+
+use glog::{ByteSize, Flags};
+use log::*;
+
+mod common;
+
+const CHILD: &str = "GLOG_MMAP_TEST_CHILD";
+
+#[test]
+fn mapped_log_files_contain_every_line_once_flushed() {
+    let log_dir = common::temp_log_dir("mmap");
+
+    let handle = glog::new()
+        .with_mmap_writer(ByteSize::kib(4))
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+    for line in 0..1000 {
+        info!("line {}", line);
+    }
+    let info_file = handle.log_files().remove(0).path;
+    let mapped = fs::read(&info_file).unwrap();
+    assert_eq!(mapped.len() % 4096, 0);
+
+    log::logger().flush();
+    let content = fs::read_to_string(&info_file).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    assert!(!content.contains('\0'));
+    let lines: Vec<_> = content.lines().filter(|line| line.contains("] line ")).collect();
+    assert_eq!(lines.len(), 1000);
+    assert!(lines[999].ends_with("] line 999"), "{}", lines[999]);
+}
+
+#[test]
+fn shutdown_cuts_off_the_files_of_other_threads() {
+    if env::var_os(CHILD).is_none() {
+        let output = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "shutdown_cuts_off_the_files_of_other_threads", "--nocapture"])
+            .env(CHILD, "1")
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        return;
+    }
+
+    let log_dir = common::temp_log_dir("mmap-thread");
+    let handle = glog::new()
+        .with_mmap_writer(ByteSize::kib(4))
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            file_per_thread: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let (logged, wait_for_log) = mpsc::channel();
+    let (shut_down, wait_for_shutdown) = mpsc::channel();
+    let thread = thread::spawn(move || {
+        info!("before the shutdown");
+        logged.send(()).unwrap();
+        // Still running, so its files are neither flushed nor dropped
+        wait_for_shutdown.recv().unwrap();
+        info!("after the shutdown");
+    });
+    wait_for_log.recv().unwrap();
+    handle.shutdown();
+
+    let read_thread_file = || {
+        fs::read_dir(&log_dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .map(|path| path.to_string_lossy().into_owned())
+            // Named after the main file with the thread id appended
+            .find(|path| path.contains(".INFO.") && !path.ends_with(&format!(".{}", std::process::id())))
+            .map(|path| fs::read_to_string(path).unwrap())
+            .unwrap()
+    };
+    let content = read_thread_file();
+    assert!(!content.contains('\0'), "{:?}", content);
+    assert!(content.ends_with("] before the shutdown\n"), "{:?}", content);
+
+    shut_down.send(()).unwrap();
+    thread.join().unwrap();
+    // Later lines are written to the descriptor
+    let content = read_thread_file();
+    fs::remove_dir_all(&log_dir).unwrap();
+    assert!(!content.contains('\0'), "{:?}", content);
+    assert!(content.ends_with("] after the shutdown\n"), "{:?}", content);
+}
@@ -0,0 +1,77 @@
+#![cfg(all(unix, feature = "capture-stderr"))]
+
+use std::{
+    env,
+    fs::File,
+    io::Read,
+    os::unix::io::FromRawFd,
+    process::{Command, Stdio},
+};
+
+use glog::Flags;
+use log::*;
+
+const CHILD: &str = "GLOG_NO_COLOR_TEST_CHILD";
+
+/// Stderr of a child logging to a terminal while capturing it, with `NO_COLOR` if `no_color` is set
+fn terminal_stderr(no_color: bool) -> String {
+    let mut command = Command::new(env::current_exe().unwrap());
+    command
+        .args(["--exact", "no_color_disables_colorlogtostderr", "--nocapture"])
+        .env(CHILD, "1")
+        .env("TERM", "xterm")
+        .env_remove("NO_COLOR")
+        .env_remove("RUST_BACKTRACE")
+        .stdout(Stdio::null());
+    if no_color {
+        command.env("NO_COLOR", "");
+    }
+
+    let pty = nix::pty::openpty(None, None).unwrap();
+    // SAFETY: the descriptors were just opened and are owned by nothing else
+    let mut child = command.stderr(unsafe { Stdio::from_raw_fd(pty.slave) }).spawn().unwrap();
+    // Closes the parent's copy of the terminal, so reading ends once the child exited
+    drop(command);
+    let mut master = unsafe { File::from_raw_fd(pty.master) };
+    let mut stderr = Vec::new();
+    // Fails with EIO once the child closed the terminal, everything written before is kept
+    let _ = master.read_to_end(&mut stderr);
+    assert!(child.wait().unwrap().success());
+    String::from_utf8_lossy(&stderr).replace("\r\n", "\n")
+}
+
+#[test]
+fn no_color_disables_colorlogtostderr() {
+    if env::var_os(CHILD).is_some() {
+        glog::new()
+            .init(Flags {
+                logtostderr: true,
+                colorlogtostderr: true,
+                log_backtrace_at: Some("traced.rs:7".to_owned()),
+                ..Default::default()
+            })
+            .unwrap();
+        // Written to the saved stderr instead of the termcolor writer, which honors NO_COLOR by itself
+        glog::capture_raw_stderr(Level::Warn, glog::RAW_STDERR_TARGET).unwrap();
+        error!("an error");
+        // Colored by the backtrace printer with the `color-backtrace` feature
+        log::logger().log(
+            &Record::builder()
+                .level(Level::Warn)
+                .file(Some("src/traced.rs"))
+                .line(Some(7))
+                .args(format_args!("traced"))
+                .build(),
+        );
+        glog::release_raw_stderr().unwrap();
+        return;
+    }
+
+    let colored = terminal_stderr(false);
+    assert!(colored.contains('\x1b'), "{:?}", colored);
+
+    let stderr = terminal_stderr(true);
+    assert!(stderr.contains("] an error\n"), "{:?}", stderr);
+    assert!(stderr.contains("] traced\n"), "{:?}", stderr);
+    assert!(!stderr.contains('\x1b'), "{:?}", stderr);
+}
@@ -0,0 +1,64 @@
+#![cfg(all(unix, feature = "capture-stderr"))]
+
+use std::{
+    error::Error,
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+use glog::{Flags, LogSink, RAW_STDERR_TARGET};
+use log::*;
+
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<(Level, String, String)>>>);
+
+impl LogSink for Collect {
+    fn write(&self, record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+        self.0
+            .lock()
+            .unwrap()
+            .push((record.level(), record.target().to_owned(), message.to_owned()));
+        Ok(())
+    }
+}
+
+#[test]
+fn raw_stderr_writes_are_logged() {
+    let collect = Collect::default();
+    let handle = glog::new()
+        .with_sink(collect.clone())
+        .try_init(Flags {
+            logtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+
+    glog::capture_raw_stderr(Level::Warn, RAW_STDERR_TARGET).unwrap();
+    assert!(glog::capture_raw_stderr(Level::Warn, RAW_STDERR_TARGET).is_err());
+    info!("logged while captured");
+    nix::unistd::write(libc::STDERR_FILENO, b"from the process\nbinary \x1b\xff\n").unwrap();
+    let status = Command::new("sh").arg("-c").arg("echo from a child >&2").status().unwrap();
+    assert!(status.success());
+    nix::unistd::write(libc::STDERR_FILENO, b"unterminated").unwrap();
+    glog::release_raw_stderr().unwrap();
+    glog::release_raw_stderr().unwrap();
+
+    // Captured again through the same saved fd, until the shutdown releases it
+    glog::capture_raw_stderr(Level::Warn, RAW_STDERR_TARGET).unwrap();
+    nix::unistd::write(libc::STDERR_FILENO, b"captured again\n").unwrap();
+    handle.shutdown();
+    nix::unistd::write(libc::STDERR_FILENO, b"not captured\n").unwrap();
+
+    let records = collect.0.lock().unwrap().clone();
+    let raw: Vec<_> = records.iter().filter(|(level, ..)| *level == Level::Warn).collect();
+    assert_eq!(raw.len(), 5, "{:?}", records);
+    assert!(raw.iter().all(|(_, target, _)| target == RAW_STDERR_TARGET));
+    assert!(raw[0].2.ends_with("] from the process"), "{:?}", raw[0]);
+    assert!(raw[1].2.ends_with("] binary \\x1b\u{fffd}"), "{:?}", raw[1]);
+    assert!(raw[2].2.ends_with("] from a child"), "{:?}", raw[2]);
+    assert!(raw[3].2.ends_with("] unterminated [partial line]"), "{:?}", raw[3]);
+    assert!(raw[4].2.ends_with("] captured again"), "{:?}", raw[4]);
+    assert!(records
+        .iter()
+        .any(|(_, _, message)| message.ends_with("] logged while captured")));
+}
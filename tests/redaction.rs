@@ -0,0 +1,91 @@
+use std::{
+    borrow::Cow,
+    error::Error,
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use glog::{context, Flags, HashedDisplay, LogSink};
+use log::*;
+
+mod common;
+
+const TOKEN: &str = "tok_3f9a81c2";
+
+#[derive(Clone, Default)]
+struct Recorder(Arc<Mutex<Vec<String>>>);
+
+impl LogSink for Recorder {
+    fn write(&self, _record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+        self.0.lock().unwrap().push(message.to_owned());
+        Ok(())
+    }
+}
+
+#[test]
+fn redacted_values_never_reach_the_log_file() {
+    let log_dir = common::temp_log_dir("redaction");
+
+    let recorder = Recorder::default();
+    let handle = glog::new()
+        .with_sink(recorder.clone())
+        .set_redactor(|message| match message.contains(TOKEN) {
+            true => Cow::Owned(message.replace(TOKEN, "[TOKEN]")),
+            false => Cow::Borrowed(message),
+        })
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            redaction_salt: "test salt".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let hashed = HashedDisplay("alice@example.com").to_string();
+    assert_eq!(HashedDisplay("alice@example.com").to_string(), hashed);
+    info!("request with {}", TOKEN);
+    error!("user {} failed with token={}", HashedDisplay("alice@example.com"), TOKEN);
+    context::with_context(&[("token", TOKEN)], || {
+        handle.log_batch(Level::Info, module_path!(), |batch| {
+            batch.line(format_args!("batch with {}", TOKEN));
+            batch.line(format_args!("batch without a token"));
+        })
+    });
+    glog::attach("request", format!("Authorization: {}", TOKEN));
+    info!("request dump");
+    info!("unrelated");
+    log::logger().flush();
+
+    let mut files = 0;
+    for entry in fs::read_dir(&log_dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.to_string_lossy().contains(".log.") {
+            let bytes = fs::read(&path).unwrap();
+            assert!(
+                !bytes.windows(TOKEN.len()).any(|window| window == TOKEN.as_bytes()),
+                "{}",
+                path.display()
+            );
+            files += 1;
+        }
+    }
+    let info_log = common::find_log_file(&log_dir, Level::Info);
+    let content = fs::read_to_string(info_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    assert_eq!(files, 3);
+    assert!(content.contains("] request with [TOKEN]\n"));
+    assert!(content.contains(&format!("] user {} failed with token=[TOKEN]\n", hashed)));
+    assert!(content.contains("] [token=[TOKEN]] batch with [TOKEN]\n"), "{}", content);
+    assert!(content.contains("] [token=[TOKEN]] batch without a token\n"), "{}", content);
+    assert!(
+        content.contains("] request dump [attached: request=22 bytes]\n    Authorization: [TOKEN]\n"),
+        "{}",
+        content
+    );
+    assert!(content.ends_with("] unrelated\n"));
+    let messages = recorder.0.lock().unwrap();
+    assert!(messages
+        .iter()
+        .any(|message| message.ends_with("] [token=[TOKEN]] batch with [TOKEN]")));
+    assert!(messages.iter().all(|message| !message.contains(TOKEN)), "{:?}", messages);
+}
@@ -0,0 +1,104 @@
+use std::{
+    env,
+    error::Error,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+        Mutex,
+    },
+};
+
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone};
+use glog::{Flags, LogSink, ScheduleWindow};
+use log::*;
+
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl LogSink for Collect {
+    fn write(&self, record: &Record, _message: &str) -> Result<(), Box<dyn Error>> {
+        self.0.lock().unwrap().push(record.args().to_string());
+        Ok(())
+    }
+}
+
+fn window(start: (u32, u32), end: (u32, u32), floor: Level) -> ScheduleWindow {
+    ScheduleWindow {
+        start: NaiveTime::from_hms_opt(start.0, start.1, 0).unwrap(),
+        end: NaiveTime::from_hms_opt(end.0, end.1, 0).unwrap(),
+        weekdays: Vec::new(),
+        floor,
+    }
+}
+
+#[test]
+fn schedule_follows_the_clock_across_boundaries_and_dst() {
+    // Central European time, switching to summer time on the last Sundays of March and back in October
+    env::set_var("TZ", "CET-1CEST,M3.5.0,M10.5.0/3");
+    let clock = Arc::new(AtomicI64::new(0));
+    let sink = Collect::default();
+    let timestamp = clock.clone();
+    let handle = glog::new()
+        .with_timestamp_fn(move || Local.timestamp_micros(timestamp.load(Ordering::SeqCst)).unwrap())
+        .with_sink(sink.clone())
+        .with_schedule(vec![window((17, 0), (9, 0), Level::Info)])
+        .try_init(Flags {
+            logtostderr: true,
+            minloglevel: Level::Debug,
+            ..Default::default()
+        })
+        .unwrap();
+
+    let set_utc = |date: (i32, u32, u32), time: (u32, u32, u32), micros: u32| {
+        let utc = NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+            .unwrap()
+            .and_hms_micro_opt(time.0, time.1, time.2, micros)
+            .unwrap();
+        clock.store(utc.and_utc().timestamp_micros(), Ordering::SeqCst);
+    };
+    let logged = |level: Level| {
+        log!(level, "{}", level);
+        // Changing the schedule logs an audit record
+        sink.0.lock().unwrap().drain(..).any(|message| message == level.as_str())
+    };
+
+    // 16:59:59.999999 CET, a record at the switch instant already has the new floor
+    set_utc((2021, 3, 5), (15, 59, 59), 999_999);
+    assert!(logged(Level::Debug));
+    set_utc((2021, 3, 5), (16, 0, 0), 0);
+    assert!(!logged(Level::Debug));
+    assert!(logged(Level::Info));
+    // 09:00 CET the next morning
+    set_utc((2021, 3, 6), (8, 0, 0), 0);
+    assert!(logged(Level::Debug));
+
+    // Summer time starts at 02:00 CET by skipping to 03:00 CEST, the skipped 02:30 start switches at 03:00
+    handle.set_schedule(vec![window((2, 30), (4, 0), Level::Warn)]);
+    set_utc((2021, 3, 28), (0, 59, 59), 0);
+    assert!(logged(Level::Info));
+    set_utc((2021, 3, 28), (1, 0, 0), 0);
+    assert!(!logged(Level::Info));
+    assert!(logged(Level::Warn));
+    // 04:00 CEST
+    set_utc((2021, 3, 28), (2, 0, 0), 0);
+    assert!(logged(Level::Info));
+
+    // Summer time ends at 03:00 CEST by repeating 02:00 to 03:00, the window is active in both passes
+    handle.set_schedule(vec![window((2, 0), (2, 30), Level::Warn)]);
+    let passes = [
+        ((23, 59, 59), true),
+        ((0, 0, 0), false),
+        ((0, 30, 0), true),
+        ((0, 59, 59), true),
+        ((1, 0, 0), false),
+        ((1, 30, 0), true),
+    ];
+    for (time, info_logged) in passes.iter() {
+        let day = if time.0 == 23 { 30 } else { 31 };
+        set_utc((2021, 10, day), *time, 0);
+        assert_eq!(logged(Level::Info), *info_logged, "{:?}", time);
+    }
+
+    handle.set_schedule(Vec::new());
+    assert!(logged(Level::Debug));
+}
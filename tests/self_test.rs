@@ -0,0 +1,54 @@
+use std::{error::Error, fs};
+
+use glog::{Flags, LogSink, QueryParams, RingBufferSink, SinkConfig};
+use log::*;
+
+mod common;
+
+struct Unreachable;
+
+impl LogSink for Unreachable {
+    fn write(&self, _record: &Record, _message: &str) -> Result<(), Box<dyn Error>> {
+        Err("connection refused".into())
+    }
+}
+
+#[test]
+fn self_test_reports_every_component() {
+    let log_dir = common::temp_log_dir("self");
+    let recent = RingBufferSink::new(100);
+    let handle = glog::new()
+        .with_sink(recent.clone())
+        .with_sink_error_handler(|_, _, _| {})
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let report = handle.self_test();
+    let components: Vec<_> = report.checks.iter().map(|check| check.component.as_str()).collect();
+    assert_eq!(components, ["log file ERROR", "log file WARN", "log file INFO", "sink 0"]);
+    assert!(report.passed(), "{:?}", report);
+    let events = recent.query(&QueryParams::default()).events;
+    assert!(events
+        .iter()
+        .all(|event| event.target == "glog::internal" && event.line.contains(&report.tag)));
+    assert_eq!(events.len(), 4, "a record per level and the ping of the sink");
+
+    handle.add_sink_with(SinkConfig::default(), Unreachable);
+    let info_file = handle.log_files().remove(0).path;
+    fs::remove_file(&info_file).unwrap();
+    let report = handle.self_test();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    assert!(!report.passed());
+    let failures: Vec<_> = report
+        .failures()
+        .map(|check| (check.component.as_str(), check.error.clone().unwrap()))
+        .collect();
+    assert_eq!(failures.len(), 2, "{:?}", failures);
+    assert_eq!(failures[0].0, "log file INFO");
+    assert!(failures[0].1.starts_with("couldn't read "), "{}", failures[0].1);
+    assert_eq!(failures[1], ("sink 1", "connection refused".to_owned()));
+}
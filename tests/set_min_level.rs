@@ -0,0 +1,40 @@
+use std::fs;
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+#[test]
+fn lowering_the_min_level_logs_the_following_records() {
+    let log_dir = common::temp_log_dir("set-min-level");
+
+    let handle = glog::new()
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            minloglevel: Level::Warn,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(handle.current_min_level(), Level::Warn);
+
+    info!("before the change");
+    handle.set_min_level(Level::Info);
+    assert_eq!(handle.current_min_level(), Level::Info);
+    assert_eq!(log::max_level(), LevelFilter::Info);
+    info!("after the change");
+    debug!("still below the level");
+    log::logger().flush();
+
+    let info_file = handle
+        .log_files()
+        .into_iter()
+        .find(|log_file| log_file.level == Level::Info)
+        .unwrap();
+    let content = fs::read_to_string(&info_file.path).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    assert!(!content.contains("before the change"), "{}", content);
+    assert!(content.contains("] after the change\n"), "{}", content);
+    assert!(!content.contains("still below the level"), "{}", content);
+}
@@ -0,0 +1,71 @@
+#![cfg(unix)]
+
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::{PathBuf, MAIN_SEPARATOR},
+    process::Command,
+};
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+const CHILD_LOG_DIR: &str = "GLOG_SHARED_FILES_TEST_LOG_DIR";
+const CHILD_LETTER: &str = "GLOG_SHARED_FILES_TEST_LETTER";
+
+#[test]
+fn processes_sharing_a_log_file_write_whole_lines() {
+    if let (Some(log_dir), Ok(letter)) = (env::var_os(CHILD_LOG_DIR), env::var(CHILD_LETTER)) {
+        glog::new()
+            .with_module_log_file("shared_files", PathBuf::from(&log_dir).join("shared.log"))
+            .init(Flags {
+                log_dir,
+                lock_shared_files: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let line = letter.repeat(8 * 1024);
+        for _ in 0..500 {
+            info!("{}", line);
+        }
+        log::logger().flush();
+        return;
+    }
+
+    let log_dir = common::temp_log_dir("shared-files");
+    let mut child_log_dir = OsString::from(&log_dir);
+    child_log_dir.push(MAIN_SEPARATOR.to_string());
+
+    let children: Vec<_> = ["a", "b"]
+        .iter()
+        .map(|letter| {
+            Command::new(env::current_exe().unwrap())
+                .args(["--exact", "processes_sharing_a_log_file_write_whole_lines", "--nocapture"])
+                .env(CHILD_LOG_DIR, &child_log_dir)
+                .env(CHILD_LETTER, letter)
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    for mut child in children {
+        assert!(child.wait().unwrap().success());
+    }
+    let content = fs::read_to_string(log_dir.join("shared.log")).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    let messages: Vec<_> = content
+        .lines()
+        .filter_map(|line| line.strip_prefix('I'))
+        .map(|line| &line[line.find("] ").expect("torn line") + 2..])
+        .collect();
+    assert_eq!(messages.len(), 1000);
+    for message in messages {
+        assert!(
+            message == "a".repeat(8 * 1024) || message == "b".repeat(8 * 1024),
+            "torn line"
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+use glog::{context, Flags, LogFormat, LogSink, SinkConfig};
+use log::*;
+
+/// The messages a sink received with their address, to tell shared messages apart
+#[derive(Clone, Default)]
+struct Collect(Arc<Mutex<Vec<(String, usize)>>>);
+
+impl Collect {
+    fn messages(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().map(|(message, _)| message.clone()).collect()
+    }
+
+    fn addresses(&self) -> Vec<usize> {
+        self.0.lock().unwrap().iter().map(|(_, address)| *address).collect()
+    }
+}
+
+impl LogSink for Collect {
+    fn write(&self, _record: &Record, message: &str) -> Result<(), Box<dyn Error>> {
+        self.0.lock().unwrap().push((message.to_owned(), message.as_ptr() as usize));
+        Ok(())
+    }
+}
+
+#[test]
+fn sinks_receive_their_levels_and_formats() {
+    let (verbose, severe, severe_too, default) = (Collect::default(), Collect::default(), Collect::default(), Collect::default());
+    let handle = glog::new()
+        .add_sink_with(
+            SinkConfig {
+                min_level: Some(Level::Warn),
+                format: LogFormat::Json,
+                include_context: false,
+            },
+            severe.clone(),
+        )
+        .add_sink_with(
+            SinkConfig {
+                min_level: Some(Level::Warn),
+                format: LogFormat::Json,
+                include_context: false,
+            },
+            severe_too.clone(),
+        )
+        .with_sink(default.clone())
+        .try_init(Flags {
+            logtostderr: true,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(log::max_level(), LevelFilter::Info);
+    let verbose_id = handle.add_sink_with(
+        SinkConfig {
+            min_level: Some(Level::Trace),
+            ..Default::default()
+        },
+        verbose.clone(),
+    );
+    assert_eq!(log::max_level(), LevelFilter::Trace);
+
+    context::with_context(&[("request", "42")], || {
+        trace!("trace");
+        debug!("debug");
+        info!("info");
+        warn!("warn \"quoted\"");
+    });
+
+    let messages = |sink: &Collect| -> Vec<String> {
+        sink.messages()
+            .iter()
+            .map(|message| message.split("] ").skip(1).collect::<Vec<_>>().join("] "))
+            .collect()
+    };
+    assert_eq!(
+        messages(&verbose),
+        [
+            "[request=42] trace",
+            "[request=42] debug",
+            "[request=42] info",
+            "[request=42] warn \"quoted\""
+        ]
+    );
+    assert_eq!(messages(&default), ["[request=42] info", "[request=42] warn \"quoted\""]);
+    let json = severe.messages();
+    assert_eq!(json.len(), 1);
+    assert!(json[0].starts_with("{\"timestamp\":\""), "{}", json[0]);
+    assert!(json[0].contains("\"level\":\"WARN\""), "{}", json[0]);
+    assert!(json[0].contains("\"file\":\"sinks.rs\""), "{}", json[0]);
+    assert!(!json[0].contains("\"context\""), "{}", json[0]);
+    assert!(json[0].ends_with(",\"message\":\"warn \\\"quoted\\\"\"}"), "{}", json[0]);
+    assert_eq!(severe_too.messages(), json);
+    assert_eq!(severe_too.addresses(), severe.addresses(), "the JSON message wasn't shared");
+
+    let stats = handle.stats_snapshot();
+    assert_eq!((stats.messages.trace, stats.messages.debug, stats.messages.info), (0, 0, 1));
+
+    assert!(handle.remove_sink(verbose_id));
+    assert!(!handle.remove_sink(verbose_id));
+    assert_eq!(log::max_level(), LevelFilter::Info);
+    trace!("not logged anymore");
+    assert_eq!(verbose.messages().len(), 4);
+}
@@ -0,0 +1,55 @@
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use glog::{ConsoleWriter, Flags, GlogError};
+use log::*;
+
+mod common;
+
+struct Collect(Arc<Mutex<Vec<String>>>);
+
+impl ConsoleWriter for Collect {
+    fn write_line(&self, _level: Level, line: &str) {
+        self.0.lock().unwrap().push(line.to_owned());
+    }
+}
+
+#[test]
+fn logs_to_stderr_until_the_log_dir_exists() {
+    let log_dir = common::temp_log_dir_path("stderr-fallback");
+    let flags = Flags {
+        log_dir: log_dir.join("").into_os_string(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        glog::new().try_init(flags.clone()),
+        Err(GlogError::CreateLogFile(..))
+    ));
+
+    let stderr = Arc::new(Mutex::new(Vec::new()));
+    glog::new()
+        .with_console_writer(Collect(stderr.clone()))
+        .init(Flags {
+            logtostderr_if_no_log_dir: true,
+            ..flags
+        })
+        .unwrap();
+    info!("before the log dir exists");
+    fs::create_dir_all(&log_dir).unwrap();
+    info!("after the log dir was created");
+    log::logger().flush();
+
+    let info_log = common::find_log_file(&log_dir, Level::Info);
+    let content = fs::read_to_string(&info_log).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    let stderr = stderr.lock().unwrap();
+    assert_eq!(stderr.len(), 2);
+    assert!(stderr[0].ends_with("] before the log dir exists"));
+    assert!(stderr[1].ends_with("] after the log dir was created"));
+    assert!(content.starts_with("Log file created at:\n"));
+    assert!(!content.contains("before the log dir exists"));
+    assert!(content.ends_with("] after the log dir was created\n"));
+}
@@ -0,0 +1,85 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    env,
+    process::Command,
+    sync::atomic::{AtomicIsize, Ordering},
+    thread,
+};
+
+use glog::Flags;
+use log::*;
+
+const CHILD: &str = "GLOG_STDERR_THREAD_CHURN_TEST_CHILD";
+
+/// Counts the bytes allocated and not yet freed
+struct Counting;
+
+static LIVE_BYTES: AtomicIsize = AtomicIsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE_BYTES.fetch_add(layout.size() as isize, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size() as isize, Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        LIVE_BYTES.fetch_add(new_size as isize - layout.size() as isize, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Log one colored record from each of `count` short lived threads
+fn log_from_threads(count: usize) {
+    for batch in 0..count / 100 {
+        let threads: Vec<_> = (0..100)
+            .map(|i| thread::spawn(move || warn!("thread {}", batch * 100 + i)))
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+}
+
+#[test]
+fn stderr_state_is_freed_with_the_threads() {
+    if env::var_os(CHILD).is_some() {
+        glog::new()
+            .init(Flags {
+                logtostderr: true,
+                // Takes the general path through the shared color writer
+                colorlogtostderr: true,
+                ..Default::default()
+            })
+            .unwrap();
+        // Lazily allocated state shared by all threads
+        log_from_threads(200);
+        let before = LIVE_BYTES.load(Ordering::Relaxed);
+        log_from_threads(3000);
+        let growth = LIVE_BYTES.load(Ordering::Relaxed) - before;
+        // A writer kept per thread would retain at least a few hundred bytes for each of them
+        assert!(
+            growth < 64 * 1024,
+            "{} bytes are still allocated after the threads exited",
+            growth
+        );
+        return;
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "stderr_state_is_freed_with_the_threads", "--nocapture"])
+        .env(CHILD, "1")
+        .env("TERM", "xterm")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", &stderr[stderr.len().saturating_sub(1000)..]);
+    assert_eq!(stderr.matches("] thread ").count(), 3200);
+}
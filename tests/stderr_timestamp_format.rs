@@ -0,0 +1,51 @@
+use std::{
+    env,
+    process::Command,
+    sync::atomic::{AtomicI64, Ordering},
+};
+
+use chrono::{Local, TimeZone};
+use glog::Flags;
+use log::*;
+
+const CHILD: &str = "GLOG_STDERR_TIMESTAMP_FORMAT_TEST_CHILD";
+
+/// 02:30 on 2021-10-31 in Berlin in summer time, an hour later the clocks show 02:30 again
+const SUMMER_TIME: i64 = 1_635_640_200;
+
+static SECONDS: AtomicI64 = AtomicI64::new(SUMMER_TIME);
+
+#[test]
+fn timestamps_keep_their_offset_when_the_clocks_are_turned_back() {
+    if env::var_os(CHILD).is_some() {
+        glog::new()
+            .with_timestamp_fn(|| Local.timestamp_opt(SECONDS.load(Ordering::Relaxed), 0).unwrap())
+            .with_stderr_timestamp_format("%Y-%m-%d %H:%M:%S%:z")
+            .init(Flags {
+                logtostderr: true,
+                ..Default::default()
+            })
+            .unwrap();
+        info!("summer time");
+        SECONDS.fetch_add(3600, Ordering::Relaxed);
+        info!("winter time");
+        return;
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "timestamps_keep_their_offset_when_the_clocks_are_turned_back",
+            "--nocapture",
+        ])
+        .env(CHILD, "1")
+        .env("TZ", "Europe/Berlin")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{}", stderr);
+    for (message, offset) in [("summer time", "+02:00"), ("winter time", "+01:00")] {
+        let line = stderr.lines().find(|line| line.ends_with(&format!("] {}", message))).unwrap();
+        assert!(line.starts_with(&format!("I2021-10-31 02:30:00{} ", offset)), "{}", line);
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs,
+    io::{self, Write},
+    thread,
+};
+
+use glog::{ByteSize, Flags};
+use log::*;
+
+mod common;
+
+const REPORT_LINES: usize = 30_000;
+
+/// About 2 MiB of lines of a synthetic report
+fn report(name: &str) -> Vec<u8> {
+    let mut report = Vec::new();
+    for i in 0..REPORT_LINES {
+        writeln!(report, "{} row {:05} {}", name, i, "x".repeat(50)).unwrap();
+    }
+    report
+}
+
+#[test]
+fn streams_are_logged_line_by_line() {
+    let log_dir = common::temp_log_dir("stream-writer");
+
+    let handle = glog::new()
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let groups: Vec<u64> = thread::scope(|scope| {
+        let streams: Vec<_> = ["alpha", "beta"]
+            .iter()
+            .map(|name| {
+                let handle = &handle;
+                scope.spawn(move || {
+                    let mut writer = handle.writer(Level::Info, name);
+                    io::copy(&mut report(name).as_slice(), &mut writer).unwrap();
+                    write!(writer, "{} without newline", name).unwrap();
+                    writer.group()
+                })
+            })
+            .collect();
+        streams.into_iter().map(|stream| stream.join().unwrap()).collect()
+    });
+
+    let mut capped = handle.writer(Level::Warn, "capped").with_max_bytes(ByteSize::b(25));
+    let error = io::copy(&mut "first line\nsecond line\nthird line\n".as_bytes(), &mut capped).unwrap_err();
+    assert!(error.to_string().contains("truncated"), "{}", error);
+    let capped_group = capped.group();
+    drop(capped);
+    log::logger().flush();
+
+    let info_file = handle
+        .log_files()
+        .into_iter()
+        .find(|log_file| log_file.level == Level::Info)
+        .unwrap();
+    let content = fs::read_to_string(&info_file.path).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+
+    for (name, group) in ["alpha", "beta"].iter().zip(&groups) {
+        let tag = format!("] [{} #{} ", name, group);
+        let parts: Vec<&str> = content
+            .lines()
+            .filter_map(|line| Some(&line[line.find(&tag)? + tag.len()..]))
+            .collect();
+        assert_eq!(parts.len(), REPORT_LINES + 2, "{}", name);
+        for (i, part) in parts[..REPORT_LINES].iter().enumerate() {
+            assert_eq!(*part, format!("{}/…] {} row {:05} {}", i + 1, name, i, "x".repeat(50)));
+        }
+        let bytes = report(name).len() + name.len() + " without newline".len();
+        assert_eq!(
+            parts[REPORT_LINES..],
+            [
+                format!("{}/…] {} without newline", REPORT_LINES + 1, name),
+                format!("done] {} lines, {} bytes", REPORT_LINES + 1, bytes)
+            ]
+        );
+    }
+
+    let tag = format!("] [capped #{} ", capped_group);
+    let capped: Vec<&str> = content
+        .lines()
+        .filter_map(|line| Some(&line[line.find(&tag)? + tag.len()..]))
+        .collect();
+    assert_eq!(
+        capped,
+        [
+            "1/…] first line",
+            "2/…] second line",
+            "3/…] th",
+            "truncated] stopped after 25 bytes",
+            "done] 3 lines, 25 bytes"
+        ]
+    );
+}
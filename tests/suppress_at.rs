@@ -0,0 +1,75 @@
+use glog::{Flags, QueryParams, RingBufferSink};
+use log::*;
+
+/// Logs a noisy record, returns the line it is logged at
+fn noisy() -> u32 {
+    info!("noisy");
+    line!() - 1
+}
+
+#[test]
+fn records_of_suppressed_call_sites_are_dropped() {
+    let recent = RingBufferSink::new(100);
+    let noisy_line = noisy();
+    let handle = glog::new()
+        .with_sink(recent.clone())
+        .try_init(Flags {
+            logtostderr: true,
+            suppress_at: vec![format!("suppress_at.rs:{},other.rs:*", noisy_line)],
+            ..Default::default()
+        })
+        .unwrap();
+    let messages = || -> Vec<String> {
+        recent
+            .query(&QueryParams::default())
+            .events
+            .iter()
+            // Without the audit records of the changes
+            .filter(|event| event.target != "glog::internal")
+            .map(|event| event.line.rsplit("] ").next().unwrap().to_owned())
+            .collect()
+    };
+
+    noisy();
+    noisy();
+    info!("useful");
+    assert_eq!(messages(), ["useful"]);
+
+    let useful_line = line!() + 1;
+    let log_useful = || info!("useful again");
+    handle.add_suppression(&format!("suppress_at.rs:{}", useful_line)).unwrap();
+    log_useful();
+    assert!(handle.remove_suppression(&format!("suppress_at.rs:{}", useful_line)));
+    log_useful();
+    assert_eq!(messages(), ["useful", "useful again"]);
+
+    handle.add_suppression("suppress_at.rs:*").unwrap();
+    warn!("dropped by the wildcard");
+    let wildcard_line = line!() - 1;
+    noisy();
+    assert_eq!(messages(), ["useful", "useful again"]);
+    // The line suppressed on its own stays suppressed without the wildcard
+    assert!(handle.remove_suppression("suppress_at.rs:*"));
+    noisy();
+    info!("logged again");
+    assert_eq!(messages(), ["useful", "useful again", "logged again"]);
+
+    // Batches are suppressed as a whole by the call site of log_batch
+    let batch_line = line!() + 1;
+    let log_block = || handle.log_batch(Level::Info, module_path!(), |batch| batch.line(format_args!("block")));
+    handle.add_suppression(&format!("suppress_at.rs:{}", batch_line)).unwrap();
+    log_block();
+    assert_eq!(messages(), ["useful", "useful again", "logged again"]);
+
+    assert_eq!(
+        handle.suppressed_call_sites(),
+        [
+            (format!("suppress_at.rs:{}", noisy_line), 4),
+            (format!("suppress_at.rs:{}", useful_line), 1),
+            (format!("suppress_at.rs:{}", wildcard_line), 1),
+            (format!("suppress_at.rs:{}", batch_line), 1),
+        ]
+    );
+    assert_eq!(handle.stats_snapshot().suppressed, 7);
+    assert!(handle.add_suppression("suppress_at.rs").is_err());
+}
@@ -0,0 +1,57 @@
+use std::fs;
+
+use glog::Flags;
+use log::*;
+
+mod common;
+
+#[test]
+fn targets_follow_their_policy() {
+    let log_dir = common::temp_log_dir("target-policy");
+    let handle = glog::new()
+        .try_init(Flags {
+            log_dir: log_dir.join("").into_os_string(),
+            target_policy: "noisy=warn, chatty=debug@sample:0.5".to_owned(),
+            ..Default::default()
+        })
+        .unwrap();
+
+    // An explicit policy wins over minloglevel in both directions
+    info!(target: "noisy::client", "dropped");
+    warn!(target: "noisy::client", "kept warning");
+    debug!(target: "app", "below minloglevel");
+    info!(target: "app", "minloglevel");
+    for i in 0..4 {
+        debug!(target: "chatty", "sampled {}", i);
+    }
+
+    handle.set_target_policy("noisy=warn, chatty=debug").unwrap();
+    for i in 0..2 {
+        debug!(target: "chatty", "after reload {}", i);
+    }
+    assert!(handle.set_target_policy("chatty=debug@rate:1/y").is_err());
+    debug!(target: "chatty", "invalid policy is not applied");
+    log::logger().flush();
+
+    let info = fs::read_to_string(common::find_log_file(&log_dir, Level::Info)).unwrap();
+    fs::remove_dir_all(&log_dir).unwrap();
+    let messages: Vec<&str> = info
+        .lines()
+        .filter(|line| !line.starts_with("Log line format: "))
+        .filter_map(|line| line.split_once("] ").map(|(_, message)| message))
+        .collect();
+    // DEBUG records are written to the INFO file by default
+    assert_eq!(
+        messages,
+        [
+            "kept warning",
+            "minloglevel",
+            "sampled 1",
+            "sampled 3",
+            "target_policy changed from \"noisy=warn, chatty=debug@sample:0.5\" to \"noisy=warn, chatty=debug\"",
+            "after reload 0",
+            "after reload 1",
+            "invalid policy is not applied"
+        ]
+    );
+}
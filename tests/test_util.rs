@@ -0,0 +1,104 @@
+#![cfg(feature = "test-util")]
+
+use std::{
+    panic::{self, UnwindSafe},
+    sync::Mutex,
+};
+
+use glog::{assert_logged, assert_logged_n, assert_not_logged, LogSink, RingBufferSink};
+use log::*;
+
+fn write(capture: &RingBufferSink, level: Level, message: &str) {
+    let line = format!("{}0401 12:34:56.000000    42 main.rs:7] {}", &level.as_str()[..1], message);
+    capture.write(&Record::builder().level(level).build(), &line).unwrap();
+}
+
+fn captured() -> RingBufferSink {
+    let capture = RingBufferSink::new(10);
+    write(&capture, Level::Info, "connecting to db");
+    write(&capture, Level::Warn, "connection timeout after 5s");
+    write(&capture, Level::Error, "query failed");
+    write(&capture, Level::Error, "query failed again");
+    capture
+}
+
+/// The message of the panic of `assertion`, which isn't printed
+fn panic_message(assertion: impl FnOnce() + UnwindSafe) -> String {
+    // The hook is global, tests running in parallel would restore each other's silent hook
+    static HOOK: Mutex<()> = Mutex::new(());
+    let _hook = HOOK.lock().unwrap();
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let payload = panic::catch_unwind(assertion).expect_err("the assertion passed");
+    panic::set_hook(hook);
+    payload.downcast::<String>().map(|message| *message).unwrap()
+}
+
+#[test]
+fn passing_assertions() {
+    let capture = captured();
+    assert_logged!(capture, Level::Warn, contains "timeout");
+    assert_logged!(capture, exact "query failed");
+    assert_logged_n!(capture, Level::Error, 2);
+    assert_logged_n!(capture, Level::Error, contains "again", 1);
+    assert_not_logged!(capture, contains "password");
+    assert_not_logged!(capture, Level::Error, contains "timeout");
+}
+
+#[test]
+fn failing_assertions_show_the_relevant_lines() {
+    let message = panic_message(|| assert_logged!(captured(), Level::Warn, contains "query"));
+    assert_eq!(
+        message,
+        "expected a record at level WARN containing \"query\", 4 records were captured, the nearest are:\n  \
+         E0401 12:34:56.000000    42 main.rs:7] query failed again\n  \
+         E0401 12:34:56.000000    42 main.rs:7] query failed\n  \
+         W0401 12:34:56.000000    42 main.rs:7] connection timeout after 5s"
+    );
+
+    let message = panic_message(|| assert_logged!(RingBufferSink::new(1), exact "query failed"));
+    assert_eq!(
+        message,
+        "expected a record equal to \"query failed\", no records were captured"
+    );
+
+    let message = panic_message(|| assert_logged_n!(captured(), Level::Error, 1));
+    assert!(
+        message.starts_with("expected 1 record(s) at level ERROR, found 2:\n  E0401"),
+        "{}",
+        message
+    );
+
+    let message = panic_message(|| assert_not_logged!(captured(), contains "db"));
+    assert_eq!(
+        message,
+        "expected no record containing \"db\", found:\n  I0401 12:34:56.000000    42 main.rs:7] connecting to db"
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_matcher() {
+    let capture = captured();
+    assert_logged!(capture, Level::Warn, regex r"timeout after \d+s$");
+    let message = panic_message(|| assert_not_logged!(captured(), regex r"^query"));
+    assert!(
+        message.starts_with("expected no record matching /^query/, found:\n"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn clear_and_scopes() {
+    let capture = captured();
+    capture.clear();
+    assert_logged_n!(capture, Level::Error, 0);
+
+    let first = capture.scope(|| write(&capture, Level::Warn, "first phase"));
+    let second = capture.scope(|| write(&capture, Level::Warn, "second phase"));
+    assert_logged!(first, exact "first phase");
+    assert_not_logged!(first, contains "second");
+    assert_logged_n!(second, Level::Warn, 1);
+    assert_eq!(second[0].seq, 6);
+}